@@ -0,0 +1,169 @@
+//! cache of the responses that only depend on immutable data
+//!
+//! a response can be reused when every field resolved for it is in `IMMUTABLE_FIELDS` and
+//! the blocks and transactions it was queried for are confirmed, that is deeper than the
+//! epoch stability depth in the main branch. The fields not listed (branches, tips,
+//! balances, epochs, pools...) change as blocks are applied, so any of them makes the
+//! response uncacheable.
+
+use super::extract_context;
+use async_graphql::{
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextResolve, ResolveInfo},
+    Context, ServerResult, Value,
+};
+use chain_impl_mockchain::block::HeaderId as HeaderHash;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+};
+
+/// The fields that don't change once the block or the transaction they are queried from is
+/// confirmed, by type
+const IMMUTABLE_FIELDS: &[(&str, &[&str])] = &[
+    ("Query", &["block", "transaction"]),
+    (
+        "Block",
+        &[
+            "id",
+            "date",
+            "transactions",
+            "chainLength",
+            "leader",
+            "previousBlock",
+            "totalInput",
+            "totalOutput",
+            "isConfirmed",
+        ],
+    ),
+    ("BlockDate", &["epoch", "slot"]),
+    ("Epoch", &["id"]),
+    ("Transaction", &["id", "inputs", "outputs"]),
+    ("TransactionInput", &["amount", "address"]),
+    ("TransactionOutput", &["amount", "address"]),
+    ("Address", &["id"]),
+    ("Pool", &["id"]),
+    ("BftLeader", &["id"]),
+    (
+        "TransactionConnection",
+        &["pageInfo", "edges", "nodes", "totalCount"],
+    ),
+    ("TransactionEdge", &["cursor", "node"]),
+    (
+        "PageInfo",
+        &["hasPreviousPage", "hasNextPage", "startCursor", "endCursor"],
+    ),
+];
+
+/// Whether the response of a request can be cached, updated while the request is executed
+#[derive(Default)]
+pub struct CacheHint {
+    mutable: AtomicBool,
+}
+
+/// Marks the responses resolving fields outside of `IMMUTABLE_FIELDS` as uncacheable
+pub struct ImmutableFields;
+
+struct ImmutableFieldsExtension;
+
+/// The serialized responses of the cacheable requests, the oldest are evicted first
+pub struct ResponseCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+#[derive(Default)]
+struct Entries {
+    responses: HashMap<String, Arc<str>>,
+    order: VecDeque<String>,
+}
+
+impl CacheHint {
+    pub fn set_mutable(&self) {
+        self.mutable.store(true, Ordering::Release);
+    }
+
+    pub fn is_cacheable(&self) -> bool {
+        !self.mutable.load(Ordering::Acquire)
+    }
+}
+
+fn is_immutable(info: &ResolveInfo<'_>) -> bool {
+    info.is_for_introspection
+        || info.name.starts_with("__")
+        || IMMUTABLE_FIELDS
+            .iter()
+            .any(|(ty, fields)| *ty == info.parent_type && fields.contains(&info.name))
+}
+
+/// Marks the response as uncacheable unless all the blocks are confirmed
+pub async fn require_confirmed(context: &Context<'_>, blocks: &[HeaderHash]) {
+    let hint = match context.data_opt::<Arc<CacheHint>>() {
+        Some(hint) => hint,
+        None => return,
+    };
+
+    for block in blocks {
+        if !extract_context(context).db.is_block_confirmed(block).await {
+            hint.set_mutable();
+            return;
+        }
+    }
+}
+
+impl ExtensionFactory for ImmutableFields {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(ImmutableFieldsExtension)
+    }
+}
+
+#[async_graphql::async_trait::async_trait]
+impl Extension for ImmutableFieldsExtension {
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        if !is_immutable(&info) {
+            if let Some(hint) = ctx.data_opt::<Arc<CacheHint>>() {
+                hint.set_mutable();
+            }
+        }
+
+        next.run(ctx, info).await
+    }
+}
+
+impl ResponseCache {
+    pub fn new(capacity: usize) -> Self {
+        ResponseCache {
+            capacity,
+            entries: Default::default(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<Arc<str>> {
+        let entries = self.entries.lock().unwrap();
+        entries.responses.get(key).map(Arc::clone)
+    }
+
+    pub fn insert(&self, key: String, response: Arc<str>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.responses.insert(key.clone(), response).is_none() {
+            entries.order.push_back(key);
+        }
+
+        while entries.order.len() > self.capacity {
+            if let Some(oldest) = entries.order.pop_front() {
+                entries.responses.remove(&oldest);
+            }
+        }
+    }
+}
@@ -5,7 +5,8 @@ use super::{
     scalars::{PayloadType, PoolId, PublicKey, TimeOffsetSeconds, VotePlanId},
     Address, BftLeader, BlockDate, ExplorerAddress, Pool, Proposal, TaxType,
 };
-use async_graphql::{Context, FieldResult, Object, Union};
+use crate::db::indexing::CertificateKind;
+use async_graphql::{Context, Enum, FieldResult, Object, Union};
 use chain_impl_mockchain::certificate;
 
 // interface for grouping certificates as a graphl union
@@ -25,7 +26,53 @@ pub enum Certificate {
     EvmMapping(EvmMapping),
 }
 
-pub struct StakeDelegation(certificate::StakeDelegation);
+/// The kinds of certificates, to select them in the subscriptions and to count them in the
+/// epoch statistics
+#[derive(Clone, Copy, PartialEq, Eq, Enum)]
+pub enum CertificateType {
+    StakeDelegation,
+    OwnerStakeDelegation,
+    PoolRegistration,
+    PoolRetirement,
+    PoolUpdate,
+    VotePlan,
+    VoteCast,
+    VoteTally,
+    UpdateProposal,
+    UpdateVote,
+    MintToken,
+    EvmMapping,
+}
+
+impl From<CertificateKind> for CertificateType {
+    fn from(kind: CertificateKind) -> CertificateType {
+        match kind {
+            CertificateKind::StakeDelegation => CertificateType::StakeDelegation,
+            CertificateKind::OwnerStakeDelegation => CertificateType::OwnerStakeDelegation,
+            CertificateKind::PoolRegistration => CertificateType::PoolRegistration,
+            CertificateKind::PoolRetirement => CertificateType::PoolRetirement,
+            CertificateKind::PoolUpdate => CertificateType::PoolUpdate,
+            CertificateKind::VotePlan => CertificateType::VotePlan,
+            CertificateKind::VoteCast => CertificateType::VoteCast,
+            CertificateKind::VoteTally => CertificateType::VoteTally,
+            CertificateKind::UpdateProposal => CertificateType::UpdateProposal,
+            CertificateKind::UpdateVote => CertificateType::UpdateVote,
+            CertificateKind::MintToken => CertificateType::MintToken,
+            CertificateKind::EvmMapping => CertificateType::EvmMapping,
+        }
+    }
+}
+
+impl From<&certificate::Certificate> for CertificateType {
+    fn from(certificate: &certificate::Certificate) -> CertificateType {
+        CertificateKind::from(certificate).into()
+    }
+}
+
+pub struct StakeDelegation {
+    delegation: certificate::StakeDelegation,
+    account: Option<ExplorerAddress>,
+}
 
 pub struct PoolRegistration(certificate::PoolRegistration);
 
@@ -50,17 +97,32 @@ pub struct MintToken(certificate::MintToken);
 
 pub struct EvmMapping(certificate::EvmMapping);
 
+impl StakeDelegation {
+    /// the account is known from the signature of the certificate, which tells a multisig
+    /// account from a single one
+    pub fn new(delegation: certificate::StakeDelegation, account: Option<ExplorerAddress>) -> Self {
+        StakeDelegation {
+            delegation,
+            account,
+        }
+    }
+}
+
 #[Object]
 impl StakeDelegation {
     // FIXME: Maybe a new Account type would be better?
     pub async fn account(&self, context: &Context<'_>) -> FieldResult<Address> {
+        if let Some(account) = &self.account {
+            return Ok(Address::from(account));
+        }
+
         let discrimination = extract_context(context).db.blockchain_config.discrimination;
-        self.0
+        self.delegation
             .account_id
             .to_single_account()
-            .ok_or_else(||
-                // TODO: Multisig address?
-                ApiError::Unimplemented.into())
+            .ok_or_else(|| {
+                ApiError::InternalError("invalid stake delegation account".to_owned()).into()
+            })
             .map(|single| {
                 chain_addr::Address(discrimination, chain_addr::Kind::Account(single.into()))
             })
@@ -70,7 +132,7 @@ impl StakeDelegation {
     pub async fn pools(&self) -> Vec<Pool> {
         use chain_impl_mockchain::account::DelegationType;
 
-        match self.0.get_delegation_type() {
+        match self.delegation.get_delegation_type() {
             DelegationType::NonDelegated => vec![],
             DelegationType::Full(id) => vec![Pool::from_valid_id(id.clone())],
             DelegationType::Ratio(delegation_ratio) => delegation_ratio
@@ -281,7 +343,7 @@ impl From<chain_impl_mockchain::certificate::Certificate> for Certificate {
     fn from(original: chain_impl_mockchain::certificate::Certificate) -> Certificate {
         match original {
             certificate::Certificate::StakeDelegation(c) => {
-                Certificate::StakeDelegation(StakeDelegation(c))
+                Certificate::StakeDelegation(StakeDelegation::new(c, None))
             }
             certificate::Certificate::OwnerStakeDelegation(c) => {
                 Certificate::OwnerStakeDelegation(OwnerStakeDelegation(c))
@@ -308,7 +370,7 @@ impl From<chain_impl_mockchain::certificate::Certificate> for Certificate {
 
 impl From<certificate::StakeDelegation> for StakeDelegation {
     fn from(delegation: certificate::StakeDelegation) -> StakeDelegation {
-        StakeDelegation(delegation)
+        StakeDelegation::new(delegation, None)
     }
 }
 
@@ -6,12 +6,12 @@ pub enum ApiError {
     InternalError(String),
     #[error("resource not found {0}")]
     NotFound(String),
-    #[error("feature not implemented yet")]
-    Unimplemented,
     #[error("invalid argument {0}")]
     ArgumentError(String),
     #[error("invalud pagination cursor {0}")]
     InvalidCursor(String),
     #[error("invalid address {0}")]
     InvalidAddress(String),
+    #[error("the subscription missed {0} updates of the main branch, subscribe again")]
+    SubscriptionLagged(u64),
 }
@@ -0,0 +1,216 @@
+use super::{
+    connections::{
+        compute_interval, ConnectionFields, InclusivePaginationInterval, PaginationInterval,
+        ValidatedPaginationArguments,
+    },
+    error::ApiError,
+    scalars::{IndexCursor, TransactionCount},
+    Block,
+};
+use crate::db::{
+    evm::{EvmAddress, ExplorerEvmTransaction},
+    indexing::ExplorerBlock,
+    persistent_sequence::PersistentSequence,
+    State,
+};
+use async_graphql::{
+    connection::{query, Connection, Edge, EmptyFields},
+    Enum, FieldResult, Object, SimpleObject,
+};
+use chain_impl_mockchain::{
+    block::HeaderId as HeaderHash, evm::EvmActionType, fragment::FragmentId,
+};
+use std::{convert::TryInto, str::FromStr};
+
+/// A transaction executed by the EVM
+pub struct EvmTransaction {
+    block: HeaderHash,
+    contents: ExplorerEvmTransaction,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Enum)]
+pub enum EvmTransactionKind {
+    Call,
+    Create,
+    Create2,
+}
+
+/// A contract deployed on the EVM and the transaction deploying it
+#[derive(SimpleObject)]
+pub struct EvmContract {
+    address: String,
+    deployment: EvmTransaction,
+}
+
+impl EvmTransaction {
+    pub fn from_block(block: &ExplorerBlock) -> Vec<EvmTransaction> {
+        block
+            .evm_transactions
+            .iter()
+            .map(|contents| EvmTransaction {
+                block: block.id(),
+                contents: contents.clone(),
+            })
+            .collect()
+    }
+
+    /// The transaction with the given id in the branch of the state
+    pub fn from_state(id: &FragmentId, state: &State) -> Option<Self> {
+        let block = state.evm().transaction_block(id)?;
+        let contents = state
+            .blocks
+            .lookup(&block)?
+            .evm_transactions
+            .iter()
+            .find(|tx| tx.id == *id)?
+            .clone();
+
+        Some(EvmTransaction { block, contents })
+    }
+}
+
+#[Object]
+impl EvmTransaction {
+    /// The hash that identifies the transaction
+    pub async fn id(&self) -> String {
+        format!("{}", self.contents.id)
+    }
+
+    pub async fn block(&self) -> Block {
+        Block::from_valid_hash(self.block)
+    }
+
+    pub async fn kind(&self) -> EvmTransactionKind {
+        match self.contents.transaction.action_type {
+            EvmActionType::Call { .. } => EvmTransactionKind::Call,
+            EvmActionType::Create { .. } => EvmTransactionKind::Create,
+            EvmActionType::Create2 { .. } => EvmTransactionKind::Create2,
+        }
+    }
+
+    pub async fn caller(&self) -> String {
+        format!("{:#x}", self.contents.transaction.caller)
+    }
+
+    /// The contract or the account called, `null` for a deployment
+    pub async fn recipient(&self) -> Option<String> {
+        self.contents
+            .recipient()
+            .map(|address| format!("{:#x}", address))
+    }
+
+    /// The address of the deployed contract, `null` for a call
+    pub async fn created_contract(&self) -> Option<String> {
+        self.contents
+            .created_contract
+            .map(|address| format!("{:#x}", address))
+    }
+
+    pub async fn value(&self) -> String {
+        self.contents.transaction.value.to_string()
+    }
+
+    pub async fn nonce(&self) -> String {
+        self.contents.transaction.nonce.to_string()
+    }
+
+    pub async fn gas_limit(&self) -> String {
+        self.contents.transaction.gas_limit.to_string()
+    }
+
+    /// The input of a call or the init code of a deployment, hex encoded
+    pub async fn data(&self) -> String {
+        let data: &[u8] = match &self.contents.transaction.action_type {
+            EvmActionType::Call { data, .. } => data,
+            EvmActionType::Create { init_code } => init_code,
+            EvmActionType::Create2 { init_code, .. } => init_code,
+        };
+
+        data.iter().fold(String::from("0x"), |mut hex, byte| {
+            hex.push_str(&format!("{:02x}", byte));
+            hex
+        })
+    }
+}
+
+fn parse_address(address: &str) -> FieldResult<EvmAddress> {
+    EvmAddress::from_str(address.trim_start_matches("0x"))
+        .map_err(|_| ApiError::InvalidAddress(address.to_string()).into())
+}
+
+pub fn contract(address: &str, state: &State) -> FieldResult<Option<EvmContract>> {
+    let contract = parse_address(address)?;
+
+    Ok(state
+        .evm()
+        .contract_deployment(&contract)
+        .and_then(|id| EvmTransaction::from_state(&id, state))
+        .map(|deployment| EvmContract {
+            address: format!("{:#x}", contract),
+            deployment,
+        }))
+}
+
+pub async fn transactions_by_address(
+    address: &str,
+    state: &State,
+    first: Option<i32>,
+    last: Option<i32>,
+    before: Option<String>,
+    after: Option<String>,
+) -> FieldResult<
+    Connection<IndexCursor, EvmTransaction, ConnectionFields<TransactionCount>, EmptyFields>,
+> {
+    let address = parse_address(address)?;
+    let transactions = state
+        .evm()
+        .transactions_by_address(&address)
+        .unwrap_or_else(PersistentSequence::new);
+
+    query(
+        after,
+        before,
+        first,
+        last,
+        |after, before, first, last| async move {
+            let boundaries = match transactions.len().checked_sub(1) {
+                Some(upper_bound) => PaginationInterval::Inclusive(InclusivePaginationInterval {
+                    lower_bound: 0u64,
+                    upper_bound,
+                }),
+                None => PaginationInterval::Empty,
+            };
+
+            let pagination_arguments = ValidatedPaginationArguments {
+                first,
+                last,
+                before: before.map(TryInto::try_into).transpose()?,
+                after: after.map(TryInto::try_into).transpose()?,
+            };
+
+            let (range, page_meta) = compute_interval(boundaries, pagination_arguments)?;
+
+            let mut connection = Connection::with_additional_fields(
+                page_meta.has_previous_page,
+                page_meta.has_next_page,
+                ConnectionFields {
+                    total_count: page_meta.total_count,
+                },
+            );
+
+            if let PaginationInterval::Inclusive(range) = range {
+                connection
+                    .edges
+                    .extend((range.lower_bound..=range.upper_bound).filter_map(|i| {
+                        transactions
+                            .get(i)
+                            .and_then(|id| EvmTransaction::from_state(id, state))
+                            .map(|tx| Edge::new(IndexCursor::from(i), tx))
+                    }));
+            }
+
+            Ok::<_, async_graphql::Error>(connection)
+        },
+    )
+    .await
+}
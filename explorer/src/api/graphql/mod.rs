@@ -1,7 +1,10 @@
+pub mod cache;
 mod certificates;
 mod config_param;
 mod connections;
 mod error;
+#[cfg(feature = "evm")]
+mod evm;
 mod scalars;
 
 use self::{
@@ -12,22 +15,24 @@ use self::{
     },
     error::ApiError,
     scalars::{
-        BlockCount, ChainLength, EpochNumber, ExternalProposalId, IndexCursor, NonZero,
-        PayloadType, PoolCount, PoolId, PublicKey, Slot, TransactionCount, Value, VoteOptionRange,
-        VotePlanId, VotePlanStatusCount, Weight,
+        BalanceChangeCount, BlockCount, ChainLength, EpochNumber, ExternalProposalId, IndexCursor,
+        NonZero, PayloadType, PoolCount, PoolId, PublicKey, Slot, TransactionCount, Value,
+        VoteOptionRange, VotePlanId, VotePlanStatusCount, Weight,
     },
 };
 use crate::db::{
     indexing::{
-        BlockProducer, EpochData, ExplorerAddress, ExplorerBlock, ExplorerTransaction,
-        ExplorerVote, ExplorerVotePlan, ExplorerVoteTally, StakePoolData,
+        BalanceChange as ExplorerBalanceChange, BlockProducer, EpochData, ExplorerAddress,
+        ExplorerBlock, ExplorerTransaction, ExplorerVote, ExplorerVotePlan, ExplorerVoteTally,
+        StakePoolData, Turnout,
     },
     persistent_sequence::PersistentSequence,
-    ExplorerDb, Settings as ChainSettings,
+    ExplorerDb, MainBranchUpdate, Settings as ChainSettings,
 };
 use async_graphql::{
     connection::{query, Connection, Edge, EmptyFields},
-    Context, EmptyMutation, FieldError, FieldResult, Object, SimpleObject, Subscription, Union,
+    ComplexObject, Context, EmptyMutation, FieldError, FieldResult, Object, SimpleObject,
+    Subscription, Union,
 };
 use cardano_legacy_address::Addr as OldAddress;
 use certificates::*;
@@ -36,6 +41,7 @@ use chain_impl_mockchain::{
     certificate,
     fragment::FragmentId,
     key::BftLeaderId,
+    value::Value as InternalValue,
     vote::{EncryptedVote, ProofOfCorrectVote},
 };
 use std::{
@@ -213,6 +219,131 @@ impl Branch {
         .await
     }
 
+    /// The balance of the address at the tip of the branch, or after the given block of the
+    /// branch
+    async fn address_balance(
+        &self,
+        address_bech32: String,
+        block: Option<String>,
+    ) -> FieldResult<Value> {
+        let address = Address::from_bech32(&address_bech32)?.id;
+        let state = self.state.state();
+
+        let balance = match state.address_balance(&address) {
+            Some(balance) => balance,
+            None => return Ok(Value::from(0u64)),
+        };
+
+        match block {
+            None => Ok(Value(balance.balance)),
+            Some(block) => {
+                let hash = HeaderHash::from_str(&block)?;
+                let block = state.blocks.lookup(&hash).ok_or_else(|| {
+                    ApiError::NotFound(format!("block {} in branch {}", hash, self.id))
+                })?;
+
+                Ok(Value(balance.balance_at(block.chain_length())))
+            }
+        }
+    }
+
+    /// The transactions that changed the balance of the address in the branch, in chain order
+    async fn address_balance_history(
+        &self,
+        address_bech32: String,
+        first: Option<i32>,
+        last: Option<i32>,
+        before: Option<String>,
+        after: Option<String>,
+    ) -> FieldResult<
+        Connection<IndexCursor, BalanceChange, ConnectionFields<BalanceChangeCount>, EmptyFields>,
+    > {
+        let address = Address::from_bech32(&address_bech32)?.id;
+        let changes = self
+            .state
+            .state()
+            .address_balance(&address)
+            .map(|balance| balance.changes.clone())
+            .unwrap_or_default();
+
+        query(
+            after,
+            before,
+            first,
+            last,
+            |after, before, first, last| async move {
+                let boundaries = if !changes.is_empty() {
+                    PaginationInterval::Inclusive(InclusivePaginationInterval {
+                        lower_bound: 0u64,
+                        upper_bound: changes.len() - 1,
+                    })
+                } else {
+                    PaginationInterval::Empty
+                };
+
+                let pagination_arguments = ValidatedPaginationArguments {
+                    first,
+                    last,
+                    before: before.map(TryInto::try_into).transpose()?,
+                    after: after.map(TryInto::try_into).transpose()?,
+                };
+
+                let (range, page_meta) = compute_interval(boundaries, pagination_arguments)?;
+
+                let mut connection = Connection::with_additional_fields(
+                    page_meta.has_previous_page,
+                    page_meta.has_next_page,
+                    ConnectionFields {
+                        total_count: page_meta.total_count,
+                    },
+                );
+
+                if let PaginationInterval::Inclusive(range) = range {
+                    connection
+                        .edges
+                        .extend((range.lower_bound..=range.upper_bound).filter_map(|i| {
+                            changes.get(i).map(|change| {
+                                Edge::new(
+                                    IndexCursor::from(i),
+                                    BalanceChange(ExplorerBalanceChange::clone(change)),
+                                )
+                            })
+                        }));
+                }
+
+                Ok::<_, async_graphql::Error>(connection)
+            },
+        )
+        .await
+    }
+
+    /// The EVM transactions of the branch sent by the given address, or calling or deploying
+    /// it, in chain order
+    #[cfg(feature = "evm")]
+    async fn evm_transactions_by_address(
+        &self,
+        address: String,
+        first: Option<i32>,
+        last: Option<i32>,
+        before: Option<String>,
+        after: Option<String>,
+    ) -> FieldResult<
+        Connection<
+            IndexCursor,
+            evm::EvmTransaction,
+            ConnectionFields<TransactionCount>,
+            EmptyFields,
+        >,
+    > {
+        evm::transactions_by_address(&address, self.state.state(), first, last, before, after).await
+    }
+
+    /// The contract deployed at the given address in the branch, `null` if there is none
+    #[cfg(feature = "evm")]
+    async fn evm_contract(&self, address: String) -> FieldResult<Option<evm::EvmContract>> {
+        evm::contract(&address, self.state.state())
+    }
+
     pub async fn all_vote_plans(
         &self,
         first: Option<i32>,
@@ -548,6 +679,17 @@ impl Block {
             .map(|b| b.date().into())
     }
 
+    /// The EVM transactions contained in the block, in the order of the block
+    #[cfg(feature = "evm")]
+    pub async fn evm_transactions(
+        &self,
+        context: &Context<'_>,
+    ) -> FieldResult<Vec<evm::EvmTransaction>> {
+        self.fetch_explorer_block(&extract_context(context).db)
+            .await
+            .map(|block| evm::EvmTransaction::from_block(&block))
+    }
+
     /// The transactions contained in the block
     pub async fn transactions(
         &self,
@@ -895,9 +1037,53 @@ impl Transaction {
         &self,
         context: &Context<'_>,
     ) -> FieldResult<Option<certificates::Certificate>> {
-        self.get_contents(context)
-            .await
-            .map(|transaction| transaction.certificate.map(Certificate::from))
+        self.get_contents(context).await.map(|transaction| {
+            let delegation_account = transaction.delegation_account;
+            transaction
+                .certificate
+                .map(|certificate| match certificate {
+                    certificate::Certificate::StakeDelegation(delegation) => {
+                        Certificate::StakeDelegation(StakeDelegation::new(
+                            delegation,
+                            delegation_account,
+                        ))
+                    }
+                    certificate => Certificate::from(certificate),
+                })
+        })
+    }
+}
+
+/// The change of the balance of an address by a transaction
+pub struct BalanceChange(ExplorerBalanceChange);
+
+#[Object]
+impl BalanceChange {
+    pub async fn block(&self) -> Block {
+        Block::from_valid_hash(self.0.block)
+    }
+
+    pub async fn transaction(&self) -> Transaction {
+        Transaction {
+            id: self.0.transaction,
+            block_hashes: vec![self.0.block],
+            contents: None,
+        }
+    }
+
+    /// The value received by the address in the outputs of the transaction
+    pub async fn received(&self) -> Value {
+        Value(self.0.received)
+    }
+
+    /// The value spent by the address in the inputs of the transaction, including the fees
+    pub async fn spent(&self) -> Value {
+        Value(self.0.spent)
+    }
+
+    /// The balance of the address after the transaction
+    pub async fn balance(&self) -> Value {
+        Value(self.0.balance)
     }
 }
 
@@ -949,11 +1135,71 @@ impl Address {
         }
     }
 
-    async fn delegation(&self, _context: &Context<'_>) -> FieldResult<Pool> {
-        Err(ApiError::Unimplemented.into())
+    /// The pools the account of the address delegates its stake to, empty if it doesn't
+    /// delegate or the address has no account
+    async fn delegation(&self, context: &Context<'_>) -> Vec<PoolDelegation> {
+        use chain_impl_mockchain::account::DelegationType;
+
+        let delegation = extract_context(context).db.get_delegation(&self.id).await;
+
+        match delegation.as_deref() {
+            None | Some(DelegationType::NonDelegated) => vec![],
+            Some(DelegationType::Full(id)) => vec![PoolDelegation {
+                pool: Pool::from_valid_id(id.clone()),
+                parts: 1,
+                total_parts: 1,
+            }],
+            Some(DelegationType::Ratio(ratio)) => ratio
+                .pools()
+                .iter()
+                .map(|(id, parts)| PoolDelegation {
+                    pool: Pool::from_valid_id(id.clone()),
+                    parts: (*parts).into(),
+                    total_parts: ratio.parts().into(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The votes cast by the account of the address in the vote plans of the main branch
+    async fn votes(&self, context: &Context<'_>) -> Vec<AddressVote> {
+        extract_context(context)
+            .db
+            .get_votes_by_address(&self.id)
+            .await
+            .into_iter()
+            .map(|(vote_plan, proposal_index, vote)| AddressVote {
+                vote_plan: VotePlanId::from(vote_plan.id.clone()),
+                proposal_index: proposal_index.into(),
+                proposal_id: ExternalProposalId::from(
+                    vote_plan.proposals[proposal_index as usize]
+                        .proposal_id
+                        .clone(),
+                ),
+                payload: vote.as_ref().into(),
+            })
+            .collect()
     }
 }
 
+/// A pool an account delegates to, receiving `parts` out of `totalParts` of the stake
+#[derive(SimpleObject)]
+pub struct PoolDelegation {
+    pool: Pool,
+    parts: i32,
+    total_parts: i32,
+}
+
+/// A vote cast on a proposal, the last one counting when the account voted several times
+#[derive(SimpleObject)]
+pub struct AddressVote {
+    vote_plan: VotePlanId,
+    /// The index of the proposal in the vote plan
+    proposal_index: i32,
+    proposal_id: ExternalProposalId,
+    payload: VotePayloadStatus,
+}
+
 pub struct TaxType(chain_impl_mockchain::rewards::TaxType);
 
 #[Object]
@@ -1156,6 +1402,90 @@ impl Pool {
                 }),
         }
     }
+
+    /// The blocks produced by the pool in the given epoch of the main branch, compared to the
+    /// blocks expected from its stake at the beginning of the epoch
+    pub async fn stats(
+        &self,
+        context: &Context<'_>,
+        epoch: EpochNumber,
+    ) -> FieldResult<PoolEpochStats> {
+        let db = &extract_context(context).db;
+        let (data, tip_date) = db
+            .get_main_branch_epoch(epoch.0)
+            .await
+            .ok_or_else(|| ApiError::NotFound(format!("epoch {}", epoch.0)))?;
+
+        // the current epoch only counts the slots up to the tip
+        let elapsed_slots = if tip_date.epoch == epoch.0 {
+            tip_date.slot_id + 1
+        } else {
+            db.blockchain_config.slots_per_epoch
+        };
+
+        let blocks_produced = data
+            .stats
+            .pool_blocks
+            .lookup(&self.id)
+            .map_or(0, |blocks| **blocks);
+
+        // a pool with the relative stake `s` leads a slot with the probability
+        // `1 - (1 - f)^s`, `f` being the active slots coefficient. The stake is the one the
+        // ledger elects the leaders with: the distribution at the end of the previous epoch,
+        // without the rewards which are not tracked
+        let expected_blocks = db.blockchain_config.active_slots_coeff.map(|coeff| {
+            let distribution = &data.stake_distribution;
+            let total_stake: u64 = distribution.pools.values().map(|stake| stake.0).sum();
+            let pool_stake = distribution.pools.get(&self.id).map_or(0, |stake| stake.0);
+            if total_stake == 0 {
+                return 0.0;
+            }
+
+            let relative_stake = pool_stake as f64 / total_stake as f64;
+            let coeff = coeff.to_millis() as f64 / 1000.0;
+            let leadership_probability = 1.0 - (1.0 - coeff).powf(relative_stake);
+            f64::from(elapsed_slots) * leadership_probability
+        });
+
+        Ok(PoolEpochStats {
+            epoch: Epoch::from_epoch_number(epoch.0),
+            blocks_produced,
+            expected_blocks,
+            missed_slots: expected_blocks
+                .map(|expected| (expected.round() as u32).saturating_sub(blocks_produced)),
+            delegators: data
+                .stake_distribution
+                .delegators
+                .get(&self.id)
+                .copied()
+                .unwrap_or(0),
+            estimated_rewards: data.estimated_rewards.as_ref().map(|rewards| {
+                Value(
+                    rewards
+                        .pools
+                        .get(&self.id)
+                        .copied()
+                        .unwrap_or_else(InternalValue::zero),
+                )
+            }),
+        })
+    }
+}
+
+/// The blocks produced by a pool in an epoch. The slots led by a pool are private, so the
+/// missed slots are estimated from the expected blocks, which are only known with the genesis
+/// praos consensus and only cover the slots up to the tip in the current epoch
+#[derive(SimpleObject)]
+pub struct PoolEpochStats {
+    epoch: Epoch,
+    blocks_produced: u32,
+    expected_blocks: Option<f64>,
+    missed_slots: Option<u32>,
+    /// The number of accounts delegating to the pool at the beginning of the epoch
+    delegators: u32,
+    /// An estimate of the rewards of the pool for the epoch, before the pool tax, `null` until
+    /// the first block of the next epoch. See `EpochStats.estimatedRewards` for its limits
+    estimated_rewards: Option<Value>,
 }
 
 pub struct Settings {}
@@ -1216,9 +1546,27 @@ impl Epoch {
         EpochNumber(self.id)
     }
 
-    /// Not yet implemented
-    pub async fn stake_distribution(&self) -> FieldResult<StakeDistribution> {
-        Err(ApiError::Unimplemented.into())
+    /// The stake delegated to the pools at the beginning of the epoch
+    pub async fn stake_distribution(
+        &self,
+        context: &Context<'_>,
+    ) -> FieldResult<StakeDistribution> {
+        let data = self
+            .get_epoch_data(&extract_context(context).db)
+            .await
+            .ok_or_else(|| ApiError::NotFound(format!("epoch {}", self.id)))?;
+
+        Ok(StakeDistribution {
+            pools: data
+                .stake_distribution
+                .pools
+                .iter()
+                .map(|(id, stake)| PoolStakeDistribution {
+                    pool: Pool::from_valid_id(id.clone()),
+                    delegated_stake: Value(*stake),
+                })
+                .collect(),
+        })
     }
 
     pub async fn first_block(&self, context: &Context<'_>) -> Option<Block> {
@@ -1238,6 +1586,95 @@ impl Epoch {
             .await
             .map_or(0u32.into(), |data| data.total_blocks.into())
     }
+
+    /// Aggregates over the blocks of the epoch and the rewards estimated at its end
+    pub async fn stats(&self, context: &Context<'_>) -> FieldResult<EpochStats> {
+        self.get_epoch_data(&extract_context(context).db)
+            .await
+            .map(EpochStats)
+            .ok_or_else(|| ApiError::NotFound(format!("epoch {}", self.id)).into())
+    }
+}
+
+pub struct EpochStats(EpochData);
+
+#[Object]
+impl EpochStats {
+    pub async fn total_transactions(&self) -> TransactionCount {
+        self.0.stats.total_transactions
+    }
+
+    pub async fn total_fees(&self) -> Value {
+        Value(self.0.stats.total_fees)
+    }
+
+    pub async fn total_output(&self) -> Value {
+        Value(self.0.stats.total_output)
+    }
+
+    /// The number of certificates of each type in the epoch, the types without certificates
+    /// are omitted
+    pub async fn certificates(&self) -> Vec<CertificateCount> {
+        let mut certificates: Vec<_> = self.0.stats.certificates.iter().collect();
+        certificates.sort_by_key(|(kind, _)| **kind);
+
+        certificates
+            .into_iter()
+            .map(|(kind, count)| CertificateCount {
+                certificate_type: CertificateType::from(*kind),
+                count: *count,
+            })
+            .collect()
+    }
+
+    /// The average size of the block contents, in bytes
+    pub async fn average_block_content_size(&self) -> f64 {
+        self.0.stats.total_content_size as f64 / f64::from(self.0.total_blocks)
+    }
+
+    /// The average ratio of the block content size to the maximum size, `null` when the
+    /// genesis block doesn't limit it
+    pub async fn average_block_fill(&self, context: &Context<'_>) -> Option<f64> {
+        extract_context(context)
+            .db
+            .blockchain_config
+            .block_content_max_size
+            .filter(|max_size| *max_size > 0)
+            .map(|max_size| {
+                self.0.stats.total_content_size as f64
+                    / f64::from(self.0.total_blocks)
+                    / f64::from(max_size)
+            })
+    }
+
+    /// An estimate of the rewards of the epoch, `null` until the first block of the next
+    /// epoch. The estimate uses the reward parameters of the genesis block and the stake at the
+    /// end of the epoch, ignoring the parameter updates and the reward limits, so it can differ
+    /// from the rewards the node distributes. The rewards are not included in the balances and
+    /// the stake
+    pub async fn estimated_rewards(&self) -> Option<EstimatedEpochRewards> {
+        self.0
+            .estimated_rewards
+            .as_ref()
+            .map(|rewards| EstimatedEpochRewards {
+                accounts: Value(rewards.accounts),
+                treasury: Value(rewards.treasury),
+            })
+    }
+}
+
+#[derive(SimpleObject)]
+pub struct EstimatedEpochRewards {
+    /// The rewards of the delegators and the pool owners
+    accounts: Value,
+    /// The part of the rewards sent to the treasury
+    treasury: Value,
+}
+
+#[derive(SimpleObject)]
+pub struct CertificateCount {
+    certificate_type: CertificateType,
+    count: u32,
 }
 
 #[derive(SimpleObject)]
@@ -1288,13 +1725,14 @@ pub enum VotePayloadStatus {
     Private(VotePayloadPrivateStatus),
 }
 
-// TODO do proper vote tally
+/// The weight of each option, from the stake of the voters at the tally
 #[derive(Clone, SimpleObject)]
 pub struct TallyPublicStatus {
     results: Vec<Weight>,
     options: VoteOptionRange,
 }
 
+/// The weight of each option, decrypted by the committee at the tally
 #[derive(Clone, SimpleObject)]
 pub struct TallyPrivateStatus {
     results: Option<Vec<Weight>>,
@@ -1308,6 +1746,7 @@ pub enum TallyStatus {
 }
 
 #[derive(Clone, SimpleObject)]
+#[graphql(complex)]
 pub struct VotePlanStatus {
     id: VotePlanId,
     vote_start: BlockDate,
@@ -1315,6 +1754,47 @@ pub struct VotePlanStatus {
     committee_end: BlockDate,
     payload_type: PayloadType,
     proposals: Vec<VoteProposalStatus>,
+    #[graphql(skip)]
+    turnout: Option<Turnout>,
+}
+
+#[ComplexObject]
+impl VotePlanStatus {
+    /// The accounts which voted on any of the proposals and their stake, at the tally once the
+    /// vote plan is tallied and at the tip of the main branch before
+    pub async fn turnout(&self, context: &Context<'_>) -> VoteTurnout {
+        match self.turnout {
+            Some(turnout) => turnout.into(),
+            None => {
+                let voters: Vec<_> = self
+                    .proposals
+                    .iter()
+                    .flat_map(|proposal| proposal.voters())
+                    .collect();
+
+                extract_context(context)
+                    .db
+                    .get_turnout(&voters)
+                    .await
+                    .into()
+            }
+        }
+    }
+}
+
+#[derive(Clone, SimpleObject)]
+pub struct VoteTurnout {
+    voters: u64,
+    voting_power: Value,
+}
+
+impl From<Turnout> for VoteTurnout {
+    fn from(turnout: Turnout) -> Self {
+        VoteTurnout {
+            voters: turnout.voters,
+            voting_power: Value(turnout.voting_power),
+        }
+    }
 }
 
 impl VotePlanStatus {
@@ -1343,6 +1823,7 @@ impl VotePlanStatus {
             committee_end,
             payload_type,
             proposals,
+            turnout,
         } = (*vote_plan).clone();
 
         VotePlanStatus {
@@ -1373,27 +1854,32 @@ impl VotePlanStatus {
                     votes: proposal
                         .votes
                         .iter()
-                        .map(|(key, vote)| match vote.as_ref() {
-                            ExplorerVote::Public(choice) => VoteStatus {
-                                address: key.into(),
-                                payload: VotePayloadStatus::Public(VotePayloadPublicStatus {
-                                    choice: choice.as_byte().into(),
-                                }),
-                            },
-                            ExplorerVote::Private {
-                                proof,
-                                encrypted_vote,
-                            } => VoteStatus {
-                                address: key.into(),
-                                payload: VotePayloadStatus::Private(VotePayloadPrivateStatus {
-                                    proof: proof.clone(),
-                                    encrypted_vote: encrypted_vote.clone(),
-                                }),
-                            },
+                        .map(|(key, vote)| VoteStatus {
+                            address: key.into(),
+                            payload: vote.as_ref().into(),
                         })
                         .collect(),
+                    turnout: proposal.turnout,
                 })
                 .collect(),
+            turnout,
+        }
+    }
+}
+
+impl From<&ExplorerVote> for VotePayloadStatus {
+    fn from(vote: &ExplorerVote) -> Self {
+        match vote {
+            ExplorerVote::Public(choice) => VotePayloadStatus::Public(VotePayloadPublicStatus {
+                choice: choice.as_byte().into(),
+            }),
+            ExplorerVote::Private {
+                proof,
+                encrypted_vote,
+            } => VotePayloadStatus::Private(VotePayloadPrivateStatus {
+                proof: proof.clone(),
+                encrypted_vote: encrypted_vote.clone(),
+            }),
         }
     }
 }
@@ -1410,6 +1896,13 @@ pub struct VoteProposalStatus {
     options: VoteOptionRange,
     tally: Option<TallyStatus>,
     votes: Vec<VoteStatus>,
+    turnout: Option<Turnout>,
+}
+
+impl VoteProposalStatus {
+    fn voters(&self) -> impl Iterator<Item = ExplorerAddress> + '_ {
+        self.votes.iter().map(|vote| vote.address.id.clone())
+    }
 }
 
 #[Object]
@@ -1426,6 +1919,23 @@ impl VoteProposalStatus {
         self.tally.as_ref()
     }
 
+    /// The accounts which voted on the proposal and their stake, at the tally once the vote
+    /// plan is tallied and at the tip of the main branch before
+    pub async fn turnout(&self, context: &Context<'_>) -> VoteTurnout {
+        match self.turnout {
+            Some(turnout) => turnout.into(),
+            None => {
+                let voters: Vec<_> = self.voters().collect();
+
+                extract_context(context)
+                    .db
+                    .get_turnout(&voters)
+                    .await
+                    .into()
+            }
+        }
+    }
+
     pub async fn votes(
         &self,
         first: Option<i32>,
@@ -1500,7 +2010,10 @@ pub struct Query;
 #[Object]
 impl Query {
     async fn block(&self, context: &Context<'_>, id: String) -> FieldResult<Block> {
-        Block::from_string_hash(id, &extract_context(context).db).await
+        let block = Block::from_string_hash(id, &extract_context(context).db).await?;
+        cache::require_confirmed(context, &[block.hash]).await;
+
+        Ok(block)
     }
 
     async fn blocks_by_chain_length(
@@ -1522,8 +2035,27 @@ impl Query {
 
     async fn transaction(&self, context: &Context<'_>, id: String) -> FieldResult<Transaction> {
         let id = FragmentId::from_str(&id)?;
+        let transaction = Transaction::from_id(id, context).await?;
+        cache::require_confirmed(context, &transaction.block_hashes).await;
 
-        Transaction::from_id(id, context).await
+        Ok(transaction)
+    }
+
+    #[cfg(feature = "evm")]
+    async fn evm_transaction(
+        &self,
+        context: &Context<'_>,
+        id: String,
+    ) -> FieldResult<evm::EvmTransaction> {
+        let id = FragmentId::from_str(&id)?;
+
+        extract_context(context)
+            .db
+            .get_branches()
+            .await
+            .iter()
+            .find_map(|(_, state)| evm::EvmTransaction::from_state(&id, state.state()))
+            .ok_or_else(|| ApiError::NotFound(format!("evm transaction not found: {}", id)).into())
     }
 
     /// get all current tips, sorted (descending) by their length
@@ -1573,12 +2105,270 @@ impl Query {
     ) -> FieldResult<VotePlanStatus> {
         VotePlanStatus::vote_plan_from_id(VotePlanId(id), context).await
     }
+
+    /// Look up a block hash, a fragment id, a chain length, an address, a stake pool id or a
+    /// vote plan id, returning every match. An empty list is returned when nothing matches.
+    pub async fn search(&self, context: &Context<'_>, term: String) -> Vec<SearchResult> {
+        search(&extract_context(context).db, &term).await
+    }
+}
+
+/// A match of the search query
+#[derive(Union)]
+pub enum SearchResult {
+    Block(Block),
+    Transaction(Transaction),
+    Address(Address),
+    Pool(Pool),
+    VotePlan(VotePlanStatus),
+}
+
+/// The matches of the term, as described by `Query::search`
+async fn search(db: &ExplorerDb, term: &str) -> Vec<SearchResult> {
+    let term = term.trim();
+    let mut results = Vec::new();
+
+    if let Ok(length) = term.parse::<u32>() {
+        results.extend(
+            db.find_blocks_by_chain_length(length.into())
+                .await
+                .into_iter()
+                .map(|hash| SearchResult::Block(Block::from_valid_hash(hash))),
+        );
+    }
+
+    // block hashes, fragment ids, pool ids and vote plan ids share the same hex encoding
+    if let Ok(hash) = HeaderHash::from_str(term) {
+        if let Some(block) = db.get_block(&hash).await {
+            results.push(SearchResult::Block(Block::from_contents(block)));
+        }
+    }
+
+    if let Ok(id) = FragmentId::from_str(term) {
+        let block_hashes = db.find_blocks_by_transaction(&id).await;
+        if !block_hashes.is_empty() {
+            results.push(SearchResult::Transaction(Transaction {
+                id,
+                block_hashes,
+                contents: None,
+            }));
+        }
+    }
+
+    if let Ok(id) = certificate::PoolId::from_str(term) {
+        if let Some(data) = db.get_stake_pool_data(&id).await {
+            results.push(SearchResult::Pool(Pool::new_with_data(id, data)));
+        }
+    }
+
+    if let Ok(id) = certificate::VotePlanId::from_str(term) {
+        if let Some(vote_plan) = db.get_vote_plan_by_id(&id).await {
+            results.push(SearchResult::VotePlan(VotePlanStatus::vote_plan_from_data(
+                vote_plan,
+            )));
+        }
+    }
+
+    // any well formed address would match otherwise, only the ones seen in a block do
+    if let Ok(address) = Address::from_bech32(term) {
+        if db.is_address_known(&address.id).await {
+            results.push(SearchResult::Address(address));
+        }
+    }
+
+    results
+}
+
+/// A switch of the main branch to another branch, the blocks rolled back are not in the main
+/// branch anymore
+pub struct Rollback(Arc<MainBranchUpdate>);
+
+#[Object]
+impl Rollback {
+    /// The last block the previous and the new main branch have in common
+    pub async fn fork_point(&self) -> Block {
+        Block::from_valid_hash(self.0.fork_point)
+    }
+
+    /// The ids of the blocks removed from the main branch, from the previous tip down to the
+    /// fork point
+    pub async fn rolled_back(&self) -> Vec<String> {
+        self.0.rolled_back.iter().map(ToString::to_string).collect()
+    }
+
+    /// The tip of the new main branch
+    pub async fn tip(&self) -> Branch {
+        Branch::from_id_and_state(self.0.tip, self.0.state.clone())
+    }
+}
+
+/// The updates of the main branch. A subscriber lagging too far behind gets an error and the
+/// stream ends, as the updates it missed, the rollbacks included, are not sent again
+fn main_branch_updates(
+    db: &ExplorerDb,
+) -> impl futures::Stream<Item = FieldResult<Arc<MainBranchUpdate>>> {
+    use futures::StreamExt;
+    use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+
+    db.main_branch_subscription()
+        .map(|update| {
+            update.map_err(|BroadcastStreamRecvError::Lagged(missed)| {
+                FieldError::from(ApiError::SubscriptionLagged(missed))
+            })
+        })
+        .scan(false, |lagged, update| {
+            if *lagged {
+                return futures::future::ready(None);
+            }
+            *lagged = update.is_err();
+            futures::future::ready(Some(update))
+        })
+}
+
+/// The transactions added to the main branch which match the filter, in chain order
+fn applied_transactions<F>(
+    db: &ExplorerDb,
+    filter: F,
+) -> impl futures::Stream<Item = FieldResult<Transaction>>
+where
+    F: Fn(&ExplorerTransaction) -> bool,
+{
+    use futures::StreamExt;
+
+    applied_blocks(db).flat_map(move |block| {
+        let transactions = match block {
+            Ok(block) => {
+                let mut transactions: Vec<_> = block
+                    .transactions
+                    .values()
+                    .filter(|tx| filter(tx))
+                    .cloned()
+                    .collect();
+                transactions.sort_unstable_by_key(|tx| tx.offset_in_block);
+
+                transactions
+                    .into_iter()
+                    .map(|tx| Ok(Transaction::from_contents_in_block(tx, block.id())))
+                    .collect()
+            }
+            Err(e) => vec![Err(e)],
+        };
+
+        futures::stream::iter(transactions)
+    })
+}
+
+/// The blocks added to the main branch, in chain order
+fn applied_blocks(db: &ExplorerDb) -> impl futures::Stream<Item = FieldResult<Arc<ExplorerBlock>>> {
+    use futures::StreamExt;
+
+    main_branch_updates(db).flat_map(|update| {
+        let blocks = match update {
+            Ok(update) => update.applied.iter().cloned().map(Ok).collect(),
+            Err(e) => vec![Err(e)],
+        };
+
+        futures::stream::iter(blocks)
+    })
+}
+
+impl Transaction {
+    fn from_contents_in_block(contents: ExplorerTransaction, block: HeaderHash) -> Transaction {
+        Transaction {
+            id: contents.id,
+            block_hashes: vec![block],
+            contents: Some(contents),
+        }
+    }
 }
 
 pub struct Subscription;
 
 #[Subscription]
 impl Subscription {
+    /// The blocks added to the main branch, in chain order. On a rollback, the blocks of the
+    /// new main branch following the fork point are sent
+    async fn blocks(
+        &self,
+        context: &Context<'_>,
+    ) -> impl futures::Stream<Item = FieldResult<Block>> {
+        use futures::StreamExt;
+
+        applied_blocks(&extract_context(context).db).map(|block| block.map(Block::from))
+    }
+
+    /// The transactions added to the main branch which have the address in their inputs or
+    /// outputs
+    async fn transactions_by_address(
+        &self,
+        context: &Context<'_>,
+        address_bech32: String,
+    ) -> FieldResult<impl futures::Stream<Item = FieldResult<Transaction>>> {
+        let address = Address::from_bech32(&address_bech32)?.id;
+
+        Ok(applied_transactions(
+            &extract_context(context).db,
+            move |tx| {
+                tx.outputs()
+                    .iter()
+                    .map(|output| &output.address)
+                    .chain(tx.inputs().iter().map(|input| &input.address))
+                    .any(|tx_address| tx_address == &address)
+            },
+        ))
+    }
+
+    /// The transactions added to the main branch which carry a certificate of the given type
+    async fn certificates(
+        &self,
+        context: &Context<'_>,
+        certificate_type: CertificateType,
+    ) -> impl futures::Stream<Item = FieldResult<Transaction>> {
+        applied_transactions(&extract_context(context).db, move |tx| {
+            tx.certificate
+                .as_ref()
+                .map(|certificate| CertificateType::from(certificate) == certificate_type)
+                .unwrap_or(false)
+        })
+    }
+
+    /// The vote casts on the given vote plan added to the main branch
+    async fn vote_casts(
+        &self,
+        context: &Context<'_>,
+        vote_plan_id: VotePlanId,
+    ) -> FieldResult<impl futures::Stream<Item = FieldResult<Transaction>>> {
+        let vote_plan_id = certificate::VotePlanId::from_str(&vote_plan_id.0)
+            .map_err(|err| -> FieldError { ApiError::ArgumentError(err.to_string()).into() })?;
+
+        Ok(applied_transactions(
+            &extract_context(context).db,
+            move |tx| {
+                matches!(
+                    &tx.certificate,
+                    Some(certificate::Certificate::VoteCast(vote_cast))
+                        if vote_cast.vote_plan() == &vote_plan_id
+                )
+            },
+        ))
+    }
+
+    /// The switches of the main branch to another branch, so that the data of the blocks
+    /// rolled back can be invalidated
+    async fn rollbacks(
+        &self,
+        context: &Context<'_>,
+    ) -> impl futures::Stream<Item = FieldResult<Rollback>> {
+        use futures::StreamExt;
+
+        main_branch_updates(&extract_context(context).db).filter_map(|update| async move {
+            match update {
+                Ok(update) => (!update.rolled_back.is_empty()).then(|| Ok(Rollback(update))),
+                Err(e) => Some(Err(e)),
+            }
+        })
+    }
+
     async fn tip(&self, context: &Context<'_>) -> impl futures::Stream<Item = Branch> {
         use futures::StreamExt;
         extract_context(context)
@@ -1603,3 +2393,176 @@ pub struct EContext {
 fn extract_context<'a>(context: &Context<'a>) -> &'a EContext {
     context.data_unchecked::<EContext>()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::{bootstrapped_db, child_block};
+    use chain_addr::Discrimination;
+    use chain_core::property::{Block as _, Fragment as _};
+    use chain_impl_mockchain::{
+        block::{Block as LibBlock, BlockDate},
+        chaintypes::ConsensusVersion,
+        fee::LinearFee,
+        fragment::Fragment,
+    };
+    use futures::StreamExt;
+    use jormungandr_lib::interfaces::{
+        Block0Configuration, BlockchainConfiguration, Initial, InitialUTxO, LegacyUTxO,
+    };
+
+    const OLD_ADDRESS: &str = "DdzFFzCqrht5TM5GznWhJ3GTpKawtJuA295F8igwXQXyt2ih1TL1XKnZqRBQBoLpyYVKfNKgCXPBUYruUneC83KjGK6QNAoBSqRJovbG";
+
+    /// A database with the wallet and the old address funded and the pool registered in the
+    /// genesis block
+    fn searchable_db(wallet: &thor::Wallet, pool: &thor::StakePool) -> (ExplorerDb, LibBlock) {
+        let block0 = Block0Configuration {
+            blockchain_configuration: BlockchainConfiguration::new(
+                Discrimination::Test,
+                ConsensusVersion::Bft,
+                LinearFee::new(0, 0, 0),
+            ),
+            initial: vec![
+                Initial::Fund(vec![InitialUTxO {
+                    address: wallet.address(),
+                    value: 1_000.into(),
+                }]),
+                Initial::LegacyFund(vec![LegacyUTxO {
+                    address: OLD_ADDRESS.parse().unwrap(),
+                    value: 1_000.into(),
+                }]),
+                Initial::Cert(
+                    thor::signed_stake_pool_cert(BlockDate::first().next_epoch(), pool).into(),
+                ),
+            ],
+        }
+        .to_block();
+
+        (ExplorerDb::bootstrap(block0.clone(), None).unwrap(), block0)
+    }
+
+    fn search_blocking(db: &ExplorerDb, term: &str) -> Vec<SearchResult> {
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(search(db, term))
+    }
+
+    #[test]
+    fn search_tells_the_block_hashes_from_the_fragment_and_pool_ids() {
+        let wallet = thor::Wallet::default();
+        let pool = thor::StakePool::new(&thor::Wallet::default());
+        let (db, block0) = searchable_db(&wallet, &pool);
+        let funding = block0
+            .contents()
+            .iter()
+            .find_map(|fragment| match fragment {
+                Fragment::Transaction(_) => Some(fragment.id()),
+                _ => None,
+            })
+            .unwrap();
+
+        match &search_blocking(&db, &block0.id().to_string())[..] {
+            [SearchResult::Block(block)] => assert_eq!(block.hash, block0.id()),
+            _ => panic!("the block hash to only match the block"),
+        }
+        match &search_blocking(&db, &funding.to_string())[..] {
+            [SearchResult::Transaction(transaction)] => {
+                assert_eq!(transaction.id, funding);
+                assert_eq!(transaction.block_hashes, vec![block0.id()]);
+            }
+            _ => panic!("the fragment id to only match the transaction"),
+        }
+        match &search_blocking(&db, &pool.id().to_string())[..] {
+            [SearchResult::Pool(found)] => assert_eq!(found.id, pool.id()),
+            _ => panic!("the pool id to only match the pool"),
+        }
+    }
+
+    #[test]
+    fn search_matches_the_chain_length_on_every_branch() {
+        let (db, block0) = bootstrapped_db();
+        let a1 = child_block(&block0, 1);
+        let b1 = child_block(&block0, 2);
+        let a2 = child_block(&a1, 3);
+
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            for block in [&a1, &b1, &a2] {
+                db.apply_block(block.clone()).await.unwrap();
+            }
+        });
+
+        let mut hashes: Vec<_> = search_blocking(&db, "1")
+            .iter()
+            .map(|result| match result {
+                SearchResult::Block(block) => block.hash,
+                _ => panic!("a chain length to only match blocks"),
+            })
+            .collect();
+        hashes.sort_unstable();
+        let mut expected = vec![a1.id(), b1.id()];
+        expected.sort_unstable();
+        assert_eq!(hashes, expected);
+
+        match &search_blocking(&db, " 2 ")[..] {
+            [SearchResult::Block(block)] => assert_eq!(block.hash, a2.id()),
+            _ => panic!("the chain length to match the block of the longer branch"),
+        }
+    }
+
+    #[test]
+    fn search_only_matches_the_addresses_in_the_chain() {
+        let wallet = thor::Wallet::default();
+        let pool = thor::StakePool::new(&thor::Wallet::default());
+        let (db, _) = searchable_db(&wallet, &pool);
+
+        match &search_blocking(&db, &wallet.address().to_string())[..] {
+            [SearchResult::Address(address)] => {
+                assert!(address.id == ExplorerAddress::New(wallet.address().into()))
+            }
+            _ => panic!("the bech32 address to match"),
+        }
+        match &search_blocking(&db, OLD_ADDRESS)[..] {
+            [SearchResult::Address(address)] => {
+                assert!(matches!(address.id, ExplorerAddress::Old(_)))
+            }
+            _ => panic!("the base58 address to match"),
+        }
+
+        let unknown = thor::Wallet::default();
+        assert!(search_blocking(&db, &unknown.address().to_string()).is_empty());
+    }
+
+    #[test]
+    fn search_without_a_match_is_empty() {
+        let (db, block0) = bootstrapped_db();
+        let unknown = child_block(&block0, 1);
+
+        assert!(search_blocking(&db, "").is_empty());
+        assert!(search_blocking(&db, "not a term").is_empty());
+        assert!(search_blocking(&db, "42").is_empty());
+        assert!(search_blocking(&db, &unknown.id().to_string()).is_empty());
+    }
+
+    #[test]
+    fn lagging_subscription_ends_with_an_error() {
+        let (db, block0) = bootstrapped_db();
+        let branches = [child_block(&block0, 1), child_block(&block0, 2)];
+
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            for block in &branches {
+                db.apply_block(block.clone()).await.unwrap();
+            }
+
+            let updates = main_branch_updates(&db);
+            futures::pin_mut!(updates);
+
+            // the main branch switches more often than the subscriber reads the updates
+            for i in 0..20 {
+                db.set_tip(branches[i % 2].id()).await.unwrap();
+            }
+
+            assert!(updates.next().await.unwrap().is_err());
+            assert!(updates.next().await.is_none());
+        });
+    }
+}
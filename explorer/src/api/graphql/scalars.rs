@@ -111,6 +111,7 @@ pub type BlockCount = u64;
 pub type TransactionCount = u64;
 pub type PoolCount = u64;
 pub type VotePlanStatusCount = u64;
+pub type BalanceChangeCount = u64;
 
 pub struct PublicKey(pub String);
 
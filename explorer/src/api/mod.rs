@@ -1,12 +1,30 @@
 pub mod graphql;
+pub mod rest;
+pub mod throttling;
 
-use self::graphql::EContext;
+use self::{
+    graphql::{
+        cache::{CacheHint, ImmutableFields, ResponseCache},
+        EContext,
+    },
+    throttling::Throttling,
+};
 use crate::db::ExplorerDb;
-use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
+use async_graphql::{
+    http::{playground_source, GraphQLPlaygroundConfig},
+    ServerError,
+};
 use futures::Future;
 use jormungandr_lib::interfaces::{Cors, Tls};
-use std::{net::SocketAddr, time::Duration};
-use warp::{http::Response as HttpResponse, Filter, Rejection, Reply};
+use std::{
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use warp::{
+    http::{Response as HttpResponse, StatusCode},
+    Filter, Rejection, Reply,
+};
 
 pub async fn setup_cors<API>(
     api: API,
@@ -67,6 +85,10 @@ pub fn filter(
     db: ExplorerDb,
     settings: crate::db::Settings,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let throttling = Arc::new(Throttling::new(&settings));
+    let rest = rest::filter(db.clone(), settings.clone(), Arc::clone(&throttling)).boxed();
+    let cache = Arc::new(ResponseCache::new(settings.response_cache_size));
+
     let schema = async_graphql::Schema::build(
         crate::api::graphql::Query {},
         async_graphql::EmptyMutation,
@@ -74,11 +96,22 @@ pub fn filter(
     )
     .limit_depth(settings.query_depth_limit)
     .limit_complexity(settings.query_complexity_limit)
+    .extension(ImmutableFields)
     .data(EContext { db, settings })
     .finish();
 
+    let subscription_throttling = Arc::clone(&throttling);
     let graphql_post = async_graphql_warp::graphql(schema.clone())
-        .and_then(|(schema, request)| handler(schema, request));
+        .and(warp::addr::remote())
+        .and_then(move |(schema, request), client: Option<SocketAddr>| {
+            handler(
+                schema,
+                request,
+                client,
+                Arc::clone(&throttling),
+                Arc::clone(&cache),
+            )
+        });
 
     let graphql = warp::path!("graphql").and(graphql_post).boxed();
 
@@ -90,14 +123,17 @@ pub fn filter(
             ))
     });
 
-    let subscription =
-        warp::path!("subscription").and(async_graphql_warp::graphql_subscription(schema));
+    // the clients over their budget can't open subscriptions until it is replenished
+    let subscription = warp::path!("subscription").and(
+        throttled(subscription_throttling).or(async_graphql_warp::graphql_subscription(schema)),
+    );
 
     let playground = warp::path!("playground").and(graphql_playground).boxed();
 
     subscription
         .or(graphql)
         .or(playground)
+        .or(rest)
         .with(warp::filters::trace::trace(|info| {
             use http_zipkin::get_trace_context;
             use tracing::field::Empty;
@@ -129,8 +165,101 @@ pub fn filter(
 pub async fn handler(
     schema: graphql::Schema,
     request: async_graphql::Request,
-) -> Result<impl Reply, std::convert::Infallible> {
-    Ok::<_, std::convert::Infallible>(async_graphql_warp::GraphQLResponse::from(
-        schema.execute(request).await,
-    ))
+    client: Option<SocketAddr>,
+    throttling: Arc<Throttling>,
+    cache: Arc<ResponseCache>,
+) -> Result<Box<dyn Reply>, std::convert::Infallible> {
+    let client = client.map(|address| address.ip());
+
+    if !throttling.allows(client) {
+        let response = async_graphql::Response::from_errors(vec![ServerError::new(
+            "query cost limit exceeded, retry later",
+            None,
+        )]);
+
+        return Ok(Box::new(warp::reply::with_status(
+            async_graphql_warp::GraphQLResponse::from(response),
+            StatusCode::TOO_MANY_REQUESTS,
+        )));
+    }
+
+    let key = serde_json::to_string(&(&request.query, &request.operation_name, &request.variables))
+        .expect("requests are always serializable");
+
+    if let Some(response) = cache.get(&key) {
+        throttling.record_cached();
+        return Ok(Box::new(json_reply(response.to_string())));
+    }
+
+    let query = request.query.clone();
+    let operation_name = request.operation_name.clone();
+    let hint = Arc::new(CacheHint::default());
+
+    let start = Instant::now();
+    let response = schema.execute(request.data(Arc::clone(&hint))).await;
+    throttling.record(client, operation_name.as_deref(), &query, start.elapsed());
+
+    if response.is_ok() && hint.is_cacheable() {
+        if let Ok(body) = serde_json::to_string(&response) {
+            cache.insert(key, Arc::from(body.as_str()));
+            return Ok(Box::new(json_reply(body)));
+        }
+    }
+
+    Ok(Box::new(async_graphql_warp::GraphQLResponse::from(
+        response,
+    )))
+}
+
+/// Replies to the requests of the throttled clients, and rejects the other requests so that
+/// the next filter handles them
+fn throttled(
+    throttling: Arc<Throttling>,
+) -> impl Filter<Extract = (Box<dyn Reply>,), Error = Rejection> + Clone {
+    warp::addr::remote().and_then(move |client: Option<SocketAddr>| {
+        let allowed = throttling.allows(client.map(|address| address.ip()));
+        async move {
+            if allowed {
+                Err(warp::reject())
+            } else {
+                Ok(Box::new(warp::reply::with_status(
+                    "query cost limit exceeded, retry later",
+                    StatusCode::TOO_MANY_REQUESTS,
+                )) as Box<dyn Reply>)
+            }
+        }
+    })
+}
+
+fn json_reply(body: String) -> impl Reply {
+    warp::reply::with_header(body, "content-type", "application/json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_throttled_clients_are_replied_to() {
+        let throttling = Arc::new(Throttling::new(&crate::db::Settings {
+            address_bech32_prefix: "addr".to_string(),
+            query_depth_limit: 15,
+            query_complexity_limit: 100,
+            query_cost_limit: Some(1000),
+            slow_query_threshold: 10_000,
+            response_cache_size: 0,
+            export_row_limit: 100,
+        }));
+        let filter = throttled(Arc::clone(&throttling));
+        let client = SocketAddr::from(([127, 0, 0, 1], 8000));
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        assert!(runtime
+            .block_on(warp::test::request().remote_addr(client).filter(&filter))
+            .is_err());
+
+        throttling.charge(Some(client.ip()), Duration::from_secs(2));
+        let response = runtime.block_on(warp::test::request().remote_addr(client).reply(&filter));
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
 }
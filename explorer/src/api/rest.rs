@@ -0,0 +1,572 @@
+//! bulk exports of the indexed data, streamed as csv or newline delimited json
+//!
+//! the exports are read from the state of the tip of the main branch. The rows are
+//! produced in batches of at most `query_complexity_limit` rows, the same setting that
+//! bounds the work of a single graphql query, and the task yields between batches so
+//! a large export doesn't starve the other requests. An address export over
+//! `export_row_limit` rows is rejected, the blocks of a pool being bounded by the slots of
+//! the epoch.
+
+use super::throttling::Throttling;
+use crate::db::{
+    indexing::{ExplorerAddress, ExplorerBlock},
+    BlockchainConfig, ExplorerDb, Settings,
+};
+use cardano_legacy_address::Addr as OldAddress;
+use chain_impl_mockchain::{block::Epoch, certificate::PoolId};
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+    time::Instant,
+};
+use warp::{
+    http::{Response, StatusCode},
+    hyper::Body,
+    Filter, Rejection, Reply,
+};
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Csv,
+    Ndjson,
+}
+
+#[derive(Deserialize)]
+pub struct AddressTransactionsQuery {
+    #[serde(default)]
+    format: Format,
+    /// Seconds since the unix epoch, only the transactions in blocks from this time are
+    /// exported
+    from: Option<u64>,
+    /// Seconds since the unix epoch, only the transactions in blocks before this time are
+    /// exported
+    to: Option<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct PoolBlocksQuery {
+    #[serde(default)]
+    format: Format,
+    epoch: Epoch,
+}
+
+/// A row of an export, with the same fields in both formats
+trait Row: Serialize {
+    const CSV_HEADER: &'static str;
+
+    fn csv_record(&self) -> String;
+}
+
+/// The change of the balance of the address by a transaction
+#[derive(Serialize)]
+struct TransactionRow {
+    transaction: String,
+    block: String,
+    chain_length: u32,
+    epoch: Epoch,
+    slot: u32,
+    time: u64,
+    received: u64,
+    spent: u64,
+    balance: u64,
+}
+
+#[derive(Serialize)]
+struct BlockRow {
+    block: String,
+    chain_length: u32,
+    epoch: Epoch,
+    slot: u32,
+    time: u64,
+    transactions: usize,
+    total_input: u64,
+    total_output: u64,
+    fees: u64,
+    content_size: u32,
+}
+
+impl Format {
+    fn content_type(self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv",
+            Format::Ndjson => "application/x-ndjson",
+        }
+    }
+
+    fn line<R: Row>(self, row: &R) -> String {
+        let mut line = match self {
+            Format::Csv => row.csv_record(),
+            Format::Ndjson => serde_json::to_string(row).expect("rows are always serializable"),
+        };
+        line.push('\n');
+        line
+    }
+}
+
+impl Row for TransactionRow {
+    const CSV_HEADER: &'static str =
+        "transaction,block,chain_length,epoch,slot,time,received,spent,balance";
+
+    fn csv_record(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{}",
+            self.transaction,
+            self.block,
+            self.chain_length,
+            self.epoch,
+            self.slot,
+            self.time,
+            self.received,
+            self.spent,
+            self.balance
+        )
+    }
+}
+
+impl BlockRow {
+    fn new(block: &ExplorerBlock, config: &BlockchainConfig) -> Self {
+        BlockRow {
+            block: block.id().to_string(),
+            chain_length: block.chain_length().into(),
+            epoch: block.date().epoch,
+            slot: block.date().slot_id,
+            time: config.block_time(&block.date()),
+            transactions: block.transactions.len(),
+            total_input: block.total_input.0,
+            total_output: block.total_output.0,
+            fees: block
+                .transactions
+                .values()
+                .fold(0u64, |fees, tx| fees.saturating_add(tx.fees().0)),
+            content_size: block.content_size,
+        }
+    }
+}
+
+impl Row for BlockRow {
+    const CSV_HEADER: &'static str = "block,chain_length,epoch,slot,time,transactions,total_input,total_output,fees,content_size";
+
+    fn csv_record(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{}",
+            self.block,
+            self.chain_length,
+            self.epoch,
+            self.slot,
+            self.time,
+            self.transactions,
+            self.total_input,
+            self.total_output,
+            self.fees,
+            self.content_size
+        )
+    }
+}
+
+/// The bounds of the exports, shared by the handlers
+#[derive(Clone)]
+struct Limits {
+    batch_size: usize,
+    row_limit: usize,
+    throttling: Arc<Throttling>,
+}
+
+pub fn filter(
+    db: ExplorerDb,
+    settings: Settings,
+    throttling: Arc<Throttling>,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let limits = Limits {
+        batch_size: settings.query_complexity_limit.max(1),
+        row_limit: settings.export_row_limit,
+        throttling,
+    };
+    let with_db = warp::any().map(move || db.clone());
+    let with_client =
+        warp::addr::remote().map(|client: Option<SocketAddr>| client.map(|address| address.ip()));
+    let with_limits = warp::any().map(move || limits.clone());
+
+    let address_transactions =
+        warp::path!("api" / "v0" / "export" / "address" / String / "transactions")
+            .and(warp::get())
+            .and(warp::query::<AddressTransactionsQuery>())
+            .and(with_db.clone())
+            .and(with_client)
+            .and(with_limits.clone())
+            .and_then(address_transactions);
+
+    let pool_blocks = warp::path!("api" / "v0" / "export" / "pool" / String / "blocks")
+        .and(warp::get())
+        .and(warp::query::<PoolBlocksQuery>())
+        .and(with_db)
+        .and(with_client)
+        .and(with_limits)
+        .and_then(pool_blocks);
+
+    address_transactions.or(pool_blocks)
+}
+
+/// The transactions changing the balance of the address, in chain order
+async fn address_transactions(
+    address: String,
+    query: AddressTransactionsQuery,
+    db: ExplorerDb,
+    client: Option<IpAddr>,
+    limits: Limits,
+) -> Result<Box<dyn Reply>, Infallible> {
+    if !limits.throttling.allows(client) {
+        return Ok(throttled());
+    }
+
+    let address = match chain_addr::AddressReadable::from_string_anyprefix(&address)
+        .map(|adr| ExplorerAddress::New(adr.to_address()))
+        .or_else(|_| OldAddress::from_str(&address).map(ExplorerAddress::Old))
+    {
+        Ok(address) => address,
+        Err(_) => return Ok(error(StatusCode::BAD_REQUEST, "invalid address")),
+    };
+
+    let (_, state) = db.get_tip().await;
+    let changes = match state.state().address_balance(&address) {
+        Some(balance) => balance.changes.clone(),
+        None => return Ok(error(StatusCode::NOT_FOUND, "address not found")),
+    };
+
+    let config = db.blockchain_config.clone();
+    let len = changes.len();
+    let change = move |i: u64| {
+        let change = Arc::clone(changes.get(i).expect("the index to be in the sequence"));
+        let date = state
+            .state()
+            .blocks
+            .lookup(&change.block)
+            .expect("the blocks of the balance changes to be indexed")
+            .date();
+        (change, date)
+    };
+    let time = |i| config.block_time(&change(i).1);
+
+    // the blocks are in chain order, so are their times
+    let start = partition_point(len, |i| time(i) < query.from.unwrap_or(0));
+    let end = match query.to {
+        Some(to) => partition_point(len, |i| time(i) < to),
+        None => len,
+    };
+    if end.saturating_sub(start) > limits.row_limit as u64 {
+        return Ok(error(
+            StatusCode::BAD_REQUEST,
+            "too many rows, narrow the time range",
+        ));
+    }
+
+    let rows = (start..end).map(move |i| {
+        let (change, date) = change(i);
+
+        TransactionRow {
+            transaction: change.transaction.to_string(),
+            block: change.block.to_string(),
+            chain_length: change.chain_length.into(),
+            epoch: date.epoch,
+            slot: date.slot_id,
+            time: config.block_time(&date),
+            received: change.received.0,
+            spent: change.spent.0,
+            balance: change.balance.0,
+        }
+    });
+
+    Ok(export(query.format, rows, client, limits))
+}
+
+/// The blocks produced by the pool in the epoch, in chain order
+async fn pool_blocks(
+    pool: String,
+    query: PoolBlocksQuery,
+    db: ExplorerDb,
+    client: Option<IpAddr>,
+    limits: Limits,
+) -> Result<Box<dyn Reply>, Infallible> {
+    if !limits.throttling.allows(client) {
+        return Ok(throttled());
+    }
+
+    let pool = match PoolId::from_str(&pool) {
+        Ok(pool) => pool,
+        Err(_) => return Ok(error(StatusCode::BAD_REQUEST, "invalid pool id")),
+    };
+
+    let (_, state) = db.get_tip().await;
+    let blocks = match state.state().stake_pool_blocks(&pool) {
+        Some(blocks) => blocks,
+        None => return Ok(error(StatusCode::NOT_FOUND, "stake pool not found")),
+    };
+
+    let config = db.blockchain_config.clone();
+    let len = blocks.len();
+    let block = move |i: u64| {
+        let hash = blocks.get(i).expect("the index to be in the sequence");
+        Arc::clone(
+            state
+                .state()
+                .blocks
+                .lookup(hash)
+                .expect("the blocks of the pool to be indexed"),
+        )
+    };
+
+    // the blocks are in chain order, so are their epochs
+    let start = partition_point(len, |i| block(i).date().epoch < query.epoch);
+    let end = partition_point(len, |i| block(i).date().epoch <= query.epoch);
+
+    let rows = (start..end).map(move |i| BlockRow::new(&block(i), &config));
+
+    Ok(export(query.format, rows, client, limits))
+}
+
+/// The first index of `0..len` for which `before` is false, `before` being true for the
+/// indices up to some point and false after it
+fn partition_point(len: u64, before: impl Fn(u64) -> bool) -> u64 {
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if before(middle) {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    low
+}
+
+/// Streams the rows, charging the client the time spent producing each batch
+fn export<R>(
+    format: Format,
+    rows: impl Iterator<Item = R> + Send + 'static,
+    client: Option<IpAddr>,
+    limits: Limits,
+) -> Box<dyn Reply>
+where
+    R: Row + Send + 'static,
+{
+    let header = match format {
+        Format::Csv => Some(format!("{}\n", R::CSV_HEADER)),
+        Format::Ndjson => None,
+    };
+
+    let batches = stream::unfold(rows, move |mut rows| {
+        let limits = limits.clone();
+        async move {
+            let start = Instant::now();
+            let batch: String = rows
+                .by_ref()
+                .take(limits.batch_size)
+                .map(|row| format.line(&row))
+                .collect();
+            limits.throttling.charge(client, start.elapsed());
+
+            if batch.is_empty() {
+                None
+            } else {
+                tokio::task::yield_now().await;
+                Some((batch, rows))
+            }
+        }
+    });
+
+    let body = stream::iter(header).chain(batches).map(Ok::<_, Infallible>);
+
+    Box::new(
+        Response::builder()
+            .header("content-type", format.content_type())
+            .body(Body::wrap_stream(body)),
+    )
+}
+
+fn error(status: StatusCode, message: &'static str) -> Box<dyn Reply> {
+    Box::new(warp::reply::with_status(message, status))
+}
+
+fn throttled() -> Box<dyn Reply> {
+    error(
+        StatusCode::TOO_MANY_REQUESTS,
+        "query cost limit exceeded, retry later",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::tests::bootstrapped_db;
+    use chain_addr::Discrimination;
+    use chain_impl_mockchain::{block::BlockDate, chaintypes::ConsensusVersion, fee::LinearFee};
+    use jormungandr_lib::interfaces::{
+        Block0Configuration, BlockchainConfiguration, Initial, InitialUTxO,
+    };
+    use std::time::Duration;
+
+    fn settings(export_row_limit: usize) -> Settings {
+        Settings {
+            address_bech32_prefix: "addr".to_string(),
+            query_depth_limit: 15,
+            query_complexity_limit: 100,
+            query_cost_limit: None,
+            slow_query_threshold: 10_000,
+            response_cache_size: 0,
+            export_row_limit,
+        }
+    }
+
+    fn funded_db(wallet: &thor::Wallet) -> ExplorerDb {
+        let block0 = Block0Configuration {
+            blockchain_configuration: BlockchainConfiguration::new(
+                Discrimination::Test,
+                ConsensusVersion::Bft,
+                LinearFee::new(0, 0, 0),
+            ),
+            initial: vec![Initial::Fund(vec![InitialUTxO {
+                address: wallet.address(),
+                value: 1_000.into(),
+            }])],
+        }
+        .to_block();
+
+        ExplorerDb::bootstrap(block0, None).unwrap()
+    }
+
+    fn get(db: &ExplorerDb, export_row_limit: usize, path: &str) -> (StatusCode, String) {
+        let settings = settings(export_row_limit);
+        let throttling = Arc::new(Throttling::new(&settings));
+        let api = filter(db.clone(), settings, throttling);
+        let response = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(warp::test::request().path(path).reply(&api));
+
+        (
+            response.status(),
+            String::from_utf8(response.body().to_vec()).unwrap(),
+        )
+    }
+
+    #[test]
+    fn address_transactions_are_exported_in_the_time_range() {
+        let wallet = thor::Wallet::default();
+        let db = funded_db(&wallet);
+        let block0_time = db.blockchain_config.block_time(&BlockDate::first());
+        let path = |query: &str| {
+            format!(
+                "/api/v0/export/address/{}/transactions?{}",
+                wallet.address(),
+                query
+            )
+        };
+
+        let (status, body) = get(&db, 100, &path("format=csv"));
+        assert_eq!(status, StatusCode::OK);
+        let lines: Vec<_> = body.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], TransactionRow::CSV_HEADER);
+        assert!(lines[1].ends_with(&format!("{},1000,0,1000", block0_time)));
+
+        let (status, body) = get(&db, 100, &path(&format!("from={}", block0_time + 1)));
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.lines().count(), 1);
+
+        let (status, body) = get(
+            &db,
+            100,
+            &path(&format!("format=ndjson&to={}", block0_time)),
+        );
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.is_empty());
+    }
+
+    #[test]
+    fn address_exports_over_the_row_limit_are_rejected() {
+        let wallet = thor::Wallet::default();
+        let db = funded_db(&wallet);
+
+        let (status, _) = get(
+            &db,
+            0,
+            &format!("/api/v0/export/address/{}/transactions", wallet.address()),
+        );
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn unknown_addresses_and_pools_are_not_found() {
+        let (db, _) = bootstrapped_db();
+        let pool = thor::StakePool::new(&thor::Wallet::default());
+
+        let (status, _) = get(
+            &db,
+            100,
+            &format!(
+                "/api/v0/export/address/{}/transactions",
+                thor::Wallet::default().address()
+            ),
+        );
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = get(
+            &db,
+            100,
+            &format!("/api/v0/export/pool/{}/blocks?epoch=0", pool.id()),
+        );
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = get(&db, 100, "/api/v0/export/address/invalid/transactions");
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn exports_of_throttled_clients_are_rejected() {
+        let wallet = thor::Wallet::default();
+        let db = funded_db(&wallet);
+        let settings = Settings {
+            query_cost_limit: Some(1000),
+            ..settings(100)
+        };
+        let throttling = Arc::new(Throttling::new(&settings));
+        let api = filter(db, settings, Arc::clone(&throttling));
+        let client = SocketAddr::from(([127, 0, 0, 1], 8000));
+        let request = || {
+            warp::test::request()
+                .remote_addr(client)
+                .path(&format!(
+                    "/api/v0/export/address/{}/transactions",
+                    wallet.address()
+                ))
+                .reply(&api)
+        };
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        assert_eq!(runtime.block_on(request()).status(), StatusCode::OK);
+
+        throttling.charge(Some(client.ip()), Duration::from_secs(2));
+        assert_eq!(
+            runtime.block_on(request()).status(),
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    #[test]
+    fn partition_point_finds_the_first_index_after_the_prefix() {
+        let times = [1, 3, 3, 5, 8];
+        let after = |time| partition_point(times.len() as u64, |i| times[i as usize] < time);
+
+        assert_eq!(after(0), 0);
+        assert_eq!(after(3), 1);
+        assert_eq!(after(4), 3);
+        assert_eq!(after(8), 4);
+        assert_eq!(after(9), 5);
+        assert_eq!(partition_point(0, |_| true), 0);
+    }
+}
@@ -0,0 +1,230 @@
+//! accounting of the execution time of the graphql queries by client
+//!
+//! each client, identified by its ip address, can spend `query_cost_limit` milliseconds
+//! executing queries per minute. The time spent is replenished continuously, and the
+//! queries of a client are rejected while it has spent its whole budget. The REST exports
+//! are charged the time spent producing their rows and share the same budget, which also
+//! gates the opening of the subscriptions. The queries slower than `slow_query_threshold`
+//! are logged along with the counters of the api.
+
+use crate::db::Settings;
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// The number of clients above which the clients that have their whole budget are forgotten,
+/// then the ones which spent the least if there are still too many
+const MAX_TRACKED_CLIENTS: usize = 4096;
+
+const REPLENISH_PERIOD: Duration = Duration::from_secs(60);
+
+pub struct Throttling {
+    limit: Option<Duration>,
+    slow_query_threshold: Duration,
+    clients: Mutex<HashMap<IpAddr, Spent>>,
+    metrics: QueryMetrics,
+}
+
+/// The execution time spent by a client, as of `updated`
+struct Spent {
+    time: Duration,
+    updated: Instant,
+}
+
+#[derive(Default)]
+struct QueryMetrics {
+    queries: AtomicU64,
+    cached: AtomicU64,
+    throttled: AtomicU64,
+    slow: AtomicU64,
+    total_time_ms: AtomicU64,
+}
+
+impl Spent {
+    fn replenish(&mut self, limit: Duration, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated);
+        let replenished = limit.mul_f64(elapsed.as_secs_f64() / REPLENISH_PERIOD.as_secs_f64());
+
+        self.time = self.time.saturating_sub(replenished);
+        self.updated = now;
+    }
+}
+
+impl Throttling {
+    pub fn new(settings: &Settings) -> Self {
+        Throttling {
+            limit: settings.query_cost_limit.map(Duration::from_millis),
+            slow_query_threshold: Duration::from_millis(settings.slow_query_threshold),
+            clients: Mutex::new(HashMap::new()),
+            metrics: Default::default(),
+        }
+    }
+
+    /// Whether the client has some execution time left, counts the rejected queries
+    pub fn allows(&self, client: Option<IpAddr>) -> bool {
+        let (limit, client) = match (self.limit, client) {
+            (Some(limit), Some(client)) => (limit, client),
+            _ => return true,
+        };
+
+        let mut clients = self.clients.lock().unwrap();
+        let allowed = match clients.get_mut(&client) {
+            Some(spent) => {
+                spent.replenish(limit, Instant::now());
+                spent.time < limit
+            }
+            None => true,
+        };
+
+        if !allowed {
+            self.metrics.throttled.fetch_add(1, Ordering::Relaxed);
+        }
+
+        allowed
+    }
+
+    pub fn record_cached(&self) {
+        self.metrics.queries.fetch_add(1, Ordering::Relaxed);
+        self.metrics.cached.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Charges the execution time of the query to the client and logs the query if it's slow
+    pub fn record(
+        &self,
+        client: Option<IpAddr>,
+        operation_name: Option<&str>,
+        query: &str,
+        time: Duration,
+    ) {
+        let time_ms = u64::try_from(time.as_millis()).unwrap_or(u64::MAX);
+        self.metrics.queries.fetch_add(1, Ordering::Relaxed);
+        self.metrics
+            .total_time_ms
+            .fetch_add(time_ms, Ordering::Relaxed);
+
+        self.charge(client, time);
+
+        if time >= self.slow_query_threshold {
+            let slow = self.metrics.slow.fetch_add(1, Ordering::Relaxed) + 1;
+
+            tracing::warn!(
+                client = ?client,
+                operation = operation_name.unwrap_or_default(),
+                time_ms,
+                query,
+                queries = self.metrics.queries.load(Ordering::Relaxed),
+                cached = self.metrics.cached.load(Ordering::Relaxed),
+                throttled = self.metrics.throttled.load(Ordering::Relaxed),
+                slow,
+                total_time_ms = self.metrics.total_time_ms.load(Ordering::Relaxed),
+                "slow graphql query"
+            );
+        }
+    }
+
+    /// Charges the execution time to the client, without counting a query
+    pub fn charge(&self, client: Option<IpAddr>, time: Duration) {
+        let (limit, client) = match (self.limit, client) {
+            (Some(limit), Some(client)) => (limit, client),
+            _ => return,
+        };
+
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap();
+
+        if clients.len() > MAX_TRACKED_CLIENTS {
+            prune(&mut clients, limit, now);
+        }
+
+        let spent = clients.entry(client).or_insert(Spent {
+            time: Duration::ZERO,
+            updated: now,
+        });
+        spent.replenish(limit, now);
+        spent.time = spent.time.saturating_add(time);
+    }
+}
+
+/// Forgets the clients which have their whole budget, then keeps the half of
+/// `MAX_TRACKED_CLIENTS` which spent the most, so the clients over the limit stay throttled
+fn prune(clients: &mut HashMap<IpAddr, Spent>, limit: Duration, now: Instant) {
+    clients.retain(|_, spent| {
+        spent.replenish(limit, now);
+        !spent.time.is_zero()
+    });
+
+    if clients.len() > MAX_TRACKED_CLIENTS / 2 {
+        let mut spent: Vec<_> = clients.drain().collect();
+        spent.sort_unstable_by(|(_, a), (_, b)| b.time.cmp(&a.time));
+        spent.truncate(MAX_TRACKED_CLIENTS / 2);
+        clients.extend(spent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn throttling() -> Throttling {
+        Throttling::new(&Settings {
+            address_bech32_prefix: "addr".to_string(),
+            query_depth_limit: 15,
+            query_complexity_limit: 100,
+            query_cost_limit: Some(1000),
+            slow_query_threshold: 10_000,
+            response_cache_size: 0,
+            export_row_limit: 100,
+        })
+    }
+
+    #[test]
+    fn clients_over_the_limit_are_throttled() {
+        let throttling = throttling();
+        let client = Some(IpAddr::from([127, 0, 0, 1]));
+        let other_client = Some(IpAddr::from([127, 0, 0, 2]));
+
+        assert!(throttling.allows(client));
+        throttling.record(client, None, "{ tip { id } }", Duration::from_millis(500));
+        assert!(throttling.allows(client));
+        throttling.record(client, None, "{ tip { id } }", Duration::from_millis(600));
+        assert!(!throttling.allows(client));
+
+        assert!(throttling.allows(other_client));
+        assert!(throttling.allows(None));
+    }
+
+    #[test]
+    fn charged_time_counts_against_the_limit() {
+        let throttling = throttling();
+        let client = Some(IpAddr::from([127, 0, 0, 1]));
+
+        throttling.charge(client, Duration::from_millis(1100));
+
+        assert!(!throttling.allows(client));
+        assert_eq!(throttling.metrics.queries.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn tracked_clients_are_bounded() {
+        let throttling = throttling();
+        let heavy_client = Some(IpAddr::from([10, 0, 0, 1]));
+
+        throttling.charge(heavy_client, Duration::from_secs(60));
+        for i in 0..2 * MAX_TRACKED_CLIENTS as u32 {
+            throttling.charge(
+                Some(IpAddr::from(Ipv4Addr::from(i))),
+                Duration::from_millis(1100),
+            );
+        }
+
+        assert!(throttling.clients.lock().unwrap().len() <= MAX_TRACKED_CLIENTS + 1);
+        assert!(!throttling.allows(heavy_client));
+    }
+}
@@ -18,6 +18,8 @@ pub enum ExplorerError {
     ChainLengthBlockAlreadyExists(ChainLength),
     #[error("the explorer's database couldn't be initialized: {0}")]
     BootstrapError(String),
+    #[error("the explorer's storage failed: {0}")]
+    StorageError(String),
 }
 
 #[derive(Debug, Error, Clone)]
@@ -0,0 +1,202 @@
+//! indexing of the EVM transactions, only built with the `evm` feature
+//!
+//! the transactions are indexed by the addresses they involve: the caller, the
+//! recipient and the created contract. The address of a created contract
+//! is derived from the transaction the way the EVM does it. The contracts are
+//! not executed by the explorer, so their logs and internal calls are unknown.
+
+use super::{
+    error::ExplorerError as Error,
+    indexing::{ExplorerBlock, Hamt},
+    persistent_sequence::PersistentSequence,
+};
+use chain_evm::ethereum_types::{H160, H256};
+use chain_impl_mockchain::{
+    block::HeaderId as HeaderHash,
+    evm::{EvmActionType, EvmTransaction},
+    fragment::FragmentId,
+};
+use sha3::{Digest, Keccak256};
+use std::sync::Arc;
+
+pub type EvmAddress = H160;
+
+#[derive(Clone)]
+pub struct ExplorerEvmTransaction {
+    pub id: FragmentId,
+    pub transaction: EvmTransaction,
+    pub offset_in_block: u32,
+    /// The address of the contract deployed by the transaction, if any
+    pub created_contract: Option<EvmAddress>,
+}
+
+/// The EVM transactions of a branch
+#[derive(Clone)]
+pub struct EvmIndex {
+    transactions: Hamt<FragmentId, HeaderHash>,
+    /// The transactions involving each address, in chain order
+    addresses: Hamt<EvmAddress, PersistentSequence<FragmentId>>,
+    /// The transaction deploying each contract
+    contracts: Hamt<EvmAddress, FragmentId>,
+}
+
+impl ExplorerEvmTransaction {
+    pub fn new(id: FragmentId, transaction: EvmTransaction, offset_in_block: u32) -> Self {
+        let created_contract = match &transaction.action_type {
+            EvmActionType::Create { .. } => {
+                Some(create_address(&transaction.caller, transaction.nonce))
+            }
+            EvmActionType::Create2 { init_code, salt } => {
+                Some(create2_address(&transaction.caller, salt, init_code))
+            }
+            EvmActionType::Call { .. } => None,
+        };
+
+        ExplorerEvmTransaction {
+            id,
+            transaction,
+            offset_in_block,
+            created_contract,
+        }
+    }
+
+    /// The contract or the account called by the transaction, `None` for a deployment
+    pub fn recipient(&self) -> Option<&EvmAddress> {
+        match &self.transaction.action_type {
+            EvmActionType::Call { address, .. } => Some(address),
+            EvmActionType::Create { .. } | EvmActionType::Create2 { .. } => None,
+        }
+    }
+
+    /// The caller followed by the recipient or the created contract
+    pub fn addresses(&self) -> Vec<EvmAddress> {
+        let mut addresses = vec![self.transaction.caller];
+        addresses.extend(self.recipient().copied());
+        addresses.extend(self.created_contract);
+        addresses.dedup();
+        addresses
+    }
+}
+
+impl EvmIndex {
+    pub fn new() -> Self {
+        EvmIndex {
+            transactions: Hamt::new(),
+            addresses: Hamt::new(),
+            contracts: Hamt::new(),
+        }
+    }
+
+    pub fn apply_block(&self, block: &ExplorerBlock) -> Result<Self, Error> {
+        let mut index = self.clone();
+
+        for tx in block.evm_transactions.iter() {
+            index.transactions = index
+                .transactions
+                .insert(tx.id, Arc::new(block.id()))
+                .map_err(|_| Error::TransactionAlreadyExists(tx.id))?;
+
+            for address in tx.addresses() {
+                index.addresses = index.addresses.insert_or_update_simple(
+                    address,
+                    Arc::new(PersistentSequence::new().append(tx.id)),
+                    |transactions| Some(Arc::new(transactions.append(tx.id))),
+                );
+            }
+
+            // a contract can be deployed again at the same address once destroyed
+            if let Some(contract) = tx.created_contract {
+                index.contracts =
+                    index
+                        .contracts
+                        .insert_or_update_simple(contract, Arc::new(tx.id), |_| {
+                            Some(Arc::new(tx.id))
+                        });
+            }
+        }
+
+        Ok(index)
+    }
+
+    pub fn transaction_block(&self, id: &FragmentId) -> Option<HeaderHash> {
+        self.transactions.lookup(id).map(|block| **block)
+    }
+
+    pub fn transactions_by_address(
+        &self,
+        address: &EvmAddress,
+    ) -> Option<PersistentSequence<FragmentId>> {
+        self.addresses
+            .lookup(address)
+            .map(|transactions| PersistentSequence::clone(transactions))
+    }
+
+    pub fn contract_deployment(&self, contract: &EvmAddress) -> Option<FragmentId> {
+        self.contracts.lookup(contract).map(|id| **id)
+    }
+}
+
+impl Default for EvmIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The address of a contract deployed with `CREATE`, the hash of the rlp encoding of the
+/// caller and its nonce
+fn create_address(caller: &EvmAddress, nonce: u64) -> EvmAddress {
+    let nonce_bytes = nonce.to_be_bytes();
+    let nonce_bytes = &nonce_bytes[nonce.leading_zeros() as usize / 8..];
+
+    let mut encoded_nonce = Vec::with_capacity(9);
+    match nonce_bytes {
+        [byte] if *byte < 0x80 => encoded_nonce.push(*byte),
+        bytes => {
+            encoded_nonce.push(0x80 + bytes.len() as u8);
+            encoded_nonce.extend_from_slice(bytes);
+        }
+    }
+
+    // the list is always shorter than 56 bytes, its length fits in the prefix
+    let mut encoded = vec![0xc0 + (21 + encoded_nonce.len()) as u8, 0x80 + 20];
+    encoded.extend_from_slice(caller.as_bytes());
+    encoded.extend_from_slice(&encoded_nonce);
+
+    EvmAddress::from_slice(&Keccak256::digest(&encoded)[12..])
+}
+
+/// The address of a contract deployed with `CREATE2`, as defined by EIP-1014
+fn create2_address(caller: &EvmAddress, salt: &H256, init_code: &[u8]) -> EvmAddress {
+    let mut hasher = Keccak256::new();
+    hasher.update([0xff]);
+    hasher.update(caller.as_bytes());
+    hasher.update(salt.as_bytes());
+    hasher.update(Keccak256::digest(init_code));
+
+    EvmAddress::from_slice(&hasher.finalize()[12..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn contract_addresses() {
+        let caller = EvmAddress::from_str("6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0").unwrap();
+        assert_eq!(
+            create_address(&caller, 0),
+            EvmAddress::from_str("cd234a471b72ba2f1ccf0a70fcaba648a5eecd8d").unwrap()
+        );
+        assert_eq!(
+            create_address(&caller, 1),
+            EvmAddress::from_str("343c43a37d37dff08ae8c4a11544c718abb4fcf8").unwrap()
+        );
+
+        // first example of EIP-1014
+        assert_eq!(
+            create2_address(&EvmAddress::zero(), &H256::zero(), &[0x00]),
+            EvmAddress::from_str("4d1a2e2bb4f88f0250f26ffff098b0b30b26bf38").unwrap()
+        );
+    }
+}
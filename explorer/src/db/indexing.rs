@@ -1,17 +1,20 @@
+#[cfg(feature = "evm")]
+use super::evm::ExplorerEvmTransaction;
 use super::persistent_sequence::PersistentSequence;
 use cardano_legacy_address::Addr as OldAddress;
 use chain_addr::{Address, Discrimination};
 use chain_core::property::{Block as _, Fragment as _};
 use chain_impl_mockchain::{
-    account::Identifier,
+    account::{DelegationType, Identifier},
     block::{Block, Proof},
     certificate::{
-        Certificate, ExternalProposalId, PoolId, PoolRegistration, PoolRetirement, VotePlanId,
+        Certificate, ExternalProposalId, PoolId, PoolRegistration, PoolRetirement, StakeDelegation,
+        VotePlanId,
     },
     fragment::{ConfigParams, Fragment, FragmentId},
     header::{BlockDate, ChainLength, Epoch, HeaderId as HeaderHash},
     key::BftLeaderId,
-    transaction::{InputEnum, TransactionSlice, Witness},
+    transaction::{AccountBindingSignature, InputEnum, TransactionSlice, Witness},
     value::Value,
     vote::{Choice, EncryptedVote, Options, PayloadType, ProofOfCorrectVote, Weight},
 };
@@ -28,6 +31,7 @@ pub type Blocks = Hamt<HeaderHash, ExplorerBlock>;
 pub type ChainLengths = Hamt<ChainLength, HeaderHash>;
 
 pub type Addresses = Hamt<ExplorerAddress, PersistentSequence<FragmentId>>;
+pub type Balances = Hamt<ExplorerAddress, AddressBalance>;
 pub type Epochs = Hamt<Epoch, EpochData>;
 
 pub type StakePoolBlocks = Hamt<PoolId, PersistentSequence<HeaderHash>>;
@@ -35,6 +39,11 @@ pub type StakePool = Hamt<PoolId, StakePoolData>;
 
 pub type VotePlans = Hamt<VotePlanId, ExplorerVotePlan>;
 
+/// The current delegation of the accounts, by account address
+pub type Delegations = Hamt<ExplorerAddress, DelegationType>;
+/// The stake of the multisig accounts, which are not tracked by the `StakeControl`
+pub type MultisigStake = Hamt<[u8; 32], Value>;
+
 #[derive(Clone)]
 pub struct StakePoolData {
     pub registration: PoolRegistration,
@@ -42,6 +51,25 @@ pub struct StakePoolData {
     // TODO: Track updates here too?
 }
 
+/// The pots of the ledger the rewards are drawn from and the treasury
+#[derive(Clone, Copy)]
+pub struct Pots {
+    /// The fees collected since the last distribution of the rewards
+    pub fees: Value,
+    pub treasury: Value,
+    pub rewards: Value,
+}
+
+impl Default for Pots {
+    fn default() -> Self {
+        Pots {
+            fees: Value::zero(),
+            treasury: Value::zero(),
+            rewards: Value::zero(),
+        }
+    }
+}
+
 /// Block with unified inputs the metadata needed in the queries
 #[derive(Clone)]
 pub struct ExplorerBlock {
@@ -54,6 +82,10 @@ pub struct ExplorerBlock {
     pub producer: BlockProducer,
     pub total_input: Value,
     pub total_output: Value,
+    pub content_size: u32,
+    /// In the order of the block
+    #[cfg(feature = "evm")]
+    pub evm_transactions: Vec<ExplorerEvmTransaction>,
 }
 
 #[derive(Clone)]
@@ -71,6 +103,8 @@ pub struct ExplorerTransaction {
     pub certificate: Option<Certificate>,
     pub offset_in_block: u32,
     pub config_params: Option<ConfigParams>,
+    /// The account whose delegation is set by the certificate of the transaction, if any
+    pub delegation_account: Option<ExplorerAddress>,
 }
 
 /// Unified Input representation for utxo and account inputs as used in the graphql API
@@ -86,11 +120,84 @@ pub struct ExplorerOutput {
     pub value: Value,
 }
 
+/// The balance of an address and the transactions that changed it, in chain order
+#[derive(Clone)]
+pub struct AddressBalance {
+    pub balance: Value,
+    pub changes: PersistentSequence<BalanceChange>,
+}
+
+/// The change of the balance of an address by a transaction. The spent value of an account
+/// includes the fees of the transaction
+#[derive(Clone)]
+pub struct BalanceChange {
+    pub block: HeaderHash,
+    pub chain_length: ChainLength,
+    pub transaction: FragmentId,
+    pub received: Value,
+    pub spent: Value,
+    /// The balance after the transaction
+    pub balance: Value,
+}
+
 #[derive(Clone)]
 pub struct EpochData {
     pub first_block: HeaderHash,
     pub last_block: HeaderHash,
     pub total_blocks: u32,
+    pub stake_distribution: Arc<StakeDistribution>,
+    pub stats: EpochStats,
+    /// `None` until the first block of the next epoch estimates the rewards
+    pub estimated_rewards: Option<EstimatedRewards>,
+}
+
+/// The rewards of an epoch, estimated with the first block of the next one. The ledger
+/// distributes them outside of the blocks, see `rewards` for the deviations of the estimate
+#[derive(Clone, Default)]
+pub struct EstimatedRewards {
+    /// The rewards credited to the accounts, the delegators and the pool owners
+    pub accounts: Value,
+    /// The part of the rewards sent to the treasury
+    pub treasury: Value,
+    /// The rewards of each pool which produced blocks, before the pool tax
+    pub pools: HashMap<PoolId, Value>,
+}
+
+/// The stake delegated to each registered pool, as of the beginning of an epoch
+#[derive(Clone, Default)]
+pub struct StakeDistribution {
+    pub pools: HashMap<PoolId, Value>,
+    /// The number of accounts delegating to each pool
+    pub delegators: HashMap<PoolId, u32>,
+}
+
+/// Aggregates over the blocks of an epoch, updated with each block
+#[derive(Clone)]
+pub struct EpochStats {
+    pub total_transactions: u64,
+    pub total_fees: Value,
+    pub total_output: Value,
+    /// The sum of the content sizes of the blocks
+    pub total_content_size: u64,
+    pub certificates: HashMap<CertificateKind, u32>,
+    /// The number of blocks produced by each pool
+    pub pool_blocks: Hamt<PoolId, u32>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CertificateKind {
+    StakeDelegation,
+    OwnerStakeDelegation,
+    PoolRegistration,
+    PoolRetirement,
+    PoolUpdate,
+    VotePlan,
+    VoteCast,
+    VoteTally,
+    UpdateProposal,
+    UpdateVote,
+    MintToken,
+    EvmMapping,
 }
 
 #[derive(Eq, PartialEq, Clone, Hash)]
@@ -107,6 +214,8 @@ pub struct ExplorerVotePlan {
     pub committee_end: BlockDate,
     pub payload_type: PayloadType,
     pub proposals: Vec<ExplorerVoteProposal>,
+    /// The turnout over all the proposals, set with the stake at the tally
+    pub turnout: Option<Turnout>,
 }
 
 #[derive(Clone)]
@@ -124,9 +233,19 @@ pub struct ExplorerVoteProposal {
     pub options: Options,
     pub tally: Option<ExplorerVoteTally>,
     pub votes: Hamt<ExplorerAddress, ExplorerVote>,
+    /// Set with the stake at the tally
+    pub turnout: Option<Turnout>,
+}
+
+/// The accounts which voted and their stake
+#[derive(Clone, Copy)]
+pub struct Turnout {
+    pub voters: u64,
+    pub voting_power: Value,
 }
 
-// TODO do proper vote tally
+/// The weight of each option, from the stake of the voters at the tally for a public vote plan
+/// and from the decrypted tally of the committee for a private one
 #[derive(Clone)]
 pub enum ExplorerVoteTally {
     Public {
@@ -169,6 +288,7 @@ impl ExplorerBlock {
                         certificate: None,
                         offset_in_block: offset,
                         config_params: Some(config.clone()),
+                        delegation_account: None,
                     }),
                     Fragment::Transaction(tx) => {
                         let tx = tx.as_slice();
@@ -183,7 +303,7 @@ impl ExplorerBlock {
                     }
                     Fragment::OwnerStakeDelegation(tx) => {
                         let tx = tx.as_slice();
-                        Some(ExplorerTransaction::from(
+                        let mut etx = ExplorerTransaction::from(
                             &context,
                             &fragment_id,
                             &tx,
@@ -192,18 +312,30 @@ impl ExplorerBlock {
                             )),
                             offset,
                             &current_block_txs,
-                        ))
+                        );
+                        // the owner is the account spending the single input
+                        etx.delegation_account =
+                            etx.inputs.first().map(|input| input.address.clone());
+                        Some(etx)
                     }
                     Fragment::StakeDelegation(tx) => {
                         let tx = tx.as_slice();
-                        Some(ExplorerTransaction::from(
+                        let delegation = tx.payload().into_payload();
+                        let delegation_account = delegation_account(
+                            context.discrimination,
+                            &delegation,
+                            &tx.payload_auth().into_payload_auth(),
+                        );
+                        let mut etx = ExplorerTransaction::from(
                             &context,
                             &fragment_id,
                             &tx,
-                            Some(Certificate::StakeDelegation(tx.payload().into_payload())),
+                            Some(Certificate::StakeDelegation(delegation)),
                             offset,
                             &current_block_txs,
-                        ))
+                        );
+                        etx.delegation_account = delegation_account;
+                        Some(etx)
                     }
                     Fragment::PoolRegistration(tx) => {
                         let tx = tx.as_slice();
@@ -287,6 +419,7 @@ impl ExplorerBlock {
                             certificate: None,
                             offset_in_block: offset,
                             config_params: None,
+                            delegation_account: None,
                         })
                     }
                     _ => None,
@@ -324,6 +457,21 @@ impl ExplorerBlock {
         )
         .expect("Couldn't compute block's total output");
 
+        #[cfg(feature = "evm")]
+        let evm_transactions = block
+            .contents()
+            .iter()
+            .enumerate()
+            .filter_map(|(offset, fragment)| match fragment {
+                Fragment::Evm(tx) => Some(ExplorerEvmTransaction::new(
+                    fragment.id(),
+                    tx.clone(),
+                    offset.try_into().unwrap(),
+                )),
+                _ => None,
+            })
+            .collect();
+
         ExplorerBlock {
             id,
             transactions,
@@ -333,6 +481,9 @@ impl ExplorerBlock {
             producer,
             total_input,
             total_output,
+            content_size: block.header().block_content_size(),
+            #[cfg(feature = "evm")]
+            evm_transactions,
         }
     }
 
@@ -440,6 +591,7 @@ impl ExplorerTransaction {
             certificate,
             offset_in_block,
             config_params: None,
+            delegation_account: None,
         }
     }
 
@@ -454,13 +606,161 @@ impl ExplorerTransaction {
     pub fn outputs(&self) -> &Vec<ExplorerOutput> {
         &self.outputs
     }
+
+    /// The difference between the inputs and the outputs, which is zero for the fragments
+    /// of the genesis block
+    pub fn fees(&self) -> Value {
+        let input = Value::sum(self.inputs.iter().map(|i| i.value))
+            .expect("Couldn't compute transaction's total input");
+        let output = Value::sum(self.outputs.iter().map(|o| o.value))
+            .expect("Couldn't compute transaction's total output");
+
+        Value(input.0.saturating_sub(output.0))
+    }
+}
+
+/// The address of the account delegating with the certificate, single or multisig depending on
+/// the signature binding the certificate
+fn delegation_account(
+    discrimination: Discrimination,
+    delegation: &StakeDelegation,
+    auth: &AccountBindingSignature,
+) -> Option<ExplorerAddress> {
+    let kind = match auth {
+        AccountBindingSignature::Single(_) => {
+            chain_addr::Kind::Account(delegation.account_id.to_single_account()?.into())
+        }
+        AccountBindingSignature::Multi(_) => chain_addr::Kind::Multisig(
+            delegation
+                .account_id
+                .to_multi_account()
+                .as_ref()
+                .try_into()
+                .expect("multisig identifier size doesn't match address kind"),
+        ),
+    };
+
+    Some(ExplorerAddress::New(Address(discrimination, kind)))
+}
+
+impl AddressBalance {
+    /// The balance after the block with the given chain length in the branch
+    pub fn balance_at(&self, chain_length: ChainLength) -> Value {
+        // the index of the first change after the given chain length
+        let (mut low, mut high) = (0, self.changes.len());
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if self.changes.get(middle).unwrap().chain_length <= chain_length {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        low.checked_sub(1)
+            .and_then(|i| self.changes.get(i))
+            .map(|change| change.balance)
+            .unwrap_or_else(Value::zero)
+    }
+}
+
+impl Default for EpochStats {
+    fn default() -> Self {
+        EpochStats {
+            total_transactions: 0,
+            total_fees: Value::zero(),
+            total_output: Value::zero(),
+            total_content_size: 0,
+            certificates: HashMap::new(),
+            pool_blocks: Hamt::new(),
+        }
+    }
+}
+
+impl EpochStats {
+    /// The statistics including the given block
+    pub fn add_block(&self, block: &ExplorerBlock) -> Self {
+        let mut certificates = self.certificates.clone();
+        for certificate in block
+            .transactions
+            .values()
+            .filter_map(|tx| tx.certificate.as_ref())
+        {
+            *certificates
+                .entry(CertificateKind::from(certificate))
+                .or_default() += 1;
+        }
+
+        let pool_blocks = match block.producer() {
+            BlockProducer::StakePool(pool) => {
+                self.pool_blocks
+                    .insert_or_update_simple(pool.clone(), Arc::new(1), |blocks| {
+                        Some(Arc::new(blocks + 1))
+                    })
+            }
+            BlockProducer::BftLeader(_) | BlockProducer::None => self.pool_blocks.clone(),
+        };
+
+        let total_fees = block
+            .transactions
+            .values()
+            .fold(self.total_fees.0, |total, tx| {
+                total.saturating_add(tx.fees().0)
+            });
+
+        EpochStats {
+            total_transactions: self.total_transactions + block.transactions.len() as u64,
+            total_fees: Value(total_fees),
+            total_output: Value(self.total_output.0.saturating_add(block.total_output.0)),
+            total_content_size: self.total_content_size + u64::from(block.content_size),
+            certificates,
+            pool_blocks,
+        }
+    }
+}
+
+impl From<&Certificate> for CertificateKind {
+    fn from(certificate: &Certificate) -> CertificateKind {
+        match certificate {
+            Certificate::StakeDelegation(_) => CertificateKind::StakeDelegation,
+            Certificate::OwnerStakeDelegation(_) => CertificateKind::OwnerStakeDelegation,
+            Certificate::PoolRegistration(_) => CertificateKind::PoolRegistration,
+            Certificate::PoolRetirement(_) => CertificateKind::PoolRetirement,
+            Certificate::PoolUpdate(_) => CertificateKind::PoolUpdate,
+            Certificate::VotePlan(_) => CertificateKind::VotePlan,
+            Certificate::VoteCast(_) => CertificateKind::VoteCast,
+            Certificate::VoteTally(_) => CertificateKind::VoteTally,
+            Certificate::UpdateProposal(_) => CertificateKind::UpdateProposal,
+            Certificate::UpdateVote(_) => CertificateKind::UpdateVote,
+            Certificate::MintToken(_) => CertificateKind::MintToken,
+            Certificate::EvmMapping(_) => CertificateKind::EvmMapping,
+        }
+    }
 }
 
 impl ExplorerAddress {
+    /// The address of the account holding the stake of this address, which is the address
+    /// itself for account and multisig addresses. Utxo addresses without a delegation key
+    /// don't have one.
+    pub fn to_account_address(&self) -> Option<ExplorerAddress> {
+        match self {
+            ExplorerAddress::New(address) => match address.kind() {
+                chain_addr::Kind::Account(_) | chain_addr::Kind::Multisig(_) => Some(self.clone()),
+                chain_addr::Kind::Group(_, key) => Some(ExplorerAddress::New(Address(
+                    address.0,
+                    chain_addr::Kind::Account(key.clone()),
+                ))),
+                _ => None,
+            },
+            ExplorerAddress::Old(_) => None,
+        }
+    }
+
+    /// The identifier of the account, for a single account address
     pub fn to_single_account(&self) -> Option<Identifier> {
         match self {
             ExplorerAddress::New(address) => match address.kind() {
-                chain_addr::Kind::Single(key) => Some(key.clone().into()),
+                chain_addr::Kind::Account(key) => Some(key.clone().into()),
                 _ => None,
             },
             ExplorerAddress::Old(_) => None,
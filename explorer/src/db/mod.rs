@@ -1,35 +1,47 @@
 pub mod error;
+#[cfg(feature = "evm")]
+pub mod evm;
 pub mod indexing;
 pub mod multiverse;
 pub mod persistent_sequence;
+mod rewards;
+pub mod snapshot;
+pub mod storage;
 mod tally;
 
 use self::{
     error::{BlockNotFound, ExplorerError as Error},
     indexing::{
-        Addresses, Blocks, ChainLengths, EpochData, Epochs, ExplorerAddress, ExplorerBlock,
-        ExplorerVote, ExplorerVotePlan, ExplorerVoteProposal, StakePool, StakePoolBlocks,
-        StakePoolData, Transactions, VotePlans,
+        AddressBalance, Addresses, BalanceChange, Balances, Blocks, ChainLengths, Delegations,
+        EpochData, EpochStats, Epochs, ExplorerAddress, ExplorerBlock, ExplorerVote,
+        ExplorerVotePlan, ExplorerVoteProposal, MultisigStake, Pots, StakeDistribution, StakePool,
+        StakePoolBlocks, StakePoolData, Transactions, Turnout, VotePlans,
     },
     persistent_sequence::PersistentSequence,
+    storage::{Storage, Stored},
 };
-use crate::db::tally::{compute_private_tally, compute_public_tally};
+use crate::db::tally::{compute_private_tally, compute_public_tally, compute_turnout};
 use chain_addr::Discrimination;
 use chain_core::property::Block as _;
 use chain_impl_mockchain::{
-    block::{Block, ChainLength, Epoch, HeaderId as HeaderHash},
+    account::DelegationType,
+    block::{Block, BlockDate, ChainLength, Epoch, HeaderId as HeaderHash},
     certificate::{Certificate, PoolId, VotePlanId},
     chaintypes::ConsensusVersion,
     config::ConfigParam,
     fee::LinearFee,
     fragment::{ConfigParams, Fragment, FragmentId},
+    milli::Milli,
+    setting::{FeesGoesTo, Settings},
     stake::{Stake, StakeControl},
+    value::Value,
     vote::PayloadType,
 };
 use futures::prelude::*;
 use multiverse::Multiverse;
 pub use multiverse::Ref;
 use std::{
+    collections::HashMap,
     convert::Infallible,
     sync::{
         atomic::{AtomicU32, Ordering},
@@ -59,6 +71,22 @@ pub struct ExplorerDb {
     pub blockchain_config: BlockchainConfig,
     stable_store: StableIndex,
     tip_broadcast: tokio::sync::broadcast::Sender<(HeaderHash, multiverse::Ref)>,
+    main_branch_broadcast: tokio::sync::broadcast::Sender<Arc<MainBranchUpdate>>,
+    /// The on-disk storage of the stable blocks, if the explorer was configured with one.
+    storage: Option<Storage>,
+}
+
+/// The change of the main branch when its tip is set
+pub struct MainBranchUpdate {
+    pub tip: HeaderHash,
+    pub state: multiverse::Ref,
+    /// The last block the previous and the new main branch have in common
+    pub fork_point: HeaderHash,
+    /// The blocks of the previous main branch which are not in the new one, from the previous
+    /// tip down to the fork point
+    pub rolled_back: Vec<HeaderHash>,
+    /// The blocks added to the main branch, in chain order
+    pub applied: Vec<Arc<ExplorerBlock>>,
 }
 
 #[derive(Clone)]
@@ -74,6 +102,20 @@ pub struct BlockchainConfig {
     pub consensus_version: ConsensusVersion,
     pub fees: LinearFee,
     pub epoch_stability_depth: u32,
+    pub slots_per_epoch: u32,
+    /// Only set with the genesis praos consensus
+    pub active_slots_coeff: Option<Milli>,
+    /// Only set when the genesis block limits the size of the block contents
+    pub block_content_max_size: Option<u32>,
+    /// Seconds since the unix epoch at the start of the slot of the genesis block
+    pub block0_time: u64,
+    pub slot_duration: u8,
+    /// The rewards drawn at each epoch
+    pub reward_params: chain_impl_mockchain::rewards::Parameters,
+    /// The cut of the rewards going to the treasury
+    pub treasury_tax: chain_impl_mockchain::rewards::TaxType,
+    /// Whether the fees go to the treasury instead of the rewards
+    pub fees_in_treasury: bool,
 }
 
 /// Inmutable data structure used to represent the explorer's state at a given Block
@@ -90,6 +132,12 @@ pub struct State {
     stake_pool_blocks: StakePoolBlocks,
     vote_plans: VotePlans,
     stake_control: StakeControl,
+    multisig_stake: MultisigStake,
+    delegations: Delegations,
+    balances: Balances,
+    pots: Pots,
+    #[cfg(feature = "evm")]
+    evm: evm::EvmIndex,
 }
 
 #[derive(Clone)]
@@ -102,20 +150,22 @@ pub struct Settings {
 
     pub query_depth_limit: usize,
     pub query_complexity_limit: usize,
+    /// Milliseconds of query execution a client can use per minute, unlimited if `None`
+    pub query_cost_limit: Option<u64>,
+    /// Milliseconds of execution after which a query is logged as slow
+    pub slow_query_threshold: u64,
+    /// Number of responses kept in the cache of the immutable data, 0 disables it
+    pub response_cache_size: usize,
+    /// Maximum number of rows of a REST export
+    pub export_row_limit: usize,
 }
 
 impl ExplorerDb {
-    pub fn bootstrap(block0: Block) -> Result<Self, Error> {
-        let blockchain_config = BlockchainConfig::from_config_params(
-            block0
-                .contents()
-                .iter()
-                .find_map(|fragment| match fragment {
-                    Fragment::Initial(config_params) => Some(config_params),
-                    _ => None,
-                })
-                .expect("the Initial fragment to be present in the genesis block"),
-        );
+    /// Initialize the indexes from the genesis block, which is stored as the first stable
+    /// block in the given storage
+    pub fn bootstrap(block0: Block, storage: Option<Storage>) -> Result<Self, Error> {
+        let config_params = initial_config_params(&block0);
+        let blockchain_config = BlockchainConfig::from_config_params(config_params);
 
         let block = ExplorerBlock::resolve_from(
             &block0,
@@ -127,14 +177,25 @@ impl ExplorerDb {
         );
 
         let blocks = apply_block_to_blocks(Blocks::new(), &block)?;
-        let epochs = apply_block_to_epochs(Epochs::new(), &block);
         let chain_lengths = apply_block_to_chain_lengths(ChainLengths::new(), &block)?;
         let transactions = apply_block_to_transactions(Transactions::new(), &block)?;
         let addresses = apply_block_to_addresses(Addresses::new(), &block);
+        let balances = apply_block_to_balances(Balances::new(), &block);
         let (stake_pool_data, stake_pool_blocks) =
             apply_block_to_stake_pools(StakePool::new(), StakePoolBlocks::new(), &block);
         let stake_control = apply_block_to_stake_control(StakeControl::new(), &block);
+        let multisig_stake = apply_block_to_multisig_stake(MultisigStake::new(), &block);
+        let delegations = apply_block_to_delegations(Delegations::new(), &block);
         let vote_plans = apply_block_to_vote_plans(VotePlans::new(), &block, &stake_control);
+        // the first epoch starts with the distribution set up by the genesis block
+        let epochs = apply_block_to_epochs(Epochs::new(), &block, || {
+            compute_stake_distribution(
+                &delegations,
+                &stake_control,
+                &multisig_stake,
+                &stake_pool_data,
+            )
+        });
 
         let initial_state = State {
             transactions,
@@ -146,26 +207,118 @@ impl ExplorerDb {
             stake_pool_blocks,
             vote_plans,
             stake_control,
+            multisig_stake,
+            delegations,
+            balances,
+            pots: rewards::initial_pots(config_params),
+            #[cfg(feature = "evm")]
+            evm: evm::EvmIndex::new().apply_block(&block)?,
         };
 
-        let block0_id = block0.id();
-        let (_, multiverse) = Multiverse::new(block0.chain_length(), block0_id, initial_state);
+        let bootstraped_db = Self::with_state(
+            blockchain_config,
+            block0.chain_length(),
+            block0.id(),
+            initial_state,
+        );
+
+        if let Some(storage) = storage {
+            storage
+                .put_block0(&block0)
+                .map_err(|e| Error::StorageError(e.to_string()))?;
+
+            Ok(ExplorerDb {
+                storage: Some(storage),
+                ..bootstraped_db
+            })
+        } else {
+            Ok(bootstraped_db)
+        }
+    }
 
-        let block0_id = block0.id();
+    fn with_state(
+        blockchain_config: BlockchainConfig,
+        chain_length: ChainLength,
+        hash: HeaderHash,
+        state: State,
+    ) -> Self {
+        let (_, multiverse) = Multiverse::new(chain_length, hash, state);
 
         let (tx, _) = broadcast::channel(10);
+        let (main_branch_tx, _) = broadcast::channel(10);
 
-        let bootstraped_db = ExplorerDb {
+        ExplorerDb {
             multiverse,
-            longest_chain_tip: Tip::new(block0_id),
+            longest_chain_tip: Tip::new(hash),
             blockchain_config,
             stable_store: StableIndex {
-                confirmed_block_chain_length: Arc::new(AtomicU32::default()),
+                confirmed_block_chain_length: Arc::new(AtomicU32::new(chain_length.into())),
             },
             tip_broadcast: tx,
+            main_branch_broadcast: main_branch_tx,
+            storage: None,
+        }
+    }
+
+    /// Rebuild the indexes from the content of the storage: the snapshot of the indexes is
+    /// decoded and the stable blocks following it are applied, or all the stable blocks are
+    /// applied from the genesis block when there is no usable snapshot. The last of them is
+    /// set as the tip and as the last confirmed block
+    pub async fn restore(stored: Stored, storage: Storage) -> Result<Self, Error> {
+        let Stored {
+            block0,
+            snapshot,
+            blocks,
+        } = stored;
+        let block0 = block0.ok_or_else(|| {
+            Error::BootstrapError("no block to restore from the storage".to_string())
+        })?;
+
+        let restored = snapshot.and_then(|snapshot| match snapshot::decode(&snapshot.indices) {
+            Ok(state) => Some(Self::with_state(
+                BlockchainConfig::from_config_params(initial_config_params(&block0)),
+                snapshot.chain_length,
+                snapshot.hash,
+                state,
+            )),
+            Err(e) => {
+                tracing::warn!(
+                    reason = %e,
+                    "cannot decode the snapshot of the indexes, replaying the stable blocks"
+                );
+                None
+            }
+        });
+        let (db, blocks) = match restored {
+            Some(db) => (db, blocks),
+            None => {
+                let blocks = storage
+                    .stable_blocks()
+                    .map_err(|e| Error::StorageError(e.to_string()))?;
+                (Self::bootstrap(block0, None)?, blocks)
+            }
         };
 
-        Ok(bootstraped_db)
+        for block in blocks {
+            let block = block.map_err(|e| Error::StorageError(e.to_string()))?;
+            let hash = block.header().hash();
+            let chain_length = block.header().chain_length();
+            db.apply_block(block).await?;
+
+            db.stable_store
+                .confirmed_block_chain_length
+                .store(chain_length.into(), Ordering::Release);
+            *db.longest_chain_tip.0.write().await = hash;
+        }
+
+        db.multiverse
+            .gc(db.blockchain_config.epoch_stability_depth)
+            .await;
+
+        Ok(ExplorerDb {
+            storage: Some(storage),
+            ..db
+        })
     }
 
     /// Try to add a new block to the indexes, this can fail if the parent of the block is
@@ -193,6 +346,12 @@ impl ExplorerDb {
             stake_pool_blocks,
             vote_plans,
             stake_control,
+            multisig_stake,
+            delegations,
+            balances,
+            pots,
+            #[cfg(feature = "evm")]
+            evm,
         } = previous_state.state().clone();
 
         let explorer_block = ExplorerBlock::resolve_from(
@@ -203,10 +362,56 @@ impl ExplorerDb {
                 prev_blocks: &blocks,
             },
         );
+        // the rewards of the previous epoch are estimated with the first block of an epoch, as
+        // an estimate they change neither the balances nor the stake
+        let epoch_rewards = blocks
+            .lookup(&previous_block)
+            .map(|parent| parent.date().epoch)
+            .filter(|ended| *ended < explorer_block.date().epoch)
+            .and_then(|ended| epochs.lookup(&ended).map(|data| (ended, data)))
+            .map(|(ended, data)| {
+                let epoch_rewards = rewards::distribute_rewards(
+                    &self.blockchain_config,
+                    ended + 1,
+                    &data.stats,
+                    pots,
+                    &compute_pool_stakes(
+                        &delegations,
+                        &stake_control,
+                        &multisig_stake,
+                        &stake_pool_data,
+                    ),
+                    &stake_pool_data,
+                );
+                (ended, epoch_rewards)
+            });
+        let pots = match &epoch_rewards {
+            Some((_, epoch_rewards)) => epoch_rewards.pots,
+            None => pots,
+        };
+        let pots = explorer_block.transactions.values().fold(pots, |pots, tx| {
+            rewards::add_fees(&self.blockchain_config, pots, tx.fees())
+        });
+
+        // the first block of an epoch takes the distribution at the end of the previous one
+        let epochs = apply_block_to_epochs(epochs, &explorer_block, || {
+            compute_stake_distribution(
+                &delegations,
+                &stake_control,
+                &multisig_stake,
+                &stake_pool_data,
+            )
+        });
+        let epochs = match &epoch_rewards {
+            Some((ended, epoch_rewards)) => apply_rewards_to_epochs(epochs, *ended, epoch_rewards),
+            None => epochs,
+        };
+
         let (stake_pool_data, stake_pool_blocks) =
             apply_block_to_stake_pools(stake_pool_data, stake_pool_blocks, &explorer_block);
 
         let stake_control = apply_block_to_stake_control(stake_control, &explorer_block);
+        let multisig_stake = apply_block_to_multisig_stake(multisig_stake, &explorer_block);
 
         let state_ref = multiverse
             .insert(
@@ -217,7 +422,8 @@ impl ExplorerDb {
                     transactions: apply_block_to_transactions(transactions, &explorer_block)?,
                     blocks: apply_block_to_blocks(blocks, &explorer_block)?,
                     addresses: apply_block_to_addresses(addresses, &explorer_block),
-                    epochs: apply_block_to_epochs(epochs, &explorer_block),
+                    balances: apply_block_to_balances(balances, &explorer_block),
+                    epochs,
                     chain_lengths: apply_block_to_chain_lengths(chain_lengths, &explorer_block)?,
                     stake_pool_data,
                     stake_pool_blocks,
@@ -227,10 +433,19 @@ impl ExplorerDb {
                         &stake_control,
                     ),
                     stake_control,
+                    multisig_stake,
+                    delegations: apply_block_to_delegations(delegations, &explorer_block),
+                    pots,
+                    #[cfg(feature = "evm")]
+                    evm: evm.apply_block(&explorer_block)?,
                 },
             )
             .await;
 
+        if let Some(storage) = &self.storage {
+            storage.put_unstable(block);
+        }
+
         Ok(state_ref)
     }
 
@@ -257,6 +472,15 @@ impl ExplorerDb {
         let state = state_ref.state();
         let block = Arc::clone(state.blocks.lookup(&hash).unwrap());
 
+        // the previous main branch is needed to find the blocks rolled back, it is taken
+        // before the garbage collection
+        let previous_tip = self.longest_chain_tip.get_block_id().await;
+        let previous_state = if self.main_branch_broadcast.receiver_count() > 0 {
+            self.multiverse.get_ref(&previous_tip).await
+        } else {
+            None
+        };
+
         if let Some(confirmed_block_chain_length) = block
             .chain_length()
             .nth_ancestor(self.blockchain_config.epoch_stability_depth)
@@ -273,6 +497,9 @@ impl ExplorerDb {
                 .confirmed_block_chain_length
                 .store(confirmed_block_chain_length.into(), Ordering::Release);
 
+            self.store_stable_blocks(state, confirmed_block_chain_length)
+                .await;
+
             self.multiverse
                 .gc(self.blockchain_config.epoch_stability_depth)
                 .await;
@@ -282,11 +509,95 @@ impl ExplorerDb {
 
         *guard = hash;
 
+        drop(guard);
+
+        if let Some(previous_state) = previous_state {
+            if let Some(update) =
+                main_branch_update(previous_tip, &previous_state, hash, state_ref.clone())
+            {
+                let _ = self.main_branch_broadcast.send(Arc::new(update));
+            }
+        }
+
         let _ = self.tip_broadcast.send((hash, state_ref));
 
         Ok(())
     }
 
+    /// Store the blocks of the main branch up to the confirmed chain length, and write the
+    /// snapshot of the indexes when it is due
+    async fn store_stable_blocks(&self, state: &State, confirmed: ChainLength) {
+        let storage = match &self.storage {
+            Some(storage) => storage,
+            None => return,
+        };
+
+        // a failed write is retried when the next tip is set, the blocks are kept until
+        // then. A missing block is received again from the `storage_resync_point`
+        if let Err(e) = storage.make_stable(confirmed, |length| {
+            state.chain_lengths.lookup(&length).map(|hash| **hash)
+        }) {
+            tracing::error!(reason = %e, "failed to store the stable blocks");
+            return;
+        }
+
+        let point = match storage.snapshot_due() {
+            Some(point) => point,
+            None => return,
+        };
+        let state_ref = match self.multiverse.get_ref(&point.hash).await {
+            Some(state_ref) => state_ref,
+            None => return,
+        };
+
+        let storage = storage.clone();
+        tokio::task::spawn_blocking(move || {
+            let result = snapshot::encode(state_ref.state())
+                .map_err(|e| e.to_string())
+                .and_then(|indices| {
+                    storage
+                        .write_snapshot(point, &indices)
+                        .map_err(|e| e.to_string())
+                });
+            if let Err(reason) = result {
+                tracing::error!(%reason, "failed to write the snapshot of the indexes");
+            }
+        });
+    }
+
+    /// The last stored block, when the blocks following it have to be received again to be
+    /// stored
+    pub fn storage_resync_point(&self) -> Option<HeaderHash> {
+        self.storage
+            .as_ref()
+            .and_then(|storage| storage.resync_point())
+    }
+
+    /// Add a block received again to resync the storage, it is only applied if it is not
+    /// already indexed
+    pub async fn resync_block(&self, block: Block) -> Result<(), Error> {
+        match (&self.storage, self.get_block(&block.header().hash()).await) {
+            (Some(storage), Some(_)) => storage.put_unstable(block),
+            _ => {
+                self.apply_block(block).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Store again the stable blocks of the main branch, once the missing ones were received
+    pub async fn store_stable_blocks_again(&self) {
+        let tip = self.longest_chain_tip.get_block_id().await;
+        if let Some(state_ref) = self.multiverse.get_ref(&tip).await {
+            let confirmed = ChainLength::from(
+                self.stable_store
+                    .confirmed_block_chain_length
+                    .load(Ordering::Acquire),
+            );
+            self.store_stable_blocks(state_ref.state(), confirmed).await;
+        }
+    }
+
     pub async fn get_block_with_branches(
         &self,
         block_id: &HeaderHash,
@@ -315,6 +626,30 @@ impl ExplorerDb {
             .map(|e| e.as_ref().clone())
     }
 
+    /// The epoch in the main branch, with the date of the tip telling how far the current epoch
+    /// went
+    pub async fn get_main_branch_epoch(&self, epoch: Epoch) -> Option<(EpochData, BlockDate)> {
+        let (tip, state_ref) = self.get_tip().await;
+        let state = state_ref.state();
+
+        let data = state.epochs.lookup(&epoch)?.as_ref().clone();
+        let tip_date = state.blocks.lookup(&tip)?.date();
+        Some((data, tip_date))
+    }
+
+    /// The delegation of the account of the given address in the main branch, `None` if the
+    /// account never delegated or the address has no account
+    pub async fn get_delegation(&self, address: &ExplorerAddress) -> Option<Arc<DelegationType>> {
+        let account = address.to_account_address()?;
+        let (_, state_ref) = self.get_tip().await;
+
+        state_ref
+            .state()
+            .delegations
+            .lookup(&account)
+            .map(Arc::clone)
+    }
+
     pub async fn is_block_confirmed(&self, block_id: &HeaderHash) -> bool {
         let current_branch = self
             .multiverse
@@ -370,6 +705,19 @@ impl ExplorerDb {
         txs
     }
 
+    /// Whether the address has transactions or a balance in any branch
+    pub async fn is_address_known(&self, address: &ExplorerAddress) -> bool {
+        self.multiverse
+            .tips()
+            .await
+            .iter()
+            .any(|(_tip_hash, state_ref)| {
+                let state = state_ref.state();
+                state.addresses.lookup(address).is_some()
+                    || state.balances.lookup(address).is_some()
+            })
+    }
+
     pub async fn get_stake_pool_blocks(
         &self,
         pool: &PoolId,
@@ -418,6 +766,24 @@ impl ExplorerDb {
         None
     }
 
+    /// The votes cast by the address in the vote plans of the main branch, with the index of
+    /// the proposal in its vote plan
+    pub async fn get_votes_by_address(
+        &self,
+        address: &ExplorerAddress,
+    ) -> Vec<(Arc<ExplorerVotePlan>, u8, Arc<ExplorerVote>)> {
+        let (_, state_ref) = self.get_tip().await;
+
+        votes_by_address(&state_ref.state().vote_plans, address)
+    }
+
+    /// The turnout of the voters with their stake at the tip of the main branch
+    pub async fn get_turnout(&self, voters: &[ExplorerAddress]) -> Turnout {
+        let (_, state_ref) = self.get_tip().await;
+
+        compute_turnout(voters, &state_ref.state().stake_control)
+    }
+
     pub async fn get_branch(&self, hash: &HeaderHash) -> Option<multiverse::Ref> {
         self.multiverse.get_ref(hash).await
     }
@@ -441,6 +807,79 @@ impl ExplorerDb {
     > {
         tokio_stream::wrappers::BroadcastStream::new(self.tip_broadcast.subscribe())
     }
+
+    /// The updates of the main branch, each time its tip is set. A subscriber lagging too far
+    /// behind gets a `Lagged` error for the updates it missed
+    pub fn main_branch_subscription(
+        &self,
+    ) -> impl Stream<
+        Item = Result<
+            Arc<MainBranchUpdate>,
+            tokio_stream::wrappers::errors::BroadcastStreamRecvError,
+        >,
+    > {
+        tokio_stream::wrappers::BroadcastStream::new(self.main_branch_broadcast.subscribe())
+    }
+}
+
+fn initial_config_params(block0: &Block) -> &ConfigParams {
+    block0
+        .contents()
+        .iter()
+        .find_map(|fragment| match fragment {
+            Fragment::Initial(config_params) => Some(config_params),
+            _ => None,
+        })
+        .expect("the Initial fragment to be present in the genesis block")
+}
+
+/// Compare the previous main branch with the new one, `None` if the tip didn't change
+fn main_branch_update(
+    previous_tip: HeaderHash,
+    previous_state: &multiverse::Ref,
+    tip: HeaderHash,
+    state_ref: multiverse::Ref,
+) -> Option<MainBranchUpdate> {
+    if previous_tip == tip {
+        return None;
+    }
+
+    let previous = previous_state.state();
+    let state = state_ref.state();
+
+    let previous_length = u32::from(previous.blocks.lookup(&previous_tip)?.chain_length());
+    let length = u32::from(state.blocks.lookup(&tip)?.chain_length());
+
+    let block_at = |state: &State, length: u32| -> Option<HeaderHash> {
+        state
+            .chain_lengths
+            .lookup(&length.into())
+            .map(|hash| **hash)
+    };
+
+    // walk down the previous branch until the block is in the new one too
+    let mut fork_length = previous_length.min(length);
+    while block_at(previous, fork_length) != block_at(state, fork_length) {
+        fork_length = fork_length.checked_sub(1)?;
+    }
+
+    let rolled_back = ((fork_length + 1)..=previous_length)
+        .rev()
+        .filter_map(|length| block_at(previous, length))
+        .collect();
+
+    let applied = ((fork_length + 1)..=length)
+        .filter_map(|length| block_at(state, length))
+        .filter_map(|hash| state.blocks.lookup(&hash).map(Arc::clone))
+        .collect();
+
+    Some(MainBranchUpdate {
+        tip,
+        fork_point: block_at(state, fork_length)?,
+        state: state_ref,
+        rolled_back,
+        applied,
+    })
 }
 
 fn apply_block_to_transactions(
@@ -496,27 +935,123 @@ fn apply_block_to_addresses(mut addresses: Addresses, block: &ExplorerBlock) ->
     addresses
 }
 
-fn apply_block_to_epochs(epochs: Epochs, block: &ExplorerBlock) -> Epochs {
+/// The changes of the balances by the transactions of the block, in the block order. The
+/// rewards are not tracked, so the balances of the rewarded accounts miss them and an account
+/// spending its rewards has its balance saturated at zero
+fn apply_block_to_balances(mut balances: Balances, block: &ExplorerBlock) -> Balances {
+    let mut changes = Vec::new();
+
+    let mut transactions: Vec<_> = block.transactions.values().collect();
+    transactions.sort_unstable_by_key(|tx| tx.offset_in_block);
+
+    for tx in transactions {
+        // an address can be both an input and an output of a transaction, the change is
+        // recorded once
+        let mut tx_changes: HashMap<&ExplorerAddress, (u64, u64)> = HashMap::new();
+
+        for output in tx.outputs() {
+            tx_changes.entry(&output.address).or_default().0 += output.value.0;
+        }
+        for input in tx.inputs() {
+            tx_changes.entry(&input.address).or_default().1 += input.value.0;
+        }
+
+        changes.extend(
+            tx_changes
+                .into_iter()
+                .map(|(address, (received, spent))| (tx.id(), address, received, spent)),
+        );
+    }
+
+    for (transaction, address, received, spent) in changes {
+        let current = balances.lookup(address).map(Arc::clone);
+        let previous = current.as_ref().map_or(0, |current| current.balance.0);
+        let balance = (previous + received).checked_sub(spent).unwrap_or_else(|| {
+            // the rewards credited by the ledger outside of the blocks are not tracked, an
+            // account spending them goes over its tracked balance
+            tracing::warn!(
+                block = %block.id(),
+                %transaction,
+                "address spending more than its tracked balance, set to zero"
+            );
+            0
+        });
+
+        let change = BalanceChange {
+            block: block.id(),
+            chain_length: block.chain_length(),
+            transaction,
+            received: Value(received),
+            spent: Value(spent),
+            balance: Value(balance),
+        };
+        let updated = Arc::new(AddressBalance {
+            balance: change.balance,
+            changes: match current {
+                Some(current) => current.changes.append(change),
+                None => PersistentSequence::new().append(change),
+            },
+        });
+        balances = balances.insert_or_update_simple(address.clone(), Arc::clone(&updated), |_| {
+            Some(Arc::clone(&updated))
+        });
+    }
+
+    balances
+}
+
+/// `stake_distribution` is only evaluated for the first block of an epoch
+fn apply_block_to_epochs<F>(epochs: Epochs, block: &ExplorerBlock, stake_distribution: F) -> Epochs
+where
+    F: FnOnce() -> StakeDistribution,
+{
     let epoch_id = block.date().epoch;
     let block_id = block.id();
 
+    let stake_distribution = match epochs.lookup(&epoch_id) {
+        Some(data) => Arc::clone(&data.stake_distribution),
+        None => Arc::new(stake_distribution()),
+    };
+
     epochs.insert_or_update_simple(
         epoch_id,
         Arc::new(EpochData {
             first_block: block_id,
             last_block: block_id,
-            total_blocks: 0,
+            total_blocks: 1,
+            stake_distribution,
+            stats: EpochStats::default().add_block(block),
+            estimated_rewards: None,
         }),
         |data| {
             Some(Arc::new(EpochData {
                 first_block: data.first_block,
                 last_block: block_id,
                 total_blocks: data.total_blocks + 1,
+                stake_distribution: Arc::clone(&data.stake_distribution),
+                stats: data.stats.add_block(block),
+                estimated_rewards: None,
             }))
         },
     )
 }
 
+/// Records the rewards of the ended epoch, estimated with the first block of the next one
+fn apply_rewards_to_epochs(
+    epochs: Epochs,
+    ended: Epoch,
+    epoch_rewards: &rewards::EpochRewards,
+) -> Epochs {
+    epochs
+        .update(&ended, |data| -> Result<_, Infallible> {
+            Ok(Some(Arc::new(EpochData {
+                estimated_rewards: Some(epoch_rewards.estimated.clone()),
+                ..data.as_ref().clone()
+            })))
+        })
+        .expect("the ended epoch to be indexed")
+}
+
 fn apply_block_to_chain_lengths(
     chain_lengths: ChainLengths,
     block: &ExplorerBlock,
@@ -585,6 +1120,28 @@ fn apply_block_to_stake_pools(
     (data, blocks)
 }
 
+fn votes_by_address(
+    vote_plans: &VotePlans,
+    address: &ExplorerAddress,
+) -> Vec<(Arc<ExplorerVotePlan>, u8, Arc<ExplorerVote>)> {
+    vote_plans
+        .iter()
+        .flat_map(|(_, vote_plan)| {
+            vote_plan
+                .proposals
+                .iter()
+                .enumerate()
+                .filter_map(|(index, proposal)| {
+                    proposal
+                        .votes
+                        .lookup(address)
+                        .map(|vote| (Arc::clone(vote_plan), index as u8, Arc::clone(vote)))
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
 fn apply_block_to_vote_plans(
     mut vote_plans: VotePlans,
     block: &ExplorerBlock,
@@ -610,8 +1167,10 @@ fn apply_block_to_vote_plans(
                                     options: proposal.options().clone(),
                                     tally: None,
                                     votes: Default::default(),
+                                    turnout: None,
                                 })
                                 .collect(),
+                            turnout: None,
                         }),
                     )
                     .unwrap(),
@@ -678,13 +1237,17 @@ fn apply_block_to_vote_plans(
                 }
                 Certificate::VoteTally(vote_tally) => vote_plans
                     .update(vote_tally.id(), |vote_plan| {
-                        let proposals = match vote_tally.tally_type() {
+                        let proposals: Vec<ExplorerVoteProposal> = match vote_tally.tally_type() {
                             PayloadType::Public => vote_plan
                                 .proposals
                                 .clone()
                                 .into_iter()
                                 .map(|mut proposal| {
                                     proposal.tally = Some(compute_public_tally(&proposal, stake));
+                                    proposal.turnout = Some(compute_turnout(
+                                        proposal.votes.iter().map(|(voter, _)| voter),
+                                        stake,
+                                    ));
 
                                     proposal
                                 })
@@ -702,6 +1265,10 @@ fn apply_block_to_vote_plans(
                                     .map(|(mut proposal, decrypted_tally)| {
                                         proposal.tally =
                                             Some(compute_private_tally(&proposal, decrypted_tally));
+                                        proposal.turnout = Some(compute_turnout(
+                                            proposal.votes.iter().map(|(voter, _)| voter),
+                                            stake,
+                                        ));
 
                                         proposal
                                     })
@@ -709,8 +1276,17 @@ fn apply_block_to_vote_plans(
                             }
                         };
 
+                        let turnout = compute_turnout(
+                            proposals
+                                .iter()
+                                .flat_map(|proposal| proposal.votes.iter())
+                                .map(|(voter, _)| voter),
+                            stake,
+                        );
+
                         let vote_plan = ExplorerVotePlan {
                             proposals,
+                            turnout: Some(turnout),
                             ..(**vote_plan).clone()
                         };
                         Ok::<_, std::convert::Infallible>(Some(Arc::new(vote_plan)))
@@ -767,12 +1343,179 @@ fn apply_block_to_stake_control(
     stake_control
 }
 
+fn apply_block_to_multisig_stake(
+    mut multisig_stake: MultisigStake,
+    block: &ExplorerBlock,
+) -> MultisigStake {
+    fn multisig_id(address: &ExplorerAddress) -> Option<[u8; 32]> {
+        match address {
+            ExplorerAddress::New(address) => match address.kind() {
+                chain_addr::Kind::Multisig(id) => Some(*id),
+                _ => None,
+            },
+            ExplorerAddress::Old(_) => None,
+        }
+    }
+
+    for tx in block.transactions.values() {
+        for input in tx.inputs() {
+            if let Some(id) = multisig_id(&input.address) {
+                let stake = multisig_stake
+                    .lookup(&id)
+                    .map(|stake| stake.0)
+                    .unwrap_or(0)
+                    .checked_sub(input.value.0)
+                    .unwrap_or_else(|| {
+                        tracing::warn!(
+                            block = %block.id(),
+                            transaction = %tx.id(),
+                            "multisig account spending more than its tracked stake, set to zero"
+                        );
+                        0
+                    });
+                multisig_stake =
+                    multisig_stake.insert_or_update_simple(id, Arc::new(Value(stake)), |_| {
+                        Some(Arc::new(Value(stake)))
+                    });
+            }
+        }
+
+        for output in tx.outputs() {
+            if let Some(id) = multisig_id(&output.address) {
+                multisig_stake =
+                    multisig_stake.insert_or_update_simple(id, Arc::new(output.value), |stake| {
+                        Some(Arc::new(Value(stake.0 + output.value.0)))
+                    });
+            }
+        }
+    }
+
+    multisig_stake
+}
+
+fn apply_block_to_delegations(mut delegations: Delegations, block: &ExplorerBlock) -> Delegations {
+    for tx in block.transactions.values() {
+        let delegation_type = match &tx.certificate {
+            Some(Certificate::StakeDelegation(delegation)) => delegation.get_delegation_type(),
+            Some(Certificate::OwnerStakeDelegation(delegation)) => delegation.get_delegation_type(),
+            _ => continue,
+        };
+
+        if let Some(account) = &tx.delegation_account {
+            delegations = delegations.insert_or_update_simple(
+                account.clone(),
+                Arc::new(delegation_type.clone()),
+                |_| Some(Arc::new(delegation_type.clone())),
+            );
+        }
+    }
+
+    delegations
+}
+
+/// The stake delegated to each registered and not retired pool by the accounts. The stake of
+/// an account is its balance, without the rewards which are not tracked, and the value of the
+/// utxo addresses of its group.
+fn compute_stake_distribution(
+    delegations: &Delegations,
+    stake_control: &StakeControl,
+    multisig_stake: &MultisigStake,
+    stake_pools: &StakePool,
+) -> StakeDistribution {
+    let pool_stakes = compute_pool_stakes(delegations, stake_control, multisig_stake, stake_pools);
+
+    StakeDistribution {
+        pools: pool_stakes
+            .iter()
+            .map(|(pool, stakes)| (pool.clone(), Value(stakes.values().sum())))
+            .collect(),
+        delegators: pool_stakes
+            .iter()
+            .map(|(pool, stakes)| (pool.clone(), stakes.len() as u32))
+            .collect(),
+    }
+}
+
+/// The stake of each account delegating to a registered and not retired pool, the stake of an
+/// account delegating to several pools is split by its delegation ratio
+fn compute_pool_stakes(
+    delegations: &Delegations,
+    stake_control: &StakeControl,
+    multisig_stake: &MultisigStake,
+    stake_pools: &StakePool,
+) -> rewards::PoolStakes {
+    let is_active = |pool: &PoolId| {
+        stake_pools
+            .lookup(pool)
+            .map(|data| data.retirement.is_none())
+            .unwrap_or(false)
+    };
+
+    let mut pools = rewards::PoolStakes::new();
+
+    for (account, delegation) in delegations.iter() {
+        let stake = match account {
+            ExplorerAddress::New(address) => match address.kind() {
+                chain_addr::Kind::Account(key) => {
+                    stake_control.by(&key.clone().into()).map(u64::from)
+                }
+                chain_addr::Kind::Multisig(id) => multisig_stake.lookup(id).map(|value| value.0),
+                _ => None,
+            },
+            ExplorerAddress::Old(_) => None,
+        }
+        .unwrap_or(0);
+
+        match delegation.as_ref() {
+            DelegationType::NonDelegated => (),
+            DelegationType::Full(pool) => {
+                if is_active(pool) {
+                    pools
+                        .entry(pool.clone())
+                        .or_default()
+                        .insert(account.clone(), stake);
+                }
+            }
+            DelegationType::Ratio(ratio) => {
+                let parts = u64::from(ratio.parts());
+                let per_part = stake / parts;
+                // the remainder of the division goes to the first pool
+                let mut remainder = stake % parts;
+
+                for (pool, pool_parts) in ratio.pools() {
+                    let pool_stake = per_part * u64::from(*pool_parts) + remainder;
+                    remainder = 0;
+
+                    if is_active(pool) {
+                        *pools
+                            .entry(pool.clone())
+                            .or_default()
+                            .entry(account.clone())
+                            .or_default() += pool_stake;
+                    }
+                }
+            }
+        }
+    }
+
+    pools
+}
+
 impl BlockchainConfig {
     fn from_config_params(params: &ConfigParams) -> BlockchainConfig {
         let mut discrimination: Option<Discrimination> = None;
         let mut consensus_version: Option<ConsensusVersion> = None;
         let mut fees: Option<LinearFee> = None;
         let mut epoch_stability_depth: Option<u32> = None;
+        let mut slots_per_epoch: Option<u32> = None;
+        let mut active_slots_coeff: Option<Milli> = None;
+        let mut block_content_max_size: Option<u32> = None;
+        let mut block0_time: Option<u64> = None;
+        let mut slot_duration: Option<u8> = None;
+        // the rewards parameters are read as the ledger does
+        let settings = Settings::new()
+            .try_apply(params)
+            .expect("the initial params to be valid ledger settings");
 
         for p in params.iter() {
             match p {
@@ -788,6 +1531,21 @@ impl BlockchainConfig {
                 ConfigParam::EpochStabilityDepth(d) => {
                     epoch_stability_depth.replace(*d);
                 }
+                ConfigParam::SlotsPerEpoch(slots) => {
+                    slots_per_epoch.replace(*slots);
+                }
+                ConfigParam::ConsensusGenesisPraosActiveSlotsCoeff(coeff) => {
+                    active_slots_coeff.replace(*coeff);
+                }
+                ConfigParam::BlockContentMaxSize(size) => {
+                    block_content_max_size.replace(*size);
+                }
+                ConfigParam::Block0Date(date) => {
+                    block0_time.replace(date.0);
+                }
+                ConfigParam::SlotDuration(duration) => {
+                    slot_duration.replace(*duration);
+                }
                 _ => (),
             }
         }
@@ -799,8 +1557,27 @@ impl BlockchainConfig {
             fees: fees.expect("fees not found in initial params"),
             epoch_stability_depth: epoch_stability_depth
                 .expect("epoch stability depth not found in initial params"),
+            slots_per_epoch: slots_per_epoch.expect("slots per epoch not found in initial params"),
+            active_slots_coeff,
+            block_content_max_size,
+            block0_time: block0_time.expect("block0 date not found in initial params"),
+            slot_duration: slot_duration.expect("slot duration not found in initial params"),
+            reward_params: settings.to_reward_params(),
+            treasury_tax: settings.to_treasury_params(),
+            fees_in_treasury: matches!(settings.fees_goes_to, FeesGoesTo::Treasury),
         }
     }
+
+    /// Seconds since the unix epoch at the start of the slot of the given date. The slot
+    /// duration and the number of slots per epoch are the ones of the genesis block
+    pub fn block_time(&self, date: &BlockDate) -> u64 {
+        let slots = u64::from(date.epoch)
+            .saturating_mul(u64::from(self.slots_per_epoch))
+            .saturating_add(u64::from(date.slot_id));
+
+        self.block0_time
+            .saturating_add(slots.saturating_mul(u64::from(self.slot_duration)))
+    }
 }
 
 impl Tip {
@@ -837,6 +1614,19 @@ impl State {
             .map(|txs| PersistentSequence::clone(txs))
     }
 
+    pub fn address_balance(&self, address: &ExplorerAddress) -> Option<Arc<AddressBalance>> {
+        self.balances.lookup(address).map(Arc::clone)
+    }
+
+    pub fn stake_pool_blocks(&self, pool: &PoolId) -> Option<Arc<PersistentSequence<HeaderHash>>> {
+        self.stake_pool_blocks.lookup(pool).map(Arc::clone)
+    }
+
+    #[cfg(feature = "evm")]
+    pub fn evm(&self) -> &evm::EvmIndex {
+        &self.evm
+    }
+
     // Get the hashes of all blocks in the range [from, to)
     // the ChainLength is returned to for easy of use in the case where
     // `to` is greater than the max
@@ -857,3 +1647,649 @@ impl State {
             .collect()
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use chain_impl_mockchain::{
+        account::DelegationRatio,
+        block::builder as block_builder,
+        certificate::{ExternalProposalId, StakeDelegation},
+        fragment::Contents,
+        header::BlockVersion,
+        key::Hash,
+        transaction::UnspecifiedAccountIdentifier,
+        vote::{Choice, Options},
+    };
+    use indexing::{
+        BlockProducer, CertificateKind, EstimatedRewards, ExplorerInput, ExplorerOutput,
+        ExplorerTransaction, Hamt,
+    };
+    use jormungandr_lib::interfaces::{Block0Configuration, BlockchainConfiguration};
+
+    /// A database bootstrapped from an empty genesis block
+    pub(crate) fn bootstrapped_db() -> (ExplorerDb, Block) {
+        let block0 = Block0Configuration {
+            blockchain_configuration: BlockchainConfiguration::new(
+                Discrimination::Test,
+                ConsensusVersion::Bft,
+                LinearFee::new(0, 0, 0),
+            ),
+            initial: Vec::new(),
+        }
+        .to_block();
+
+        (ExplorerDb::bootstrap(block0.clone(), None).unwrap(), block0)
+    }
+
+    /// An empty block following the parent, at the given slot of the first epoch
+    pub(crate) fn child_block(parent: &Block, slot_id: u32) -> Block {
+        block_builder(BlockVersion::Genesis, Contents::empty(), |hdr_builder| {
+            Ok::<_, Infallible>(
+                hdr_builder
+                    .set_parent(&parent.id(), parent.chain_length().increase())
+                    .set_date(BlockDate { epoch: 0, slot_id })
+                    .into_unsigned_header()
+                    .unwrap()
+                    .generalize(),
+            )
+        })
+        .unwrap()
+    }
+
+    fn account(wallet: &thor::Wallet) -> ExplorerAddress {
+        ExplorerAddress::New(wallet.address().into())
+    }
+
+    fn account_key(account: &ExplorerAddress) -> chain_impl_mockchain::account::Identifier {
+        match account {
+            ExplorerAddress::New(address) => match address.kind() {
+                chain_addr::Kind::Account(key) => key.clone().into(),
+                _ => panic!("not an account"),
+            },
+            ExplorerAddress::Old(_) => panic!("not an account"),
+        }
+    }
+
+    fn multisig(id: u8) -> ExplorerAddress {
+        ExplorerAddress::New(chain_addr::Address(
+            Discrimination::Test,
+            chain_addr::Kind::Multisig([id; 32]),
+        ))
+    }
+
+    fn transaction(
+        offset: u32,
+        inputs: &[(ExplorerAddress, u64)],
+        outputs: &[(ExplorerAddress, u64)],
+    ) -> ExplorerTransaction {
+        ExplorerTransaction {
+            id: Hash::hash_bytes(&offset.to_be_bytes()),
+            inputs: inputs
+                .iter()
+                .map(|(address, value)| ExplorerInput {
+                    address: address.clone(),
+                    value: Value(*value),
+                })
+                .collect(),
+            outputs: outputs
+                .iter()
+                .map(|(address, value)| ExplorerOutput {
+                    address: address.clone(),
+                    value: Value(*value),
+                })
+                .collect(),
+            certificate: None,
+            offset_in_block: offset,
+            config_params: None,
+            delegation_account: None,
+        }
+    }
+
+    fn delegation(
+        offset: u32,
+        account: &ExplorerAddress,
+        delegation: DelegationType,
+    ) -> ExplorerTransaction {
+        ExplorerTransaction {
+            certificate: Some(Certificate::StakeDelegation(StakeDelegation {
+                account_id: UnspecifiedAccountIdentifier::from_single_account(account_key(account)),
+                delegation,
+            })),
+            delegation_account: Some(account.clone()),
+            ..transaction(offset, &[(account.clone(), 1)], &[])
+        }
+    }
+
+    fn block(chain_length: u32, transactions: Vec<ExplorerTransaction>) -> ExplorerBlock {
+        ExplorerBlock {
+            transactions: transactions.into_iter().map(|tx| (tx.id, tx)).collect(),
+            id: Hash::hash_bytes(&chain_length.to_be_bytes()),
+            date: BlockDate {
+                epoch: 0,
+                slot_id: chain_length,
+            },
+            chain_length: ChainLength::from(chain_length),
+            parent_hash: HeaderHash::zero_hash(),
+            producer: BlockProducer::None,
+            total_input: Value::zero(),
+            total_output: Value::zero(),
+            content_size: 0,
+            #[cfg(feature = "evm")]
+            evm_transactions: Vec::new(),
+        }
+    }
+
+    fn stake_pools(pools: &[&thor::StakePool]) -> StakePool {
+        pools.iter().fold(StakePool::new(), |stake_pools, pool| {
+            stake_pools
+                .insert(
+                    pool.id(),
+                    Arc::new(StakePoolData {
+                        registration: pool.info(),
+                        retirement: None,
+                    }),
+                )
+                .unwrap()
+        })
+    }
+
+    #[test]
+    fn delegations_follow_the_last_certificate() {
+        let owner = thor::Wallet::default();
+        let pools = [thor::StakePool::new(&owner), thor::StakePool::new(&owner)];
+        let delegator = account(&thor::Wallet::default());
+
+        let delegations = apply_block_to_delegations(
+            Delegations::new(),
+            &block(
+                1,
+                vec![delegation(
+                    0,
+                    &delegator,
+                    DelegationType::Full(pools[0].id()),
+                )],
+            ),
+        );
+        assert!(matches!(
+            delegations.lookup(&delegator).map(|delegation| delegation.as_ref()),
+            Some(DelegationType::Full(pool)) if *pool == pools[0].id()
+        ));
+
+        let delegations = apply_block_to_delegations(
+            delegations,
+            &block(
+                2,
+                vec![delegation(
+                    0,
+                    &delegator,
+                    DelegationType::Full(pools[1].id()),
+                )],
+            ),
+        );
+        assert!(matches!(
+            delegations.lookup(&delegator).map(|delegation| delegation.as_ref()),
+            Some(DelegationType::Full(pool)) if *pool == pools[1].id()
+        ));
+
+        // a transaction without certificate leaves the delegation as it is
+        let delegations =
+            apply_block_to_delegations(delegations, &block(3, vec![transaction(0, &[], &[])]));
+        assert_eq!(delegations.iter().count(), 1);
+    }
+
+    #[test]
+    fn stake_is_split_by_the_delegation_ratio() {
+        let owner = thor::Wallet::default();
+        let pools = [thor::StakePool::new(&owner), thor::StakePool::new(&owner)];
+        let delegator = account(&thor::Wallet::default());
+        let ratio = DelegationRatio::new(3, vec![(pools[0].id(), 2), (pools[1].id(), 1)]).unwrap();
+
+        let delegations = Delegations::new()
+            .insert(delegator.clone(), Arc::new(DelegationType::Ratio(ratio)))
+            .unwrap();
+        let stake_control =
+            StakeControl::new().add_to(account_key(&delegator), Stake::from_value(Value(10)));
+
+        let distribution = compute_stake_distribution(
+            &delegations,
+            &stake_control,
+            &MultisigStake::new(),
+            &stake_pools(&[&pools[0], &pools[1]]),
+        );
+
+        // the remainder of the split goes to the first pool
+        assert_eq!(distribution.pools[&pools[0].id()], Value(7));
+        assert_eq!(distribution.pools[&pools[1].id()], Value(3));
+        assert_eq!(distribution.delegators[&pools[0].id()], 1);
+        assert_eq!(distribution.delegators[&pools[1].id()], 1);
+
+        // the part delegated to a pool which is not registered is not counted
+        let distribution = compute_stake_distribution(
+            &delegations,
+            &stake_control,
+            &MultisigStake::new(),
+            &stake_pools(&[&pools[1]]),
+        );
+        assert!(distribution.pools.get(&pools[0].id()).is_none());
+        assert_eq!(distribution.pools[&pools[1].id()], Value(3));
+    }
+
+    #[test]
+    fn multisig_stake_is_tracked_by_identifier() {
+        let utxo = ExplorerAddress::New(
+            thor::Wallet::new_utxo(&mut rand::rngs::OsRng)
+                .address()
+                .into(),
+        );
+
+        let multisig_stake = apply_block_to_multisig_stake(
+            MultisigStake::new(),
+            &block(
+                1,
+                vec![transaction(
+                    0,
+                    &[(utxo.clone(), 20)],
+                    &[(multisig(1), 10), (multisig(2), 5), (utxo, 5)],
+                )],
+            ),
+        );
+        let multisig_stake = apply_block_to_multisig_stake(
+            multisig_stake,
+            &block(
+                2,
+                vec![transaction(0, &[(multisig(1), 4)], &[(multisig(2), 3)])],
+            ),
+        );
+
+        assert_eq!(*multisig_stake.lookup(&[1; 32]).unwrap().as_ref(), Value(6));
+        assert_eq!(*multisig_stake.lookup(&[2; 32]).unwrap().as_ref(), Value(8));
+        assert_eq!(multisig_stake.iter().count(), 2);
+    }
+
+    #[test]
+    fn multisig_stake_does_not_underflow() {
+        let multisig_stake = apply_block_to_multisig_stake(
+            MultisigStake::new(),
+            &block(1, vec![transaction(0, &[(multisig(1), 1)], &[])]),
+        );
+
+        assert_eq!(
+            *multisig_stake.lookup(&[1; 32]).unwrap().as_ref(),
+            Value::zero()
+        );
+    }
+
+    #[test]
+    fn balance_at_is_the_balance_after_the_block() {
+        let address = account(&thor::Wallet::default());
+        let other = account(&thor::Wallet::default());
+
+        let balances = apply_block_to_balances(
+            Balances::new(),
+            &block(1, vec![transaction(0, &[], &[(address.clone(), 10)])]),
+        );
+        let balances = apply_block_to_balances(
+            balances,
+            &block(
+                3,
+                vec![transaction(0, &[(address.clone(), 4)], &[(other, 4)])],
+            ),
+        );
+        let balances = apply_block_to_balances(
+            balances,
+            &block(
+                5,
+                vec![
+                    transaction(0, &[], &[(address.clone(), 2)]),
+                    transaction(1, &[(address.clone(), 7)], &[(address.clone(), 6)]),
+                ],
+            ),
+        );
+
+        let balance = balances.lookup(&address).unwrap();
+        assert_eq!(balance.balance, Value(7));
+        assert_eq!(balance.changes.len(), 4);
+        assert_eq!(balance.balance_at(ChainLength::from(0)), Value::zero());
+        assert_eq!(balance.balance_at(ChainLength::from(1)), Value(10));
+        assert_eq!(balance.balance_at(ChainLength::from(2)), Value(10));
+        assert_eq!(balance.balance_at(ChainLength::from(3)), Value(6));
+        assert_eq!(balance.balance_at(ChainLength::from(4)), Value(6));
+        // both transactions of the block are applied in the order of the block
+        assert_eq!(balance.balance_at(ChainLength::from(5)), Value(7));
+        assert_eq!(balance.balance_at(ChainLength::from(100)), Value(7));
+    }
+
+    #[test]
+    fn balances_of_the_branches_are_independent() {
+        let address = account(&thor::Wallet::default());
+        let other = account(&thor::Wallet::default());
+
+        let fork = apply_block_to_balances(
+            Balances::new(),
+            &block(1, vec![transaction(0, &[], &[(address.clone(), 10)])]),
+        );
+        let spending_branch = apply_block_to_balances(
+            fork.clone(),
+            &block(
+                2,
+                vec![transaction(
+                    0,
+                    &[(address.clone(), 10)],
+                    &[(other.clone(), 9)],
+                )],
+            ),
+        );
+        let receiving_branch = apply_block_to_balances(
+            fork.clone(),
+            &block(2, vec![transaction(0, &[], &[(address.clone(), 5)])]),
+        );
+
+        assert_eq!(fork.lookup(&address).unwrap().balance, Value(10));
+        assert_eq!(fork.lookup(&address).unwrap().changes.len(), 1);
+        assert!(fork.lookup(&other).is_none());
+
+        assert_eq!(
+            spending_branch.lookup(&address).unwrap().balance,
+            Value::zero()
+        );
+        assert_eq!(spending_branch.lookup(&other).unwrap().balance, Value(9));
+
+        assert_eq!(
+            receiving_branch.lookup(&address).unwrap().balance,
+            Value(15)
+        );
+        assert_eq!(receiving_branch.lookup(&address).unwrap().changes.len(), 2);
+        assert!(receiving_branch.lookup(&other).is_none());
+    }
+
+    #[test]
+    fn balance_does_not_underflow() {
+        let address = account(&thor::Wallet::default());
+
+        let balances = apply_block_to_balances(
+            Balances::new(),
+            &block(1, vec![transaction(0, &[(address.clone(), 1)], &[])]),
+        );
+
+        assert_eq!(balances.lookup(&address).unwrap().balance, Value::zero());
+    }
+
+    #[test]
+    fn spending_untracked_rewards_saturates_the_balance() {
+        let address = account(&thor::Wallet::default());
+        let other = account(&thor::Wallet::default());
+
+        let balances = apply_block_to_balances(
+            Balances::new(),
+            &block(1, vec![transaction(0, &[], &[(address.clone(), 10)])]),
+        );
+        // the account spends 5 of rewards the ledger credited outside of the blocks
+        let balances = apply_block_to_balances(
+            balances,
+            &block(
+                2,
+                vec![transaction(
+                    0,
+                    &[(address.clone(), 15)],
+                    &[(other.clone(), 15)],
+                )],
+            ),
+        );
+        let balances = apply_block_to_balances(
+            balances,
+            &block(3, vec![transaction(0, &[], &[(address.clone(), 3)])]),
+        );
+
+        let balance = balances.lookup(&address).unwrap();
+        assert_eq!(balance.balance, Value(3));
+        assert_eq!(balance.balance_at(ChainLength::from(2)), Value::zero());
+        let overspending = balance.changes.get(1).unwrap();
+        assert_eq!(overspending.spent, Value(15));
+        assert_eq!(overspending.balance, Value::zero());
+        assert_eq!(balances.lookup(&other).unwrap().balance, Value(15));
+    }
+
+    #[test]
+    fn main_branch_update_finds_the_fork_point() {
+        let (db, block0) = bootstrapped_db();
+        let a1 = child_block(&block0, 1);
+        let a2 = child_block(&a1, 2);
+        let b2 = child_block(&a1, 3);
+        let b3 = child_block(&b2, 4);
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let states = runtime.block_on(async {
+            let mut states = HashMap::new();
+            for block in [&a1, &a2, &b2, &b3] {
+                states.insert(block.id(), db.apply_block(block.clone()).await.unwrap());
+            }
+            states
+        });
+        let update = |from: &Block, to: &Block| {
+            main_branch_update(
+                from.id(),
+                &states[&from.id()],
+                to.id(),
+                states[&to.id()].clone(),
+            )
+        };
+        let applied = |update: &MainBranchUpdate| -> Vec<HeaderHash> {
+            update.applied.iter().map(|block| block.id()).collect()
+        };
+
+        // switching to the other branch rolls back the blocks following the fork point
+        let switch = update(&a2, &b3).unwrap();
+        assert_eq!(switch.tip, b3.id());
+        assert_eq!(switch.fork_point, a1.id());
+        assert_eq!(switch.rolled_back, vec![a2.id()]);
+        assert_eq!(applied(&switch), vec![b2.id(), b3.id()]);
+
+        // switching back to a shorter branch
+        let switch_back = update(&b3, &a2).unwrap();
+        assert_eq!(switch_back.fork_point, a1.id());
+        assert_eq!(switch_back.rolled_back, vec![b3.id(), b2.id()]);
+        assert_eq!(applied(&switch_back), vec![a2.id()]);
+
+        // extending the branch rolls back nothing
+        let extension = update(&a1, &b3).unwrap();
+        assert_eq!(extension.fork_point, a1.id());
+        assert!(extension.rolled_back.is_empty());
+        assert_eq!(applied(&extension), vec![b2.id(), b3.id()]);
+
+        assert!(update(&b3, &b3).is_none());
+    }
+
+    #[test]
+    fn epoch_blocks_include_the_first_one() {
+        let next_epoch = ExplorerBlock {
+            date: BlockDate {
+                epoch: 1,
+                slot_id: 0,
+            },
+            ..block(3, Vec::new())
+        };
+
+        let epochs = [block(1, Vec::new()), block(2, Vec::new()), next_epoch]
+            .iter()
+            .fold(Epochs::new(), |epochs, block| {
+                apply_block_to_epochs(epochs, block, StakeDistribution::default)
+            });
+
+        let first_epoch = epochs.lookup(&0).unwrap();
+        assert_eq!(first_epoch.total_blocks, 2);
+        assert_eq!(first_epoch.first_block, block(1, Vec::new()).id());
+        assert_eq!(first_epoch.last_block, block(2, Vec::new()).id());
+        assert_eq!(epochs.lookup(&1).unwrap().total_blocks, 1);
+    }
+
+    #[test]
+    fn epoch_stats_add_up_the_blocks() {
+        let pool = thor::StakePool::new(&thor::Wallet::default());
+        let sender = account(&thor::Wallet::default());
+        let receiver = account(&thor::Wallet::default());
+        let payments = ExplorerBlock {
+            total_output: Value(12),
+            content_size: 100,
+            ..block(
+                1,
+                vec![
+                    transaction(0, &[(sender.clone(), 10)], &[(receiver.clone(), 7)]),
+                    transaction(1, &[(sender.clone(), 6)], &[(receiver, 5)]),
+                ],
+            )
+        };
+        let delegation = ExplorerBlock {
+            producer: BlockProducer::StakePool(pool.id()),
+            content_size: 50,
+            ..block(
+                2,
+                vec![delegation(0, &sender, DelegationType::Full(pool.id()))],
+            )
+        };
+
+        let stats = EpochStats::default()
+            .add_block(&payments)
+            .add_block(&delegation)
+            .add_block(&ExplorerBlock {
+                producer: BlockProducer::StakePool(pool.id()),
+                ..block(3, Vec::new())
+            });
+
+        assert_eq!(stats.total_transactions, 3);
+        assert_eq!(stats.total_fees, Value(5));
+        assert_eq!(stats.total_output, Value(12));
+        assert_eq!(stats.total_content_size, 150);
+        assert_eq!(stats.certificates.len(), 1);
+        assert_eq!(stats.certificates[&CertificateKind::StakeDelegation], 1);
+        assert_eq!(**stats.pool_blocks.lookup(&pool.id()).unwrap(), 2);
+    }
+
+    #[test]
+    fn epoch_stats_ignore_the_blocks_of_the_bft_leaders() {
+        let stats = EpochStats::default().add_block(&block(1, Vec::new()));
+
+        assert_eq!(stats.total_transactions, 0);
+        assert_eq!(stats.total_fees, Value::zero());
+        assert!(stats.certificates.is_empty());
+        assert_eq!(stats.pool_blocks.iter().count(), 0);
+    }
+
+    #[test]
+    fn rewards_are_recorded_in_the_ended_epoch() {
+        let pool = thor::StakePool::new(&thor::Wallet::default());
+        let epochs = apply_block_to_epochs(
+            Epochs::new(),
+            &block(1, Vec::new()),
+            StakeDistribution::default,
+        );
+        let epoch_rewards = rewards::EpochRewards {
+            accounts: HashMap::new(),
+            pots: Pots::default(),
+            estimated: EstimatedRewards {
+                accounts: Value(9),
+                treasury: Value(1),
+                pools: vec![(pool.id(), Value(10))].into_iter().collect(),
+            },
+        };
+
+        let epochs = apply_rewards_to_epochs(epochs, 0, &epoch_rewards);
+
+        let rewards = epochs
+            .lookup(&0)
+            .unwrap()
+            .estimated_rewards
+            .clone()
+            .unwrap();
+        assert_eq!(rewards.accounts, Value(9));
+        assert_eq!(rewards.treasury, Value(1));
+        assert_eq!(rewards.pools[&pool.id()], Value(10));
+    }
+
+    #[test]
+    fn main_branch_epoch_comes_with_the_tip_date() {
+        let (db, block0) = bootstrapped_db();
+        let a1 = child_block(&block0, 1);
+        let a2 = child_block(&a1, 2);
+        let b2 = child_block(&a1, 3);
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let (data, tip_date) = runtime.block_on(async {
+            for block in [&a1, &a2, &b2] {
+                db.apply_block(block.clone()).await.unwrap();
+            }
+            db.set_tip(a2.id()).await.unwrap();
+            db.get_main_branch_epoch(0).await.unwrap()
+        });
+
+        assert_eq!(data.last_block, a2.id());
+        assert_eq!(
+            tip_date,
+            BlockDate {
+                epoch: 0,
+                slot_id: 2
+            }
+        );
+        assert!(runtime.block_on(db.get_main_branch_epoch(1)).is_none());
+    }
+
+    #[test]
+    fn votes_are_found_by_address_across_the_vote_plans() {
+        let voter = account(&thor::Wallet::default());
+        let other = account(&thor::Wallet::default());
+        let vote_plan = |id: u8, votes: &[&[(ExplorerAddress, u8)]]| ExplorerVotePlan {
+            id: VotePlanId::from([id; 32]),
+            vote_start: BlockDate::first(),
+            vote_end: BlockDate::first(),
+            committee_end: BlockDate::first(),
+            payload_type: PayloadType::Public,
+            proposals: votes
+                .iter()
+                .map(|votes| ExplorerVoteProposal {
+                    proposal_id: ExternalProposalId::from([0; 32]),
+                    options: Options::new_length(3).unwrap(),
+                    tally: None,
+                    votes: votes.iter().fold(Hamt::new(), |votes, (voter, choice)| {
+                        votes
+                            .insert(
+                                voter.clone(),
+                                Arc::new(ExplorerVote::Public(Choice::new(*choice))),
+                            )
+                            .unwrap()
+                    }),
+                    turnout: None,
+                })
+                .collect(),
+            turnout: None,
+        };
+        let vote_plans = [
+            vote_plan(1, &[&[(other.clone(), 0)], &[(voter.clone(), 2)]]),
+            vote_plan(2, &[&[(voter.clone(), 1), (other.clone(), 1)]]),
+            vote_plan(3, &[&[(other, 2)]]),
+        ]
+        .into_iter()
+        .fold(VotePlans::new(), |vote_plans, vote_plan| {
+            vote_plans
+                .insert(vote_plan.id.clone(), Arc::new(vote_plan))
+                .unwrap()
+        });
+
+        let mut votes: Vec<_> = votes_by_address(&vote_plans, &voter)
+            .into_iter()
+            .map(|(vote_plan, index, vote)| match vote.as_ref() {
+                ExplorerVote::Public(choice) => (vote_plan.id.clone(), index, choice.as_byte()),
+                ExplorerVote::Private { .. } => panic!("expected a public vote"),
+            })
+            .collect();
+        votes.sort_by_key(|(_, index, _)| *index);
+
+        assert_eq!(
+            votes,
+            vec![
+                (VotePlanId::from([2; 32]), 0, 1),
+                (VotePlanId::from([1; 32]), 1, 2),
+            ]
+        );
+        assert!(votes_by_address(&vote_plans, &account(&thor::Wallet::default())).is_empty());
+    }
+}
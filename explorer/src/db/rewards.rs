@@ -0,0 +1,414 @@
+//! an estimate of the rewards of an epoch, made with the first block of the next one
+//!
+//! this follows the outline of the distribution of the ledger: the rewards
+//! drawn from the rewards pot and the fees of the epoch are taxed by the
+//! treasury, then split between the pools by the number of blocks they
+//! produced. The tax of a pool goes to its reward account, or to its owners,
+//! and the rest is shared by its delegators in proportion to their stake.
+//!
+//! The ledger distributes the rewards outside of the blocks, and this estimate
+//! differs from it:
+//!
+//! * the reward parameters and the treasury tax are the ones of the genesis
+//!   block, the updates are not followed;
+//! * `RewardLimitByAbsoluteStake` and `PoolRewardParticipationCapping` are
+//!   ignored;
+//! * the stake of the delegators is the one at the end of the epoch, not the
+//!   leadership distribution the ledger uses.
+//!
+//! So the estimate is only reported as such, it is not credited to the
+//! balances and is not part of the stake.
+
+use super::{
+    indexing::{EpochStats, EstimatedRewards, ExplorerAddress, Pots, StakePool},
+    BlockchainConfig,
+};
+use chain_addr::Discrimination;
+use chain_impl_mockchain::{
+    block::Epoch,
+    certificate::{PoolId, PoolRegistration},
+    config::ConfigParam,
+    fragment::ConfigParams,
+    rewards::{self, SystemInformation},
+    stake::Stake,
+    transaction::AccountIdentifier,
+    value::Value,
+};
+use std::collections::HashMap;
+
+/// The stake of the accounts delegating to each pool
+pub type PoolStakes = HashMap<PoolId, HashMap<ExplorerAddress, u64>>;
+
+pub struct EpochRewards {
+    /// The rewards of the accounts, the delegators and the pool owners
+    pub accounts: HashMap<ExplorerAddress, Value>,
+    /// The pots after the distribution
+    pub pots: Pots,
+    pub estimated: EstimatedRewards,
+}
+
+/// The pots with the fees of the block added, they go to the rewards of the epoch unless the
+/// genesis block sends them to the treasury
+pub fn add_fees(config: &BlockchainConfig, pots: Pots, fees: Value) -> Pots {
+    if config.fees_in_treasury {
+        Pots {
+            treasury: add(pots.treasury, fees),
+            ..pots
+        }
+    } else {
+        Pots {
+            fees: add(pots.fees, fees),
+            ..pots
+        }
+    }
+}
+
+/// The pots set up by the genesis block
+pub fn initial_pots(params: &ConfigParams) -> Pots {
+    params
+        .iter()
+        .fold(Pots::default(), |pots, param| match param {
+            ConfigParam::TreasuryAdd(value) => Pots {
+                treasury: add(pots.treasury, *value),
+                ..pots
+            },
+            ConfigParam::RewardPot(value) => Pots {
+                rewards: add(pots.rewards, *value),
+                ..pots
+            },
+            _ => pots,
+        })
+}
+
+/// The rewards of the epoch which ended, `epoch` being the one which starts. Nothing is
+/// distributed if no pool produced a block, the fees are then kept for the next epoch
+pub fn distribute_rewards(
+    config: &BlockchainConfig,
+    epoch: Epoch,
+    ended: &EpochStats,
+    pots: Pots,
+    pool_stakes: &PoolStakes,
+    stake_pools: &StakePool,
+) -> EpochRewards {
+    let mut accounts = HashMap::new();
+    let mut pool_rewards = HashMap::new();
+    let no_stakes = HashMap::new();
+    let initial_treasury = pots.treasury;
+
+    let total_blocks = ended
+        .pool_blocks
+        .iter()
+        .fold(0u64, |total, (_, blocks)| total + u64::from(**blocks));
+    if total_blocks == 0 {
+        return EpochRewards {
+            accounts,
+            pots,
+            estimated: EstimatedRewards::default(),
+        };
+    }
+
+    let declared_stake = pool_stakes
+        .values()
+        .flat_map(|stakes| stakes.values())
+        .fold(0u64, |total, stake| total + stake);
+    let expected = rewards::rewards_contribution_calculation(
+        epoch,
+        &config.reward_params,
+        &SystemInformation {
+            declared_stake: Stake::from_value(Value(declared_stake)),
+        },
+    );
+    let drawn = Value(expected.0.min(pots.rewards.0));
+    let total_reward = add(drawn, pots.fees);
+    let mut pots = Pots {
+        fees: Value::zero(),
+        rewards: sub(pots.rewards, drawn),
+        ..pots
+    };
+
+    let treasury_cut = rewards::tax_cut(total_reward, &config.treasury_tax)
+        .expect("the treasury tax to be lower than the rewards");
+    pots.treasury = add(pots.treasury, treasury_cut.taxed);
+    let total_reward = treasury_cut.after_tax.0;
+
+    if total_reward > 0 {
+        let per_block = total_reward / total_blocks;
+        pots.treasury = add(pots.treasury, Value(total_reward % total_blocks));
+
+        for (pool, blocks) in ended.pool_blocks.iter() {
+            let pool_reward = Value(per_block * u64::from(**blocks));
+            let registration = stake_pools
+                .lookup(pool)
+                .filter(|data| data.retirement.is_none())
+                .map(|data| &data.registration);
+
+            match registration {
+                Some(registration) => {
+                    pool_rewards.insert(pool.clone(), pool_reward);
+                    let leftover = distribute_pool_rewards(
+                        config.discrimination,
+                        registration,
+                        pool_stakes.get(pool).unwrap_or(&no_stakes),
+                        pool_reward,
+                        &mut accounts,
+                    );
+                    pots.treasury = add(pots.treasury, leftover);
+                }
+                // the rewards of a retired pool go to the treasury
+                None => pots.treasury = add(pots.treasury, pool_reward),
+            }
+        }
+    }
+
+    let estimated = EstimatedRewards {
+        accounts: accounts
+            .values()
+            .fold(Value::zero(), |total, reward| add(total, *reward)),
+        treasury: sub(pots.treasury, initial_treasury),
+        pools: pool_rewards,
+    };
+
+    EpochRewards {
+        accounts,
+        pots,
+        estimated,
+    }
+}
+
+/// Credits the rewards of the pool, the part which can't be split is returned
+fn distribute_pool_rewards(
+    discrimination: Discrimination,
+    registration: &PoolRegistration,
+    stakes: &HashMap<ExplorerAddress, u64>,
+    pool_reward: Value,
+    accounts: &mut HashMap<ExplorerAddress, Value>,
+) -> Value {
+    let mut credit = |account: ExplorerAddress, value: Value| {
+        if value > Value::zero() {
+            let reward = accounts.entry(account).or_insert_with(Value::zero);
+            *reward = add(*reward, value);
+        }
+    };
+
+    let pool_cut = rewards::tax_cut(pool_reward, &registration.rewards)
+        .expect("the pool tax to be lower than the rewards");
+    let mut leftover = 0;
+
+    if pool_cut.taxed > Value::zero() {
+        match &registration.reward_account {
+            Some(reward_account) => credit(
+                reward_account_address(discrimination, reward_account),
+                pool_cut.taxed,
+            ),
+            None => {
+                let owners = registration.owners.len() as u64;
+                let per_owner = pool_cut.taxed.0 / owners;
+                leftover += pool_cut.taxed.0 % owners;
+                for owner in &registration.owners {
+                    credit(
+                        ExplorerAddress::New(chain_addr::Address(
+                            discrimination,
+                            chain_addr::Kind::Account(owner.clone()),
+                        )),
+                        Value(per_owner),
+                    );
+                }
+            }
+        }
+    }
+
+    let after_tax = pool_cut.after_tax.0;
+    let total_stake = stakes.values().fold(0u64, |total, stake| total + stake);
+    let mut distributed = 0;
+    if total_stake > 0 {
+        for (account, stake) in stakes {
+            let reward =
+                (u128::from(after_tax) * u128::from(*stake) / u128::from(total_stake)) as u64;
+            distributed += reward;
+            credit(account.clone(), Value(reward));
+        }
+    }
+
+    Value(leftover + after_tax - distributed)
+}
+
+fn reward_account_address(
+    discrimination: Discrimination,
+    reward_account: &AccountIdentifier,
+) -> ExplorerAddress {
+    let kind = match reward_account {
+        AccountIdentifier::Single(id) => chain_addr::Kind::Account(id.clone().into()),
+        AccountIdentifier::Multi(id) => {
+            let mut bytes = [0u8; 32];
+            bytes.copy_from_slice(&id.as_ref()[0..32]);
+            chain_addr::Kind::Multisig(bytes)
+        }
+    };
+    ExplorerAddress::New(chain_addr::Address(discrimination, kind))
+}
+
+fn add(a: Value, b: Value) -> Value {
+    (a + b).expect("the total supply to fit in a value")
+}
+
+fn sub(a: Value, b: Value) -> Value {
+    (a - b).expect("the pot to hold the value drawn from it")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{
+        indexing::{Hamt, StakePoolData},
+        ExplorerDb,
+    };
+    use chain_impl_mockchain::{
+        certificate::PoolRetirement, chaintypes::ConsensusVersion, fee::LinearFee, rewards::Ratio,
+    };
+    use jormungandr_lib::interfaces::{Block0Configuration, BlockchainConfiguration};
+    use std::{num::NonZeroU64, sync::Arc};
+
+    fn config() -> BlockchainConfig {
+        let block0 = Block0Configuration {
+            blockchain_configuration: BlockchainConfiguration::new(
+                Discrimination::Test,
+                ConsensusVersion::GenesisPraos,
+                LinearFee::new(0, 0, 0),
+            ),
+            initial: Vec::new(),
+        }
+        .to_block();
+
+        BlockchainConfig {
+            treasury_tax: rewards::TaxType {
+                fixed: Value::zero(),
+                ratio: Ratio {
+                    numerator: 1,
+                    denominator: NonZeroU64::new(10).unwrap(),
+                },
+                max_limit: None,
+            },
+            ..ExplorerDb::bootstrap(block0, None)
+                .unwrap()
+                .blockchain_config
+        }
+    }
+
+    fn account(wallet: &thor::Wallet) -> ExplorerAddress {
+        ExplorerAddress::New(wallet.address().into())
+    }
+
+    fn stake_pools(pool: &thor::StakePool) -> StakePool {
+        StakePool::new()
+            .insert(
+                pool.id(),
+                Arc::new(StakePoolData {
+                    registration: pool.info(),
+                    retirement: None,
+                }),
+            )
+            .unwrap()
+    }
+
+    fn ended_epoch(pool: &thor::StakePool, blocks: u32) -> EpochStats {
+        EpochStats {
+            pool_blocks: Hamt::new().insert(pool.id(), Arc::new(blocks)).unwrap(),
+            ..EpochStats::default()
+        }
+    }
+
+    #[test]
+    fn rewards_are_split_between_the_treasury_the_owners_and_the_delegators() {
+        let owner = thor::Wallet::default();
+        let delegators = [thor::Wallet::default(), thor::Wallet::default()];
+        let pool = thor::StakePool::new(&owner);
+        let pool_stakes: PoolStakes = vec![(
+            pool.id(),
+            vec![(account(&delegators[0]), 2), (account(&delegators[1]), 1)]
+                .into_iter()
+                .collect(),
+        )]
+        .into_iter()
+        .collect();
+        let pots = Pots {
+            fees: Value(1_000),
+            ..Pots::default()
+        };
+
+        let rewards = distribute_rewards(
+            &config(),
+            1,
+            &ended_epoch(&pool, 3),
+            pots,
+            &pool_stakes,
+            &stake_pools(&pool),
+        );
+
+        // the treasury takes a tenth of the fees, the pool takes 100 and a tenth of the rest
+        assert_eq!(rewards.pots.fees, Value::zero());
+        assert_eq!(rewards.pots.treasury, Value(100));
+        assert_eq!(rewards.accounts[&account(&owner)], Value(180));
+        assert_eq!(rewards.accounts[&account(&delegators[0])], Value(480));
+        assert_eq!(rewards.accounts[&account(&delegators[1])], Value(240));
+        assert_eq!(rewards.estimated.accounts, Value(900));
+        assert_eq!(rewards.estimated.treasury, Value(100));
+        assert_eq!(rewards.estimated.pools[&pool.id()], Value(900));
+    }
+
+    #[test]
+    fn rewards_of_a_retired_pool_go_to_the_treasury() {
+        let pool = thor::StakePool::new(&thor::Wallet::default());
+        let stake_pools = StakePool::new()
+            .insert(
+                pool.id(),
+                Arc::new(StakePoolData {
+                    registration: pool.info(),
+                    retirement: Some(PoolRetirement {
+                        pool_id: pool.id(),
+                        retirement_time: chain_time::DurationSeconds::from(0).into(),
+                    }),
+                }),
+            )
+            .unwrap();
+        let pots = Pots {
+            fees: Value(1_000),
+            ..Pots::default()
+        };
+
+        let rewards = distribute_rewards(
+            &config(),
+            1,
+            &ended_epoch(&pool, 1),
+            pots,
+            &PoolStakes::new(),
+            &stake_pools,
+        );
+
+        assert!(rewards.accounts.is_empty());
+        assert_eq!(rewards.pots.treasury, Value(1_000));
+        assert_eq!(rewards.estimated.treasury, Value(1_000));
+        assert!(rewards.estimated.pools.is_empty());
+    }
+
+    #[test]
+    fn fees_are_kept_when_no_pool_produced_a_block() {
+        let pool = thor::StakePool::new(&thor::Wallet::default());
+        let pots = Pots {
+            fees: Value(1_000),
+            ..Pots::default()
+        };
+
+        let rewards = distribute_rewards(
+            &config(),
+            1,
+            &EpochStats::default(),
+            pots,
+            &PoolStakes::new(),
+            &stake_pools(&pool),
+        );
+
+        assert!(rewards.accounts.is_empty());
+        assert_eq!(rewards.pots.fees, Value(1_000));
+        assert_eq!(rewards.pots.treasury, Value::zero());
+    }
+}
@@ -0,0 +1,1144 @@
+//! binary encoding of the indices at a stable block
+//!
+//! the snapshot of the indices is kept with the stable blocks, see the
+//! `storage` module, so that a restart only replays the blocks following it.
+//! The chain types are written with their own serialization. The stake control
+//! and the EVM index are not written: they are rebuilt from the balances of the
+//! accounts and from the blocks of the snapshot.
+
+#[cfg(feature = "evm")]
+use super::evm::{EvmIndex, ExplorerEvmTransaction};
+use super::{
+    indexing::{
+        AddressBalance, Addresses, BalanceChange, Balances, BlockProducer, Blocks, CertificateKind,
+        ChainLengths, Delegations, EpochData, EpochStats, Epochs, EstimatedRewards,
+        ExplorerAddress, ExplorerBlock, ExplorerInput, ExplorerOutput, ExplorerTransaction,
+        ExplorerVote, ExplorerVotePlan, ExplorerVoteProposal, ExplorerVoteTally, Hamt,
+        MultisigStake, Pots, StakeDistribution, StakePool, StakePoolBlocks, StakePoolData,
+        Transactions, Turnout, VotePlans,
+    },
+    persistent_sequence::PersistentSequence,
+    State,
+};
+use cardano_legacy_address::Addr as OldAddress;
+use chain_addr::Address;
+use chain_core::{
+    packer::Codec,
+    property::{Deserialize, DeserializeFromSlice, ReadError, Serialize, WriteError},
+};
+use chain_crypto::{Ed25519, PublicKey};
+#[cfg(feature = "evm")]
+use chain_impl_mockchain::fragment::Fragment;
+use chain_impl_mockchain::{
+    account::{DelegationRatio, DelegationType},
+    block::{BlockDate, ChainLength, HeaderId as HeaderHash},
+    certificate::{
+        Certificate, ExternalProposalId, PoolId, PoolRegistration, PoolRetirement, VoteCast,
+        VotePlanId,
+    },
+    fragment::ConfigParams,
+    key::BftLeaderId,
+    stake::{Stake, StakeControl},
+    value::Value,
+    vote::{Choice, Options, Payload, PayloadType, Weight},
+};
+use std::{collections::HashMap, hash::Hash, str::FromStr, sync::Arc};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("the snapshot is cut short")]
+    Truncated,
+    #[error("the snapshot holds an invalid {0}")]
+    Invalid(&'static str),
+    #[error("cannot write a chain value of the snapshot")]
+    Write(#[from] WriteError),
+    #[error("cannot read a chain value of the snapshot")]
+    Read(#[from] ReadError),
+}
+
+/// encodes the indices of the state
+pub fn encode(state: &State) -> Result<Vec<u8>, SnapshotError> {
+    let mut out = Vec::new();
+    state.transactions.encode(&mut out)?;
+    state.blocks.encode(&mut out)?;
+    state.addresses.encode(&mut out)?;
+    state.epochs.encode(&mut out)?;
+    state.chain_lengths.encode(&mut out)?;
+    state.stake_pool_data.encode(&mut out)?;
+    state.stake_pool_blocks.encode(&mut out)?;
+    state.vote_plans.encode(&mut out)?;
+    state.multisig_stake.encode(&mut out)?;
+    state.delegations.encode(&mut out)?;
+    state.balances.encode(&mut out)?;
+    state.pots.encode(&mut out)?;
+    Ok(out)
+}
+
+/// decodes the indices written by `encode`
+pub fn decode(bytes: &[u8]) -> Result<State, SnapshotError> {
+    let mut input = Reader(bytes);
+    let transactions: Transactions = Decode::decode(&mut input)?;
+    let blocks: Blocks = Decode::decode(&mut input)?;
+    let addresses: Addresses = Decode::decode(&mut input)?;
+    let epochs: Epochs = Decode::decode(&mut input)?;
+    let chain_lengths: ChainLengths = Decode::decode(&mut input)?;
+    let stake_pool_data: StakePool = Decode::decode(&mut input)?;
+    let stake_pool_blocks: StakePoolBlocks = Decode::decode(&mut input)?;
+    let vote_plans: VotePlans = Decode::decode(&mut input)?;
+    let multisig_stake: MultisigStake = Decode::decode(&mut input)?;
+    let delegations: Delegations = Decode::decode(&mut input)?;
+    let balances: Balances = Decode::decode(&mut input)?;
+    let pots: Pots = Decode::decode(&mut input)?;
+    if !input.0.is_empty() {
+        return Err(SnapshotError::Invalid("trailing data"));
+    }
+
+    // the stake control follows the inputs and the outputs of the accounts
+    // and of the utxo addresses delegating to them, as the balances do
+    let stake_control = balances.iter().fold(
+        StakeControl::new(),
+        |stake_control, (address, balance)| match address {
+            ExplorerAddress::New(address) => match address.kind() {
+                chain_addr::Kind::Group(_, id) | chain_addr::Kind::Account(id) => {
+                    stake_control.add_to(id.clone().into(), Stake::from_value(balance.balance))
+                }
+                _ => stake_control,
+            },
+            ExplorerAddress::Old(_) => stake_control,
+        },
+    );
+
+    #[cfg(feature = "evm")]
+    let evm = {
+        let mut main_branch: Vec<_> = chain_lengths
+            .iter()
+            .map(|(chain_length, hash)| (*chain_length, **hash))
+            .collect();
+        main_branch.sort_unstable_by_key(|(chain_length, _)| *chain_length);
+        main_branch
+            .into_iter()
+            .try_fold(EvmIndex::new(), |evm, (_, hash)| {
+                let block = blocks
+                    .lookup(&hash)
+                    .ok_or(SnapshotError::Invalid("main branch"))?;
+                evm.apply_block(block)
+                    .map_err(|_| SnapshotError::Invalid("EVM transaction"))
+            })?
+    };
+
+    Ok(State {
+        transactions,
+        blocks,
+        addresses,
+        epochs,
+        chain_lengths,
+        stake_pool_data,
+        stake_pool_blocks,
+        vote_plans,
+        stake_control,
+        multisig_stake,
+        delegations,
+        balances,
+        pots,
+        #[cfg(feature = "evm")]
+        evm,
+    })
+}
+
+trait Encode {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError>;
+}
+
+trait Decode: Sized {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError>;
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.0.len() < len {
+            return Err(SnapshotError::Truncated);
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], SnapshotError> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], SnapshotError> {
+        let len = u32::decode(self)?;
+        self.take(len as usize)
+    }
+
+    // a chain value written with `put_bytes`
+    fn chain<T: DeserializeFromSlice>(&mut self) -> Result<T, SnapshotError> {
+        let bytes = self.bytes()?;
+        Ok(T::deserialize_from_slice(&mut Codec::new(bytes))?)
+    }
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) -> Result<(), SnapshotError> {
+    (bytes.len() as u32).encode(out)?;
+    out.extend_from_slice(bytes);
+    Ok(())
+}
+
+fn put_len(out: &mut Vec<u8>, len: usize) -> Result<(), SnapshotError> {
+    (len as u64).encode(out)
+}
+
+fn get_len(input: &mut Reader) -> Result<usize, SnapshotError> {
+    let len = u64::decode(input)? as usize;
+    // every element takes at least a byte, this bounds the allocations
+    if len > input.0.len() {
+        return Err(SnapshotError::Truncated);
+    }
+    Ok(len)
+}
+
+macro_rules! impl_integer {
+    ($($ty:ty),*) => {
+        $(
+            impl Encode for $ty {
+                fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+                    out.extend_from_slice(&self.to_be_bytes());
+                    Ok(())
+                }
+            }
+
+            impl Decode for $ty {
+                fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+                    Ok(<$ty>::from_be_bytes(input.array()?))
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(u8, u32, u64);
+
+macro_rules! impl_digest {
+    ($($ty:ty),*) => {
+        $(
+            impl Encode for $ty {
+                fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+                    out.extend_from_slice(self.as_ref());
+                    Ok(())
+                }
+            }
+
+            impl Decode for $ty {
+                fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+                    Ok(<$ty>::from(input.array::<32>()?))
+                }
+            }
+        )*
+    };
+}
+
+impl_digest!(PoolId, VotePlanId, ExternalProposalId);
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        match self {
+            None => 0u8.encode(out),
+            Some(value) => {
+                1u8.encode(out)?;
+                value.encode(out)
+            }
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        match u8::decode(input)? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode(input)?)),
+            _ => Err(SnapshotError::Invalid("option")),
+        }
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.0.encode(out)?;
+        self.1.encode(out)
+    }
+}
+
+impl<A: Decode, B: Decode> Decode for (A, B) {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok((A::decode(input)?, B::decode(input)?))
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        put_len(out, self.len())?;
+        self.iter().try_for_each(|element| element.encode(out))
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.as_slice().encode(out)
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        let len = get_len(input)?;
+        (0..len).map(|_| T::decode(input)).collect()
+    }
+}
+
+impl<T: Encode> Encode for Arc<T> {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        (**self).encode(out)
+    }
+}
+
+impl<T: Decode> Decode for Arc<T> {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(Arc::new(T::decode(input)?))
+    }
+}
+
+impl<K: Encode, V: Encode> Encode for HashMap<K, V> {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        put_len(out, self.len())?;
+        self.iter().try_for_each(|(key, value)| {
+            key.encode(out)?;
+            value.encode(out)
+        })
+    }
+}
+
+impl<K: Decode + Hash + Eq, V: Decode> Decode for HashMap<K, V> {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        let len = get_len(input)?;
+        (0..len).map(|_| <(K, V)>::decode(input)).collect()
+    }
+}
+
+impl<K, V> Encode for Hamt<K, V>
+where
+    K: Encode + Hash + Eq + Clone,
+    V: Encode,
+{
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        let entries: Vec<_> = self.iter().collect();
+        put_len(out, entries.len())?;
+        entries.into_iter().try_for_each(|(key, value)| {
+            key.encode(out)?;
+            value.encode(out)
+        })
+    }
+}
+
+impl<K, V> Decode for Hamt<K, V>
+where
+    K: Decode + Hash + Eq + Clone,
+    V: Decode,
+{
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        let len = get_len(input)?;
+        (0..len).try_fold(imhamt::Hamt::new(), |hamt, _| {
+            let (key, value) = <(K, Arc<V>)>::decode(input)?;
+            hamt.insert(key, value)
+                .map_err(|_| SnapshotError::Invalid("duplicated key"))
+        })
+    }
+}
+
+impl<T: Encode> Encode for PersistentSequence<T> {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        put_len(out, self.len() as usize)?;
+        (0..self.len()).try_for_each(|i| self.get(i).unwrap().encode(out))
+    }
+}
+
+impl<T: Decode> Decode for PersistentSequence<T> {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        let len = get_len(input)?;
+        (0..len).try_fold(PersistentSequence::new(), |sequence, _| {
+            Ok(sequence.append(T::decode(input)?))
+        })
+    }
+}
+
+impl Encode for HeaderHash {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        out.extend_from_slice(self.as_bytes());
+        Ok(())
+    }
+}
+
+impl Decode for HeaderHash {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(HeaderHash::deserialize(&mut Codec::new(input.take(32)?))?)
+    }
+}
+
+impl Encode for ChainLength {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        u32::from(*self).encode(out)
+    }
+}
+
+impl Decode for ChainLength {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(ChainLength::from(u32::decode(input)?))
+    }
+}
+
+impl Encode for BlockDate {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.epoch.encode(out)?;
+        self.slot_id.encode(out)
+    }
+}
+
+impl Decode for BlockDate {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(BlockDate {
+            epoch: u32::decode(input)?,
+            slot_id: u32::decode(input)?,
+        })
+    }
+}
+
+impl Encode for Value {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.0.encode(out)
+    }
+}
+
+impl Decode for Value {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(Value(u64::decode(input)?))
+    }
+}
+
+impl Encode for ExplorerAddress {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        match self {
+            ExplorerAddress::New(address) => {
+                0u8.encode(out)?;
+                put_bytes(out, &address.to_bytes())
+            }
+            ExplorerAddress::Old(address) => {
+                1u8.encode(out)?;
+                put_bytes(out, address.to_string().as_bytes())
+            }
+        }
+    }
+}
+
+impl Decode for ExplorerAddress {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        match u8::decode(input)? {
+            0 => Address::from_bytes(input.bytes()?)
+                .map(ExplorerAddress::New)
+                .map_err(|_| SnapshotError::Invalid("address")),
+            1 => std::str::from_utf8(input.bytes()?)
+                .ok()
+                .and_then(|address| OldAddress::from_str(address).ok())
+                .map(ExplorerAddress::Old)
+                .ok_or(SnapshotError::Invalid("legacy address")),
+            _ => Err(SnapshotError::Invalid("address")),
+        }
+    }
+}
+
+impl Encode for DelegationType {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        match self {
+            DelegationType::NoDelegation => 0u8.encode(out),
+            DelegationType::Full(pool) => {
+                1u8.encode(out)?;
+                pool.encode(out)
+            }
+            DelegationType::Ratio(ratio) => {
+                2u8.encode(out)?;
+                ratio.parts().encode(out)?;
+                ratio.pools().encode(out)
+            }
+        }
+    }
+}
+
+impl Decode for DelegationType {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        match u8::decode(input)? {
+            0 => Ok(DelegationType::NoDelegation),
+            1 => Ok(DelegationType::Full(PoolId::decode(input)?)),
+            2 => {
+                let parts = u8::decode(input)?;
+                let pools = Vec::decode(input)?;
+                DelegationRatio::new(parts, pools)
+                    .map(DelegationType::Ratio)
+                    .ok_or(SnapshotError::Invalid("delegation ratio"))
+            }
+            _ => Err(SnapshotError::Invalid("delegation")),
+        }
+    }
+}
+
+impl Encode for Certificate {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        let certificate = jormungandr_lib::interfaces::Certificate(self.clone());
+        put_bytes(out, &certificate.serialize_as_vec()?)
+    }
+}
+
+impl Decode for Certificate {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(input.chain::<jormungandr_lib::interfaces::Certificate>()?.0)
+    }
+}
+
+impl Encode for ConfigParams {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        put_bytes(out, &self.serialize_as_vec()?)
+    }
+}
+
+impl Decode for ConfigParams {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        input.chain()
+    }
+}
+
+impl Encode for BlockProducer {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        match self {
+            BlockProducer::None => 0u8.encode(out),
+            BlockProducer::StakePool(pool) => {
+                1u8.encode(out)?;
+                pool.encode(out)
+            }
+            BlockProducer::BftLeader(leader) => {
+                2u8.encode(out)?;
+                put_bytes(out, leader.as_public_key().as_ref())
+            }
+        }
+    }
+}
+
+impl Decode for BlockProducer {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        match u8::decode(input)? {
+            0 => Ok(BlockProducer::None),
+            1 => Ok(BlockProducer::StakePool(PoolId::decode(input)?)),
+            2 => PublicKey::<Ed25519>::from_binary(input.bytes()?)
+                .map(|key| BlockProducer::BftLeader(BftLeaderId::from(key)))
+                .map_err(|_| SnapshotError::Invalid("BFT leader")),
+            _ => Err(SnapshotError::Invalid("block producer")),
+        }
+    }
+}
+
+impl Encode for ExplorerInput {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.address.encode(out)?;
+        self.value.encode(out)
+    }
+}
+
+impl Decode for ExplorerInput {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(ExplorerInput {
+            address: Decode::decode(input)?,
+            value: Decode::decode(input)?,
+        })
+    }
+}
+
+impl Encode for ExplorerOutput {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.address.encode(out)?;
+        self.value.encode(out)
+    }
+}
+
+impl Decode for ExplorerOutput {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(ExplorerOutput {
+            address: Decode::decode(input)?,
+            value: Decode::decode(input)?,
+        })
+    }
+}
+
+impl Encode for ExplorerTransaction {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.id.encode(out)?;
+        self.inputs.encode(out)?;
+        self.outputs.encode(out)?;
+        self.certificate.encode(out)?;
+        self.offset_in_block.encode(out)?;
+        self.config_params.encode(out)?;
+        self.delegation_account.encode(out)
+    }
+}
+
+impl Decode for ExplorerTransaction {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(ExplorerTransaction {
+            id: Decode::decode(input)?,
+            inputs: Decode::decode(input)?,
+            outputs: Decode::decode(input)?,
+            certificate: Decode::decode(input)?,
+            offset_in_block: Decode::decode(input)?,
+            config_params: Decode::decode(input)?,
+            delegation_account: Decode::decode(input)?,
+        })
+    }
+}
+
+#[cfg(feature = "evm")]
+impl Encode for ExplorerEvmTransaction {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.id.encode(out)?;
+        put_bytes(
+            out,
+            &Fragment::Evm(self.transaction.clone()).serialize_as_vec()?,
+        )?;
+        self.offset_in_block.encode(out)
+    }
+}
+
+#[cfg(feature = "evm")]
+impl Decode for ExplorerEvmTransaction {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        let id = Decode::decode(input)?;
+        let transaction = match input.chain()? {
+            Fragment::Evm(transaction) => transaction,
+            _ => return Err(SnapshotError::Invalid("EVM transaction")),
+        };
+        let offset_in_block = Decode::decode(input)?;
+        Ok(ExplorerEvmTransaction::new(
+            id,
+            transaction,
+            offset_in_block,
+        ))
+    }
+}
+
+impl Encode for ExplorerBlock {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.transactions.encode(out)?;
+        self.id.encode(out)?;
+        self.date.encode(out)?;
+        self.chain_length.encode(out)?;
+        self.parent_hash.encode(out)?;
+        self.producer.encode(out)?;
+        self.total_input.encode(out)?;
+        self.total_output.encode(out)?;
+        self.content_size.encode(out)?;
+        #[cfg(feature = "evm")]
+        self.evm_transactions.encode(out)?;
+        Ok(())
+    }
+}
+
+impl Decode for ExplorerBlock {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(ExplorerBlock {
+            transactions: Decode::decode(input)?,
+            id: Decode::decode(input)?,
+            date: Decode::decode(input)?,
+            chain_length: Decode::decode(input)?,
+            parent_hash: Decode::decode(input)?,
+            producer: Decode::decode(input)?,
+            total_input: Decode::decode(input)?,
+            total_output: Decode::decode(input)?,
+            content_size: Decode::decode(input)?,
+            #[cfg(feature = "evm")]
+            evm_transactions: Decode::decode(input)?,
+        })
+    }
+}
+
+impl Encode for BalanceChange {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.block.encode(out)?;
+        self.chain_length.encode(out)?;
+        self.transaction.encode(out)?;
+        self.received.encode(out)?;
+        self.spent.encode(out)?;
+        self.balance.encode(out)
+    }
+}
+
+impl Decode for BalanceChange {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(BalanceChange {
+            block: Decode::decode(input)?,
+            chain_length: Decode::decode(input)?,
+            transaction: Decode::decode(input)?,
+            received: Decode::decode(input)?,
+            spent: Decode::decode(input)?,
+            balance: Decode::decode(input)?,
+        })
+    }
+}
+
+impl Encode for AddressBalance {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.balance.encode(out)?;
+        self.changes.encode(out)
+    }
+}
+
+impl Decode for AddressBalance {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(AddressBalance {
+            balance: Decode::decode(input)?,
+            changes: Decode::decode(input)?,
+        })
+    }
+}
+
+const CERTIFICATE_KINDS: [CertificateKind; 12] = [
+    CertificateKind::StakeDelegation,
+    CertificateKind::OwnerStakeDelegation,
+    CertificateKind::PoolRegistration,
+    CertificateKind::PoolRetirement,
+    CertificateKind::PoolUpdate,
+    CertificateKind::VotePlan,
+    CertificateKind::VoteCast,
+    CertificateKind::VoteTally,
+    CertificateKind::UpdateProposal,
+    CertificateKind::UpdateVote,
+    CertificateKind::MintToken,
+    CertificateKind::EvmMapping,
+];
+
+impl Encode for CertificateKind {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        (*self as u8).encode(out)
+    }
+}
+
+impl Decode for CertificateKind {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        CERTIFICATE_KINDS
+            .get(u8::decode(input)? as usize)
+            .copied()
+            .ok_or(SnapshotError::Invalid("certificate kind"))
+    }
+}
+
+impl Encode for StakeDistribution {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.pools.encode(out)?;
+        self.delegators.encode(out)
+    }
+}
+
+impl Decode for StakeDistribution {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(StakeDistribution {
+            pools: Decode::decode(input)?,
+            delegators: Decode::decode(input)?,
+        })
+    }
+}
+
+impl Encode for Pots {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.fees.encode(out)?;
+        self.treasury.encode(out)?;
+        self.rewards.encode(out)
+    }
+}
+
+impl Decode for Pots {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(Pots {
+            fees: Decode::decode(input)?,
+            treasury: Decode::decode(input)?,
+            rewards: Decode::decode(input)?,
+        })
+    }
+}
+
+impl Encode for EpochStats {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.total_transactions.encode(out)?;
+        self.total_fees.encode(out)?;
+        self.total_output.encode(out)?;
+        self.total_content_size.encode(out)?;
+        self.certificates.encode(out)?;
+        self.pool_blocks.encode(out)
+    }
+}
+
+impl Decode for EpochStats {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(EpochStats {
+            total_transactions: Decode::decode(input)?,
+            total_fees: Decode::decode(input)?,
+            total_output: Decode::decode(input)?,
+            total_content_size: Decode::decode(input)?,
+            certificates: Decode::decode(input)?,
+            pool_blocks: Decode::decode(input)?,
+        })
+    }
+}
+
+impl Encode for EpochData {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.first_block.encode(out)?;
+        self.last_block.encode(out)?;
+        self.total_blocks.encode(out)?;
+        self.stake_distribution.encode(out)?;
+        self.stats.encode(out)?;
+        self.estimated_rewards.encode(out)
+    }
+}
+
+impl Decode for EpochData {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(EpochData {
+            first_block: Decode::decode(input)?,
+            last_block: Decode::decode(input)?,
+            total_blocks: Decode::decode(input)?,
+            stake_distribution: Decode::decode(input)?,
+            stats: Decode::decode(input)?,
+            estimated_rewards: Decode::decode(input)?,
+        })
+    }
+}
+
+impl Encode for EstimatedRewards {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.accounts.encode(out)?;
+        self.treasury.encode(out)?;
+        self.pools.encode(out)
+    }
+}
+
+impl Decode for EstimatedRewards {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(EstimatedRewards {
+            accounts: Decode::decode(input)?,
+            treasury: Decode::decode(input)?,
+            pools: Decode::decode(input)?,
+        })
+    }
+}
+
+impl Encode for StakePoolData {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        put_bytes(out, self.registration.serialize().as_slice())?;
+        match &self.retirement {
+            None => 0u8.encode(out),
+            Some(retirement) => {
+                1u8.encode(out)?;
+                put_bytes(out, retirement.serialize().as_slice())
+            }
+        }
+    }
+}
+
+impl Decode for StakePoolData {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        let registration = input.chain::<PoolRegistration>()?;
+        let retirement = match u8::decode(input)? {
+            0 => None,
+            1 => Some(input.chain::<PoolRetirement>()?),
+            _ => return Err(SnapshotError::Invalid("pool retirement")),
+        };
+        Ok(StakePoolData {
+            registration,
+            retirement,
+        })
+    }
+}
+
+impl Encode for PayloadType {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        match self {
+            PayloadType::Public => 0u8.encode(out),
+            PayloadType::Private => 1u8.encode(out),
+        }
+    }
+}
+
+impl Decode for PayloadType {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        match u8::decode(input)? {
+            0 => Ok(PayloadType::Public),
+            1 => Ok(PayloadType::Private),
+            _ => Err(SnapshotError::Invalid("payload type")),
+        }
+    }
+}
+
+impl Encode for Options {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.choice_range().end.encode(out)
+    }
+}
+
+impl Decode for Options {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Options::new_length(u8::decode(input)?).map_err(|_| SnapshotError::Invalid("vote options"))
+    }
+}
+
+impl Encode for Weight {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        u64::from(*self).encode(out)
+    }
+}
+
+impl Decode for Weight {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(Weight::from(u64::decode(input)?))
+    }
+}
+
+impl Encode for ExplorerVote {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        match self {
+            ExplorerVote::Public(choice) => {
+                0u8.encode(out)?;
+                choice.as_byte().encode(out)
+            }
+            ExplorerVote::Private {
+                proof,
+                encrypted_vote,
+            } => {
+                // the private vote is written the way the vote cast certificate holds it
+                let vote_cast = VoteCast::new(
+                    VotePlanId::from([0; 32]),
+                    0,
+                    Payload::Private {
+                        encrypted_vote: encrypted_vote.clone(),
+                        proof: proof.clone(),
+                    },
+                );
+                1u8.encode(out)?;
+                put_bytes(out, vote_cast.serialize().as_slice())
+            }
+        }
+    }
+}
+
+impl Decode for ExplorerVote {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        match u8::decode(input)? {
+            0 => Ok(ExplorerVote::Public(Choice::new(u8::decode(input)?))),
+            1 => match input.chain::<VoteCast>()?.payload() {
+                Payload::Private {
+                    encrypted_vote,
+                    proof,
+                } => Ok(ExplorerVote::Private {
+                    proof: proof.clone(),
+                    encrypted_vote: encrypted_vote.clone(),
+                }),
+                Payload::Public { .. } => Err(SnapshotError::Invalid("private vote")),
+            },
+            _ => Err(SnapshotError::Invalid("vote")),
+        }
+    }
+}
+
+impl Encode for Turnout {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.voters.encode(out)?;
+        self.voting_power.encode(out)
+    }
+}
+
+impl Decode for Turnout {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(Turnout {
+            voters: Decode::decode(input)?,
+            voting_power: Decode::decode(input)?,
+        })
+    }
+}
+
+impl Encode for ExplorerVoteTally {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        match self {
+            ExplorerVoteTally::Public { results, options } => {
+                0u8.encode(out)?;
+                results.encode(out)?;
+                options.encode(out)
+            }
+            ExplorerVoteTally::Private { results, options } => {
+                1u8.encode(out)?;
+                results.encode(out)?;
+                options.encode(out)
+            }
+        }
+    }
+}
+
+impl Decode for ExplorerVoteTally {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        match u8::decode(input)? {
+            0 => Ok(ExplorerVoteTally::Public {
+                results: Vec::decode(input)?.into_boxed_slice(),
+                options: Decode::decode(input)?,
+            }),
+            1 => Ok(ExplorerVoteTally::Private {
+                results: Decode::decode(input)?,
+                options: Decode::decode(input)?,
+            }),
+            _ => Err(SnapshotError::Invalid("vote tally")),
+        }
+    }
+}
+
+impl Encode for ExplorerVoteProposal {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.proposal_id.encode(out)?;
+        self.options.encode(out)?;
+        self.tally.encode(out)?;
+        self.votes.encode(out)?;
+        self.turnout.encode(out)
+    }
+}
+
+impl Decode for ExplorerVoteProposal {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(ExplorerVoteProposal {
+            proposal_id: Decode::decode(input)?,
+            options: Decode::decode(input)?,
+            tally: Decode::decode(input)?,
+            votes: Decode::decode(input)?,
+            turnout: Decode::decode(input)?,
+        })
+    }
+}
+
+impl Encode for ExplorerVotePlan {
+    fn encode(&self, out: &mut Vec<u8>) -> Result<(), SnapshotError> {
+        self.id.encode(out)?;
+        self.vote_start.encode(out)?;
+        self.vote_end.encode(out)?;
+        self.committee_end.encode(out)?;
+        self.payload_type.encode(out)?;
+        self.proposals.encode(out)?;
+        self.turnout.encode(out)
+    }
+}
+
+impl Decode for ExplorerVotePlan {
+    fn decode(input: &mut Reader) -> Result<Self, SnapshotError> {
+        Ok(ExplorerVotePlan {
+            id: Decode::decode(input)?,
+            vote_start: Decode::decode(input)?,
+            vote_end: Decode::decode(input)?,
+            committee_end: Decode::decode(input)?,
+            payload_type: Decode::decode(input)?,
+            proposals: Decode::decode(input)?,
+            turnout: Decode::decode(input)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::ExplorerDb;
+    use chain_addr::Discrimination;
+    use chain_impl_mockchain::{chaintypes::ConsensusVersion, fee::LinearFee};
+    use jormungandr_lib::interfaces::{
+        Block0Configuration, BlockchainConfiguration, Initial, InitialUTxO,
+    };
+
+    fn bootstrapped_state() -> (State, Vec<ExplorerAddress>) {
+        let wallets = [thor::Wallet::default(), thor::Wallet::default()];
+        let block0 = Block0Configuration {
+            blockchain_configuration: BlockchainConfiguration::new(
+                Discrimination::Test,
+                ConsensusVersion::Bft,
+                LinearFee::new(0, 0, 0),
+            ),
+            initial: vec![Initial::Fund(
+                wallets
+                    .iter()
+                    .map(|wallet| InitialUTxO {
+                        address: wallet.address(),
+                        value: 1_000.into(),
+                    })
+                    .collect(),
+            )],
+        }
+        .to_block();
+        let hash = block0.header().hash();
+
+        let db = ExplorerDb::bootstrap(block0, None).unwrap();
+        let state = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(db.multiverse.get_ref(&hash))
+            .unwrap()
+            .state()
+            .clone();
+        let addresses = wallets
+            .iter()
+            .map(|wallet| ExplorerAddress::New(wallet.address().into()))
+            .collect();
+        (state, addresses)
+    }
+
+    #[test]
+    fn indices_round_trip() {
+        let (state, addresses) = bootstrapped_state();
+
+        let bytes = encode(&state).unwrap();
+        let decoded = decode(&bytes).unwrap();
+
+        assert_eq!(encode(&decoded).unwrap(), bytes);
+        for address in &addresses {
+            assert_eq!(
+                decoded.balances.lookup(address).unwrap().balance,
+                state.balances.lookup(address).unwrap().balance
+            );
+            assert!(decoded.addresses.lookup(address).is_some());
+        }
+        assert_eq!(
+            decoded
+                .blocks
+                .iter()
+                .map(|(hash, _)| *hash)
+                .collect::<Vec<_>>(),
+            state
+                .blocks
+                .iter()
+                .map(|(hash, _)| *hash)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn truncated_indices_are_rejected() {
+        let (state, _) = bootstrapped_state();
+
+        let bytes = encode(&state).unwrap();
+
+        assert!(matches!(
+            decode(&bytes[..bytes.len() - 1]),
+            Err(SnapshotError::Truncated)
+        ));
+        let mut trailing = bytes;
+        trailing.push(0);
+        assert!(matches!(decode(&trailing), Err(SnapshotError::Invalid(_))));
+    }
+}
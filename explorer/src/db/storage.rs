@@ -0,0 +1,784 @@
+//! on-disk storage of the stable blocks and of the indices
+//!
+//! a block is stable once it is `epoch_stability_depth` blocks deep in the
+//! main branch, it can't be rolled back afterwards. The stable blocks are
+//! appended in chain order to a log in the storage directory, and a snapshot of
+//! the indices at the last stable block is written next to it every ten
+//! minutes, so that on restart only the blocks following the snapshot are
+//! replayed and only the blocks following the last stable one are requested
+//! from the node. The unstable blocks are kept in memory until they become
+//! stable, or are dropped with their branch.
+//!
+//! the log is only synchronized to the disk before a snapshot is written: the
+//! blocks lost with the end of the log are requested again from the node. Every
+//! record carries the checksum of its block, the log is read as a stream and
+//! truncated at the first record which is cut short or fails its checksum.
+
+use chain_core::{
+    packer::Codec,
+    property::{Deserialize, Serialize},
+};
+use chain_impl_mockchain::block::{Block, ChainLength, HeaderId as HeaderHash};
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+const STABLE_BLOCKS_FILE: &str = "stable_blocks";
+const SNAPSHOT_FILE: &str = "indices";
+// every record is the length of the serialized block and its CRC32 checksum,
+// followed by the block
+const RECORD_HEADER_SIZE: usize = 4 + 4;
+// bumped when the encoding of the indices changes, the older snapshots are
+// then ignored
+const SNAPSHOT_VERSION: u32 = 5;
+// the version, the length of the log up to the block of the snapshot, the
+// chain length and the hash of the block
+const SNAPSHOT_HEADER_SIZE: usize = 4 + 8 + 4 + 32;
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("cannot access the explorer storage")]
+    Io(#[from] io::Error),
+    #[error("cannot decode the block stored at position {position}")]
+    Decode {
+        position: usize,
+        #[source]
+        source: chain_core::property::ReadError,
+    },
+    #[error("cannot serialize the block {0}")]
+    Serialize(HeaderHash),
+    #[error("the block {0} to make stable is not in the explorer storage")]
+    MissingBlock(ChainLength),
+}
+
+/// the content of the storage when it is opened
+pub struct Stored {
+    /// the genesis block, unless the storage is empty
+    pub block0: Option<Block>,
+    /// the latest snapshot of the indices
+    pub snapshot: Option<Snapshot>,
+    /// the stable blocks following the snapshot, or following the genesis block without
+    /// snapshot
+    pub blocks: StableBlocks,
+}
+
+/// the stable blocks read one at a time from the log, in chain order
+pub struct StableBlocks {
+    reader: BufReader<File>,
+    // the records left to read with their position in the log
+    records: std::iter::Skip<std::iter::Enumerate<std::vec::IntoIter<Record>>>,
+}
+
+// a complete record of the log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Record {
+    offset: u64,
+    len: u32,
+}
+
+/// the indices at a stable block
+pub struct Snapshot {
+    pub hash: HeaderHash,
+    pub chain_length: ChainLength,
+    pub indices: Vec<u8>,
+}
+
+/// the stable block whose indices are due to be written
+#[derive(Debug, Clone, Copy)]
+pub struct SnapshotPoint {
+    pub hash: HeaderHash,
+    pub chain_length: ChainLength,
+    // the length of the log up to the block
+    log_len: u64,
+}
+
+/// the stable blocks on disk and the unstable ones waiting to become stable,
+/// the clones share the same storage
+#[derive(Clone)]
+pub struct Storage {
+    dir: Arc<PathBuf>,
+    inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+    file: File,
+    log_len: u64,
+    last_stable: Option<(ChainLength, HeaderHash)>,
+    unstable: HashMap<HeaderHash, Block>,
+    // set when a block to make stable was missing, until it is received again
+    missing: bool,
+    last_snapshot: Instant,
+}
+
+impl Storage {
+    /// opens the storage in the given directory, creating it if needed, and
+    /// returns the latest snapshot of the indices and the stable blocks
+    /// following it in chain order
+    ///
+    /// the log is truncated at the first record cut short by an interrupted
+    /// write or failing its checksum.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<(Self, Stored), StorageError> {
+        fs::create_dir_all(dir.as_ref())?;
+        let path = dir.as_ref().join(STABLE_BLOCKS_FILE);
+
+        let file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .open(&path)?;
+        let file_len = file.metadata()?.len();
+        let records = scan_records(BufReader::new(&file), file_len)?;
+        let valid_len = records.last().map_or(0, Record::end);
+
+        if valid_len < file_len {
+            tracing::warn!(
+                dropped_bytes = file_len - valid_len,
+                "dropping the end of the explorer storage"
+            );
+            file.set_len(valid_len)?;
+            file.sync_all()?;
+        }
+
+        let block0 = records
+            .first()
+            .map(|record| read_block(&file, record, 0))
+            .transpose()?;
+
+        let snapshot = match &block0 {
+            Some(_) => read_snapshot(dir.as_ref(), &file, &records)?,
+            None => None,
+        };
+        let first_record = match &snapshot {
+            Some((_, position)) => *position,
+            None => 1,
+        };
+        let last_block = records
+            .iter()
+            .enumerate()
+            .skip(first_record)
+            .last()
+            .map(|(position, record)| read_block(&file, record, position))
+            .transpose()?;
+        let blocks = StableBlocks::new(file, records, first_record)?;
+
+        let file = OpenOptions::new().append(true).open(&path)?;
+
+        let last_stable = last_block
+            .map(|block| (block.header().chain_length(), block.header().hash()))
+            .or_else(|| {
+                snapshot
+                    .as_ref()
+                    .map(|(snapshot, _)| (snapshot.chain_length, snapshot.hash))
+            })
+            .or_else(|| {
+                block0
+                    .as_ref()
+                    .map(|block| (block.header().chain_length(), block.header().hash()))
+            });
+
+        let storage = Storage {
+            dir: Arc::new(dir.as_ref().to_path_buf()),
+            inner: Arc::new(Mutex::new(Inner {
+                file,
+                log_len: valid_len,
+                last_stable,
+                unstable: HashMap::new(),
+                missing: false,
+                last_snapshot: Instant::now(),
+            })),
+        };
+
+        Ok((
+            storage,
+            Stored {
+                block0,
+                snapshot: snapshot.map(|(snapshot, _)| snapshot),
+                blocks,
+            },
+        ))
+    }
+
+    /// reads again the stable blocks following the genesis block
+    pub fn stable_blocks(&self) -> Result<StableBlocks, StorageError> {
+        let log_len = self.inner.lock().unwrap().log_len;
+        let file = File::open(self.dir.join(STABLE_BLOCKS_FILE))?;
+        let records = scan_records(BufReader::new(&file), log_len)?;
+
+        StableBlocks::new(file, records, 1)
+    }
+
+    /// appends the block0 to the stable blocks of an empty storage
+    pub(super) fn put_block0(&self, block0: &Block) -> Result<(), StorageError> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.append(block0)?;
+        inner.last_stable = Some((block0.header().chain_length(), block0.header().hash()));
+        Ok(())
+    }
+
+    /// keeps the block until it becomes stable or its branch is dropped
+    pub(super) fn put_unstable(&self, block: Block) {
+        let mut inner = self.inner.lock().unwrap();
+        let stable = inner.is_stable(block.header().chain_length());
+        if !stable {
+            inner.unstable.insert(block.header().hash(), block);
+        }
+    }
+
+    /// appends the blocks of the main branch up to the given chain length to
+    /// the stable blocks, `main_branch` giving the block of the branch at a
+    /// chain length. The unstable blocks of the other branches at or below
+    /// that length are dropped.
+    ///
+    /// on error, the blocks which were not stored are kept so that the next
+    /// call stores them. A block missing from the unstable ones has to be
+    /// received again, from the block given by `resync_point`.
+    pub(super) fn make_stable<F>(
+        &self,
+        confirmed: ChainLength,
+        main_branch: F,
+    ) -> Result<(), StorageError>
+    where
+        F: Fn(ChainLength) -> Option<HeaderHash>,
+    {
+        let mut inner = self.inner.lock().unwrap();
+        let result = inner.make_stable(confirmed, main_branch);
+        inner.missing = matches!(result, Err(StorageError::MissingBlock(_)));
+
+        if let Some((last_stable, _)) = inner.last_stable {
+            inner
+                .unstable
+                .retain(|_, block| block.header().chain_length() > last_stable);
+        }
+
+        result
+    }
+
+    /// the last stable block, when a block following it was missing to make
+    /// the next ones stable
+    pub(super) fn resync_point(&self) -> Option<HeaderHash> {
+        let inner = self.inner.lock().unwrap();
+        if inner.missing {
+            inner.last_stable.map(|(_, hash)| hash)
+        } else {
+            None
+        }
+    }
+
+    /// the last stable block, when its indices are due to be written
+    pub(super) fn snapshot_due(&self) -> Option<SnapshotPoint> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.last_snapshot.elapsed() < SNAPSHOT_INTERVAL {
+            return None;
+        }
+        inner.last_snapshot = Instant::now();
+
+        inner.last_stable.map(|(chain_length, hash)| SnapshotPoint {
+            hash,
+            chain_length,
+            log_len: inner.log_len,
+        })
+    }
+
+    /// writes the indices at the given stable block, replacing the previous
+    /// snapshot once the new one is complete
+    pub(super) fn write_snapshot(
+        &self,
+        point: SnapshotPoint,
+        indices: &[u8],
+    ) -> Result<(), StorageError> {
+        // the snapshot must not follow blocks lost from the log
+        self.inner.lock().unwrap().file.sync_data()?;
+
+        let path = self.dir.join(SNAPSHOT_FILE);
+        let tmp_path = path.with_extension("tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&SNAPSHOT_VERSION.to_be_bytes())?;
+        file.write_all(&point.log_len.to_be_bytes())?;
+        file.write_all(&u32::from(point.chain_length).to_be_bytes())?;
+        file.write_all(point.hash.as_bytes())?;
+        file.write_all(indices)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+}
+
+impl Inner {
+    fn is_stable(&self, chain_length: ChainLength) -> bool {
+        self.last_stable
+            .map(|(last_stable, _)| chain_length <= last_stable)
+            .unwrap_or(false)
+    }
+
+    fn make_stable<F>(&mut self, confirmed: ChainLength, main_branch: F) -> Result<(), StorageError>
+    where
+        F: Fn(ChainLength) -> Option<HeaderHash>,
+    {
+        while !self.is_stable(confirmed) {
+            let chain_length = match self.last_stable {
+                Some((last_stable, _)) => last_stable.increase(),
+                None => return Ok(()),
+            };
+            let block = main_branch(chain_length)
+                .and_then(|hash| self.unstable.remove(&hash))
+                .ok_or(StorageError::MissingBlock(chain_length))?;
+
+            if let Err(e) = self.append(&block) {
+                self.unstable.insert(block.header().hash(), block);
+                return Err(e);
+            }
+            self.last_stable = Some((chain_length, block.header().hash()));
+        }
+
+        Ok(())
+    }
+
+    fn append(&mut self, block: &Block) -> Result<(), StorageError> {
+        let bytes = block
+            .serialize_as_vec()
+            .map_err(|_| StorageError::Serialize(block.header().hash()))?;
+
+        let mut record = Vec::with_capacity(RECORD_HEADER_SIZE + bytes.len());
+        record.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        record.extend_from_slice(&crc32fast::hash(&bytes).to_be_bytes());
+        record.extend_from_slice(&bytes);
+
+        self.file.write_all(&record)?;
+        self.log_len += record.len() as u64;
+        Ok(())
+    }
+}
+
+// the snapshot of the indices and the position of the record following its
+// block, unless there is no valid snapshot for the records
+fn read_snapshot(
+    dir: &Path,
+    log: &File,
+    records: &[Record],
+) -> Result<Option<(Snapshot, usize)>, StorageError> {
+    let content = match fs::read(dir.join(SNAPSHOT_FILE)) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let snapshot = parse_snapshot(&content).and_then(|(log_len, snapshot)| {
+        // the snapshot follows the record of its block
+        let (position, record) = records
+            .iter()
+            .enumerate()
+            .find(|(_, record)| record.end() == log_len)?;
+        let block = read_block(log, record, position).ok()?;
+        (block.header().hash() == snapshot.hash).then(|| (snapshot, position + 1))
+    });
+
+    if snapshot.is_none() {
+        tracing::warn!(
+            "ignoring the snapshot of the indices which does not match the stable blocks"
+        );
+    }
+    Ok(snapshot)
+}
+
+// the length of the log the snapshot follows and the snapshot
+fn parse_snapshot(content: &[u8]) -> Option<(u64, Snapshot)> {
+    if content.len() < SNAPSHOT_HEADER_SIZE {
+        return None;
+    }
+    let (header, indices) = content.split_at(SNAPSHOT_HEADER_SIZE);
+
+    let version = u32::from_be_bytes(header[0..4].try_into().unwrap());
+    if version != SNAPSHOT_VERSION {
+        return None;
+    }
+    let log_len = u64::from_be_bytes(header[4..12].try_into().unwrap());
+    let chain_length = u32::from_be_bytes(header[12..16].try_into().unwrap());
+    let hash = HeaderHash::deserialize(&mut Codec::new(&header[16..])).ok()?;
+
+    Some((
+        log_len,
+        Snapshot {
+            hash,
+            chain_length: chain_length.into(),
+            indices: indices.to_vec(),
+        },
+    ))
+}
+
+impl Record {
+    // the offset of the record following this one
+    fn end(&self) -> u64 {
+        self.offset + RECORD_HEADER_SIZE as u64 + u64::from(self.len)
+    }
+}
+
+// the complete records of the log up to `log_len`, read as a stream. The log
+// ends at the first record cut short or failing its checksum
+fn scan_records<R: Read>(mut reader: R, log_len: u64) -> io::Result<Vec<Record>> {
+    let mut records = Vec::new();
+    let mut offset = 0;
+    let mut bytes = Vec::new();
+
+    loop {
+        let mut header = [0u8; RECORD_HEADER_SIZE];
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+        let record = Record {
+            offset,
+            len: u32::from_be_bytes(header[0..4].try_into().unwrap()),
+        };
+        let checksum = u32::from_be_bytes(header[4..8].try_into().unwrap());
+
+        // the length of a torn record can be anything, it is checked before reading
+        if record.end() > log_len {
+            break;
+        }
+        bytes.resize(record.len as usize, 0);
+        match reader.read_exact(&mut bytes) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+        if crc32fast::hash(&bytes) != checksum {
+            tracing::warn!(
+                offset,
+                "the record of the explorer storage does not match its checksum"
+            );
+            break;
+        }
+
+        offset = record.end();
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+// the block of the record, `position` being the one of the record in the log
+fn read_block(log: &File, record: &Record, position: usize) -> Result<Block, StorageError> {
+    let mut log = log;
+    let mut bytes = vec![0u8; record.len as usize];
+    log.seek(SeekFrom::Start(record.offset + RECORD_HEADER_SIZE as u64))?;
+    log.read_exact(&mut bytes)?;
+
+    Block::deserialize(&mut Codec::new(bytes.as_slice()))
+        .map_err(|source| StorageError::Decode { position, source })
+}
+
+impl StableBlocks {
+    // the blocks of the records from the given position
+    fn new(log: File, records: Vec<Record>, first: usize) -> Result<Self, StorageError> {
+        let mut reader = BufReader::new(log);
+        if let Some(record) = records.get(first) {
+            reader.seek(SeekFrom::Start(record.offset))?;
+        }
+
+        Ok(StableBlocks {
+            reader,
+            records: records.into_iter().enumerate().skip(first),
+        })
+    }
+
+    // the block of the next record, the reader being at its offset
+    fn read_next(&mut self, position: usize, record: Record) -> Result<Block, StorageError> {
+        let mut header = [0u8; RECORD_HEADER_SIZE];
+        self.reader.read_exact(&mut header)?;
+        let mut bytes = vec![0u8; record.len as usize];
+        self.reader.read_exact(&mut bytes)?;
+
+        Block::deserialize(&mut Codec::new(bytes.as_slice()))
+            .map_err(|source| StorageError::Decode { position, source })
+    }
+}
+
+impl Iterator for StableBlocks {
+    type Item = Result<Block, StorageError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (position, record) = self.records.next()?;
+        Some(self.read_next(position, record))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.records.size_hint()
+    }
+}
+
+impl ExactSizeIterator for StableBlocks {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_impl_mockchain::{
+        block::{builder as block_builder, BlockDate},
+        fragment::Contents,
+        header::BlockVersion,
+    };
+    use std::convert::Infallible;
+
+    fn block(parent: &HeaderHash, chain_length: u32) -> Block {
+        block_builder(BlockVersion::Genesis, Contents::empty(), |hdr_builder| {
+            Ok::<_, Infallible>(
+                hdr_builder
+                    .set_parent(parent, ChainLength::from(chain_length))
+                    .set_date(BlockDate {
+                        epoch: 0,
+                        slot_id: chain_length,
+                    })
+                    .into_unsigned_header()
+                    .unwrap()
+                    .generalize(),
+            )
+        })
+        .unwrap()
+    }
+
+    // the block0 followed by `len` blocks
+    fn chain(len: u32) -> Vec<Block> {
+        let mut blocks = vec![block(&HeaderHash::zero_hash(), 0)];
+        for chain_length in 1..=len {
+            let parent = blocks.last().unwrap().header().hash();
+            blocks.push(block(&parent, chain_length));
+        }
+        blocks
+    }
+
+    fn storage_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "jormungandr-explorer-storage-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn hashes(blocks: &[Block]) -> Vec<HeaderHash> {
+        blocks.iter().map(|block| block.header().hash()).collect()
+    }
+
+    fn read_all(blocks: StableBlocks) -> Vec<Block> {
+        blocks.collect::<Result<_, _>>().unwrap()
+    }
+
+    // stores the block0 and makes the blocks up to `confirmed` stable
+    fn store(storage: &Storage, blocks: &[Block], confirmed: u32) {
+        storage.put_block0(&blocks[0]).unwrap();
+        for block in &blocks[1..] {
+            storage.put_unstable(block.clone());
+        }
+        let main_branch = hashes(blocks);
+        storage
+            .make_stable(ChainLength::from(confirmed), |chain_length| {
+                main_branch.get(u32::from(chain_length) as usize).copied()
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn stable_blocks_are_restored() {
+        let dir = storage_dir("restored");
+        let blocks = chain(4);
+
+        let (storage, stored) = Storage::open(&dir).unwrap();
+        assert!(stored.block0.is_none());
+        store(&storage, &blocks, 3);
+        drop(storage);
+
+        let (storage, stored) = Storage::open(&dir).unwrap();
+        assert_eq!(
+            stored.block0.unwrap().header().hash(),
+            blocks[0].header().hash()
+        );
+        assert!(stored.snapshot.is_none());
+        assert_eq!(hashes(&read_all(stored.blocks)), hashes(&blocks[1..4]));
+        assert_eq!(
+            hashes(&read_all(storage.stable_blocks().unwrap())),
+            hashes(&blocks[1..4])
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn snapshot_is_followed_by_the_later_blocks() {
+        let dir = storage_dir("snapshot");
+        let blocks = chain(4);
+
+        let (storage, _) = Storage::open(&dir).unwrap();
+        store(&storage, &blocks, 2);
+        storage.inner.lock().unwrap().last_snapshot -= SNAPSHOT_INTERVAL;
+        let point = storage.snapshot_due().unwrap();
+        assert_eq!(point.hash, blocks[2].header().hash());
+        assert!(storage.snapshot_due().is_none());
+        storage.write_snapshot(point, b"indices").unwrap();
+
+        let main_branch = hashes(&blocks);
+        storage
+            .make_stable(ChainLength::from(4), |chain_length| {
+                main_branch.get(u32::from(chain_length) as usize).copied()
+            })
+            .unwrap();
+        drop(storage);
+
+        let (_, stored) = Storage::open(&dir).unwrap();
+        let snapshot = stored.snapshot.unwrap();
+        assert_eq!(snapshot.hash, blocks[2].header().hash());
+        assert_eq!(snapshot.chain_length, ChainLength::from(2));
+        assert_eq!(snapshot.indices, b"indices");
+        assert_eq!(hashes(&read_all(stored.blocks)), hashes(&blocks[3..]));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn snapshot_of_a_lost_block_is_ignored() {
+        let dir = storage_dir("lost");
+        let blocks = chain(2);
+
+        let (storage, _) = Storage::open(&dir).unwrap();
+        store(&storage, &blocks, 2);
+        storage.inner.lock().unwrap().last_snapshot -= SNAPSHOT_INTERVAL;
+        let point = storage.snapshot_due().unwrap();
+        storage.write_snapshot(point, b"indices").unwrap();
+        drop(storage);
+
+        // the end of the log is lost
+        let log = dir.join(STABLE_BLOCKS_FILE);
+        let len = fs::metadata(&log).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&log)
+            .unwrap()
+            .set_len(len - 1)
+            .unwrap();
+
+        let (_, stored) = Storage::open(&dir).unwrap();
+        assert!(stored.snapshot.is_none());
+        assert_eq!(hashes(&read_all(stored.blocks)), hashes(&blocks[1..2]));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_block_is_received_again() {
+        let dir = storage_dir("missing");
+        let blocks = chain(3);
+        let main_branch = hashes(&blocks);
+        let branch =
+            |chain_length: ChainLength| main_branch.get(u32::from(chain_length) as usize).copied();
+
+        let (storage, _) = Storage::open(&dir).unwrap();
+        storage.put_block0(&blocks[0]).unwrap();
+        storage.put_unstable(blocks[1].clone());
+        storage.put_unstable(blocks[3].clone());
+
+        assert!(matches!(
+            storage.make_stable(ChainLength::from(3), branch),
+            Err(StorageError::MissingBlock(chain_length)) if chain_length == ChainLength::from(2)
+        ));
+        assert_eq!(storage.resync_point(), Some(blocks[1].header().hash()));
+
+        storage.put_unstable(blocks[2].clone());
+        storage.make_stable(ChainLength::from(3), branch).unwrap();
+        assert_eq!(storage.resync_point(), None);
+        assert_eq!(
+            hashes(&read_all(storage.stable_blocks().unwrap())),
+            hashes(&blocks[1..])
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn corrupted_record_ends_the_log() {
+        let dir = storage_dir("corrupted");
+        let blocks = chain(3);
+
+        let (storage, _) = Storage::open(&dir).unwrap();
+        store(&storage, &blocks, 3);
+        drop(storage);
+
+        // the last byte of the last block is flipped
+        let log = dir.join(STABLE_BLOCKS_FILE);
+        let mut content = fs::read(&log).unwrap();
+        let len = content.len();
+        *content.last_mut().unwrap() ^= 0xff;
+        fs::write(&log, &content).unwrap();
+
+        let (storage, stored) = Storage::open(&dir).unwrap();
+        assert_eq!(hashes(&read_all(stored.blocks)), hashes(&blocks[1..3]));
+        let truncated_len = fs::metadata(&log).unwrap().len();
+        assert!(truncated_len < len as u64);
+        assert_eq!(storage.inner.lock().unwrap().log_len, truncated_len);
+
+        // the block lost with the corrupted record is stored again after the others
+        let main_branch = hashes(&blocks);
+        storage.put_unstable(blocks[3].clone());
+        storage
+            .make_stable(ChainLength::from(3), |chain_length| {
+                main_branch.get(u32::from(chain_length) as usize).copied()
+            })
+            .unwrap();
+        assert_eq!(
+            hashes(&read_all(storage.stable_blocks().unwrap())),
+            hashes(&blocks[1..])
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn record(bytes: &[u8]) -> Vec<u8> {
+        let mut record = (bytes.len() as u32).to_be_bytes().to_vec();
+        record.extend_from_slice(&crc32fast::hash(bytes).to_be_bytes());
+        record.extend_from_slice(bytes);
+        record
+    }
+
+    #[test]
+    fn incomplete_record_is_ignored() {
+        let mut content = record(b"first");
+        content.extend(record(b"second"));
+        let complete_len = content.len() as u64;
+        content.extend_from_slice(&10u32.to_be_bytes());
+        content.extend_from_slice(&0u32.to_be_bytes());
+        content.extend_from_slice(b"cut");
+
+        let records = scan_records(&content[..], content.len() as u64).unwrap();
+        assert_eq!(
+            records,
+            vec![Record { offset: 0, len: 5 }, Record { offset: 13, len: 6 }]
+        );
+        assert_eq!(records.last().unwrap().end(), complete_len);
+
+        let cut = complete_len as usize + 2;
+        let records = scan_records(&content[..cut], cut as u64).unwrap();
+        assert_eq!(records.len(), 2);
+
+        // a length over the end of the log is not read
+        let records = scan_records(&content[..], complete_len + 5).unwrap();
+        assert_eq!(records.len(), 2);
+    }
+
+    #[test]
+    fn record_failing_its_checksum_ends_the_log() {
+        let mut content = record(b"first");
+        let mut second = record(b"second");
+        *second.last_mut().unwrap() ^= 1;
+        content.extend(second);
+        content.extend(record(b"third"));
+
+        let records = scan_records(&content[..], content.len() as u64).unwrap();
+        assert_eq!(records, vec![Record { offset: 0, len: 5 }]);
+    }
+}
@@ -1,6 +1,9 @@
 use super::indexing::ExplorerVoteProposal;
-use crate::db::indexing::{ExplorerVote, ExplorerVoteTally};
-use chain_impl_mockchain::{certificate::DecryptedPrivateTallyProposal, stake::StakeControl};
+use crate::db::indexing::{ExplorerAddress, ExplorerVote, ExplorerVoteTally, Turnout};
+use chain_impl_mockchain::{
+    certificate::DecryptedPrivateTallyProposal, stake::StakeControl, value::Value,
+};
+use std::collections::HashSet;
 
 pub fn compute_private_tally(
     proposal: &ExplorerVoteProposal,
@@ -19,20 +22,18 @@ pub fn compute_public_tally(
     let mut results = vec![0u64; proposal.options.choice_range().end as usize];
 
     for (address, vote) in proposal.votes.iter() {
-        if let Some(account_id) = address.to_single_account() {
-            if let Some(stake) = stake.by(&account_id) {
-                match vote.as_ref() {
-                    ExplorerVote::Public(choice) => {
-                        let index = choice.as_byte() as usize;
-                        results[index] = results[index].saturating_add(stake.into());
-                    }
-                    ExplorerVote::Private {
-                        proof: _,
-                        encrypted_vote: _,
-                    } => {
-                        unreachable!("internal error: found private vote when computing tally for public proposal")
-                    }
-                }
+        match vote.as_ref() {
+            ExplorerVote::Public(choice) => {
+                let index = choice.as_byte() as usize;
+                results[index] = results[index].saturating_add(voting_power(address, stake));
+            }
+            ExplorerVote::Private {
+                proof: _,
+                encrypted_vote: _,
+            } => {
+                unreachable!(
+                    "internal error: found private vote when computing tally for public proposal"
+                )
             }
         }
     }
@@ -42,3 +43,127 @@ pub fn compute_public_tally(
         options: proposal.options.clone(),
     }
 }
+
+/// The turnout of the given voters, an account voting several times is counted once
+pub fn compute_turnout<'a, I>(voters: I, stake: &StakeControl) -> Turnout
+where
+    I: IntoIterator<Item = &'a ExplorerAddress>,
+{
+    let voters: HashSet<_> = voters.into_iter().collect();
+
+    Turnout {
+        voters: voters.len() as u64,
+        voting_power: Value(voters.into_iter().fold(0u64, |power, voter| {
+            power.saturating_add(voting_power(voter, stake))
+        })),
+    }
+}
+
+fn voting_power(voter: &ExplorerAddress, stake: &StakeControl) -> u64 {
+    voter
+        .to_single_account()
+        .and_then(|account_id| stake.by(&account_id))
+        .map(u64::from)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::indexing::Hamt;
+    use chain_impl_mockchain::{
+        account::Identifier,
+        certificate::ExternalProposalId,
+        stake::Stake,
+        vote::{Choice, Options},
+    };
+    use std::sync::Arc;
+
+    fn account(wallet: &thor::Wallet) -> ExplorerAddress {
+        ExplorerAddress::New(wallet.address().into())
+    }
+
+    fn stake(stakes: &[(&thor::Wallet, u64)]) -> StakeControl {
+        stakes
+            .iter()
+            .fold(StakeControl::new(), |stake, (wallet, value)| {
+                stake.add_to(
+                    Identifier::from(wallet.public_key()),
+                    Stake::from_value(Value(*value)),
+                )
+            })
+    }
+
+    fn proposal(votes: &[(ExplorerAddress, u8)]) -> ExplorerVoteProposal {
+        ExplorerVoteProposal {
+            proposal_id: ExternalProposalId::from([0; 32]),
+            options: Options::new_length(3).unwrap(),
+            tally: None,
+            votes: votes.iter().fold(Hamt::new(), |votes, (voter, choice)| {
+                votes
+                    .insert(
+                        voter.clone(),
+                        Arc::new(ExplorerVote::Public(Choice::new(*choice))),
+                    )
+                    .unwrap()
+            }),
+            turnout: None,
+        }
+    }
+
+    #[test]
+    fn single_account_is_the_account_of_the_address() {
+        let wallet = thor::Wallet::default();
+        let utxo = thor::Wallet::new_utxo(&mut rand::rngs::OsRng);
+
+        assert_eq!(
+            account(&wallet).to_single_account(),
+            Some(Identifier::from(wallet.public_key()))
+        );
+        assert_eq!(account(&utxo).to_single_account(), None);
+    }
+
+    #[test]
+    fn turnout_counts_each_voter_once() {
+        let voters = [thor::Wallet::default(), thor::Wallet::default()];
+        let utxo = thor::Wallet::new_utxo(&mut rand::rngs::OsRng);
+        let stake = stake(&[(&voters[0], 5), (&voters[1], 3)]);
+
+        let turnout = compute_turnout(
+            &[
+                account(&voters[0]),
+                account(&voters[1]),
+                account(&voters[0]),
+                account(&utxo),
+            ],
+            &stake,
+        );
+
+        // the utxo address votes without stake
+        assert_eq!(turnout.voters, 3);
+        assert_eq!(turnout.voting_power, Value(8));
+    }
+
+    #[test]
+    fn public_tally_weights_the_choices_with_the_stake() {
+        let voters = [
+            thor::Wallet::default(),
+            thor::Wallet::default(),
+            thor::Wallet::default(),
+        ];
+        let stake = stake(&[(&voters[0], 5), (&voters[1], 3)]);
+        let proposal = proposal(&[
+            (account(&voters[0]), 1),
+            (account(&voters[1]), 2),
+            (account(&voters[2]), 1),
+        ]);
+
+        match compute_public_tally(&proposal, &stake) {
+            ExplorerVoteTally::Public { results, .. } => assert_eq!(
+                results.iter().copied().map(u64::from).collect::<Vec<_>>(),
+                vec![0, 5, 3]
+            ),
+            ExplorerVoteTally::Private { .. } => panic!("expected a public tally"),
+        }
+    }
+}
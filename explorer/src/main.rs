@@ -11,10 +11,11 @@ use crate::indexer::Indexer;
 use anyhow::Context;
 use chain_core::{packer::Codec, property::Deserialize};
 use chain_impl_mockchain::block::Block;
-use chain_network::grpc::watch::client::{
-    BlockSubscription, Client, SyncMultiverseStream, TipSubscription,
+use chain_network::{
+    data::BlockId,
+    grpc::watch::client::{BlockSubscription, Client, SyncMultiverseStream, TipSubscription},
 };
-use db::ExplorerDb;
+use db::{storage::Storage, ExplorerDb};
 use futures::stream::StreamExt;
 use futures_util::{future, pin_mut, FutureExt, TryFutureExt};
 use settings::Settings;
@@ -46,6 +47,8 @@ pub enum Error {
 pub enum BootstrapError {
     #[error(transparent)]
     DbError(db::error::ExplorerError),
+    #[error("failed to open the explorer storage")]
+    StorageError(#[from] db::storage::StorageError),
     #[error("empty bootstrap stream")]
     EmptyStream,
 }
@@ -88,13 +91,52 @@ async fn main() -> Result<(), Error> {
     let (bootstrap, mut services) = {
         let settings = settings.take().unwrap();
 
+        let (restored_db, storage) = match settings.storage.as_ref() {
+            Some(path) => {
+                let (storage, stored) = Storage::open(path).map_err(BootstrapError::from)?;
+
+                if stored.block0.is_none() {
+                    (None, Some(storage))
+                } else {
+                    match &stored.snapshot {
+                        Some(snapshot) => tracing::info!(
+                            "restoring the indexes at block {} and {} following stable blocks from the storage",
+                            snapshot.chain_length,
+                            stored.blocks.len()
+                        ),
+                        None => tracing::info!(
+                            "restoring {} stable blocks from the storage",
+                            stored.blocks.len() + 1
+                        ),
+                    }
+
+                    let db = ExplorerDb::restore(stored, storage)
+                        .await
+                        .map_err(BootstrapError::DbError)?;
+
+                    (Some(db), None)
+                }
+            }
+            None => (None, None),
+        };
+
+        // the node sends the blocks of all the branches following the last stable block, the
+        // unstable ones are not stored and are received again
+        let checkpoints = match restored_db.as_ref() {
+            Some(db) => {
+                let (last_stable, _) = db.get_tip().await;
+                vec![BlockId::try_from(last_stable.as_bytes()).unwrap()]
+            }
+            None => vec![],
+        };
+
         let mut client = Client::connect(settings.node.clone())
             .await
             .context("Couldn't establish connection with node")
             .map_err(Error::UnrecoverableError)?;
 
         let sync_stream = client
-            .sync_multiverse(vec![])
+            .sync_multiverse(checkpoints)
             .await
             .context("Failed to establish bootstrap stream")
             .map_err(Error::UnrecoverableError)?;
@@ -116,7 +158,7 @@ async fn main() -> Result<(), Error> {
 
             tokio::spawn(
                 async move {
-                    let db = bootstrap(sync_stream).await?;
+                    let db = bootstrap(sync_stream, restored_db, storage).await?;
 
                     let msg = GlobalState::Ready(Indexer::new(db));
 
@@ -133,8 +175,13 @@ async fn main() -> Result<(), Error> {
         tracing::info!("starting subscriptions");
 
         let subscriptions = tokio::spawn(
-            process_subscriptions(state_tx.subscribe(), block_events, tip_events)
-                .instrument(span!(Level::INFO, "subscriptions")),
+            process_subscriptions(
+                state_tx.subscribe(),
+                block_events,
+                tip_events,
+                settings.node.clone(),
+            )
+            .instrument(span!(Level::INFO, "subscriptions")),
         );
 
         tracing::info!("starting rest task");
@@ -221,11 +268,13 @@ async fn main() -> Result<(), Error> {
     Ok(())
 }
 
-async fn bootstrap(mut sync_stream: SyncMultiverseStream) -> Result<ExplorerDb, Error> {
+async fn bootstrap(
+    mut sync_stream: SyncMultiverseStream,
+    mut db: Option<ExplorerDb>,
+    mut storage: Option<Storage>,
+) -> Result<ExplorerDb, Error> {
     tracing::info!("starting bootstrap process");
 
-    let mut db: Option<ExplorerDb> = None;
-
     // TODO: technically, blocks with the same length can be applied in parallel
     // but it is simpler to do it serially for now at least
     while let Some(block) = sync_stream.next().await {
@@ -240,6 +289,12 @@ async fn bootstrap(mut sync_stream: SyncMultiverseStream) -> Result<ExplorerDb,
             .map_err(Error::UnrecoverableError)?;
 
         if let Some(ref db) = db {
+            // the node sends the block0 again when the last stable block is its only checkpoint
+            if db.get_block(&block.header().hash()).await.is_some() {
+                tracing::trace!("skipping known block {:?}", block.header().hash());
+                continue;
+            }
+
             tracing::trace!(
                 "applying block {:?} {:?}",
                 block.header().hash(),
@@ -249,7 +304,9 @@ async fn bootstrap(mut sync_stream: SyncMultiverseStream) -> Result<ExplorerDb,
                 .await
                 .map_err(BootstrapError::DbError)?;
         } else {
-            db = Some(ExplorerDb::bootstrap(block).map_err(BootstrapError::DbError)?)
+            db = Some(
+                ExplorerDb::bootstrap(block, storage.take()).map_err(BootstrapError::DbError)?,
+            )
         }
     }
 
@@ -292,6 +349,10 @@ async fn rest_service(mut state: broadcast::Receiver<GlobalState>, settings: Set
             address_bech32_prefix: settings.address_bech32_prefix,
             query_depth_limit: settings.query_depth_limit,
             query_complexity_limit: settings.query_complexity_limit,
+            query_cost_limit: settings.query_cost_limit,
+            slow_query_threshold: settings.slow_query_threshold,
+            response_cache_size: settings.response_cache_size,
+            export_row_limit: settings.export_row_limit,
         },
     );
 
@@ -334,10 +395,56 @@ async fn handle_block(
     Ok(())
 }
 
+/// receives again from the node the blocks following the last stored one, when one of the
+/// blocks to store was missed
+async fn resync_storage(node: tonic::transport::Uri, indexer: &Indexer) -> Result<(), Error> {
+    let last_stored = match indexer.db.storage_resync_point() {
+        Some(hash) => hash,
+        None => return Ok(()),
+    };
+
+    tracing::warn!(
+        "receiving again the blocks following {} to store them",
+        last_stored
+    );
+
+    let mut client = Client::connect(node)
+        .await
+        .context("Couldn't establish connection with node")
+        .map_err(Error::Other)?;
+
+    let mut sync_stream = client
+        .sync_multiverse(vec![BlockId::try_from(last_stored.as_bytes()).unwrap()])
+        .await
+        .context("Failed to establish resync stream")
+        .map_err(Error::Other)?;
+
+    while let Some(block) = sync_stream.next().await {
+        let bytes = block
+            .context("failed to receive a block to resync the storage")
+            .map_err(Error::Other)?;
+
+        let block = Block::deserialize(&mut Codec::new(bytes.as_ref()))
+            .context("failed to decode a block to resync the storage")
+            .map_err(Error::Other)?;
+
+        indexer
+            .db
+            .resync_block(block)
+            .await
+            .map_err(indexer::IndexerError::from)?;
+    }
+
+    indexer.db.store_stable_blocks_again().await;
+
+    Ok(())
+}
+
 async fn process_subscriptions(
     state: broadcast::Receiver<GlobalState>,
     blocks: BlockSubscription,
     tips: TipSubscription,
+    node: tonic::transport::Uri,
 ) -> Result<(), Error> {
     tracing::info!("start consuming subscriptions");
 
@@ -408,6 +515,13 @@ async fn process_subscriptions(
                     .await
                 }
                 .instrument(span!(Level::INFO, "handle_tip")).await?;
+
+                if let Err(e) = resync_storage(node.clone(), &indexer)
+                    .instrument(span!(Level::INFO, "resync_storage"))
+                    .await
+                {
+                    error!("failed to resync the storage: {}", e);
+                }
             },
             else => break,
         };
@@ -19,6 +19,9 @@ const DEFAULT_LOG_SETTINGS_ENTRY: LogSettingsEntry = LogSettingsEntry {
 
 const DEFAULT_QUERY_DEPTH_LIMIT: usize = 15;
 const DEFAULT_QUERY_COMPLEXITY_LIMIT: usize = 100;
+const DEFAULT_SLOW_QUERY_THRESHOLD: u64 = 1000;
+const DEFAULT_RESPONSE_CACHE_SIZE: usize = 1024;
+const DEFAULT_EXPORT_ROW_LIMIT: usize = 100_000;
 
 lazy_static! {
     pub static ref LOG_FILTER_LEVEL_POSSIBLE_VALUES: Vec<&'static str> = {
@@ -53,6 +56,11 @@ pub struct Settings {
     pub address_bech32_prefix: String,
     pub query_depth_limit: usize,
     pub query_complexity_limit: usize,
+    pub query_cost_limit: Option<u64>,
+    pub slow_query_threshold: u64,
+    pub response_cache_size: usize,
+    pub export_row_limit: usize,
+    pub storage: Option<PathBuf>,
     pub tls: Option<Tls>,
     pub cors: Option<Cors>,
     pub log_settings: Option<LogSettings>,
@@ -97,6 +105,25 @@ impl Settings {
             .or(file.query_complexity_limit)
             .unwrap_or(DEFAULT_QUERY_COMPLEXITY_LIMIT);
 
+        let query_cost_limit = cmd.query_cost_limit.or(file.query_cost_limit);
+
+        let slow_query_threshold = cmd
+            .slow_query_threshold
+            .or(file.slow_query_threshold)
+            .unwrap_or(DEFAULT_SLOW_QUERY_THRESHOLD);
+
+        let response_cache_size = cmd
+            .response_cache_size
+            .or(file.response_cache_size)
+            .unwrap_or(DEFAULT_RESPONSE_CACHE_SIZE);
+
+        let export_row_limit = cmd
+            .export_row_limit
+            .or(file.export_row_limit)
+            .unwrap_or(DEFAULT_EXPORT_ROW_LIMIT);
+
+        let storage = cmd.storage.clone().or_else(|| file.storage.clone());
+
         let log_settings = Some(Self::log_settings(&cmd, &file));
 
         let tls = file.tls;
@@ -108,6 +135,11 @@ impl Settings {
             address_bech32_prefix,
             query_depth_limit,
             query_complexity_limit,
+            query_cost_limit,
+            slow_query_threshold,
+            response_cache_size,
+            export_row_limit,
+            storage,
             tls,
             cors,
             log_settings,
@@ -188,6 +220,27 @@ struct CommandLine {
     pub query_depth_limit: Option<usize>,
     #[structopt(long)]
     pub query_complexity_limit: Option<usize>,
+    /// Milliseconds of graphql query execution a client can use per minute, the queries of
+    /// a client over it are rejected until the time is replenished. Unlimited if not set.
+    #[structopt(long)]
+    pub query_cost_limit: Option<u64>,
+    /// Milliseconds of execution after which a graphql query is logged as slow, defaults
+    /// to 1000.
+    #[structopt(long)]
+    pub slow_query_threshold: Option<u64>,
+    /// Number of responses to queries of confirmed blocks and transactions kept in memory,
+    /// 0 disables the cache. Defaults to 1024.
+    #[structopt(long)]
+    pub response_cache_size: Option<usize>,
+    /// Maximum number of rows of a REST export, the exports over it are rejected and the time
+    /// range has to be narrowed. Defaults to 100000.
+    #[structopt(long)]
+    pub export_row_limit: Option<usize>,
+    /// Directory where the stable blocks and a periodic snapshot of the indices are stored, so
+    /// that the indices are restored from it on restart instead of being synchronized again
+    /// from the node.
+    #[structopt(long)]
+    pub storage: Option<PathBuf>,
 
     pub config: Option<PathBuf>,
     /// Set log messages minimum severity. If not configured anywhere, defaults to "info".
@@ -223,6 +276,10 @@ pub struct Config {
     pub address_bech32_prefix: Option<String>,
     pub query_depth_limit: Option<usize>,
     pub query_complexity_limit: Option<usize>,
+    pub query_cost_limit: Option<u64>,
+    pub slow_query_threshold: Option<u64>,
+    pub response_cache_size: Option<usize>,
+    pub export_row_limit: Option<usize>,
     pub logs: Option<ConfigLogSettings>,
 }
 
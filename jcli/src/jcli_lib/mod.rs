@@ -5,6 +5,7 @@ pub mod certificate;
 pub mod debug;
 pub mod key;
 pub mod rest;
+pub mod secret;
 pub mod transaction;
 pub mod vote;
 
@@ -55,6 +56,8 @@ pub enum JCliCommand {
     Utils(utils::Utils),
     /// Vote related operations
     Votes(vote::Vote),
+    /// Node secret file encryption
+    Secret(secret::Secret),
 }
 
 impl JCli {
@@ -87,6 +90,7 @@ impl JCliCommand {
             AutoCompletion(auto_completion) => auto_completion.exec::<Self>()?,
             Utils(utils) => utils.exec()?,
             Votes(vote) => vote.exec()?,
+            Secret(secret) => secret.exec()?,
         };
         Ok(())
     }
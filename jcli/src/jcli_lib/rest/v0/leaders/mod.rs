@@ -1,7 +1,9 @@
 use crate::jcli_lib::{
     rest::{Error, RestArgs},
-    utils::OutputFormat,
+    utils::{io, OutputFormat},
 };
+use jormungandr_lib::interfaces::SigningWatermarks;
+use std::path::PathBuf;
 use structopt::StructOpt;
 
 #[derive(StructOpt)]
@@ -9,6 +11,33 @@ use structopt::StructOpt;
 pub enum Leaders {
     /// Leadership log operations
     Logs(GetLogs),
+    /// Get the slots of the current epoch, and of the next one once it is
+    /// known, for which the leaders of the node are elected
+    Schedule {
+        #[structopt(flatten)]
+        args: RestArgs,
+        #[structopt(flatten)]
+        output_format: OutputFormat,
+    },
+    /// Stop the node signing blocks and print the signing watermarks of its
+    /// leaders, for a standby node to take them over
+    Handoff {
+        #[structopt(flatten)]
+        args: RestArgs,
+        #[structopt(flatten)]
+        output_format: OutputFormat,
+    },
+    /// Let a standby node sign blocks from the signing watermarks printed by
+    /// the handoff of the active node
+    TakeOver {
+        #[structopt(flatten)]
+        args: RestArgs,
+        /// the signing watermarks, read from the standard input if not given
+        #[structopt(long = "input", parse(from_os_str))]
+        input: Option<PathBuf>,
+        #[structopt(flatten)]
+        output_format: OutputFormat,
+    },
 }
 
 #[derive(StructOpt)]
@@ -30,6 +59,19 @@ impl Leaders {
                 args,
                 output_format,
             }) => get_logs(args, output_format),
+            Leaders::Schedule {
+                args,
+                output_format,
+            } => get_schedule(args, output_format),
+            Leaders::Handoff {
+                args,
+                output_format,
+            } => handoff(args, output_format),
+            Leaders::TakeOver {
+                args,
+                input,
+                output_format,
+            } => take_over(args, input, output_format),
         }
     }
 }
@@ -44,3 +86,42 @@ fn get_logs(args: RestArgs, output_format: OutputFormat) -> Result<(), Error> {
     println!("{}", formatted);
     Ok(())
 }
+
+fn get_schedule(args: RestArgs, output_format: OutputFormat) -> Result<(), Error> {
+    let response = args
+        .client()?
+        .get(&["v0", "leaders", "schedule"])
+        .execute()?
+        .json()?;
+    let formatted = output_format.format_json(response)?;
+    println!("{}", formatted);
+    Ok(())
+}
+
+fn handoff(args: RestArgs, output_format: OutputFormat) -> Result<(), Error> {
+    let response = args
+        .client()?
+        .post(&["v0", "leaders", "handoff"])
+        .execute()?
+        .json()?;
+    let formatted = output_format.format_json(response)?;
+    println!("{}", formatted);
+    Ok(())
+}
+
+fn take_over(
+    args: RestArgs,
+    input: Option<PathBuf>,
+    output_format: OutputFormat,
+) -> Result<(), Error> {
+    let watermarks: SigningWatermarks = io::read_yaml(&input)?;
+    let response = args
+        .client()?
+        .post(&["v0", "leaders", "takeover"])
+        .json(&watermarks)
+        .execute()?
+        .json()?;
+    let formatted = output_format.format_json(response)?;
+    println!("{}", formatted);
+    Ok(())
+}
@@ -0,0 +1,255 @@
+use crate::jcli_lib::utils::{
+    io,
+    output_file::{self, OutputFile},
+};
+use chain_crypto::SumEd25519_12;
+use jormungandr_lib::{
+    crypto::key::SigningKey,
+    interfaces::{EncryptedSecretFile, NodeSecret, SecretEncryptionError, DEFAULT_KDF_ITERATIONS},
+};
+use rand::rngs::OsRng;
+use std::{
+    io::{Read, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O error")]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    InvalidOutput(#[from] output_file::Error),
+    #[error("invalid node secret file")]
+    InvalidSecret(#[source] serde_yaml::Error),
+    #[error("the node secret file is already encrypted")]
+    AlreadyEncrypted,
+    #[error("invalid encrypted node secret file")]
+    InvalidEncryptedSecret(#[source] serde_yaml::Error),
+    #[error("cannot write the encrypted node secret file")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error(transparent)]
+    Encryption(#[from] SecretEncryptionError),
+    #[error("the passphrases do not match")]
+    PassphraseMismatch,
+    #[error("the passphrase is empty")]
+    EmptyPassphrase,
+    #[error("the node secret file is encrypted, decrypt it first")]
+    Encrypted,
+    #[error("the node secret file has no Genesis Praos leader")]
+    NoGenesisLeader,
+    #[error("invalid KES signing key")]
+    InvalidKesKey(#[from] chain_crypto::bech32::Error),
+}
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "secret", rename_all = "kebab-case")]
+pub enum Secret {
+    /// encrypt a node secret file with a passphrase
+    Encrypt(Encrypt),
+    /// decrypt an encrypted node secret file
+    Decrypt(Decrypt),
+    /// change the passphrase of an encrypted node secret file
+    ChangePassphrase(ChangePassphrase),
+    /// replace the KES signing key of the Genesis Praos leader of a node
+    /// secret file by its successor
+    RotateKes(RotateKes),
+}
+
+#[derive(StructOpt, Debug)]
+pub struct Encrypt {
+    /// the node secret file to encrypt, or read from the standard input
+    #[structopt(long = "input")]
+    input: Option<PathBuf>,
+
+    /// file containing the passphrase on its first line, the passphrase
+    /// is asked interactively if not provided
+    #[structopt(long = "passphrase-file")]
+    passphrase_file: Option<PathBuf>,
+
+    /// the number of iterations of the key derivation function, the
+    /// recommended default of the secret file format if not given
+    #[structopt(long = "iterations")]
+    iterations: Option<u32>,
+
+    #[structopt(flatten)]
+    output_file: OutputFile,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct Decrypt {
+    /// the encrypted node secret file, or read from the standard input
+    #[structopt(long = "input")]
+    input: Option<PathBuf>,
+
+    /// file containing the passphrase on its first line, the passphrase
+    /// is asked interactively if not provided
+    #[structopt(long = "passphrase-file")]
+    passphrase_file: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    output_file: OutputFile,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct ChangePassphrase {
+    /// the encrypted node secret file, or read from the standard input
+    #[structopt(long = "input")]
+    input: Option<PathBuf>,
+
+    /// file containing the current passphrase on its first line, the
+    /// passphrase is asked interactively if not provided
+    #[structopt(long = "passphrase-file")]
+    passphrase_file: Option<PathBuf>,
+
+    /// file containing the new passphrase on its first line, the
+    /// passphrase is asked interactively if not provided
+    #[structopt(long = "new-passphrase-file")]
+    new_passphrase_file: Option<PathBuf>,
+
+    /// the number of iterations of the key derivation function, the
+    /// recommended default of the secret file format if not given
+    #[structopt(long = "iterations")]
+    iterations: Option<u32>,
+
+    #[structopt(flatten)]
+    output_file: OutputFile,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct RotateKes {
+    /// the node secret file, or read from the standard input
+    #[structopt(long = "input")]
+    input: Option<PathBuf>,
+
+    /// file containing the bech32 encoded successor KES signing key, as
+    /// generated by `jcli key generate --type SumEd25519_12`
+    #[structopt(long = "kes-key")]
+    kes_key: PathBuf,
+
+    /// the KES period the successor key is in use from, the key signs
+    /// blocks over 4096 periods from there
+    #[structopt(long = "start-period")]
+    start_period: u32,
+
+    #[structopt(flatten)]
+    output_file: OutputFile,
+}
+
+impl Secret {
+    pub fn exec(self) -> Result<(), Error> {
+        match self {
+            Secret::Encrypt(args) => args.exec(),
+            Secret::Decrypt(args) => args.exec(),
+            Secret::ChangePassphrase(args) => args.exec(),
+            Secret::RotateKes(args) => args.exec(),
+        }
+    }
+}
+
+impl Encrypt {
+    fn exec(self) -> Result<(), Error> {
+        let plaintext = read_input(&self.input)?;
+        if serde_yaml::from_slice::<EncryptedSecretFile>(&plaintext).is_ok() {
+            return Err(Error::AlreadyEncrypted);
+        }
+        serde_yaml::from_slice::<NodeSecret>(&plaintext).map_err(Error::InvalidSecret)?;
+
+        let passphrase = new_passphrase(&self.passphrase_file, "Passphrase: ")?;
+        write_encrypted(&plaintext, &passphrase, self.iterations, &self.output_file)
+    }
+}
+
+impl Decrypt {
+    fn exec(self) -> Result<(), Error> {
+        let encrypted = read_encrypted(&self.input)?;
+        let passphrase = passphrase(&self.passphrase_file, "Passphrase: ")?;
+        let plaintext = encrypted.decrypt(passphrase.as_bytes())?;
+        self.output_file.open()?.write_all(&plaintext)?;
+        Ok(())
+    }
+}
+
+impl ChangePassphrase {
+    fn exec(self) -> Result<(), Error> {
+        let encrypted = read_encrypted(&self.input)?;
+        let passphrase = passphrase(&self.passphrase_file, "Current passphrase: ")?;
+        let plaintext = encrypted.decrypt(passphrase.as_bytes())?;
+
+        let new_passphrase = new_passphrase(&self.new_passphrase_file, "New passphrase: ")?;
+        write_encrypted(
+            &plaintext,
+            &new_passphrase,
+            self.iterations,
+            &self.output_file,
+        )
+    }
+}
+
+impl RotateKes {
+    fn exec(self) -> Result<(), Error> {
+        let bytes = read_input(&self.input)?;
+        if serde_yaml::from_slice::<EncryptedSecretFile>(&bytes).is_ok() {
+            return Err(Error::Encrypted);
+        }
+        let mut secret: NodeSecret =
+            serde_yaml::from_slice(&bytes).map_err(Error::InvalidSecret)?;
+        let genesis = secret.genesis.as_mut().ok_or(Error::NoGenesisLeader)?;
+
+        let kes_key = io::read_line(&Some(self.kes_key))?;
+        genesis.sig_key = SigningKey::<SumEd25519_12>::from_bech32_str(&kes_key)?;
+        genesis.kes_start_period = Some(self.start_period);
+
+        let output = self.output_file.open()?;
+        serde_yaml::to_writer(output, &secret)?;
+        Ok(())
+    }
+}
+
+fn read_input(input: &Option<PathBuf>) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    io::open_file_read(input)?.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_encrypted(input: &Option<PathBuf>) -> Result<EncryptedSecretFile, Error> {
+    let bytes = read_input(input)?;
+    serde_yaml::from_slice(&bytes).map_err(Error::InvalidEncryptedSecret)
+}
+
+fn write_encrypted(
+    plaintext: &[u8],
+    passphrase: &str,
+    iterations: Option<u32>,
+    output_file: &OutputFile,
+) -> Result<(), Error> {
+    let iterations = iterations.unwrap_or(DEFAULT_KDF_ITERATIONS);
+    let encrypted =
+        EncryptedSecretFile::encrypt(plaintext, passphrase.as_bytes(), iterations, &mut OsRng)?;
+    let output = output_file.open()?;
+    serde_yaml::to_writer(output, &encrypted)?;
+    Ok(())
+}
+
+fn passphrase(passphrase_file: &Option<PathBuf>, prompt: &str) -> Result<String, Error> {
+    let passphrase = match passphrase_file {
+        Some(_) => io::read_line(passphrase_file)?,
+        None => rpassword::prompt_password(prompt)?,
+    };
+    if passphrase.is_empty() {
+        return Err(Error::EmptyPassphrase);
+    }
+    Ok(passphrase)
+}
+
+// a passphrase typed interactively is asked twice to catch typos
+fn new_passphrase(passphrase_file: &Option<PathBuf>, prompt: &str) -> Result<String, Error> {
+    let passphrase = passphrase(passphrase_file, prompt)?;
+    if passphrase_file.is_none()
+        && rpassword::prompt_password("Confirm passphrase: ")? != passphrase
+    {
+        return Err(Error::PassphraseMismatch);
+    }
+    Ok(passphrase)
+}
@@ -6,7 +6,10 @@ mod secret;
 pub use log::{Log, LogEntry, LogOutput};
 pub use mempool::{LogMaxEntries, Mempool, PersistentLog, PoolMaxEntries};
 pub use node::{
-    Cors, CorsOrigin, JRpc, LayersConfig, NodeConfig, NodeId, P2p, Policy, PreferredListConfig,
-    Rest, Tls, TopicsOfInterest, TrustedPeer,
+    Cors, CorsOrigin, JRpc, LayersConfig, NodeConfig, NodeId, P2p, P2pConnectionFilter, P2pTls,
+    Policy, PreferredListConfig, Rest, Tls, TopicsOfInterest, TrustedPeer,
+};
+pub use secret::{
+    Bft, Cipher, EncryptedSecret, EncryptedSecretFile, GenesisPraos, Kdf, NodeSecret,
+    SecretEncryptionError, DEFAULT_KDF_ITERATIONS, MAX_KDF_ITERATIONS,
 };
-pub use secret::{Bft, GenesisPraos, NodeSecret};
@@ -36,6 +36,50 @@ pub struct Tls {
     pub priv_key_file: String,
 }
 
+/// TLS settings for the node-to-node gRPC connections.
+///
+/// The certificate is self-signed on the fly with the node key
+/// (see `node_key_file`), so the TLS identity of a peer is its node ID.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct P2pTls {
+    /// Refuse connections with peers that do not negotiate TLS.
+    /// If not set, plaintext connections are still accepted and outbound
+    /// connections fall back to plaintext for backward compatibility.
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// Restrictions on the addresses of p2p peers. The allow and deny lists
+/// are applied to inbound connections as well as to the peers selected
+/// from gossip.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct P2pConnectionFilter {
+    /// If not empty, only peers with addresses in one of these
+    /// CIDR ranges (e.g. "10.0.0.0/8") are accepted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allow: Vec<String>,
+
+    /// Peers with addresses in one of these CIDR ranges are refused,
+    /// even if also covered by the allow list.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deny: Vec<String>,
+
+    /// Maximum number of inbound connections accepted from a single subnet.
+    /// Not limited if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_inbound_per_subnet: Option<usize>,
+
+    /// Prefix length grouping IPv4 addresses into subnets, 24 by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipv4_subnet_prefix: Option<u8>,
+
+    /// Prefix length grouping IPv6 addresses into subnets, 48 by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ipv6_subnet_prefix: Option<u8>,
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(deny_unknown_fields)]
 pub struct Cors {
@@ -224,6 +268,13 @@ pub struct P2p {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub network_stuck_check: Option<Duration>,
+
+    /// Enables TLS for p2p connections if provided
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls: Option<P2pTls>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connection_filter: Option<P2pConnectionFilter>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -1,6 +1,16 @@
 use crate::crypto::{hash::Hash, key::SigningKey};
+use chacha20poly1305::{
+    aead::{Aead, NewAead},
+    ChaCha20Poly1305, Key, Nonce,
+};
 use chain_crypto::{Ed25519, RistrettoGroup2HashDh, SumEd25519_12};
+use hmac::Hmac;
+use pbkdf2::pbkdf2;
+use rand_core::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use thiserror::Error;
+use zeroize::Zeroizing;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct NodeSecret {
@@ -23,4 +33,198 @@ pub struct GenesisPraos {
     pub node_id: Hash,
     pub sig_key: SigningKey<SumEd25519_12>,
     pub vrf_key: SigningKey<RistrettoGroup2HashDh>,
+    /// the KES period the signing key is in use from, counted in periods of
+    /// `kes_update_speed` from the block0 date. The key is in use from the
+    /// block0 date if not given.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kes_start_period: Option<u32>,
+}
+
+/// The number of PBKDF2 iterations used when encrypting a secret file.
+pub const DEFAULT_KDF_ITERATIONS: u32 = 600_000;
+/// The largest number of PBKDF2 iterations accepted, so that a crafted
+/// file cannot keep the node deriving the key for hours.
+pub const MAX_KDF_ITERATIONS: u32 = 10_000_000;
+
+const KEY_SIZE: usize = 32;
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+
+#[derive(Debug, Error)]
+pub enum SecretEncryptionError {
+    #[error("invalid {0} in the encrypted secret file")]
+    InvalidEncoding(&'static str),
+    #[error("cannot decrypt the secret file, the passphrase is wrong or the file is corrupted")]
+    Decryption,
+    #[error("cannot encrypt the secret file")]
+    Encryption,
+    #[error(
+        "invalid number of key derivation iterations {0}, expected 1 to {}",
+        MAX_KDF_ITERATIONS
+    )]
+    KdfIterations(u32),
+}
+
+/// A node secret file encrypted with a passphrase, in place of the
+/// plain YAML document.
+///
+/// ```yaml
+/// encrypted:
+///   kdf:
+///     algorithm: pbkdf2-hmac-sha256
+///     iterations: 600000
+///   cipher: chacha20-poly1305
+///   salt: <hex>
+///   nonce: <hex>
+///   ciphertext: <base64>
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct EncryptedSecretFile {
+    pub encrypted: EncryptedSecret,
+}
+
+/// The key derivation function turning the passphrase into the encryption key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", tag = "algorithm")]
+pub enum Kdf {
+    Pbkdf2HmacSha256 { iterations: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Cipher {
+    Chacha20Poly1305,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct EncryptedSecret {
+    pub kdf: Kdf,
+    pub cipher: Cipher,
+    pub salt: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+impl EncryptedSecretFile {
+    /// Encrypts the content of a secret file, with a key derived from the
+    /// passphrase and a random salt.
+    pub fn encrypt<R: RngCore + CryptoRng>(
+        plaintext: &[u8],
+        passphrase: &[u8],
+        iterations: u32,
+        rng: &mut R,
+    ) -> Result<Self, SecretEncryptionError> {
+        let mut salt = [0; SALT_SIZE];
+        rng.fill_bytes(&mut salt);
+        let mut nonce = [0; NONCE_SIZE];
+        rng.fill_bytes(&mut nonce);
+
+        let kdf = Kdf::Pbkdf2HmacSha256 { iterations };
+        let key = kdf.derive(passphrase, &salt)?;
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&key[..]))
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|_| SecretEncryptionError::Encryption)?;
+
+        Ok(EncryptedSecretFile {
+            encrypted: EncryptedSecret {
+                kdf,
+                cipher: Cipher::Chacha20Poly1305,
+                salt: hex::encode(salt),
+                nonce: hex::encode(nonce),
+                ciphertext: base64::encode(ciphertext),
+            },
+        })
+    }
+
+    /// Decrypts the content of the secret file. The ciphertext is
+    /// authenticated, a wrong passphrase is reported as an error.
+    pub fn decrypt(&self, passphrase: &[u8]) -> Result<Zeroizing<Vec<u8>>, SecretEncryptionError> {
+        let EncryptedSecret {
+            kdf,
+            cipher: Cipher::Chacha20Poly1305,
+            salt,
+            nonce,
+            ciphertext,
+        } = &self.encrypted;
+        let salt = hex::decode(salt).map_err(|_| SecretEncryptionError::InvalidEncoding("salt"))?;
+        let nonce = hex::decode(nonce)
+            .ok()
+            .filter(|nonce| nonce.len() == NONCE_SIZE)
+            .ok_or(SecretEncryptionError::InvalidEncoding("nonce"))?;
+        let ciphertext = base64::decode(ciphertext)
+            .map_err(|_| SecretEncryptionError::InvalidEncoding("ciphertext"))?;
+
+        let key = kdf.derive(passphrase, &salt)?;
+        ChaCha20Poly1305::new(Key::from_slice(&key[..]))
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+            .map(Zeroizing::new)
+            .map_err(|_| SecretEncryptionError::Decryption)
+    }
+}
+
+impl Kdf {
+    fn derive(
+        &self,
+        passphrase: &[u8],
+        salt: &[u8],
+    ) -> Result<Zeroizing<[u8; KEY_SIZE]>, SecretEncryptionError> {
+        let mut key = Zeroizing::new([0; KEY_SIZE]);
+        match self {
+            Kdf::Pbkdf2HmacSha256 { iterations } => {
+                if !(1..=MAX_KDF_ITERATIONS).contains(iterations) {
+                    return Err(SecretEncryptionError::KdfIterations(*iterations));
+                }
+                pbkdf2::<Hmac<Sha256>>(passphrase, salt, *iterations, &mut key[..])
+            }
+        }
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn encrypted_secret_roundtrip() {
+        let plaintext = b"bft:\n  signing_key: ed25519_sk1...\n";
+        let file =
+            EncryptedSecretFile::encrypt(plaintext, b"passphrase", 1_000, &mut OsRng).unwrap();
+
+        let yaml = serde_yaml::to_string(&file).unwrap();
+        let file: EncryptedSecretFile = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(file.decrypt(b"passphrase").unwrap().as_slice(), plaintext);
+        assert!(matches!(
+            file.decrypt(b"wrong passphrase"),
+            Err(SecretEncryptionError::Decryption)
+        ));
+    }
+
+    #[test]
+    fn kdf_iterations_are_capped() {
+        let plaintext = b"bft:\n  signing_key: ed25519_sk1...\n";
+        assert!(matches!(
+            EncryptedSecretFile::encrypt(plaintext, b"passphrase", 0, &mut OsRng),
+            Err(SecretEncryptionError::KdfIterations(0))
+        ));
+
+        let mut file =
+            EncryptedSecretFile::encrypt(plaintext, b"passphrase", 1_000, &mut OsRng).unwrap();
+        file.encrypted.kdf = Kdf::Pbkdf2HmacSha256 {
+            iterations: u32::MAX,
+        };
+        assert!(matches!(
+            file.decrypt(b"passphrase"),
+            Err(SecretEncryptionError::KdfIterations(u32::MAX))
+        ));
+    }
+
+    #[test]
+    fn plain_secret_is_not_encrypted() {
+        let yaml = "bft:\n  signing_key: ed25519_sk1\n";
+        assert!(serde_yaml::from_str::<EncryptedSecretFile>(yaml).is_err());
+    }
 }
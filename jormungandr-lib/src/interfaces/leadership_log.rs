@@ -1,5 +1,10 @@
-use crate::{crypto::hash::Hash, interfaces::BlockDate, time::SystemTime};
+use crate::{
+    crypto::hash::Hash,
+    interfaces::BlockDate,
+    time::{Duration, SystemTime},
+};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// log identifier in the leadership log. Can be used to update
 /// back some.
@@ -18,6 +23,170 @@ pub enum LeadershipLogStatus {
         parent: Hash,
         chain_length: u32,
     },
+    /// the block produced for the slot is on the main branch
+    Adopted {
+        block: Hash,
+        parent: Hash,
+        chain_length: u32,
+    },
+    /// the block produced for the slot lost to the `adopted` block, created
+    /// by another leader for the same date
+    OrphanedBySlotBattle {
+        block: Hash,
+        parent: Hash,
+        chain_length: u32,
+        adopted: Hash,
+    },
+    /// the block produced for the slot is not on the main branch, which
+    /// has no block for the slot
+    OrphanedByFork {
+        block: Hash,
+        parent: Hash,
+        chain_length: u32,
+    },
+    /// the node runs in dry-run mode, the block produced for the slot was
+    /// stored apart from the blockchain and not propagated
+    DryRun {
+        block: Hash,
+        parent: Hash,
+        chain_length: u32,
+    },
+    /// the leader keys were reloaded, the schedule from this date on is
+    /// evaluated for the given leaders
+    LeadersReloaded {
+        leaders: Vec<String>,
+    },
+}
+
+impl LeadershipLogStatus {
+    /// the block produced for the slot, whether or not its outcome on the
+    /// main branch is known
+    pub fn block(&self) -> Option<(&Hash, &Hash, u32)> {
+        match self {
+            LeadershipLogStatus::Block {
+                block,
+                parent,
+                chain_length,
+            }
+            | LeadershipLogStatus::Adopted {
+                block,
+                parent,
+                chain_length,
+            }
+            | LeadershipLogStatus::OrphanedBySlotBattle {
+                block,
+                parent,
+                chain_length,
+                ..
+            }
+            | LeadershipLogStatus::OrphanedByFork {
+                block,
+                parent,
+                chain_length,
+            } => Some((block, parent, *chain_length)),
+            _ => None,
+        }
+    }
+}
+
+/// the timed steps of the production of a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockProductionStep {
+    /// the delay between the start of the slot and the wake up of the node
+    WakeDelay,
+    /// the evaluation of the leadership schedule for the slot
+    Schedule,
+    /// the ledger preparation, including the epoch transition
+    Ledger,
+    /// the selection of the fragments by the fragment pool
+    FragmentSelection,
+    /// the creation and the signing of the block header
+    Signing,
+    /// the application of the block to the blockchain and its storage
+    Storage,
+    /// the propagation of the block to the tip and to the network
+    Propagation,
+}
+
+impl BlockProductionStep {
+    pub const ALL: [BlockProductionStep; 7] = [
+        BlockProductionStep::WakeDelay,
+        BlockProductionStep::Schedule,
+        BlockProductionStep::Ledger,
+        BlockProductionStep::FragmentSelection,
+        BlockProductionStep::Signing,
+        BlockProductionStep::Storage,
+        BlockProductionStep::Propagation,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BlockProductionStep::WakeDelay => "wake_delay",
+            BlockProductionStep::Schedule => "schedule",
+            BlockProductionStep::Ledger => "ledger",
+            BlockProductionStep::FragmentSelection => "fragment_selection",
+            BlockProductionStep::Signing => "signing",
+            BlockProductionStep::Storage => "storage",
+            BlockProductionStep::Propagation => "propagation",
+        }
+    }
+}
+
+/// the time taken by the steps of the production of a block, the steps
+/// which were not reached are not set
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockProductionTimings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wake_delay: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ledger: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fragment_selection: Option<Duration>,
+    /// the fragment selection ran past the end of the slot
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub soft_deadline_hit: bool,
+    /// the fragment selection ran past the hard deadline and was cut short
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub hard_deadline_hit: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub propagation: Option<Duration>,
+}
+
+impl BlockProductionTimings {
+    pub fn is_empty(&self) -> bool {
+        self == &BlockProductionTimings::default()
+    }
+
+    pub fn get(&self, step: BlockProductionStep) -> Option<Duration> {
+        match step {
+            BlockProductionStep::WakeDelay => self.wake_delay,
+            BlockProductionStep::Schedule => self.schedule,
+            BlockProductionStep::Ledger => self.ledger,
+            BlockProductionStep::FragmentSelection => self.fragment_selection,
+            BlockProductionStep::Signing => self.signing,
+            BlockProductionStep::Storage => self.storage,
+            BlockProductionStep::Propagation => self.propagation,
+        }
+    }
+
+    pub fn set(&mut self, step: BlockProductionStep, duration: Duration) {
+        let timing = match step {
+            BlockProductionStep::WakeDelay => &mut self.wake_delay,
+            BlockProductionStep::Schedule => &mut self.schedule,
+            BlockProductionStep::Ledger => &mut self.ledger,
+            BlockProductionStep::FragmentSelection => &mut self.fragment_selection,
+            BlockProductionStep::Signing => &mut self.signing,
+            BlockProductionStep::Storage => &mut self.storage,
+            BlockProductionStep::Propagation => &mut self.propagation,
+        };
+        *timing = Some(duration);
+    }
 }
 
 /// provides information regarding events in the leadership schedule
@@ -30,6 +199,13 @@ pub struct LeadershipLog {
     wake_at_time: Option<SystemTime>,
     finished_at_time: Option<SystemTime>,
     status: LeadershipLogStatus,
+    /// the stake pool id, or the BFT leader public key, of the leader
+    /// hosted by the node which was elected for the slot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pool_id: Option<String>,
+    /// the time taken by the production of the block for the slot
+    #[serde(default, skip_serializing_if = "BlockProductionTimings::is_empty")]
+    timings: BlockProductionTimings,
 }
 
 impl LeadershipLog {
@@ -41,6 +217,8 @@ impl LeadershipLog {
             wake_at_time: None,
             finished_at_time: None,
             status: LeadershipLogStatus::Pending,
+            pool_id: None,
+            timings: BlockProductionTimings::default(),
         }
     }
 
@@ -67,6 +245,12 @@ impl LeadershipLog {
     pub fn status(&self) -> &LeadershipLogStatus {
         &self.status
     }
+    pub fn pool_id(&self) -> &Option<String> {
+        &self.pool_id
+    }
+    pub fn timings(&self) -> &BlockProductionTimings {
+        &self.timings
+    }
 
     /// make a leadership event as triggered.
     ///
@@ -101,4 +285,132 @@ impl LeadershipLog {
     pub fn set_status(&mut self, status: LeadershipLogStatus) {
         self.status = status
     }
+
+    /// set the leader elected for the slot.
+    pub fn set_pool_id(&mut self, pool_id: String) {
+        self.pool_id = Some(pool_id)
+    }
+
+    /// record the time taken by a step of the block production.
+    pub fn set_timing(&mut self, step: BlockProductionStep, duration: Duration) {
+        self.timings.set(step, duration)
+    }
+
+    /// record that the fragment selection ran past the end of the slot.
+    pub fn mark_soft_deadline_hit(&mut self) {
+        self.timings.soft_deadline_hit = true
+    }
+
+    /// record that the fragment selection ran past the hard deadline.
+    pub fn mark_hard_deadline_hit(&mut self) {
+        self.timings.hard_deadline_hit = true
+    }
+}
+
+/// a slot of the leadership schedule for which one of the leaders hosted
+/// by the node is elected
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeadershipScheduleEntry {
+    pub date: BlockDate,
+    pub time: SystemTime,
+    /// the stake pool id, or the BFT leader public key, of the leader
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool_id: Option<String>,
+}
+
+/// the date of the last block signed by each of the leaders hosted by a
+/// node, keyed by the leader identity as given in the leadership logs.
+///
+/// A node does not sign a block at or below the watermark of its leader.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SigningWatermarks(BTreeMap<String, BlockDate>);
+
+impl SigningWatermarks {
+    pub fn get(&self, leader_id: &str) -> Option<BlockDate> {
+        self.0.get(leader_id).copied()
+    }
+
+    pub fn set(&mut self, leader_id: String, date: BlockDate) {
+        self.0.insert(leader_id, date);
+    }
+
+    /// raise the watermarks to the ones of `other` where they are higher
+    pub fn merge(&mut self, other: &SigningWatermarks) {
+        for (leader_id, date) in &other.0 {
+            let watermark = self.0.entry(leader_id.clone()).or_insert(*date);
+            *watermark = std::cmp::max(*watermark, *date);
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &BlockDate)> {
+        self.0.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge_keeps_the_highest_watermarks() {
+        let mut watermarks = SigningWatermarks::default();
+        watermarks.set("a".to_owned(), BlockDate::new(2, 10));
+        watermarks.set("b".to_owned(), BlockDate::new(1, 5));
+
+        let mut other = SigningWatermarks::default();
+        other.set("a".to_owned(), BlockDate::new(1, 20));
+        other.set("b".to_owned(), BlockDate::new(1, 6));
+        other.set("c".to_owned(), BlockDate::new(0, 1));
+
+        watermarks.merge(&other);
+        assert_eq!(watermarks.get("a"), Some(BlockDate::new(2, 10)));
+        assert_eq!(watermarks.get("b"), Some(BlockDate::new(1, 6)));
+        assert_eq!(watermarks.get("c"), Some(BlockDate::new(0, 1)));
+    }
+
+    #[test]
+    fn timings_only_serialize_the_reached_steps() {
+        let mut timings = BlockProductionTimings::default();
+        assert!(timings.is_empty());
+
+        timings.set(BlockProductionStep::Ledger, Duration::from_millis(12));
+        timings.soft_deadline_hit = true;
+        assert_eq!(
+            timings.get(BlockProductionStep::Ledger),
+            Some(Duration::from_millis(12))
+        );
+        assert_eq!(timings.get(BlockProductionStep::Signing), None);
+
+        let json = serde_json::to_value(&timings).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "ledger": "12ms", "soft_deadline_hit": true })
+        );
+        let decoded: BlockProductionTimings = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded, timings);
+    }
+
+    #[test]
+    fn log_serializes_the_timings_of_the_reached_steps() {
+        let mut log = LeadershipLog::new(BlockDate::new(1, 2), SystemTime::now());
+        let json = serde_json::to_value(&log).unwrap();
+        assert!(json.get("timings").is_none());
+
+        log.set_timing(BlockProductionStep::WakeDelay, Duration::from_millis(250));
+        log.set_timing(BlockProductionStep::Schedule, Duration::from_millis(5));
+        log.mark_hard_deadline_hit();
+
+        let json = serde_json::to_value(&log).unwrap();
+        assert_eq!(
+            json["timings"],
+            serde_json::json!({
+                "wake_delay": "250ms",
+                "schedule": "5ms",
+                "hard_deadline_hit": true,
+            })
+        );
+        let decoded: LeadershipLog = serde_json::from_value(json).unwrap();
+        assert_eq!(decoded.timings(), log.timings());
+    }
 }
@@ -61,11 +61,14 @@ pub use self::{
     fragments_processing_summary::{
         FragmentRejectionReason, FragmentsProcessingSummary, RejectedFragmentInfo,
     },
-    leadership_log::{LeadershipLog, LeadershipLogId, LeadershipLogStatus},
+    leadership_log::{
+        BlockProductionStep, BlockProductionTimings, LeadershipLog, LeadershipLogId,
+        LeadershipLogStatus, LeadershipScheduleEntry, SigningWatermarks,
+    },
     linear_fee::{LinearFeeDef, PerCertificateFeeDef, PerVoteCertificateFeeDef},
     mint_token::TokenIdentifier,
     old_address::OldAddress,
-    peer_stats::{PeerRecord, PeerStats, Subscription},
+    peer_stats::{PeerRecord, PeerStats, RejectedConnections, Subscription},
     ratio::{ParseRatioError, Ratio},
     reward_parameters::RewardParams,
     rewards_info::EpochRewardsInfo,
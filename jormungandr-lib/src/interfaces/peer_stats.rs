@@ -28,3 +28,14 @@ pub struct Subscription {
     pub interest: u32,
     pub topic: String,
 }
+
+/// Counts of inbound p2p connections refused by the connection filter
+/// since the node started.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "camelCase")]
+pub struct RejectedConnections {
+    /// connections from addresses outside the allow list or in the deny list
+    pub denied: u64,
+    /// connections refused because their subnet reached the inbound limit
+    pub subnet_limit: u64,
+}
@@ -34,6 +34,14 @@ pub struct NodeStats {
     pub tx_rejected_cnt: u64,
     pub votes_cast: u64,
     pub uptime: Option<u64>,
+    /// the fewest KES periods remaining to the keys of the Genesis Praos
+    /// leaders hosted by the node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kes_remaining_periods: Option<u32>,
+    /// the skew in milliseconds of the local clock found by the last check,
+    /// positive if the local clock is ahead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock_skew_millis: Option<i64>,
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
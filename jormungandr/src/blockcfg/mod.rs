@@ -42,12 +42,15 @@ pub enum Block0Malformed {
     NoSlotDuration,
     #[error("missing `slots_per_epoch' value in the block0")]
     NoSlotsPerEpoch,
+    #[error("missing `kes_update_speed' value in the block0")]
+    NoKesUpdateSpeed,
 }
 
 pub trait Block0DataSource {
     fn slot_duration(&self) -> Result<Duration, Block0Error>;
     fn slots_per_epoch(&self) -> Result<u32, Block0Error>;
     fn start_time(&self) -> Result<SystemTime, Block0Error>;
+    fn kes_update_speed(&self) -> Result<Duration, Block0Error>;
 }
 
 impl Block0DataSource for Block {
@@ -77,6 +80,15 @@ impl Block0DataSource for Block {
         }
         Err(Block0Malformed::NoStartTime.into())
     }
+
+    fn kes_update_speed(&self) -> Result<Duration, Block0Error> {
+        for config in initial(self)?.iter() {
+            if let ConfigParam::KesUpdateSpeed(speed) = config {
+                return Ok(Duration::from_secs(*speed as u64));
+            }
+        }
+        Err(Block0Malformed::NoKesUpdateSpeed.into())
+    }
 }
 
 fn initial(block: &Block) -> Result<&ConfigParams, Block0Malformed> {
@@ -0,0 +1,556 @@
+//! Block archives, to provision a node with the blocks of a chain without
+//! bootstrapping over the network or copying the storage directory.
+//!
+//! An archive is a single file laid out as follows, all integers being
+//! big endian:
+//!
+//! * header: the magic bytes `JORMBLKA`, the format version (`u16`) and the
+//!   hash of the genesis block of the chain the blocks belong to;
+//! * the blocks in chain order, each prefixed with its chain length (`u32`)
+//!   and its serialized size (`u32`);
+//! * an end marker (`u32::MAX` in place of the chain length);
+//! * trailer: the number of blocks (`u32`), the hash of the last block and
+//!   a CRC32 checksum of all the preceding bytes.
+//!
+//! Archives are only ever read sequentially, as every block is checked
+//! against its parent, so there is no index of the block offsets.
+
+use super::{
+    chain::{CheckHeaderProof, StreamReporter},
+    tip::TipUpdater,
+    Blockchain, Error as BlockchainError, Ref, Storage, StorageError, Tip, MAIN_BRANCH_TAG,
+};
+use crate::{
+    blockcfg::{Block, HeaderHash},
+    intercom,
+    metrics::Metrics,
+};
+use chain_core::{
+    packer::Codec,
+    property::{Deserialize, ReadError, Serialize, WriteError},
+};
+use futures::prelude::*;
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::Arc,
+};
+use thiserror::Error;
+
+const MAGIC: &[u8; 8] = b"JORMBLKA";
+const VERSION: u16 = 1;
+const END_MARKER: u32 = u32::MAX;
+// Sanity limit to not allocate arbitrary amounts of memory on corrupted input
+const MAX_BLOCK_SIZE: u32 = 64 * 1024 * 1024;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O error on the block archive")]
+    Io(#[from] io::Error),
+    #[error("not a block archive")]
+    InvalidMagic,
+    #[error("unsupported block archive version {0}")]
+    UnsupportedVersion(u16),
+    #[error("the archive is for the chain with genesis block {found}, expected {expected}")]
+    Block0Mismatch {
+        expected: HeaderHash,
+        found: HeaderHash,
+    },
+    #[error("block of {0} bytes exceeds the maximum size")]
+    BlockTooLarge(u32),
+    #[error("cannot deserialize block from the archive")]
+    Deserialize(#[source] ReadError),
+    #[error("cannot serialize block")]
+    Serialize(#[source] WriteError),
+    #[error("block {hash} has chain length {found}, expected {expected}")]
+    ChainLength {
+        hash: HeaderHash,
+        expected: u32,
+        found: u32,
+    },
+    #[error("block {0} is not a child of the previous block in the archive")]
+    BrokenChain(HeaderHash),
+    #[error("the archive trailer does not match its content")]
+    InvalidTrailer,
+    #[error("checksum mismatch, the archive is corrupted")]
+    ChecksumMismatch,
+    #[error("the storage does not contain a main branch")]
+    NoMainBranch,
+    #[error(transparent)]
+    Storage(#[from] StorageError),
+    #[error("failed to read blocks from the storage")]
+    StorageStream(#[source] intercom::Error),
+    #[error(transparent)]
+    Blockchain(Box<BlockchainError>),
+}
+
+/// Summary of the content of an archive.
+#[derive(Debug, Clone, Copy)]
+pub struct ArchiveInfo {
+    pub block0: HeaderHash,
+    pub blocks: u32,
+    pub tip: HeaderHash,
+}
+
+/// Writes the blocks given in chain order in the archive format.
+pub struct ArchiveWriter<W: Write> {
+    inner: Checksummed<W>,
+    block0: HeaderHash,
+    last: Option<(HeaderHash, u32)>,
+    blocks: u32,
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    pub fn new(writer: W, block0: HeaderHash) -> Result<Self, Error> {
+        let mut inner = Checksummed::new(writer);
+        inner.write_all(MAGIC)?;
+        inner.write_all(&VERSION.to_be_bytes())?;
+        inner.write_all(block0.as_bytes())?;
+        Ok(ArchiveWriter {
+            inner,
+            block0,
+            last: None,
+            blocks: 0,
+        })
+    }
+
+    pub fn append(&mut self, block: &Block) -> Result<(), Error> {
+        let header = block.header();
+        let hash = header.hash();
+        let chain_length = u32::from(header.chain_length());
+        check_link(self.last, &header.block_parent_hash(), hash, chain_length)?;
+
+        let bytes = block.serialize_as_vec().map_err(Error::Serialize)?;
+        if bytes.len() > MAX_BLOCK_SIZE as usize {
+            return Err(Error::BlockTooLarge(bytes.len() as u32));
+        }
+        self.inner.write_all(&chain_length.to_be_bytes())?;
+        self.inner.write_all(&(bytes.len() as u32).to_be_bytes())?;
+        self.inner.write_all(&bytes)?;
+        self.last = Some((hash, chain_length));
+        self.blocks += 1;
+        Ok(())
+    }
+
+    /// Writes the trailer and returns the summary of the archive.
+    pub fn finish(mut self) -> Result<ArchiveInfo, Error> {
+        let tip = self.last.map_or(self.block0, |(hash, _)| hash);
+        self.inner.write_all(&END_MARKER.to_be_bytes())?;
+        self.inner.write_all(&self.blocks.to_be_bytes())?;
+        self.inner.write_all(tip.as_bytes())?;
+        let checksum = self.inner.hasher.clone().finalize();
+        self.inner.inner.write_all(&checksum.to_be_bytes())?;
+        self.inner.inner.flush()?;
+        Ok(ArchiveInfo {
+            block0: self.block0,
+            blocks: self.blocks,
+            tip,
+        })
+    }
+}
+
+/// Reads the blocks of an archive, checking that they form a chain.
+/// The trailer and the checksum are verified once the last block is read.
+pub struct ArchiveReader<R: Read> {
+    inner: Checksummed<R>,
+    block0: HeaderHash,
+    last: Option<(HeaderHash, u32)>,
+    blocks: u32,
+    done: bool,
+}
+
+impl<R: Read> ArchiveReader<R> {
+    pub fn new(reader: R) -> Result<Self, Error> {
+        let mut inner = Checksummed::new(reader);
+        let mut magic = [0; 8];
+        inner.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(Error::InvalidMagic);
+        }
+        let version = u16::from_be_bytes(read_array(&mut inner)?);
+        if version != VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let block0 = read_hash(&mut inner)?;
+        Ok(ArchiveReader {
+            inner,
+            block0,
+            last: None,
+            blocks: 0,
+            done: false,
+        })
+    }
+
+    pub fn block0(&self) -> HeaderHash {
+        self.block0
+    }
+
+    /// Returns the next block, or `None` after the trailer has been
+    /// read and verified.
+    pub fn next_block(&mut self) -> Result<Option<Block>, Error> {
+        if self.done {
+            return Ok(None);
+        }
+        let chain_length = u32::from_be_bytes(read_array(&mut self.inner)?);
+        if chain_length == END_MARKER {
+            self.read_trailer()?;
+            self.done = true;
+            return Ok(None);
+        }
+        let size = u32::from_be_bytes(read_array(&mut self.inner)?);
+        if size > MAX_BLOCK_SIZE {
+            return Err(Error::BlockTooLarge(size));
+        }
+        let mut bytes = vec![0; size as usize];
+        self.inner.read_exact(&mut bytes)?;
+        let block =
+            Block::deserialize(&mut Codec::new(bytes.as_slice())).map_err(Error::Deserialize)?;
+
+        let header = block.header();
+        let hash = header.hash();
+        let found = u32::from(header.chain_length());
+        if found != chain_length {
+            return Err(Error::ChainLength {
+                hash,
+                expected: chain_length,
+                found,
+            });
+        }
+        check_link(self.last, &header.block_parent_hash(), hash, chain_length)?;
+        self.last = Some((hash, chain_length));
+        self.blocks += 1;
+        Ok(Some(block))
+    }
+
+    fn read_trailer(&mut self) -> Result<(), Error> {
+        let blocks = u32::from_be_bytes(read_array(&mut self.inner)?);
+        let tip = read_hash(&mut self.inner)?;
+        let expected_tip = self.last.map_or(self.block0, |(hash, _)| hash);
+        if blocks != self.blocks || tip != expected_tip {
+            return Err(Error::InvalidTrailer);
+        }
+        let checksum = self.inner.hasher.clone().finalize();
+        let found = u32::from_be_bytes(read_array(&mut self.inner.inner)?);
+        if checksum != found {
+            return Err(Error::ChecksumMismatch);
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for ArchiveReader<R> {
+    type Item = Result<Block, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_block().transpose()
+    }
+}
+
+/// Reads the whole archive at the given path, checking its integrity
+/// without applying the blocks.
+pub fn verify_archive<P: AsRef<Path>>(path: P) -> Result<ArchiveInfo, Error> {
+    let mut reader = ArchiveReader::new(BufReader::new(File::open(path)?))?;
+    let mut tip = reader.block0();
+    while let Some(block) = reader.next_block()? {
+        tip = block.header().hash();
+    }
+    Ok(ArchiveInfo {
+        block0: reader.block0(),
+        blocks: reader.blocks,
+        tip,
+    })
+}
+
+/// Exports the main branch of the storage, from the genesis block
+/// (excluded) to the tip.
+pub async fn export_archive<P: AsRef<Path>>(
+    storage: &Storage,
+    path: P,
+) -> Result<ArchiveInfo, Error> {
+    let head = storage
+        .get_tag(MAIN_BRANCH_TAG)?
+        .ok_or(Error::NoMainBranch)?;
+    let head_length = storage.get_chain_length(head).ok_or(Error::NoMainBranch)?;
+    let block0 = storage
+        .get_nth_ancestor(head, head_length)?
+        .ok_or(Error::NoMainBranch)?
+        .header()
+        .hash();
+
+    let mut writer = ArchiveWriter::new(BufWriter::new(File::create(path)?), block0)?;
+    if head != block0 {
+        let mut stream = storage.stream_from_to(block0, head)?.boxed();
+        while let Some(block) = stream.next().await {
+            writer.append(&block.map_err(Error::StorageStream)?)?;
+        }
+    }
+    writer.finish()
+}
+
+/// Verifies the archive and applies its blocks on top of the blockchain
+/// through the same path as the blocks loaded from storage, then moves
+/// the tip to the last block of the archive.
+pub async fn import_archive<P: AsRef<Path>>(
+    blockchain: Blockchain,
+    tip: Tip,
+    path: P,
+) -> Result<Option<Arc<Ref>>, Error> {
+    let path = path.as_ref();
+    let info = verify_archive(path)?;
+    if info.block0 != *blockchain.block0() {
+        return Err(Error::Block0Mismatch {
+            expected: *blockchain.block0(),
+            found: info.block0,
+        });
+    }
+    tracing::info!(
+        "importing {} blocks up to {} from archive {}",
+        info.blocks,
+        info.tip,
+        path.display()
+    );
+
+    let mut tip_updater = TipUpdater::new(
+        tip,
+        blockchain.clone(),
+        None,
+        None,
+        Metrics::builder().build(),
+    );
+    let mut reporter = StreamReporter::new(|stream_info| {
+        tracing::info!(
+            "importing from archive, currently at {}",
+            stream_info.last_block_description.as_ref().unwrap()
+        )
+    });
+    let mut last_ref = None;
+    for block in ArchiveReader::new(BufReader::new(File::open(path)?))? {
+        let block = block?;
+        reporter.append_block(&block);
+        let applied = blockchain
+            .handle_bootstrap_block(block, CheckHeaderProof::Enabled)
+            .await;
+        match applied {
+            Ok(block_ref) => last_ref = Some(block_ref),
+            Err(e) => {
+                if let Some(block_ref) = last_ref {
+                    tip_updater
+                        .process_new_ref(block_ref)
+                        .await
+                        .map_err(|e| Error::Blockchain(Box::new(e)))?;
+                }
+                return Err(Error::Blockchain(Box::new(e)));
+            }
+        }
+    }
+    if let Some(block_ref) = &last_ref {
+        tip_updater
+            .process_new_ref(block_ref.clone())
+            .await
+            .map_err(|e| Error::Blockchain(Box::new(e)))?;
+    }
+    Ok(last_ref)
+}
+
+fn check_link(
+    last: Option<(HeaderHash, u32)>,
+    parent: &HeaderHash,
+    hash: HeaderHash,
+    chain_length: u32,
+) -> Result<(), Error> {
+    if let Some((last_hash, last_length)) = last {
+        if parent != &last_hash {
+            return Err(Error::BrokenChain(hash));
+        }
+        if chain_length != last_length + 1 {
+            return Err(Error::ChainLength {
+                hash,
+                expected: last_length + 1,
+                found: chain_length,
+            });
+        }
+    }
+    Ok(())
+}
+
+fn read_hash<R: Read>(reader: &mut R) -> Result<HeaderHash, Error> {
+    let bytes: [u8; 32] = read_array(reader)?;
+    HeaderHash::deserialize(&mut Codec::new(&bytes[..])).map_err(Error::Deserialize)
+}
+
+fn read_array<R: Read, const N: usize>(reader: &mut R) -> io::Result<[u8; N]> {
+    let mut buf = [0; N];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+// Keeps track of the checksum of the bytes passed through.
+struct Checksummed<T> {
+    inner: T,
+    hasher: crc32fast::Hasher,
+}
+
+impl<T> Checksummed<T> {
+    fn new(inner: T) -> Self {
+        Checksummed {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+        }
+    }
+}
+
+impl<R: Read> Read for Checksummed<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<W: Write> Write for Checksummed<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockcfg::{block_builder, BlockDate, BlockVersion, ChainLength, Contents};
+    use std::convert::Infallible;
+
+    fn block(parent: &HeaderHash, chain_length: u32) -> Block {
+        block_builder(BlockVersion::Genesis, Contents::empty(), |hdr_builder| {
+            Ok::<_, Infallible>(
+                hdr_builder
+                    .set_parent(parent, ChainLength::from(chain_length))
+                    .set_date(BlockDate {
+                        epoch: 0,
+                        slot_id: chain_length,
+                    })
+                    .into_unsigned_header()
+                    .unwrap()
+                    .generalize(),
+            )
+        })
+        .unwrap()
+    }
+
+    fn chain(len: u32) -> (HeaderHash, Vec<Block>) {
+        let block0 = block(&HeaderHash::zero_hash(), 0).header().hash();
+        let mut parent = block0;
+        let blocks = (1..=len)
+            .map(|chain_length| {
+                let block = block(&parent, chain_length);
+                parent = block.header().hash();
+                block
+            })
+            .collect();
+        (block0, blocks)
+    }
+
+    fn write_archive(block0: HeaderHash, blocks: &[Block]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut writer = ArchiveWriter::new(&mut bytes, block0).unwrap();
+        for block in blocks {
+            writer.append(block).unwrap();
+        }
+        writer.finish().unwrap();
+        bytes
+    }
+
+    fn read_archive(bytes: &[u8]) -> Result<Vec<Block>, Error> {
+        ArchiveReader::new(bytes)?.collect()
+    }
+
+    #[test]
+    fn round_trip() {
+        let (block0, blocks) = chain(5);
+        let mut bytes = Vec::new();
+        let mut writer = ArchiveWriter::new(&mut bytes, block0).unwrap();
+        for block in &blocks {
+            writer.append(block).unwrap();
+        }
+        let info = writer.finish().unwrap();
+        assert_eq!(info.block0, block0);
+        assert_eq!(info.blocks, 5);
+        assert_eq!(info.tip, blocks[4].header().hash());
+
+        let reader = ArchiveReader::new(bytes.as_slice()).unwrap();
+        assert_eq!(reader.block0(), block0);
+        let read = reader.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            read.iter().map(|b| b.header().hash()).collect::<Vec<_>>(),
+            blocks.iter().map(|b| b.header().hash()).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn empty_archive_round_trip() {
+        let (block0, _) = chain(0);
+        let bytes = write_archive(block0, &[]);
+        assert!(read_archive(&bytes).unwrap().is_empty());
+    }
+
+    #[test]
+    fn broken_chain_is_not_written() {
+        let (block0, blocks) = chain(3);
+        let mut writer = ArchiveWriter::new(Vec::new(), block0).unwrap();
+        writer.append(&blocks[0]).unwrap();
+        assert!(matches!(
+            writer.append(&blocks[2]),
+            Err(Error::BrokenChain(_))
+        ));
+    }
+
+    #[test]
+    fn truncated_archive_is_rejected() {
+        let (block0, blocks) = chain(3);
+        let bytes = write_archive(block0, &blocks);
+        // in the header, in a block, in the trailer and without the checksum
+        for len in [4, 60, bytes.len() - 10, bytes.len() - 4] {
+            match read_archive(&bytes[..len]) {
+                Err(Error::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+                other => panic!("unexpected result for {} bytes: {:?}", len, other),
+            }
+        }
+    }
+
+    #[test]
+    fn corrupted_archive_is_rejected() {
+        let (block0, blocks) = chain(3);
+        let bytes = write_archive(block0, &blocks);
+
+        // the genesis hash in the header is not otherwise checked by the reader
+        let mut corrupted = bytes.clone();
+        corrupted[MAGIC.len() + 2] ^= 1;
+        assert!(matches!(
+            read_archive(&corrupted),
+            Err(Error::ChecksumMismatch)
+        ));
+
+        let mut corrupted = bytes.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            read_archive(&corrupted),
+            Err(Error::ChecksumMismatch)
+        ));
+    }
+
+    #[test]
+    fn bad_trailer_is_rejected() {
+        let (block0, blocks) = chain(3);
+        let mut bytes = write_archive(block0, &blocks);
+        // the number of blocks, right after the end marker
+        let count = bytes.len() - 4 - 32 - 4;
+        bytes[count + 3] = 2;
+        assert!(matches!(read_archive(&bytes), Err(Error::InvalidTrailer)));
+    }
+}
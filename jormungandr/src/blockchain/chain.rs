@@ -49,13 +49,14 @@ See Internal documentation for more details: doc/internal_design.md
 [`Branch`]: ./struct.Branch.html
 */
 #![allow(clippy::large_enum_variant)]
-use super::reference_cache::RefCache;
+use super::{reference_cache::RefCache, snapshot::LedgerSnapshots};
 use crate::{
     blockcfg::{
-        Block, Block0Error, BlockDate, ChainLength, Epoch, EpochRewardsInfo, Header, HeaderDesc,
-        HeaderHash, Leadership, Ledger, RewardsInfoParameters,
+        Block, Block0Error, BlockDate, ChainLength, ConsensusVersion, Epoch, EpochRewardsInfo,
+        Header, HeaderDesc, HeaderHash, Leadership, Ledger, RewardsInfoParameters,
     },
     blockchain::{Branch, Checkpoints, Multiverse, Ref, Storage, StorageError, Tip},
+    leadership::LeadershipLogHandle,
 };
 use chain_impl_mockchain::{leadership::Verification, ledger};
 use chain_time::TimeFrame;
@@ -176,6 +177,8 @@ pub struct Blockchain {
     block0: HeaderHash,
 
     rewards_report_all: bool,
+
+    ledger_snapshots: Option<LedgerSnapshots>,
 }
 
 pub enum PreCheckedHeader {
@@ -227,6 +230,9 @@ pub struct LeadershipBlock {
     pub block: Block,
     pub new_ledger: Ledger,
     pub leadership: EpochLeadership,
+    /// the log of the slot, the storage and the propagation of the block
+    /// are timed in it
+    pub log: LeadershipLogHandle,
 }
 
 impl PostCheckedHeader {
@@ -268,6 +274,7 @@ impl Blockchain {
         storage: Storage,
         cache_capacity: usize,
         rewards_report_all: bool,
+        ledger_snapshots: Option<LedgerSnapshots>,
     ) -> Self {
         Blockchain {
             ref_cache: RefCache::new(cache_capacity),
@@ -275,6 +282,7 @@ impl Blockchain {
             storage,
             block0,
             rewards_report_all,
+            ledger_snapshots,
         }
     }
 
@@ -301,6 +309,12 @@ impl Blockchain {
         let depth = tip.ledger().settings().epoch_stability_depth;
         self.ledgers.gc(depth).await;
         self.storage.gc(depth, tip.hash().as_ref())?;
+
+        if let Some(ledger_snapshots) = &self.ledger_snapshots {
+            if let Err(err) = snapshot_ledger(ledger_snapshots, &tip, depth).await {
+                tracing::warn!(err = %err, "failed to write the ledger snapshot");
+            }
+        }
         Ok(())
     }
 
@@ -571,6 +585,7 @@ impl Blockchain {
             block,
             new_ledger,
             leadership,
+            log: _,
         } = leadership_block;
 
         let header = block.header().clone();
@@ -708,9 +723,123 @@ impl Blockchain {
         Ok(new_ref)
     }
 
+    /// restore the states of the newest ledger snapshot whose block is still
+    /// on the main branch, returning the `Ref` of this block. `None` is
+    /// returned if there is no such snapshot, the invalid snapshots are skipped.
+    async fn restore_ledger_snapshot(
+        &self,
+        block0_ref: &Ref,
+        head_hash: HeaderHash,
+    ) -> Result<Option<Arc<Ref>>> {
+        let ledger_snapshots = match &self.ledger_snapshots {
+            Some(ledger_snapshots) => ledger_snapshots,
+            None => return Ok(None),
+        };
+        let head_length = match self.storage.get(head_hash)? {
+            Some(head) => u32::from(head.header().chain_length()),
+            None => return Ok(None),
+        };
+        let paths = ledger_snapshots.list().unwrap_or_else(|err| {
+            tracing::warn!(err = %err, "failed to list the ledger snapshots");
+            Vec::new()
+        });
+
+        for path in paths {
+            let read_path = path.clone();
+            let states = tokio::task::spawn_blocking(move || LedgerSnapshots::read(&read_path))
+                .await
+                .expect("the ledger snapshot reading panicked");
+            let mut states = match states {
+                Ok(states) => states,
+                Err(err) => {
+                    tracing::warn!(err = %err, "skipping the ledger snapshot {:?}", path);
+                    continue;
+                }
+            };
+
+            let mut headers = Vec::with_capacity(states.len());
+            for (hash, _) in &states {
+                match self.storage.get(*hash)? {
+                    Some(block) => headers.push(block.header().clone()),
+                    None => break,
+                }
+            }
+            if headers.len() != states.len() || states.len() < 2 {
+                tracing::warn!("skipping the ledger snapshot {:?}: missing blocks", path);
+                continue;
+            }
+
+            // the block of the snapshot must be an ancestor of the head
+            let snapshot_length = u32::from(headers[0].chain_length());
+            let on_main_branch = snapshot_length <= head_length
+                && self
+                    .storage
+                    .get_nth_ancestor(head_hash, head_length - snapshot_length)?
+                    .map(|ancestor| ancestor.header().hash())
+                    == Some(headers[0].hash());
+            if !on_main_branch {
+                tracing::warn!(
+                    "skipping the ledger snapshot {:?}: not on the main branch",
+                    path
+                );
+                continue;
+            }
+
+            // the leaderships of the epochs of the 2 newest states are evaluated
+            // the same way as in `leadership_state`
+            let mut leaderships = Vec::with_capacity(2);
+            for (i, header) in headers.iter().take(2).enumerate() {
+                let ledger = &states[i].1;
+                let leadership_ledger =
+                    if ledger.consensus_version() == ConsensusVersion::GenesisPraos {
+                        states.get(i + 2).map(|(_, ledger)| ledger)
+                    } else {
+                        Some(ledger)
+                    };
+                match leadership_ledger {
+                    Some(leadership_ledger) => leaderships.push(Arc::new(Leadership::new(
+                        header.block_date().epoch,
+                        leadership_ledger,
+                    ))),
+                    None => break,
+                }
+            }
+            if leaderships.len() != 2 {
+                tracing::warn!("skipping the ledger snapshot {:?}: missing states", path);
+                continue;
+            }
+
+            // the older epochs are not restored: the epoch rewards and the
+            // leaderships from before the snapshot are not available
+            headers.truncate(2);
+            states.truncate(2);
+            let mut previous_epoch_state = None;
+            for ((header, (hash, ledger)), leadership) in
+                headers.into_iter().zip(states).zip(leaderships).rev()
+            {
+                let reference = self
+                    .create_and_store_reference(
+                        hash,
+                        header,
+                        ledger,
+                        Arc::clone(block0_ref.time_frame()),
+                        leadership,
+                        None,
+                        previous_epoch_state,
+                    )
+                    .await;
+                previous_epoch_state = Some(reference);
+            }
+
+            tracing::info!("restored the ledger snapshot {:?}", path);
+            return Ok(previous_epoch_state);
+        }
+        Ok(None)
+    }
+
     /// returns a future that will propagate the initial states and leadership
-    /// from the block0 to the `Head` of the storage (the last known block which
-    /// made consensus).
+    /// from the newest ledger snapshot on the main branch, or the block0, to
+    /// the `Head` of the storage (the last known block which made consensus).
     ///
     /// The Future will returns a branch pointing to the `Head`.
     ///
@@ -737,7 +866,11 @@ impl Blockchain {
             return Err(Error::NoTag(MAIN_BRANCH_TAG.to_owned()));
         };
 
-        let mut last_ref = self.apply_block0(&block0).await?.get_ref();
+        let block0_ref = self.apply_block0(&block0).await?.get_ref();
+        let mut last_ref = self
+            .restore_ledger_snapshot(&block0_ref, head_hash)
+            .await?
+            .unwrap_or(block0_ref);
         let mut reporter = StreamReporter::new(|stream_info| {
             let elapsed = stream_info
                 .last_reported
@@ -753,7 +886,7 @@ impl Blockchain {
 
         let mut block_stream = self
             .storage
-            .stream_from_to(block0_id, head_hash)
+            .stream_from_to(last_ref.hash(), head_hash)
             .map(Box::pin)?;
 
         while let Some(block) = block_stream.next().await.transpose()? {
@@ -842,8 +975,6 @@ pub fn new_epoch_leadership_from(
         // TODO: the time frame may change in the future, we will need to handle this
         //       special case but it is not actually clear how to modify the time frame
         //       for the blockchain
-        use chain_impl_mockchain::chaintypes::ConsensusVersion;
-
         let ledger = parent_ledger_state
             .apply_protocol_changes()
             .expect("protocol update should not fail");
@@ -870,17 +1001,7 @@ pub fn new_epoch_leadership_from(
 
         // 2. now that the rewards have been distributed, prepare the schedule
         //    for the next leader
-        let epoch_state = if transition_state.consensus_version() == ConsensusVersion::GenesisPraos
-        {
-            // if there is no parent state available this might be because it is not
-            // available in memory or it is the epoch0 or epoch1
-            parent
-                .last_ref_previous_epoch()
-                .map(|r| r.ledger())
-                .unwrap_or(parent_ledger_state)
-        } else {
-            transition_state.clone()
-        };
+        let epoch_state = leadership_state(&parent, &transition_state);
 
         let leadership = Arc::new(Leadership::new(epoch, &epoch_state));
         let previous_epoch_state = Some(parent);
@@ -902,6 +1023,85 @@ pub fn new_epoch_leadership_from(
     }
 }
 
+/// the leadership of an epoch following the parent's one, as evaluated at the
+/// epoch transition but without distributing the rewards of the transition:
+/// the leadership doesn't depend on them and no reward dump is written.
+pub fn next_epoch_leadership(epoch: Epoch, parent: &Arc<Ref>) -> Arc<Leadership> {
+    let transition_state = Arc::new(
+        parent
+            .ledger()
+            .apply_protocol_changes()
+            .expect("protocol update should not fail"),
+    );
+    let epoch_state = leadership_state(parent, &transition_state);
+
+    Arc::new(Leadership::new(epoch, &epoch_state))
+}
+
+// the ledger the leadership of the new epoch is evaluated from, the Genesis
+// Praos stake distribution is the one at the end of the epoch preceding the
+// parent's one
+fn leadership_state(parent: &Ref, transition_state: &Arc<Ledger>) -> Arc<Ledger> {
+    if transition_state.consensus_version() == ConsensusVersion::GenesisPraos {
+        // if there is no parent state available this might be because it is not
+        // available in memory or it is the epoch0 or epoch1
+        parent
+            .last_ref_previous_epoch()
+            .map(|r| r.ledger())
+            .unwrap_or_else(|| parent.ledger())
+    } else {
+        Arc::clone(transition_state)
+    }
+}
+
+/// write a snapshot of the last block of the newest epoch ended deeper than
+/// the stability depth from the tip, unless it is already written. The
+/// states of the last blocks of the epochs before it rebuild the leadership of
+/// its epoch and of the one before it, see `leadership_state`.
+async fn snapshot_ledger(
+    ledger_snapshots: &LedgerSnapshots,
+    tip: &Ref,
+    depth: u32,
+) -> core::result::Result<(), super::snapshot::Error> {
+    let tip_length = u32::from(tip.chain_length());
+    let mut stable = tip.last_ref_previous_epoch();
+    while let Some(epoch_end) = stable {
+        if tip_length - u32::from(epoch_end.chain_length()) >= depth {
+            break;
+        }
+        stable = epoch_end.last_ref_previous_epoch();
+    }
+    let stable = match stable {
+        Some(stable) => stable,
+        None => return Ok(()),
+    };
+    if ledger_snapshots.contains(stable.chain_length(), &stable.hash()) {
+        return Ok(());
+    }
+
+    let needed = if stable.ledger().consensus_version() == ConsensusVersion::GenesisPraos {
+        4
+    } else {
+        2
+    };
+    let mut states = Vec::with_capacity(needed);
+    let mut epoch_end = Some(stable);
+    while let Some(reference) = epoch_end.filter(|_| states.len() < needed) {
+        states.push((reference.hash(), reference.ledger()));
+        epoch_end = reference.last_ref_previous_epoch();
+    }
+    if states.len() < needed {
+        // too close to the block0
+        return Ok(());
+    }
+
+    let ledger_snapshots = ledger_snapshots.clone();
+    let chain_length = stable.chain_length();
+    tokio::task::spawn_blocking(move || ledger_snapshots.write(chain_length, &states))
+        .await
+        .expect("the ledger snapshot writing panicked")
+}
+
 pub struct StreamReporter<R> {
     stream_info: StreamInfo,
     report: R,
@@ -1,3 +1,4 @@
+pub mod archive;
 mod bootstrap;
 mod branch;
 mod candidate;
@@ -8,6 +9,7 @@ mod multiverse;
 mod process;
 mod reference;
 mod reference_cache;
+mod snapshot;
 mod storage;
 mod tip;
 
@@ -29,14 +31,15 @@ pub use self::{
     bootstrap::{bootstrap_from_stream, Error as BootstrapError},
     branch::Branch,
     chain::{
-        new_epoch_leadership_from, Blockchain, CheckHeaderProof, EpochLeadership, Error,
-        LeadershipBlock, PreCheckedHeader, MAIN_BRANCH_TAG,
+        new_epoch_leadership_from, next_epoch_leadership, Blockchain, CheckHeaderProof,
+        EpochLeadership, Error, LeadershipBlock, PreCheckedHeader, MAIN_BRANCH_TAG,
     },
     chain_selection::{compare_against, ComparisonResult},
     checkpoints::Checkpoints,
     multiverse::Multiverse,
     process::{start, TaskData},
     reference::Ref,
+    snapshot::LedgerSnapshots,
     storage::{Error as StorageError, Storage},
     tip::Tip,
 };
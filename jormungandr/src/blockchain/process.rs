@@ -7,6 +7,7 @@ use super::{
 use crate::{
     blockcfg::{Block, Header, HeaderHash},
     blockchain::Checkpoints,
+    clock_skew::ClockSkewMonitor,
     intercom::{self, BlockMsg, NetworkMsg, PropagateMsg, TransactionMsg, WatchMsg},
     metrics::{Metrics, MetricsBackend},
     topology::NodeId,
@@ -19,8 +20,13 @@ use crate::{
     },
 };
 use chain_core::property::{Block as _, Header as _};
+use chain_impl_mockchain::leadership::Verification;
 use futures::prelude::*;
-use std::{sync::Arc, time::Duration};
+use jormungandr_lib::interfaces::BlockProductionStep;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
 use tracing::{span, Level};
 use tracing_futures::Instrument;
 
@@ -56,6 +62,7 @@ pub struct TaskData {
     pub fragment_msgbox: MessageBox<TransactionMsg>,
     pub watch_msgbox: MessageBox<WatchMsg>,
     pub garbage_collection_interval: Duration,
+    pub clock_skew: ClockSkewMonitor,
 }
 
 /// The blockchain process is comprised mainly of two parts:
@@ -73,6 +80,7 @@ struct Process {
     fragment_msgbox: MessageBox<TransactionMsg>,
     watch_msgbox: MessageBox<WatchMsg>,
     garbage_collection_interval: Duration,
+    clock_skew: ClockSkewMonitor,
     tip_update_mbox: MessageBox<Arc<Ref>>,
     pull_headers_scheduler: PullHeadersScheduler,
     get_next_block_scheduler: GetNextBlockScheduler,
@@ -143,6 +151,7 @@ pub async fn start(
         fragment_msgbox,
         garbage_collection_interval,
         watch_msgbox,
+        clock_skew,
     } = task_data;
 
     let (tip_update_mbox, tip_update_queue) = async_msg::channel(TIP_UPDATE_QUEUE_SIZE);
@@ -159,6 +168,7 @@ pub async fn start(
         fragment_msgbox,
         watch_msgbox,
         garbage_collection_interval,
+        clock_skew,
         tip_update_mbox,
         pull_headers_scheduler,
         get_next_block_scheduler,
@@ -222,6 +232,7 @@ impl Process {
                         network_msg_box,
                         watch_msg_box,
                         *leadership_block,
+                        stats_counter,
                     )
                     .instrument(span.clone()),
                 );
@@ -238,6 +249,7 @@ impl Process {
                 );
                 let _enter = span.enter();
                 tracing::debug!("received block announcement from network");
+                let arrival = SystemTime::now();
 
                 self.service_info.timeout_spawn_fallible(
                     "process block announcement",
@@ -249,6 +261,8 @@ impl Process {
                         node_id,
                         self.pull_headers_scheduler.clone(),
                         self.get_next_block_scheduler.clone(),
+                        self.clock_skew.clone(),
+                        arrival,
                     )
                     .instrument(span.clone()),
                 )
@@ -356,16 +370,29 @@ async fn process_leadership_block(
     network_msg_box: MessageBox<NetworkMsg>,
     mut watch_msg_box: MessageBox<WatchMsg>,
     leadership_block: LeadershipBlock,
+    stats_counter: Metrics,
 ) -> chain::Result<()> {
     let block = leadership_block.block.clone();
+    let log = leadership_block.log.clone();
+
+    let started = Instant::now();
     let new_block_ref = process_leadership_block_inner(&mut blockchain, leadership_block).await?;
+    let storage_time = started.elapsed();
+    log.set_timing(BlockProductionStep::Storage, storage_time)
+        .await;
+    stats_counter.observe_block_production_step(BlockProductionStep::Storage, storage_time);
 
     watch_msg_box
         .send(WatchMsg::NewBlock(block.clone()))
         .await?;
 
+    let started = Instant::now();
     process_and_propagate_new_ref(Arc::clone(&new_block_ref), tip_update_mbox, network_msg_box)
         .await?;
+    let propagation_time = started.elapsed();
+    log.set_timing(BlockProductionStep::Propagation, propagation_time)
+        .await;
+    stats_counter.observe_block_production_step(BlockProductionStep::Propagation, propagation_time);
 
     Ok(())
 }
@@ -384,6 +411,7 @@ async fn process_leadership_block_inner(
     Ok(new_ref)
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn process_block_announcement(
     blockchain: Blockchain,
     blockchain_tip: Tip,
@@ -391,6 +419,8 @@ async fn process_block_announcement(
     node_id: NodeId,
     mut pull_headers_scheduler: PullHeadersScheduler,
     mut get_next_block_scheduler: GetNextBlockScheduler,
+    clock_skew: ClockSkewMonitor,
+    arrival: SystemTime,
 ) -> Result<(), Error> {
     let pre_checked = blockchain.pre_check_header(header, false).await?;
     match pre_checked {
@@ -412,10 +442,12 @@ async fn process_block_announcement(
                 });
             Ok(())
         }
-        PreCheckedHeader::HeaderWithCache {
-            header,
-            parent_ref: _,
-        } => {
+        PreCheckedHeader::HeaderWithCache { header, parent_ref } => {
+            // only the announcements of headers from an elected leader tell
+            // when the blocks are announced
+            if verify_leadership(&header, &parent_ref).await {
+                clock_skew.record_announcement(header.block_date(), arrival);
+            }
             tracing::debug!("Announced block has a locally stored parent, fetch it");
             get_next_block_scheduler
                 .schedule(header.id(), node_id, ())
@@ -430,6 +462,32 @@ async fn process_block_announcement(
     }
 }
 
+// checks the leadership of an announced header in the epoch of its parent.
+// The leadership of a new epoch is only evaluated with the block itself, so
+// the announcements crossing an epoch are not counted
+async fn verify_leadership(header: &Header, parent: &Arc<Ref>) -> bool {
+    if header.block_date().epoch != parent.block_date().epoch {
+        return false;
+    }
+
+    let header = header.clone();
+    let parent = Arc::clone(parent);
+    let verification =
+        tokio::task::spawn_blocking(move || parent.epoch_leadership_schedule().verify(&header))
+            .await;
+    match verification {
+        Ok(Verification::Success) => true,
+        Ok(Verification::Failure(error)) => {
+            tracing::debug!(%error, "announced header is not from an elected leader");
+            false
+        }
+        Err(error) => {
+            tracing::error!(%error, "cannot verify the leadership of the announced header");
+            false
+        }
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn process_network_blocks(
     blockchain: Blockchain,
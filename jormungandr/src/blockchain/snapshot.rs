@@ -0,0 +1,211 @@
+//! snapshots of the ledger states of a stable block
+//!
+//! a snapshot is taken of the last block of an epoch once it is deeper than
+//! the epoch stability depth. It holds the ledger state of this block and of
+//! the last blocks of the epochs before it, as linked by
+//! `Ref::last_ref_previous_epoch`, which are needed to rebuild the leadership
+//! of the following epochs. The snapshots are written to the
+//! `ledger_snapshots` directory of the storage directory, named after the
+//! chain length and hash of their block, and only the latest ones are kept.
+
+use crate::blockcfg::{ChainLength, HeaderHash, Ledger};
+use chain_core::{
+    packer::Codec,
+    property::{Deserialize, ReadError, Serialize, WriteError},
+};
+use std::{
+    fs,
+    io::{self, BufReader, BufWriter, Write as _},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+const SNAPSHOT_DIR: &str = "ledger_snapshots";
+
+const SNAPSHOT_EXTENSION: &str = "ledger";
+
+/// bumped whenever the layout of the file, or the serialization of the
+/// ledger, changes
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// the number of snapshots kept, the oldest are removed first
+const SNAPSHOTS_KEPT: usize = 2;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("cannot access the ledger snapshot")]
+    Io(#[from] io::Error),
+    #[error("cannot serialize the ledger snapshot")]
+    Serialize(#[source] WriteError),
+    #[error("cannot deserialize the ledger snapshot")]
+    Deserialize(#[source] ReadError),
+    #[error("unsupported ledger snapshot version {0}")]
+    UnsupportedVersion(u32),
+}
+
+#[derive(Clone)]
+pub struct LedgerSnapshots {
+    dir: PathBuf,
+}
+
+impl LedgerSnapshots {
+    pub fn new(storage: &Path) -> Self {
+        LedgerSnapshots {
+            dir: storage.join(SNAPSHOT_DIR),
+        }
+    }
+
+    fn path(&self, chain_length: ChainLength, hash: &HeaderHash) -> PathBuf {
+        self.dir.join(format!(
+            "{:010}-{}.{}",
+            u32::from(chain_length),
+            hash,
+            SNAPSHOT_EXTENSION
+        ))
+    }
+
+    pub fn contains(&self, chain_length: ChainLength, hash: &HeaderHash) -> bool {
+        self.path(chain_length, hash).exists()
+    }
+
+    /// the paths of the snapshots, the newest first
+    pub fn list(&self) -> io::Result<Vec<PathBuf>> {
+        let mut paths = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<io::Result<Vec<_>>>()?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        paths.retain(|path| path.extension() == Some(SNAPSHOT_EXTENSION.as_ref()));
+        // the file names start with the zero padded chain length
+        paths.sort();
+        paths.reverse();
+        Ok(paths)
+    }
+
+    /// write the snapshot of the given states, the first one being the state
+    /// of the block of the snapshot
+    pub fn write(
+        &self,
+        chain_length: ChainLength,
+        states: &[(HeaderHash, Arc<Ledger>)],
+    ) -> Result<(), Error> {
+        let (hash, _) = states.first().expect("a snapshot of no ledger state");
+        let path = self.path(chain_length, hash);
+        let tmp_path = path.with_extension("tmp");
+
+        fs::create_dir_all(&self.dir)?;
+        {
+            let mut codec = Codec::new(BufWriter::new(fs::File::create(&tmp_path)?));
+            codec
+                .put_be_u32(SNAPSHOT_VERSION)
+                .map_err(Error::Serialize)?;
+            codec
+                .put_be_u32(states.len() as u32)
+                .map_err(Error::Serialize)?;
+            for (hash, ledger) in states {
+                hash.serialize(&mut codec).map_err(Error::Serialize)?;
+                ledger.serialize(&mut codec).map_err(Error::Serialize)?;
+            }
+            let mut writer = codec.into_inner();
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+        }
+        fs::rename(tmp_path, path)?;
+
+        self.prune()
+    }
+
+    /// read the states of a snapshot, the first one being the state of the
+    /// block of the snapshot
+    pub fn read(path: &Path) -> Result<Vec<(HeaderHash, Ledger)>, Error> {
+        let mut codec = Codec::new(BufReader::new(fs::File::open(path)?));
+        let version = codec.get_be_u32().map_err(Error::Deserialize)?;
+        if version != SNAPSHOT_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        let count = codec.get_be_u32().map_err(Error::Deserialize)?;
+        (0..count)
+            .map(|_| {
+                let hash = HeaderHash::deserialize(&mut codec).map_err(Error::Deserialize)?;
+                let ledger = Ledger::deserialize(&mut codec).map_err(Error::Deserialize)?;
+                Ok((hash, ledger))
+            })
+            .collect()
+    }
+
+    fn prune(&self) -> Result<(), Error> {
+        for path in self.list()?.iter().skip(SNAPSHOTS_KEPT) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storage_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "jormungandr-ledger-snapshots-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn list_newest_first() {
+        let storage = storage_dir("list");
+        let snapshots = LedgerSnapshots::new(&storage);
+        assert!(snapshots.list().unwrap().is_empty());
+
+        let hash = HeaderHash::zero_hash();
+        fs::create_dir_all(&snapshots.dir).unwrap();
+        for chain_length in [9, 1200, 30] {
+            fs::write(snapshots.path(ChainLength::from(chain_length), &hash), []).unwrap();
+        }
+        fs::write(snapshots.dir.join("interrupted.tmp"), []).unwrap();
+
+        assert_eq!(
+            snapshots.list().unwrap(),
+            [1200, 30, 9]
+                .iter()
+                .map(|chain_length| snapshots.path(ChainLength::from(*chain_length), &hash))
+                .collect::<Vec<_>>()
+        );
+        assert!(snapshots.contains(ChainLength::from(30), &hash));
+
+        snapshots.prune().unwrap();
+        assert_eq!(snapshots.list().unwrap().len(), SNAPSHOTS_KEPT);
+        assert!(!snapshots.contains(ChainLength::from(9), &hash));
+
+        fs::remove_dir_all(storage).unwrap();
+    }
+
+    #[test]
+    fn reject_unknown_version() {
+        let storage = storage_dir("version");
+        let snapshots = LedgerSnapshots::new(&storage);
+        let path = snapshots.path(ChainLength::from(1), &HeaderHash::zero_hash());
+        fs::create_dir_all(&snapshots.dir).unwrap();
+
+        fs::write(&path, (SNAPSHOT_VERSION + 1).to_be_bytes()).unwrap();
+        assert!(matches!(
+            LedgerSnapshots::read(&path),
+            Err(Error::UnsupportedVersion(v)) if v == SNAPSHOT_VERSION + 1
+        ));
+
+        // torn while writing the number of states
+        fs::write(&path, SNAPSHOT_VERSION.to_be_bytes()).unwrap();
+        assert!(matches!(
+            LedgerSnapshots::read(&path),
+            Err(Error::Deserialize(_))
+        ));
+
+        fs::remove_dir_all(storage).unwrap();
+    }
+}
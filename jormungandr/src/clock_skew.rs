@@ -0,0 +1,421 @@
+//! detection of a skew of the local clock
+//!
+//! the slots are timed from the block0 date with the local clock, so a node
+//! whose clock is off misses its slots or produces blocks too late for the
+//! network. The skew is measured against the configured time sources,
+//! queried with SNTP, or else estimated from the arrival of the blocks
+//! announced by the peers: a block is not announced before the start of its
+//! slot, so its arrival relative to the slot start bounds the skew of the
+//! local clock from above. Only the announcements of valid headers count, and
+//! the earliest quarter of them is ignored as outliers.
+//!
+//! The bound given by the peers only proves a skew when it is negative, that
+//! is when the blocks arrive before the start of their slot by the local
+//! clock. A positive bound may as well be the propagation delay of the blocks,
+//! so it never stops the node from leading.
+
+use crate::{
+    blockcfg::BlockDate,
+    blockchain::{Ref, Tip},
+    metrics::{Metrics, MetricsBackend},
+};
+use chain_time::{
+    era::{EpochPosition, EpochSlotOffset},
+    Epoch,
+};
+use std::{
+    io,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use thiserror::Error;
+use tokio::net::UdpSocket;
+
+// the announcements kept between two checks
+const MAX_ANNOUNCEMENTS: usize = 1024;
+// the announcements needed to estimate the skew from the peers
+const MIN_ANNOUNCEMENTS: usize = 5;
+const TIME_SOURCE_TIMEOUT: Duration = Duration::from_secs(5);
+
+const NTP_PACKET_SIZE: usize = 48;
+// the seconds from the NTP era origin, 1900-01-01, to the Unix epoch
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+
+#[derive(Debug, Error)]
+pub enum TimeSourceError {
+    #[error("cannot resolve the address of the time source")]
+    Resolve(#[source] io::Error),
+    #[error("the address of the time source resolves to nothing")]
+    NoAddress,
+    #[error("cannot query the time source")]
+    Io(#[from] io::Error),
+    #[error("the time source did not answer in time")]
+    Timeout,
+    #[error("invalid answer from the time source")]
+    InvalidAnswer,
+}
+
+/// an estimate of the skew of the local clock in milliseconds, positive if
+/// the local clock is ahead
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SkewEstimate {
+    /// measured against the time sources
+    Measured(i64),
+    /// bounded from above by the arrival of the blocks announced by the peers
+    UpperBound(i64),
+}
+
+impl SkewEstimate {
+    fn millis(self) -> i64 {
+        match self {
+            SkewEstimate::Measured(skew) | SkewEstimate::UpperBound(skew) => skew,
+        }
+    }
+
+    // whether the skew is certainly beyond the tolerance
+    fn exceeds(self, tolerance: i64) -> bool {
+        match self {
+            SkewEstimate::Measured(skew) => skew.abs() > tolerance,
+            SkewEstimate::UpperBound(bound) => bound < -tolerance,
+        }
+    }
+}
+
+/// follows the skew of the local clock, the clones share the same state
+#[derive(Clone)]
+pub struct ClockSkewMonitor {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    max_skew_slot_ratio: f64,
+    time_sources: Vec<String>,
+    refuse_to_lead: bool,
+    // the dates of the blocks announced since the last check, with their
+    // local arrival time
+    announcements: Mutex<Vec<(BlockDate, SystemTime)>>,
+    // the skew in milliseconds found by the last check, if beyond tolerance
+    skewed: Mutex<Option<i64>>,
+}
+
+impl ClockSkewMonitor {
+    pub fn new(max_skew_slot_ratio: f64, time_sources: Vec<String>, refuse_to_lead: bool) -> Self {
+        ClockSkewMonitor {
+            inner: Arc::new(Inner {
+                max_skew_slot_ratio,
+                time_sources,
+                refuse_to_lead,
+                announcements: Mutex::new(Vec::new()),
+                skewed: Mutex::new(None),
+            }),
+        }
+    }
+
+    /// records the arrival time of the announcement of a block from a peer,
+    /// once its header is validated
+    pub fn record_announcement(&self, date: BlockDate, arrival: SystemTime) {
+        let mut announcements = self.inner.announcements.lock().unwrap();
+        if announcements.len() < MAX_ANNOUNCEMENTS {
+            announcements.push((date, arrival));
+        }
+    }
+
+    /// the skew in milliseconds of the local clock if the node is configured
+    /// not to lead while its clock is skewed and it currently is
+    pub fn refuse_to_lead(&self) -> Option<i64> {
+        if self.inner.refuse_to_lead {
+            *self.inner.skewed.lock().unwrap()
+        } else {
+            None
+        }
+    }
+
+    /// checks the skew of the local clock every `check_interval`, warns and
+    /// updates the metrics when it exceeds the tolerated fraction of the
+    /// slot duration
+    pub async fn run(self, tip: Tip, metrics: Metrics, check_interval: Duration) {
+        let mut interval = tokio::time::interval(check_interval);
+        loop {
+            interval.tick().await;
+            let tip = tip.get_ref().await;
+
+            let estimate = match self.time_sources_skew().await {
+                Some(skew) => Some(SkewEstimate::Measured(skew)),
+                None => self
+                    .peers_skew(|date| slot_start(&tip, date))
+                    .map(SkewEstimate::UpperBound),
+            };
+            if let Some(estimate) = estimate {
+                metrics.set_clock_skew_millis(estimate.millis());
+            }
+
+            let tolerance = Duration::from_secs(tip.time_frame().slot_duration())
+                .mul_f64(self.inner.max_skew_slot_ratio)
+                .as_millis() as i64;
+            self.check(estimate, tolerance);
+        }
+    }
+
+    // updates the skewed state with the estimate of the last check, the
+    // state is cleared when the check has no estimate
+    fn check(&self, estimate: Option<SkewEstimate>, tolerance: i64) {
+        let mut skewed = self.inner.skewed.lock().unwrap();
+        match estimate {
+            Some(estimate) if estimate.exceeds(tolerance) => {
+                tracing::warn!(
+                    skew_millis = estimate.millis(),
+                    tolerance_millis = tolerance,
+                    "the local clock is skewed, the node may miss its slots"
+                );
+                *skewed = Some(estimate.millis());
+            }
+            Some(SkewEstimate::UpperBound(bound)) if bound > tolerance => {
+                tracing::debug!(
+                    bound_millis = bound,
+                    tolerance_millis = tolerance,
+                    "the blocks of the peers arrive late, the local clock may be ahead"
+                );
+                if skewed.take().is_some() {
+                    tracing::info!("the local clock is no longer known to be skewed");
+                }
+            }
+            Some(estimate) => {
+                if skewed.take().is_some() {
+                    tracing::info!(
+                        skew_millis = estimate.millis(),
+                        "the local clock is back in time"
+                    );
+                }
+            }
+            None => {
+                if skewed.take().is_some() {
+                    tracing::info!("no estimate of the clock skew, the local clock is no longer known to be skewed");
+                }
+            }
+        }
+    }
+
+    // the median of the skews measured against the time sources which
+    // answered
+    async fn time_sources_skew(&self) -> Option<i64> {
+        let mut skews = Vec::new();
+        for source in &self.inner.time_sources {
+            match query_time_source(source, TIME_SOURCE_TIMEOUT).await {
+                Ok(skew) => skews.push(skew),
+                Err(e) => {
+                    tracing::warn!(%source, reason = %e, "cannot query the time source")
+                }
+            }
+        }
+        skews.sort_unstable();
+        skews.get(skews.len() / 2).copied()
+    }
+
+    // the first quartile of the arrivals of the announcements since the last
+    // check relative to the start of their slot, the earlier ones being
+    // outliers
+    fn peers_skew<F>(&self, slot_start: F) -> Option<i64>
+    where
+        F: Fn(BlockDate) -> Option<SystemTime>,
+    {
+        let announcements = std::mem::take(&mut *self.inner.announcements.lock().unwrap());
+        let mut arrivals: Vec<_> = announcements
+            .into_iter()
+            .filter_map(|(date, arrival)| Some(signed_millis(arrival, slot_start(date)?)))
+            .collect();
+        if arrivals.len() < MIN_ANNOUNCEMENTS {
+            return None;
+        }
+        arrivals.sort_unstable();
+        arrivals.get(arrivals.len() / 4).copied()
+    }
+}
+
+fn slot_start(tip: &Ref, date: BlockDate) -> Option<SystemTime> {
+    let slot = tip
+        .epoch_leadership_schedule()
+        .era()
+        .from_era_to_slot(EpochPosition {
+            epoch: Epoch(date.epoch),
+            slot: EpochSlotOffset(date.slot_id),
+        });
+    tip.time_frame().slot_to_systemtime(slot)
+}
+
+// `a - b` in milliseconds
+fn signed_millis(a: SystemTime, b: SystemTime) -> i64 {
+    match a.duration_since(b) {
+        Ok(d) => d.as_millis() as i64,
+        Err(e) => -(e.duration().as_millis() as i64),
+    }
+}
+
+/// queries the time source at `address` (`host:port`) with SNTP, returns the
+/// skew of the local clock in milliseconds, positive if the local clock is
+/// ahead of the time source
+pub async fn query_time_source(address: &str, timeout: Duration) -> Result<i64, TimeSourceError> {
+    let address = tokio::net::lookup_host(address)
+        .await
+        .map_err(TimeSourceError::Resolve)?
+        .next()
+        .ok_or(TimeSourceError::NoAddress)?;
+    let local: SocketAddr = if address.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(address).await?;
+
+    let mut request = [0u8; NTP_PACKET_SIZE];
+    // no leap indicator, version 4, client mode
+    request[0] = 0b00_100_011;
+    let sent = SystemTime::now();
+    request[40..48].copy_from_slice(&to_ntp_timestamp(sent).to_be_bytes());
+    socket.send(&request).await?;
+
+    let mut answer = [0u8; NTP_PACKET_SIZE];
+    let len = tokio::time::timeout(timeout, socket.recv(&mut answer))
+        .await
+        .map_err(|_| TimeSourceError::Timeout)??;
+    let received = SystemTime::now();
+    // a server mode answer echoing the transmit timestamp of the request
+    if len < NTP_PACKET_SIZE || answer[0] & 0b111 != 4 || answer[24..32] != request[40..48] {
+        return Err(TimeSourceError::InvalidAnswer);
+    }
+
+    let server_received = from_ntp_timestamp(&answer[32..40]);
+    let server_sent = from_ntp_timestamp(&answer[40..48]);
+    let offset = ((server_received - unix_nanos(sent)) + (server_sent - unix_nanos(received))) / 2;
+    Ok((-offset / 1_000_000) as i64)
+}
+
+fn unix_nanos(time: SystemTime) -> i128 {
+    match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(d) => d.as_nanos() as i128,
+        Err(e) => -(e.duration().as_nanos() as i128),
+    }
+}
+
+fn to_ntp_timestamp(time: SystemTime) -> u64 {
+    let since_epoch = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = since_epoch.as_secs() + NTP_UNIX_OFFSET;
+    let fraction = (u64::from(since_epoch.subsec_nanos()) << 32) / 1_000_000_000;
+    (seconds << 32) | fraction
+}
+
+// nanoseconds since the Unix epoch of a big endian NTP timestamp
+fn from_ntp_timestamp(bytes: &[u8]) -> i128 {
+    let mut timestamp = [0u8; 8];
+    timestamp.copy_from_slice(bytes);
+    let timestamp = u64::from_be_bytes(timestamp);
+    let seconds = i128::from(timestamp >> 32) - i128::from(NTP_UNIX_OFFSET);
+    let fraction = (i128::from(timestamp & 0xffff_ffff) * NANOS_PER_SEC) >> 32;
+    seconds * NANOS_PER_SEC + fraction
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn skew_against_a_local_time_source() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let address = server.local_addr().unwrap().to_string();
+
+        // the time source is five seconds ahead of the local clock
+        tokio::spawn(async move {
+            let mut request = [0u8; NTP_PACKET_SIZE];
+            let (_, client) = server.recv_from(&mut request).await.unwrap();
+            let now = to_ntp_timestamp(SystemTime::now() + Duration::from_secs(5)).to_be_bytes();
+            let mut answer = [0u8; NTP_PACKET_SIZE];
+            answer[0] = 0b00_100_100;
+            answer[24..32].copy_from_slice(&request[40..48]);
+            answer[32..40].copy_from_slice(&now);
+            answer[40..48].copy_from_slice(&now);
+            server.send_to(&answer, client).await.unwrap();
+        });
+
+        let skew = query_time_source(&address, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert!((-5_100..=-4_900).contains(&skew), "skew: {}", skew);
+    }
+
+    // slots of ten seconds from the Unix epoch
+    fn slot_start(date: BlockDate) -> Option<SystemTime> {
+        Some(SystemTime::UNIX_EPOCH + Duration::from_secs(u64::from(date.slot_id) * 10))
+    }
+
+    fn announce(monitor: &ClockSkewMonitor, slot_id: u32, delay_millis: i64) {
+        let start = slot_start(BlockDate { epoch: 0, slot_id }).unwrap();
+        let arrival = if delay_millis >= 0 {
+            start + Duration::from_millis(delay_millis as u64)
+        } else {
+            start - Duration::from_millis(-delay_millis as u64)
+        };
+        monitor.record_announcement(BlockDate { epoch: 0, slot_id }, arrival);
+    }
+
+    #[test]
+    fn peers_skew_ignores_the_earliest_announcements() {
+        let monitor = ClockSkewMonitor::new(0.5, Vec::new(), true);
+        // one block announced well before its slot, the others are delayed
+        // by their propagation
+        announce(&monitor, 1, -4_000);
+        for (slot_id, delay) in [(2, 300), (3, 250), (4, 800), (5, 400), (6, 350), (7, 500)] {
+            announce(&monitor, slot_id, delay);
+        }
+
+        assert_eq!(monitor.peers_skew(slot_start), Some(300));
+        // the announcements are consumed by the check
+        assert_eq!(monitor.peers_skew(slot_start), None);
+    }
+
+    #[test]
+    fn peers_skew_needs_enough_announcements() {
+        let monitor = ClockSkewMonitor::new(0.5, Vec::new(), true);
+        for slot_id in 0..MIN_ANNOUNCEMENTS as u32 - 1 {
+            announce(&monitor, slot_id, -6_000);
+        }
+        assert_eq!(monitor.peers_skew(slot_start), None);
+
+        for slot_id in 0..MIN_ANNOUNCEMENTS as u32 {
+            announce(&monitor, slot_id, -6_000);
+        }
+        assert_eq!(monitor.peers_skew(slot_start), Some(-6_000));
+    }
+
+    #[test]
+    fn only_a_negative_peer_bound_refuses_to_lead() {
+        let monitor = ClockSkewMonitor::new(0.5, Vec::new(), true);
+
+        // blocks arriving late may be the propagation delay
+        monitor.check(Some(SkewEstimate::UpperBound(8_000)), 5_000);
+        assert_eq!(monitor.refuse_to_lead(), None);
+
+        // blocks arriving before their slot prove the clock is behind
+        monitor.check(Some(SkewEstimate::UpperBound(-8_000)), 5_000);
+        assert_eq!(monitor.refuse_to_lead(), Some(-8_000));
+
+        monitor.check(Some(SkewEstimate::UpperBound(-1_000)), 5_000);
+        assert_eq!(monitor.refuse_to_lead(), None);
+
+        monitor.check(Some(SkewEstimate::Measured(8_000)), 5_000);
+        assert_eq!(monitor.refuse_to_lead(), Some(8_000));
+    }
+
+    #[test]
+    fn skewed_state_expires_without_estimate() {
+        let monitor = ClockSkewMonitor::new(0.5, Vec::new(), true);
+        monitor.check(Some(SkewEstimate::Measured(-8_000)), 5_000);
+        assert_eq!(monitor.refuse_to_lead(), Some(-8_000));
+
+        monitor.check(None, 5_000);
+        assert_eq!(monitor.refuse_to_lead(), None);
+    }
+}
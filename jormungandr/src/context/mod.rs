@@ -2,7 +2,7 @@ use crate::{
     blockchain::{Blockchain, Tip},
     diagnostic::Diagnostic,
     intercom::{NetworkMsg, TopologyMsg, TransactionMsg},
-    leadership::Logs as LeadershipLogs,
+    leadership::{Logs as LeadershipLogs, ScheduleLookAhead, SecretsReloader, SigningGuard},
     metrics::backends::SimpleCounter,
     network::GlobalStateR as NetworkStateR,
     secure::enclave::Enclave,
@@ -168,6 +168,9 @@ pub struct FullContext {
     pub transaction_task: MessageBox<TransactionMsg>,
     pub leadership_logs: LeadershipLogs,
     pub enclave: Enclave,
+    pub schedule_look_ahead: ScheduleLookAhead,
+    pub secrets_reloader: SecretsReloader,
+    pub signing_guard: SigningGuard,
     #[cfg(feature = "evm")]
     pub evm_keys: Arc<Vec<chain_evm::util::Secret>>,
     pub network_state: NetworkStateR,
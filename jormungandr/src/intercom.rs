@@ -15,6 +15,7 @@ use futures::{
 };
 use jormungandr_lib::interfaces::{
     BlockDate, FragmentLog, FragmentOrigin, FragmentStatus, FragmentsProcessingSummary,
+    RejectedConnections,
 };
 use poldercast::layer::Selection;
 use std::{
@@ -607,6 +608,7 @@ pub enum NetworkMsg {
         to: HeaderHash,
     },
     PeerInfo(ReplyHandle<Vec<PeerInfo>>),
+    RejectedConnections(ReplyHandle<RejectedConnections>),
 }
 
 /// Messages to the topology task
@@ -0,0 +1,184 @@
+//! storage of the blocks produced in dry-run mode
+//!
+//! the blocks are kept apart from the blockchain storage, so that they are
+//! never served to the peers nor taken for blocks of the chain. They are
+//! written to the `dry_run_blocks` directory of the storage directory, named
+//! after their chain length and hash, and only the latest ones are kept.
+//! Without storage directory the latest blocks are kept in memory.
+
+use crate::blockcfg::Block;
+use chain_core::property::Serialize as _;
+use std::{
+    collections::VecDeque,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+const DRY_RUN_DIR: &str = "dry_run_blocks";
+
+/// the number of dry-run blocks kept, the oldest are removed first
+const DRY_RUN_BLOCKS_KEPT: usize = 256;
+
+#[derive(Clone)]
+pub struct DryRunBlocks {
+    dir: Option<PathBuf>,
+    memory: Arc<Mutex<VecDeque<Block>>>,
+}
+
+impl DryRunBlocks {
+    pub fn new(storage: Option<&Path>) -> Self {
+        DryRunBlocks {
+            dir: storage.map(|dir| dir.join(DRY_RUN_DIR)),
+            memory: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    /// where the blocks are written, if they are not kept in memory
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    pub fn store(&self, block: &Block) -> io::Result<()> {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => {
+                let mut memory = self.memory.lock().unwrap();
+                memory.push_back(block.clone());
+                if memory.len() > DRY_RUN_BLOCKS_KEPT {
+                    memory.pop_front();
+                }
+                return Ok(());
+            }
+        };
+
+        let header = block.header();
+        let name = format!(
+            "{:010}-{}.block",
+            u32::from(header.chain_length()),
+            header.hash()
+        );
+        let bytes = block
+            .serialize_as_vec()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        fs::create_dir_all(dir)?;
+        let path = dir.join(&name);
+        let tmp_path = dir.join(format!("{}.tmp", name));
+        fs::write(&tmp_path, bytes)?;
+        fs::rename(tmp_path, path)?;
+
+        prune(dir)
+    }
+}
+
+// the file names start with the zero padded chain length, so they sort
+// from the oldest block
+fn prune(dir: &Path) -> io::Result<()> {
+    let mut names = fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .collect::<io::Result<Vec<_>>>()?;
+    names.retain(|name| Path::new(name).extension() == Some("block".as_ref()));
+    names.sort();
+
+    let excess = names.len().saturating_sub(DRY_RUN_BLOCKS_KEPT);
+    for name in &names[..excess] {
+        fs::remove_file(dir.join(name))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockcfg::{
+        block_builder, BlockDate, BlockVersion, ChainLength, Contents, HeaderHash,
+    };
+    use std::convert::Infallible;
+
+    fn block(chain_length: u32) -> Block {
+        block_builder(BlockVersion::Genesis, Contents::empty(), |hdr_builder| {
+            Ok::<_, Infallible>(
+                hdr_builder
+                    .set_parent(&HeaderHash::zero_hash(), ChainLength::from(chain_length))
+                    .set_date(BlockDate {
+                        epoch: 0,
+                        slot_id: chain_length,
+                    })
+                    .into_unsigned_header()
+                    .unwrap()
+                    .generalize(),
+            )
+        })
+        .unwrap()
+    }
+
+    fn storage_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "jormungandr-dry-run-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn stored_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn blocks_are_written_in_their_own_directory() {
+        let storage = storage_dir("write");
+        let dry_run_blocks = DryRunBlocks::new(Some(&storage));
+        let block = block(3);
+
+        dry_run_blocks.store(&block).unwrap();
+
+        let dir = storage.join(DRY_RUN_DIR);
+        assert_eq!(dry_run_blocks.dir(), Some(dir.as_path()));
+        assert_eq!(
+            stored_names(&dir),
+            vec![format!("0000000003-{}.block", block.header().hash())]
+        );
+        let bytes = fs::read(dir.join(&stored_names(&dir)[0])).unwrap();
+        assert_eq!(bytes, block.serialize_as_vec().unwrap());
+        fs::remove_dir_all(storage).unwrap();
+    }
+
+    #[test]
+    fn oldest_blocks_are_removed() {
+        let storage = storage_dir("prune");
+        let dry_run_blocks = DryRunBlocks::new(Some(&storage));
+        let kept = DRY_RUN_BLOCKS_KEPT as u32;
+
+        for chain_length in 0..kept + 2 {
+            dry_run_blocks.store(&block(chain_length)).unwrap();
+        }
+
+        let names = stored_names(&storage.join(DRY_RUN_DIR));
+        assert_eq!(names.len(), DRY_RUN_BLOCKS_KEPT);
+        assert!(names[0].starts_with("0000000002-"));
+        fs::remove_dir_all(storage).unwrap();
+    }
+
+    #[test]
+    fn blocks_are_kept_in_memory_without_storage() {
+        let dry_run_blocks = DryRunBlocks::new(None);
+        let kept = DRY_RUN_BLOCKS_KEPT as u32;
+
+        for chain_length in 0..kept + 1 {
+            dry_run_blocks.store(&block(chain_length)).unwrap();
+        }
+
+        assert_eq!(dry_run_blocks.dir(), None);
+        let memory = dry_run_blocks.memory.lock().unwrap();
+        assert_eq!(memory.len(), DRY_RUN_BLOCKS_KEPT);
+        assert_eq!(memory[0].header().hash(), block(1).header().hash());
+    }
+}
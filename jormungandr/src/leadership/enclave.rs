@@ -1,4 +1,9 @@
 pub use crate::secure::enclave::{LeaderEvent, Schedule};
+#[cfg(unix)]
+use crate::{
+    blockcfg::LeaderOutput,
+    secure::remote::{Error as RemoteError, RemoteSigner, VrfParameters},
+};
 use crate::{
     blockcfg::{
         HeaderBft, HeaderBftBuilder, HeaderGenesisPraos, HeaderGenesisPraosBuilder,
@@ -6,13 +11,20 @@ use crate::{
     },
     secure::enclave::Enclave as SecureEnclave,
 };
+#[cfg(unix)]
+use chain_impl_mockchain::leadership::{genesis, LeadershipConsensus};
+use chain_impl_mockchain::{certificate::PoolId, key::BftLeaderId};
+#[cfg(unix)]
+use std::str::FromStr;
 use std::sync::Arc;
 use thiserror::Error;
 
 #[derive(Debug, Clone, Error)]
 pub enum EnclaveError {
-    #[error("Enclave does not have a leader set")]
+    #[error("Enclave does not hold the keys of the elected leader")]
     EmptyEnclave,
+    #[error("Remote signer error: {0}")]
+    Remote(String),
 }
 
 /// represent the client side of an enclave. From there we will query the
@@ -20,19 +32,32 @@ pub enum EnclaveError {
 ///
 #[derive(Clone)]
 pub struct Enclave {
-    /// TODO: we will need to remove this to instead query data to an
-    /// external service (running in the secure enclave). For now we
-    /// hold on the `SecureEnclave` . But it will need to be separated
-    /// when we have the necessary crypto done.
-    inner: Arc<SecureEnclave>,
+    inner: Backend,
+}
+
+#[derive(Clone)]
+enum Backend {
+    /// the keys are held in the memory of the node
+    Local(Arc<SecureEnclave>),
+    /// the keys are held by a signer process reached over a Unix socket
+    #[cfg(unix)]
+    Remote(Arc<RemoteSigner>),
 }
 
 impl Enclave {
-    /// create a new enclave structure. This will need to change in the future
-    /// with a parameter to contact the secure enclave instead of the dummy type.
+    /// create a new enclave structure holding the keys in process.
     pub fn new(secure_enclave: SecureEnclave) -> Self {
         Enclave {
-            inner: Arc::new(secure_enclave),
+            inner: Backend::Local(Arc::new(secure_enclave)),
+        }
+    }
+
+    /// create a new enclave structure delegating the leadership evaluation
+    /// and the signing of the blocks to a remote signer.
+    #[cfg(unix)]
+    pub fn remote(signer: RemoteSigner) -> Self {
+        Enclave {
+            inner: Backend::Remote(Arc::new(signer)),
         }
     }
 
@@ -47,12 +72,23 @@ impl Enclave {
         slot_start: u32,
         nb_slots: u32,
     ) -> Result<Schedule, EnclaveError> {
-        Ok(Schedule::new(
-            self.inner.clone(),
-            leadership,
-            slot_start,
-            nb_slots,
-        ))
+        match &self.inner {
+            Backend::Local(enclave) => Ok(Schedule::new(
+                enclave.clone(),
+                leadership,
+                slot_start,
+                nb_slots,
+            )),
+            #[cfg(unix)]
+            Backend::Remote(signer) => {
+                let signer = signer.clone();
+                tokio::task::spawn_blocking(move || {
+                    remote_schedule(&signer, leadership, slot_start, nb_slots)
+                })
+                .await
+                .map_err(|e| EnclaveError::Remote(e.to_string()))
+            }
+        }
     }
 
     /// ask the leader associated to the `LeaderEvent` to finalize the given
@@ -63,11 +99,20 @@ impl Enclave {
     pub fn query_header_bft_finalize(
         &self,
         block_builder: HeaderBftBuilder<HeaderSetConsensusSignature>,
+        leader_id: &BftLeaderId,
     ) -> Result<HeaderBft, EnclaveError> {
-        if let Some(block) = self.inner.create_header_bft(block_builder) {
-            Ok(block)
-        } else {
-            Err(EnclaveError::EmptyEnclave)
+        match &self.inner {
+            Backend::Local(enclave) => enclave
+                .create_header_bft(block_builder, leader_id)
+                .ok_or(EnclaveError::EmptyEnclave),
+            // the signer holds a single leader, the one it scheduled
+            #[cfg(unix)]
+            Backend::Remote(signer) => {
+                let data = block_builder.get_authenticated_data();
+                let signature = tokio::task::block_in_place(|| signer.sign_bft(data))
+                    .map_err(|e| EnclaveError::Remote(e.to_string()))?;
+                Ok(block_builder.set_signature(signature.into()))
+            }
         }
     }
 
@@ -79,11 +124,108 @@ impl Enclave {
     pub fn query_header_genesis_praos_finalize(
         &self,
         block_builder: HeaderGenesisPraosBuilder<HeaderSetConsensusSignature>,
+        node_id: &PoolId,
     ) -> Result<HeaderGenesisPraos, EnclaveError> {
-        if let Some(block) = self.inner.create_header_genesis_praos(block_builder) {
-            Ok(block)
-        } else {
-            Err(EnclaveError::EmptyEnclave)
+        match &self.inner {
+            Backend::Local(enclave) => enclave
+                .create_header_genesis_praos(block_builder, node_id)
+                .ok_or(EnclaveError::EmptyEnclave),
+            // the signer holds a single leader, the one it scheduled
+            #[cfg(unix)]
+            Backend::Remote(signer) => {
+                let data = block_builder.get_authenticated_data();
+                let signature = tokio::task::block_in_place(|| signer.sign_genesis_praos(data))
+                    .map_err(|e| EnclaveError::Remote(e.to_string()))?;
+                Ok(block_builder.set_signature(signature.into()))
+            }
         }
     }
 }
+
+#[cfg(unix)]
+fn remote_schedule(
+    signer: &RemoteSigner,
+    leadership: Arc<Leadership>,
+    slot_start: u32,
+    nb_slots: u32,
+) -> Schedule {
+    let slots = (slot_start..slot_start + nb_slots).collect();
+    let events = match leadership.consensus() {
+        LeadershipConsensus::Bft(data) => {
+            remote_bft_events(signer, &leadership, data.leaders(), slots)
+        }
+        LeadershipConsensus::GenesisPraos(data) => {
+            remote_genesis_praos_events(signer, &leadership, data, slots)
+        }
+    };
+    // an unreachable signer does not stop the leadership task, the schedule
+    // is queried again at the next epoch
+    let events = events.unwrap_or_else(|e| {
+        tracing::error!(
+            reason = %e,
+            "cannot query the leader schedule of epoch {} from the remote signer",
+            leadership.epoch()
+        );
+        Vec::new()
+    });
+    Schedule::from_events(leadership, events)
+}
+
+#[cfg(unix)]
+fn remote_bft_events(
+    signer: &RemoteSigner,
+    leadership: &Leadership,
+    leaders: &[BftLeaderId],
+    slots: Vec<u32>,
+) -> Result<Vec<LeaderEvent>, RemoteError> {
+    if leaders.is_empty() {
+        return Ok(Vec::new());
+    }
+    let encoded_leaders = leaders
+        .iter()
+        .map(|leader| hex::encode(leader.as_public_key()))
+        .collect();
+    let elected = signer.bft_schedule(encoded_leaders, slots)?;
+    Ok(elected
+        .into_iter()
+        .map(|slot| LeaderEvent {
+            date: leadership.date_at_slot(slot),
+            output: LeaderOutput::Bft(leaders[slot as usize % leaders.len()].clone()),
+        })
+        .collect())
+}
+
+#[cfg(unix)]
+fn remote_genesis_praos_events(
+    signer: &RemoteSigner,
+    leadership: &Leadership,
+    data: &genesis::LeadershipData,
+    slots: Vec<u32>,
+) -> Result<Vec<LeaderEvent>, RemoteError> {
+    let node_id = match signer.identity()? {
+        (_, Some(node_id)) => {
+            PoolId::from_str(&node_id).map_err(|_| RemoteError::UnexpectedResponse)?
+        }
+        (_, None) => return Ok(Vec::new()),
+    };
+    // a pool without stake in the distribution of the epoch is never elected
+    let distribution = data.distribution();
+    let stake = match distribution.to_pools.get(&node_id) {
+        Some(pool) => pool.stake.total,
+        None => return Ok(Vec::new()),
+    };
+    let parameters = VrfParameters {
+        nonce: data.nonce().clone(),
+        active_slots_coeff: data.active_slots_coeff(),
+        stake,
+        total_stake: distribution.total_stake(),
+    };
+    let elected = signer.vrf_evaluate(&parameters, slots)?;
+    Ok(elected
+        .into_iter()
+        .map(|(slot, witness)| LeaderEvent {
+            date: leadership.date_at_slot(slot),
+            output: LeaderOutput::GenesisPraos(node_id.clone(), witness),
+        })
+        .collect())
+}
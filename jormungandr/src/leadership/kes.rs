@@ -0,0 +1,176 @@
+//! expiry of the KES keys of the Genesis Praos leaders
+//!
+//! a `SumEd25519_12` key signs blocks over a fixed number of KES periods,
+//! the periods lasting `kes_update_speed` seconds from the block0 date. The
+//! key is in use from the `kes_start_period` of the node secret file. The
+//! node reports the periods remaining to its keys and warns ahead of their
+//! expiry, so that a successor key can be prepared and swapped in.
+//!
+//! The KES update speed is the one of block0: the ledger does not apply the
+//! protocol updates of this parameter. The key of a remote signer is the one
+//! the signer reports when the node starts.
+
+use crate::metrics::{Metrics, MetricsBackend};
+use arc_swap::ArcSwap;
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+/// the number of periods a `SumEd25519_12` key signs over
+pub const KES_PERIODS: u32 = 1 << 12;
+
+// the keys are checked at least this often, the KES update speed may be
+// in the order of days
+const KES_CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// the KES key of a Genesis Praos leader hosted by the node
+#[derive(Debug, Clone)]
+pub struct KesKey {
+    pub pool_id: String,
+    pub start_period: u32,
+}
+
+impl KesKey {
+    /// the number of periods the key can still sign over at the given
+    /// period, the current one included
+    pub fn remaining_periods(&self, period: u32) -> u32 {
+        self.start_period
+            .saturating_add(KES_PERIODS)
+            .saturating_sub(period)
+    }
+}
+
+/// follows the KES keys held by the node, the clones share the same keys
+/// so that they are replaced on a reload of the leader keys
+#[derive(Clone)]
+pub struct KesMonitor {
+    keys: Arc<ArcSwap<Vec<KesKey>>>,
+    block0_time: SystemTime,
+    kes_update_speed: Duration,
+}
+
+impl KesMonitor {
+    pub fn new(keys: Vec<KesKey>, block0_time: SystemTime, kes_update_speed: Duration) -> Self {
+        KesMonitor {
+            keys: Arc::new(ArcSwap::from_pointee(keys)),
+            block0_time,
+            kes_update_speed,
+        }
+    }
+
+    pub fn set_keys(&self, keys: Vec<KesKey>) {
+        self.keys.store(Arc::new(keys));
+    }
+
+    /// the KES period at the given time
+    pub fn period_at(&self, time: SystemTime) -> u32 {
+        let elapsed = time
+            .duration_since(self.block0_time)
+            .unwrap_or_default()
+            .as_secs();
+        (elapsed / self.kes_update_speed.as_secs().max(1))
+            .try_into()
+            .unwrap_or(u32::MAX)
+    }
+
+    /// the time the key stops signing blocks
+    pub fn expiry_time(&self, key: &KesKey) -> Option<SystemTime> {
+        let periods = key.start_period.checked_add(KES_PERIODS)?;
+        self.block0_time
+            .checked_add(self.kes_update_speed.checked_mul(periods)?)
+    }
+
+    /// the fewest periods remaining to the keys at the given time, none if
+    /// the node holds no Genesis Praos key
+    pub fn remaining_periods(&self, time: SystemTime) -> Option<u32> {
+        let period = self.period_at(time);
+        self.keys
+            .load()
+            .iter()
+            .map(|key| key.remaining_periods(period))
+            .min()
+    }
+
+    /// updates the metrics with the remaining periods of the keys and
+    /// warns about the keys expiring within `warning`
+    pub async fn run(self, metrics: Metrics, warning: Duration) {
+        let mut interval = tokio::time::interval(self.kes_update_speed.min(KES_CHECK_INTERVAL));
+        loop {
+            interval.tick().await;
+            let now = SystemTime::now();
+            let period = self.period_at(now);
+
+            for key in self.keys.load().iter() {
+                let remaining_periods = key.remaining_periods(period);
+                let expiry_time = match self.expiry_time(key) {
+                    Some(expiry_time) => expiry_time,
+                    None => continue,
+                };
+                if remaining_periods == 0 {
+                    tracing::error!(
+                        pool_id = %key.pool_id,
+                        "the KES key has expired, a successor key needs to be registered \
+                        for the stake pool"
+                    );
+                } else if expiry_time.duration_since(now).unwrap_or_default() <= warning {
+                    tracing::warn!(
+                        pool_id = %key.pool_id,
+                        remaining_periods,
+                        expiry_time = %jormungandr_lib::time::SystemTime::from(expiry_time),
+                        "the KES key expires soon, a successor key needs to be registered \
+                        for the stake pool"
+                    );
+                }
+            }
+
+            if let Some(remaining_periods) = self.remaining_periods(now) {
+                metrics.set_kes_remaining_periods(remaining_periods);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remaining_periods_from_the_start_period() {
+        let block0_time = SystemTime::UNIX_EPOCH;
+        let monitor = KesMonitor::new(
+            vec![
+                KesKey {
+                    pool_id: "a".to_owned(),
+                    start_period: 0,
+                },
+                KesKey {
+                    pool_id: "b".to_owned(),
+                    start_period: 100,
+                },
+            ],
+            block0_time,
+            Duration::from_secs(60),
+        );
+
+        let time = block0_time + Duration::from_secs(60 * 10 + 30);
+        assert_eq!(monitor.period_at(time), 10);
+        assert_eq!(monitor.remaining_periods(time), Some(KES_PERIODS - 10));
+
+        let time = block0_time + Duration::from_secs(60 * u64::from(KES_PERIODS));
+        assert_eq!(monitor.remaining_periods(time), Some(0));
+
+        monitor.set_keys(vec![KesKey {
+            pool_id: "b".to_owned(),
+            start_period: 100,
+        }]);
+        assert_eq!(monitor.remaining_periods(time), Some(100));
+        assert_eq!(
+            monitor.expiry_time(&monitor.keys.load()[0]),
+            Some(block0_time + Duration::from_secs(60 * u64::from(KES_PERIODS + 100)))
+        );
+
+        monitor.set_keys(Vec::new());
+        assert_eq!(monitor.remaining_periods(time), None);
+    }
+}
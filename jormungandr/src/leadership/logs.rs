@@ -1,6 +1,7 @@
 pub use jormungandr_lib::interfaces::LeadershipLogStatus;
-use jormungandr_lib::interfaces::{LeadershipLog, LeadershipLogId};
+use jormungandr_lib::interfaces::{BlockProductionStep, LeadershipLog, LeadershipLogId};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
 /// all leadership logs, allow for following up on the different entity
@@ -49,6 +50,29 @@ impl LeadershipLogHandle {
     pub async fn mark_finished(&self) {
         self.logs.mark_finished(self.internal_id).await
     }
+
+    /// record the time taken by a step of the block production.
+    pub async fn set_timing(&self, step: BlockProductionStep, duration: Duration) {
+        self.logs
+            .update(self.internal_id, |log| {
+                log.set_timing(step, duration.into())
+            })
+            .await
+    }
+
+    /// record that the fragment selection ran past the end of the slot.
+    pub async fn mark_soft_deadline_hit(&self) {
+        self.logs
+            .update(self.internal_id, LeadershipLog::mark_soft_deadline_hit)
+            .await
+    }
+
+    /// record that the fragment selection ran past the hard deadline.
+    pub async fn mark_hard_deadline_hit(&self) {
+        self.logs
+            .update(self.internal_id, LeadershipLog::mark_hard_deadline_hit)
+            .await
+    }
 }
 
 impl Logs {
@@ -68,12 +92,22 @@ impl Logs {
         })
     }
 
+    /// insert the log unless a log is already recorded at the same date,
+    /// returns whether the log was inserted
+    pub async fn insert_if_vacant(&self, log: LeadershipLog) -> bool {
+        self.0.write().await.insert_if_vacant(log)
+    }
+
     async fn mark_wake(&self, leadership_log_id: LeadershipLogId) {
         let inner = self.0.clone();
         inner.write().await.mark_wake(&leadership_log_id);
     }
 
-    async fn set_status(&self, leadership_log_id: LeadershipLogId, status: LeadershipLogStatus) {
+    pub async fn set_status(
+        &self,
+        leadership_log_id: LeadershipLogId,
+        status: LeadershipLogStatus,
+    ) {
         let inner = self.0.clone();
         inner.write().await.set_status(&leadership_log_id, status);
     }
@@ -83,6 +117,14 @@ impl Logs {
         inner.write().await.mark_finished(&leadership_log_id);
     }
 
+    async fn update<F>(&self, leadership_log_id: LeadershipLogId, f: F)
+    where
+        F: FnOnce(&mut LeadershipLog),
+    {
+        let inner = self.0.clone();
+        inner.write().await.update(&leadership_log_id, f);
+    }
+
     pub async fn logs(&self) -> Vec<LeadershipLog> {
         let inner = self.0.clone();
         let guard = inner.read().await;
@@ -112,6 +154,15 @@ pub(super) mod internal {
             id
         }
 
+        pub fn insert_if_vacant(&mut self, log: LeadershipLog) -> bool {
+            let id = log.leadership_log_id();
+            if self.entries.contains(&id) {
+                return false;
+            }
+            self.entries.put(id, log);
+            true
+        }
+
         pub fn mark_wake(&mut self, leadership_log_id: &LeadershipLogId) {
             if let Some(ref mut log) = self.entries.get_mut(leadership_log_id) {
                 log.mark_wake();
@@ -134,6 +185,15 @@ pub(super) mod internal {
             }
         }
 
+        pub fn update<F>(&mut self, leadership_log_id: &LeadershipLogId, f: F)
+        where
+            F: FnOnce(&mut LeadershipLog),
+        {
+            if let Some(log) = self.entries.get_mut(leadership_log_id) {
+                f(log);
+            }
+        }
+
         pub fn logs(&self) -> impl Iterator<Item = &LeadershipLog> {
             self.entries.iter().map(|(_, v)| v)
         }
@@ -0,0 +1,147 @@
+//! evaluation of the leadership schedule ahead of time
+//!
+//! the schedule of the whole current epoch is evaluated for the leaders
+//! hosted by the node. The schedule of the next epoch is evaluated too once
+//! its stake distribution is fixed, that is once the tip reached the
+//! current epoch.
+//!
+//! the leadership of the epochs the tip hasn't reached yet is evaluated
+//! without distributing the rewards, and kept until the stake distribution
+//! it was evaluated from changes.
+
+use crate::{
+    blockcfg::{HeaderHash, Leadership},
+    blockchain::{next_epoch_leadership, Ref},
+    leadership::enclave::{Enclave, EnclaveError},
+};
+use chain_impl_mockchain::chaintypes::ConsensusVersion;
+use chain_time::{
+    era::{EpochPosition, EpochSlotOffset},
+    Epoch,
+};
+use jormungandr_lib::{interfaces::LeadershipScheduleEntry, time::SystemTime};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum LookAheadError {
+    #[error("the blockchain timeline hasn't started yet")]
+    TooEarlyForTimeFrame,
+    #[error("cannot query the enclave for the leader schedules")]
+    Enclave(#[from] EnclaveError),
+    #[error("the schedule evaluation task failed")]
+    Join(#[from] tokio::task::JoinError),
+}
+
+#[derive(Clone)]
+pub struct ScheduleLookAhead {
+    enclave: Enclave,
+    ahead: Arc<Mutex<Option<LeadershipAhead>>>,
+}
+
+/// the leadership of an epoch after the tip's one, along with the block
+/// it was evaluated from
+struct LeadershipAhead {
+    epoch: u32,
+    anchor: HeaderHash,
+    leadership: Arc<Leadership>,
+}
+
+impl ScheduleLookAhead {
+    pub fn new(enclave: Enclave) -> Self {
+        ScheduleLookAhead {
+            enclave,
+            ahead: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// the leadership of the given epoch, evaluated from the tip
+    async fn leadership(
+        &self,
+        epoch: u32,
+        tip: &Arc<Ref>,
+    ) -> Result<Arc<Leadership>, LookAheadError> {
+        if epoch == tip.block_date().epoch {
+            return Ok(Arc::clone(tip.epoch_leadership_schedule()));
+        }
+
+        // the Genesis Praos leadership only depends on the stake distribution
+        // at the end of the epoch preceding the tip's one, while the BFT leaders
+        // may change with any block but are cheap to evaluate
+        let anchor = match tip.ledger().consensus_version() {
+            ConsensusVersion::GenesisPraos => tip
+                .last_ref_previous_epoch()
+                .map_or_else(|| tip.hash(), |previous| previous.hash()),
+            ConsensusVersion::Bft => tip.hash(),
+        };
+        if let Some(ahead) = self.ahead.lock().unwrap().as_ref() {
+            if ahead.epoch == epoch && ahead.anchor == anchor {
+                return Ok(Arc::clone(&ahead.leadership));
+            }
+        }
+
+        let parent = Arc::clone(tip);
+        let leadership =
+            tokio::task::spawn_blocking(move || next_epoch_leadership(epoch, &parent)).await?;
+        *self.ahead.lock().unwrap() = Some(LeadershipAhead {
+            epoch,
+            anchor,
+            leadership: Arc::clone(&leadership),
+        });
+        Ok(leadership)
+    }
+
+    /// the slots of the current epoch, and of the next one if it is already
+    /// known, for which one of the leaders is elected, in slot order
+    pub async fn schedule(
+        &self,
+        tip: Arc<Ref>,
+    ) -> Result<Vec<LeadershipScheduleEntry>, LookAheadError> {
+        let time_frame = Arc::clone(tip.time_frame());
+        let era = tip.epoch_leadership_schedule().era().clone();
+        let current_epoch = time_frame
+            .slot_at(&std::time::SystemTime::now())
+            .and_then(|slot| era.from_slot_to_era(slot))
+            .ok_or(LookAheadError::TooEarlyForTimeFrame)?
+            .epoch;
+
+        let tip_epoch = tip.block_date().epoch;
+        let mut epochs = vec![current_epoch.0];
+        // the next epoch is elected from the stake distribution at the end
+        // of the epoch preceding the tip's one
+        if tip_epoch == current_epoch.0 {
+            epochs.push(current_epoch.0 + 1);
+        }
+
+        let mut entries = Vec::new();
+        for epoch in epochs {
+            let leadership = self.leadership(epoch, &tip).await?;
+            let nb_slots = leadership.era().slots_per_epoch();
+            let mut schedule = self
+                .enclave
+                .query_schedules(leadership, 0, nb_slots)
+                .await?;
+            let events = tokio::task::spawn_blocking(move || {
+                std::iter::from_fn(|| schedule.next_event()).collect::<Vec<_>>()
+            })
+            .await?;
+
+            entries.extend(events.into_iter().map(|event| {
+                let slot = era.from_era_to_slot(EpochPosition {
+                    epoch: Epoch(event.date.epoch),
+                    slot: EpochSlotOffset(event.date.slot_id),
+                });
+                let time: SystemTime = time_frame
+                    .slot_to_systemtime(slot)
+                    .expect("the slot is after the beginning of the time frame")
+                    .into();
+                LeadershipScheduleEntry {
+                    date: event.date.into(),
+                    time,
+                    pool_id: event.leader_id(),
+                }
+            }));
+        }
+        Ok(entries)
+    }
+}
@@ -57,12 +57,22 @@
 //! we don't duplicate the work everywhere.
 //!
 
+mod dry_run;
 mod enclave;
+mod kes;
 mod logs;
+mod look_ahead;
 mod process;
+mod reload;
+mod signing_guard;
 
 pub use self::{
+    dry_run::DryRunBlocks,
     enclave::{Enclave, EnclaveError, LeaderEvent},
+    kes::{KesKey, KesMonitor},
     logs::{LeadershipLogHandle, Logs},
+    look_ahead::{LookAheadError, ScheduleLookAhead},
     process::{Module, ModuleConfig},
+    reload::{LeadersReloaded, ReloadError, SecretsReloader},
+    signing_guard::{SigningGuard, SigningGuardError},
 };
@@ -1,27 +1,44 @@
 use crate::{
-    blockcfg::{block_builder, ApplyBlockLedger, BlockVersion, Contents, LeaderOutput, Leadership},
-    blockchain::{new_epoch_leadership_from, EpochLeadership, LeadershipBlock, Ref, Tip},
+    blockcfg::{
+        block_builder, ApplyBlockLedger, Block, BlockVersion, Contents, HeaderHash, LeaderOutput,
+        Leadership,
+    },
+    blockchain::{new_epoch_leadership_from, EpochLeadership, LeadershipBlock, Ref, Storage, Tip},
+    clock_skew::ClockSkewMonitor,
     intercom::{unary_reply, BlockMsg, Error as IntercomError, TransactionMsg},
     leadership::{
         enclave::{Enclave, EnclaveError, LeaderEvent, Schedule},
-        LeadershipLogHandle, Logs,
+        DryRunBlocks, LeadersReloaded, LeadershipLogHandle, Logs, SigningGuard,
     },
+    metrics::{Metrics, MetricsBackend},
     utils::{async_msg::MessageBox, task::TokioServiceInfo},
 };
 use chain_time::{
     era::{EpochPosition, EpochSlotOffset},
     Epoch, Slot,
 };
-use futures::{future::TryFutureExt, sink::SinkExt};
+use futures::{
+    future::{Future, TryFutureExt},
+    sink::SinkExt,
+};
 use jormungandr_lib::{
-    interfaces::{LeadershipLog, LeadershipLogStatus},
+    crypto::hash::Hash,
+    interfaces::{BlockDate, BlockProductionStep, LeadershipLog, LeadershipLogStatus},
     time::SystemTime,
 };
-use std::{cmp::Ordering, sync::Arc, time::Instant};
+use std::{
+    cmp::Ordering,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use thiserror::Error;
 use tracing::{span, Level, Span};
 use tracing_futures::Instrument;
 
+// how often the produced blocks are looked up on the main branch while
+// waiting for the next event
+const BLOCK_OUTCOMES_INTERVAL: Duration = Duration::from_secs(10);
+
 #[derive(Error, Debug)]
 pub enum LeadershipError {
     #[error("The blockchain Timeline hasn't started yet")]
@@ -58,29 +75,52 @@ struct Entry {
     log: LeadershipLogHandle,
 }
 
+/// what ended the wait for the next event
+#[derive(Debug, PartialEq, Eq)]
+enum Wakeup {
+    Deadline,
+    LeadersReloaded,
+}
+
 pub struct Module {
     schedule: Option<Schedule>,
+    // the time spent evaluating the schedule up to its next event
+    schedule_time: Duration,
     service_info: TokioServiceInfo,
     logs: Logs,
     tip_ref: Arc<Ref>,
     tip: Tip,
+    storage: Storage,
     pool: MessageBox<TransactionMsg>,
     enclave: Enclave,
+    leaders_reloaded: LeadersReloaded,
+    signing_guard: SigningGuard,
     block_message: MessageBox<BlockMsg>,
     rewards_report_all: bool,
     // the maximum number of slots we can allow the leader event to run for
     block_hard_deadline: u32,
+    // in dry-run mode, the blocks are stored apart instead of being sent to
+    // the blockchain module
+    dry_run: Option<DryRunBlocks>,
+    clock_skew: ClockSkewMonitor,
+    stats_counter: Metrics,
 }
 
 pub struct ModuleConfig {
     pub service_info: TokioServiceInfo,
     pub logs: Logs,
     pub tip: Tip,
+    pub storage: Storage,
     pub pool: MessageBox<TransactionMsg>,
     pub enclave: Enclave,
+    pub leaders_reloaded: LeadersReloaded,
+    pub signing_guard: SigningGuard,
     pub block_message: MessageBox<BlockMsg>,
     pub rewards_report_all: bool,
     pub block_hard_deadline: u32,
+    pub dry_run: Option<DryRunBlocks>,
+    pub clock_skew: ClockSkewMonitor,
+    pub stats_counter: Metrics,
 }
 
 impl Module {
@@ -89,15 +129,22 @@ impl Module {
 
         Ok(Self {
             schedule: None,
+            schedule_time: Duration::ZERO,
             service_info: config.service_info,
             logs: config.logs,
             tip_ref,
             tip: config.tip,
+            storage: config.storage,
             pool: config.pool,
             enclave: config.enclave,
+            leaders_reloaded: config.leaders_reloaded,
+            signing_guard: config.signing_guard,
             block_message: config.block_message,
             rewards_report_all: config.rewards_report_all,
             block_hard_deadline: config.block_hard_deadline,
+            dry_run: config.dry_run,
+            clock_skew: config.clock_skew,
+            stats_counter: config.stats_counter,
         })
     }
 
@@ -228,19 +275,60 @@ impl Module {
 
     async fn wait(mut self) -> Result<Self, LeadershipError> {
         let deadline = self.wait_peek_deadline().await?;
-        tokio::time::sleep_until(tokio::time::Instant::from_std(deadline)).await;
+        let (tip, storage, logs) = (self.tip.clone(), self.storage.clone(), self.logs.clone());
+        let wakeup = sleep_until(deadline, &mut self.leaders_reloaded, || {
+            let (tip, storage, logs) = (tip.clone(), storage.clone(), logs.clone());
+            async move {
+                let tip_ref = tip.get_ref().await;
+                update_block_outcomes(
+                    &logs,
+                    &storage,
+                    tip_ref.hash(),
+                    tip_ref.chain_length().into(),
+                    tip_ref.ledger().settings().epoch_stability_depth,
+                )
+                .await
+            }
+        })
+        .await;
+        if wakeup == Wakeup::LeadersReloaded {
+            self.on_leaders_reloaded().await?;
+        }
         let tip = self.tip.clone();
         self.tip_ref = tip.get_ref().await;
         Ok(self)
     }
 
+    // the schedule was computed for the previous keys, it is recomputed
+    // from the next slot on by the following action
+    async fn on_leaders_reloaded(&mut self) -> Result<(), LeadershipError> {
+        let leaders = self.leaders_reloaded.borrow().clone();
+        self.schedule = None;
+        self.schedule_time = Duration::ZERO;
+
+        let EpochPosition { epoch, slot } = self.current_slot_position()?;
+        let date = BlockDate::new(epoch.0, slot.0);
+        let mut log = LeadershipLog::new(date, self.slot_time(epoch, slot));
+        log.mark_wake();
+        log.set_status(LeadershipLogStatus::LeadersReloaded { leaders });
+        log.mark_finished();
+        // an event of this slot has precedence over the reload
+        self.logs.insert_if_vacant(log).await;
+        Ok(())
+    }
+
     async fn wait_peek_deadline(&mut self) -> Result<Instant, LeadershipError> {
-        match self
+        // the schedule is evaluated lazily, up to the next event
+        let started = Instant::now();
+        let next_event = self
             .schedule
             .as_mut()
             .expect("schedule must be available at this point")
             .peek()
-        {
+            .map(|event| event.date);
+        self.schedule_time += started.elapsed();
+
+        match next_event {
             None => {
                 // the schedule is empty we were in the _action_ mode, so that means
                 // there is no other schedule to have for the current epoch. Better
@@ -249,15 +337,15 @@ impl Module {
                 tracing::debug!("no item scheduled, waiting for next epoch");
                 self.epoch_instant(Epoch(self.schedule.as_ref().unwrap().epoch().0 + 1))
             }
-            Some(event) => {
+            Some(date) => {
                 let span = tracing::span!(
                     parent: self.service_info.span(),
                     Level::TRACE, "leader_event",
-                    event_date = %event.date.to_string(),
+                    event_date = %date.to_string(),
                 );
 
-                let epoch = Epoch(event.date.epoch);
-                let slot = EpochSlotOffset(event.date.slot_id);
+                let epoch = Epoch(date.epoch);
+                let slot = EpochSlotOffset(date.slot_id);
                 if let Some(instant) = self.slot_instant(epoch, slot) {
                     async move {
                         tracing::debug!("awaiting");
@@ -282,21 +370,45 @@ impl Module {
 
     async fn action(mut self) -> Result<Self, LeadershipError> {
         match self.schedule.as_mut() {
+            // the event was evaluated by the peek of the wait
             Some(schedule) => match schedule.next_event() {
-                Some(event) => self.action_entry(event).await,
+                Some(event) => {
+                    let schedule_time = std::mem::take(&mut self.schedule_time);
+                    self.action_entry(event, schedule_time).await
+                }
                 None => self.action_schedule().await,
             },
             None => self.action_schedule().await,
         }
     }
 
-    async fn action_entry(self, event: LeaderEvent) -> Result<Self, LeadershipError> {
+    // records the time taken by a step of the block production in the log
+    // of the slot and in the metrics
+    async fn record_timing(
+        &self,
+        log: &LeadershipLogHandle,
+        step: BlockProductionStep,
+        duration: Duration,
+    ) {
+        log.set_timing(step, duration).await;
+        self.stats_counter
+            .observe_block_production_step(step, duration);
+    }
+
+    async fn action_entry(
+        self,
+        event: LeaderEvent,
+        schedule_time: Duration,
+    ) -> Result<Self, LeadershipError> {
         let module = self;
 
         let epoch = Epoch(event.date.epoch);
         let slot = EpochSlotOffset(event.date.slot_id);
         let scheduled_at_time = module.slot_time(epoch, slot);
-        let log = LeadershipLog::new(event.date.into(), scheduled_at_time);
+        let mut log = LeadershipLog::new(event.date.into(), scheduled_at_time);
+        if let Some(pool_id) = event.leader_id() {
+            log.set_pool_id(pool_id);
+        }
 
         let entry = match module.logs.insert(log).await {
             Ok(log) => Entry { event, log },
@@ -305,6 +417,16 @@ impl Module {
 
         let end_log = entry.log.clone();
         entry.log.mark_wake().await;
+        let wake_delay = SystemTime::now()
+            .duration_since(scheduled_at_time)
+            .map(Duration::from)
+            .unwrap_or_default();
+        module
+            .record_timing(&entry.log, BlockProductionStep::WakeDelay, wake_delay)
+            .await;
+        module
+            .record_timing(&entry.log, BlockProductionStep::Schedule, schedule_time)
+            .await;
         let module = module.action_run_entry(entry).await?;
         end_log.mark_finished().await;
         Ok(module)
@@ -374,7 +496,6 @@ impl Module {
         event_end_hard: SystemTime,
     ) -> Result<Self, LeadershipError> {
         use futures::future::{select, Either};
-        use std::time::Duration;
 
         let now = SystemTime::now();
 
@@ -403,6 +524,7 @@ impl Module {
             event_remaining_time = %remaining_time.to_string()
         );
 
+        let deadline_log = entry.log.clone();
         async {
             let (soft_deadline_tx, soft_deadline_rx) = futures::channel::oneshot::channel();
             let soft_deadline_future =
@@ -419,11 +541,15 @@ impl Module {
                 Either::Left((result, _)) => result,
                 Either::Right((_, build_block_future)) => {
                     soft_deadline_tx.send(()).unwrap();
+                    deadline_log.mark_soft_deadline_hit().await;
+                    self.stats_counter.add_soft_deadline_hit_cnt(1);
                     match select(Box::pin(build_block_future), Box::pin(hard_deadline_future)).await
                     {
                         Either::Left((result, _)) => result,
                         Either::Right((_, build_block_future)) => {
                             hard_deadline_tx.send(()).unwrap();
+                            deadline_log.mark_hard_deadline_hit().await;
+                            self.stats_counter.add_hard_deadline_hit_cnt(1);
                             build_block_future.await
                         }
                     }
@@ -474,6 +600,38 @@ impl Module {
             return Ok(());
         };
 
+        if let Some(skew) = self.clock_skew.refuse_to_lead() {
+            tracing::warn!(
+                skew_millis = skew,
+                "not producing a block while the local clock is skewed"
+            );
+            event_logs
+                .set_status(LeadershipLogStatus::Rejected {
+                    reason: format!("The local clock is skewed by {}ms", skew),
+                })
+                .await;
+            return Ok(());
+        }
+
+        // the watermark is recorded before the block is signed so that
+        // the slot is never signed twice, even across restarts. The blocks
+        // of a dry run are never published, they do not move the watermarks
+        if let Some(leader_id) = event.leader_id().filter(|_| self.dry_run.is_none()) {
+            let authorized = tokio::task::block_in_place(|| {
+                self.signing_guard.authorize(&leader_id, event.date.into())
+            });
+            if let Err(e) = authorized {
+                tracing::warn!(reason = %e, "not signing a block for this slot");
+                event_logs
+                    .set_status(LeadershipLogStatus::Rejected {
+                        reason: e.to_string(),
+                    })
+                    .await;
+                return Ok(());
+            }
+        }
+
+        let started = Instant::now();
         let leadership = new_epoch_leadership_from(
             event.date.epoch,
             Arc::clone(&self.tip_ref),
@@ -484,11 +642,21 @@ impl Module {
         let ledger = ledger
             .begin_block(chain_length, event.date)
             .map_err(Box::new)?;
+        self.record_timing(&event_logs, BlockProductionStep::Ledger, started.elapsed())
+            .await;
 
+        let started = Instant::now();
         let (contents, ledger) =
             prepare_block(pool, ledger, soft_deadline_future, hard_deadline_future).await?;
+        self.record_timing(
+            &event_logs,
+            BlockProductionStep::FragmentSelection,
+            started.elapsed(),
+        )
+        .await;
 
         let event_logs_error = event_logs.clone();
+        let started = Instant::now();
         let signing = {
             let ver = match event.output {
                 LeaderOutput::None => BlockVersion::Genesis,
@@ -518,7 +686,7 @@ impl Module {
                             .set_consensus_data(&leader_id);
 
                         enclave
-                            .query_header_bft_finalize(final_builder)
+                            .query_header_bft_finalize(final_builder, &leader_id)
                             .map(|h| h.generalize())
                     });
 
@@ -544,7 +712,7 @@ impl Module {
                             .set_consensus_data(&node_id, &vrfproof.into());
 
                         enclave
-                            .query_header_genesis_praos_finalize(final_builder)
+                            .query_header_genesis_praos_finalize(final_builder, &node_id)
                             .map(|h| h.generalize())
                     });
 
@@ -566,26 +734,34 @@ impl Module {
         match signing {
             Ok(maybe_block) => {
                 if let Some(block) = maybe_block {
-                    let id = block.header().hash();
-                    let parent = block.header().block_parent_hash();
-                    let chain_length: u32 = block.header().chain_length().into();
-                    let ledger = ledger.finish(&block.header().get_consensus_eval_context());
-                    let leadership_block = LeadershipBlock {
-                        block,
-                        new_ledger: ledger,
-                        leadership,
+                    self.record_timing(
+                        &event_logs,
+                        BlockProductionStep::Signing,
+                        started.elapsed(),
+                    )
+                    .await;
+                    let log = event_logs.clone();
+                    let leadership_block = |block: Block| {
+                        let ledger = ledger.finish(&block.header().get_consensus_eval_context());
+                        LeadershipBlock {
+                            block,
+                            new_ledger: ledger,
+                            leadership,
+                            log,
+                        }
                     };
-                    sender
-                        .send(BlockMsg::LeadershipBlock(Box::new(leadership_block)))
-                        .map_err(|_send_error| LeadershipError::CannotSendLeadershipBlock)
-                        .await?;
-                    event_logs
-                        .set_status(LeadershipLogStatus::Block {
-                            block: id.into(),
-                            parent: parent.into(),
-                            chain_length,
-                        })
-                        .await;
+                    let dry_run_storage_time = hand_over_block(
+                        block,
+                        self.dry_run.as_ref(),
+                        &mut sender,
+                        &event_logs,
+                        leadership_block,
+                    )
+                    .await?;
+                    if let Some(duration) = dry_run_storage_time {
+                        self.record_timing(&event_logs, BlockProductionStep::Storage, duration)
+                            .await;
+                    }
                 };
                 Ok(())
             }
@@ -669,12 +845,16 @@ impl Module {
         slot_start: u32,
         nb_slots: u32,
     ) -> Result<Self, LeadershipError> {
+        // the schedule of a remote signer is evaluated by the query, it is
+        // counted in the schedule time of the first event
+        let started = Instant::now();
         self.schedule = Some(
             self.enclave
                 .query_schedules(leadership, slot_start, nb_slots)
                 .map_err(|e| LeadershipError::CannotScheduleWithEnclave { source: e })
                 .await?,
         );
+        self.schedule_time = started.elapsed();
 
         Ok(self)
     }
@@ -714,6 +894,381 @@ async fn prepare_block(
     }
 }
 
+// hands the signed block over to the blockchain module, which applies,
+// stores and propagates it. In dry-run mode the block is stored apart
+// instead, and the time taken by its storage is returned.
+async fn hand_over_block<F>(
+    block: Block,
+    dry_run: Option<&DryRunBlocks>,
+    block_message: &mut MessageBox<BlockMsg>,
+    log: &LeadershipLogHandle,
+    leadership_block: F,
+) -> Result<Option<Duration>, LeadershipError>
+where
+    F: FnOnce(Block) -> LeadershipBlock,
+{
+    let id = block.header().hash();
+    let parent = block.header().block_parent_hash();
+    let chain_length: u32 = block.header().chain_length().into();
+
+    let dry_run_blocks = match dry_run {
+        Some(dry_run_blocks) => dry_run_blocks,
+        None => {
+            block_message
+                .send(BlockMsg::LeadershipBlock(Box::new(leadership_block(block))))
+                .map_err(|_send_error| LeadershipError::CannotSendLeadershipBlock)
+                .await?;
+            log.set_status(LeadershipLogStatus::Block {
+                block: id.into(),
+                parent: parent.into(),
+                chain_length,
+            })
+            .await;
+            return Ok(None);
+        }
+    };
+
+    let started = Instant::now();
+    if let Err(e) = tokio::task::block_in_place(|| dry_run_blocks.store(&block)) {
+        tracing::error!(reason = %e, "cannot store the dry-run block");
+        log.set_status(LeadershipLogStatus::Rejected {
+            reason: format!("Cannot store the dry-run block: {}", e),
+        })
+        .await;
+        return Ok(None);
+    }
+    let storage_time = started.elapsed();
+
+    tracing::info!(hash = %id, "dry-run block stored");
+    log.set_status(LeadershipLogStatus::DryRun {
+        block: id.into(),
+        parent: parent.into(),
+        chain_length,
+    })
+    .await;
+    Ok(Some(storage_time))
+}
+
+// sleeps until the deadline or until the leaders are reloaded, following the
+// outcomes of the produced blocks every `BLOCK_OUTCOMES_INTERVAL` meanwhile
+async fn sleep_until<F, Fut>(
+    deadline: Instant,
+    leaders_reloaded: &mut LeadersReloaded,
+    mut follow_block_outcomes: F,
+) -> Wakeup
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = ()>,
+{
+    let sleep = tokio::time::sleep_until(tokio::time::Instant::from_std(deadline));
+    tokio::pin!(sleep);
+    let mut block_outcomes = tokio::time::interval(BLOCK_OUTCOMES_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = &mut sleep => return Wakeup::Deadline,
+            // the branch is disabled if the reloader is gone
+            Ok(()) = leaders_reloaded.changed() => return Wakeup::LeadersReloaded,
+            _ = block_outcomes.tick() => follow_block_outcomes().await,
+        }
+    }
+}
+
+// the outcome of a produced block is followed until it is deeper than
+// the epoch stability depth, the main branch cannot be rolled back
+// past it anymore.
+//
+// A block on the main branch at the same chain length and with the same
+// date as the produced block won the slot battle against it, any other
+// block at this chain length means the produced block ended on a fork.
+async fn update_block_outcomes(
+    logs: &Logs,
+    storage: &Storage,
+    tip: HeaderHash,
+    tip_length: u32,
+    stability_depth: u32,
+) {
+    for log in logs.logs().await {
+        let (block, parent, chain_length) = match log.status().block() {
+            Some((block, parent, chain_length)) => (*block, *parent, chain_length),
+            None => continue,
+        };
+        if chain_length > tip_length || tip_length - chain_length > stability_depth {
+            continue;
+        }
+
+        let main = match storage.get_nth_ancestor(tip, tip_length - chain_length) {
+            Ok(Some(main)) => main,
+            Ok(None) => continue,
+            Err(e) => {
+                tracing::warn!(reason = %e, "cannot look up the main branch");
+                return;
+            }
+        };
+        let main_id: Hash = main.header().hash().into();
+        let main_date: BlockDate = main.header().block_date().into();
+
+        let outcome = if main_id == block {
+            LeadershipLogStatus::Adopted {
+                block,
+                parent,
+                chain_length,
+            }
+        } else if &main_date == log.scheduled_at_date() {
+            LeadershipLogStatus::OrphanedBySlotBattle {
+                block,
+                parent,
+                chain_length,
+                adopted: main_id,
+            }
+        } else {
+            LeadershipLogStatus::OrphanedByFork {
+                block,
+                parent,
+                chain_length,
+            }
+        };
+        if &outcome != log.status() {
+            logs.set_status(log.leadership_log_id(), outcome).await;
+        }
+    }
+}
+
 fn too_late(now: SystemTime, event_end: SystemTime) -> bool {
     event_end <= now
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockcfg::{BlockDate as ChainBlockDate, ChainLength, Header};
+    use crate::utils::async_msg;
+    use chain_crypto::Blake2b256;
+    use futures::stream::StreamExt;
+    use std::convert::Infallible;
+    use tokio::sync::watch;
+
+    fn block(parent: &HeaderHash, chain_length: u32) -> Block {
+        block_builder(BlockVersion::Genesis, Contents::empty(), |hdr_builder| {
+            Ok::<_, Infallible>(
+                hdr_builder
+                    .set_parent(parent, ChainLength::from(chain_length))
+                    .set_date(ChainBlockDate {
+                        epoch: 0,
+                        slot_id: chain_length,
+                    })
+                    .into_unsigned_header()
+                    .unwrap()
+                    .generalize(),
+            )
+        })
+        .unwrap()
+    }
+
+    /// stores a main branch of the given length, with one block per slot
+    fn main_branch(storage: &Storage, length: u32) -> Vec<Header> {
+        let mut parent = HeaderHash::zero_hash();
+        (0..=length)
+            .map(|chain_length| {
+                let block = block(&parent, chain_length);
+                storage.put_block(&block).unwrap();
+                parent = block.header().hash();
+                block.header().clone()
+            })
+            .collect()
+    }
+
+    async fn produced(logs: &Logs, slot_id: u32, block: Hash, parent: Hash, chain_length: u32) {
+        let log = logs
+            .insert(LeadershipLog::new(
+                BlockDate::new(0, slot_id),
+                SystemTime::now(),
+            ))
+            .await
+            .unwrap();
+        log.set_status(LeadershipLogStatus::Block {
+            block,
+            parent,
+            chain_length,
+        })
+        .await;
+    }
+
+    async fn status_at(logs: &Logs, slot_id: u32) -> LeadershipLogStatus {
+        logs.logs()
+            .await
+            .into_iter()
+            .find(|log| log.scheduled_at_date() == &BlockDate::new(0, slot_id))
+            .unwrap()
+            .status()
+            .clone()
+    }
+
+    #[tokio::test]
+    async fn block_outcomes_follow_the_main_branch() {
+        let storage = Storage::memory(Span::none()).unwrap();
+        let main = main_branch(&storage, 4);
+        let id = |n: usize| -> Hash { main[n].hash().into() };
+        let lost = Hash::from(Blake2b256::new(b"lost block"));
+        let logs = Logs::new(10);
+
+        // the block of the main branch for slot 2
+        produced(&logs, 2, id(2), id(1), 2).await;
+        // another block than the main branch one for slot 3
+        produced(&logs, 3, lost, id(2), 3).await;
+        // a block for slot 7 at the chain length of the slot 1 block
+        produced(&logs, 7, lost, id(0), 1).await;
+        // a block past the tip
+        produced(&logs, 8, lost, id(4), 5).await;
+
+        update_block_outcomes(&logs, &storage, main[4].hash(), 4, 10).await;
+
+        assert_eq!(
+            status_at(&logs, 2).await,
+            LeadershipLogStatus::Adopted {
+                block: id(2),
+                parent: id(1),
+                chain_length: 2,
+            }
+        );
+        assert_eq!(
+            status_at(&logs, 3).await,
+            LeadershipLogStatus::OrphanedBySlotBattle {
+                block: lost,
+                parent: id(2),
+                chain_length: 3,
+                adopted: id(3),
+            }
+        );
+        assert_eq!(
+            status_at(&logs, 7).await,
+            LeadershipLogStatus::OrphanedByFork {
+                block: lost,
+                parent: id(0),
+                chain_length: 1,
+            }
+        );
+        assert_eq!(
+            status_at(&logs, 8).await,
+            LeadershipLogStatus::Block {
+                block: lost,
+                parent: id(4),
+                chain_length: 5,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn stable_block_outcomes_are_not_followed() {
+        let storage = Storage::memory(Span::none()).unwrap();
+        let main = main_branch(&storage, 4);
+        let id = |n: usize| -> Hash { main[n].hash().into() };
+        let logs = Logs::new(10);
+        produced(&logs, 1, id(1), id(0), 1).await;
+        produced(&logs, 3, id(3), id(2), 3).await;
+
+        update_block_outcomes(&logs, &storage, main[4].hash(), 4, 2).await;
+
+        // the slot 1 block is deeper than the stability depth
+        assert_eq!(
+            status_at(&logs, 1).await,
+            LeadershipLogStatus::Block {
+                block: id(1),
+                parent: id(0),
+                chain_length: 1,
+            }
+        );
+        assert!(matches!(
+            status_at(&logs, 3).await,
+            LeadershipLogStatus::Adopted { .. }
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn dry_run_block_stays_out_of_the_blockchain() {
+        let storage = Storage::memory(Span::none()).unwrap();
+        let main = main_branch(&storage, 1);
+        let dry_run_blocks = DryRunBlocks::new(None);
+        let (mut block_message, block_queue) = async_msg::channel(1);
+        let logs = Logs::new(10);
+        let log = logs
+            .insert(LeadershipLog::new(BlockDate::new(0, 2), SystemTime::now()))
+            .await
+            .unwrap();
+        let block = block(&main[1].hash(), 2);
+        let hash = block.header().hash();
+
+        let storage_time = hand_over_block(
+            block,
+            Some(&dry_run_blocks),
+            &mut block_message,
+            &log,
+            |_| unreachable!("a dry-run block is not handed to the blockchain"),
+        )
+        .await
+        .unwrap();
+
+        assert!(storage_time.is_some());
+        assert_eq!(
+            status_at(&logs, 2).await,
+            LeadershipLogStatus::DryRun {
+                block: hash.into(),
+                parent: main[1].hash().into(),
+                chain_length: 2,
+            }
+        );
+        // nothing was sent to the blockchain module to be propagated
+        drop(block_message);
+        assert!(block_queue.collect::<Vec<_>>().await.is_empty());
+        // the main storage only has the main branch
+        assert!(!storage.block_exists(hash).unwrap());
+        assert_eq!(storage.get_branches().unwrap(), vec![main[1].hash()]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn block_outcomes_are_followed_until_the_deadline() {
+        let (_reload, mut leaders_reloaded) = watch::channel(Vec::new());
+        let deadline = tokio::time::Instant::now().into_std() + Duration::from_secs(35);
+        let mut follows = 0;
+
+        let wakeup = sleep_until(deadline, &mut leaders_reloaded, || {
+            follows += 1;
+            async {}
+        })
+        .await;
+
+        assert_eq!(wakeup, Wakeup::Deadline);
+        // at 0, 10, 20 and 30 seconds
+        assert_eq!(follows, 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reloading_the_leaders_ends_the_wait() {
+        let (reload, mut leaders_reloaded) = watch::channel(Vec::new());
+        let deadline = tokio::time::Instant::now().into_std() + Duration::from_secs(35);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_secs(15)).await;
+            reload.send(vec!["leader".to_string()]).unwrap();
+        });
+        let mut follows = 0;
+
+        let wakeup = sleep_until(deadline, &mut leaders_reloaded, || {
+            follows += 1;
+            async {}
+        })
+        .await;
+
+        assert_eq!(wakeup, Wakeup::LeadersReloaded);
+        assert_eq!(follows, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_goes_on_without_the_reloader() {
+        let (reload, mut leaders_reloaded) = watch::channel(Vec::new());
+        drop(reload);
+        let deadline = tokio::time::Instant::now().into_std() + Duration::from_secs(5);
+
+        let wakeup = sleep_until(deadline, &mut leaders_reloaded, || async {}).await;
+
+        assert_eq!(wakeup, Wakeup::Deadline);
+    }
+}
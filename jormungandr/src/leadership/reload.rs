@@ -0,0 +1,287 @@
+//! reloading of the leader keys while the node is running
+//!
+//! the node's secret files are read again and their leader keys replace the
+//! ones held by the enclave and the KES keys followed for expiry. The
+//! leadership module is notified of the new leaders so it can recompute the
+//! remaining schedule of the epoch with the new keys.
+
+use crate::{
+    blockcfg::Leader,
+    leadership::KesMonitor,
+    secure::{
+        enclave::{leader_ids, Enclave},
+        NodeSecretFromFileError, SecretFiles,
+    },
+};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use tokio::sync::watch;
+
+#[derive(Debug, Error)]
+pub enum ReloadError {
+    #[error("the node was started without a secret file")]
+    NoSecretFile,
+    #[error("the leader keys are held by the remote signer, they are reloaded by restarting it")]
+    RemoteSigner,
+    #[error("cannot reload the node's secret files")]
+    Load(#[from] NodeSecretFromFileError),
+    #[error("the secret file reloading task failed")]
+    Join(#[from] tokio::task::JoinError),
+}
+
+/// notified with the public identities of the leaders each time the
+/// leader keys are reloaded
+pub type LeadersReloaded = watch::Receiver<Vec<String>>;
+
+#[derive(Clone)]
+pub struct SecretsReloader {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    secret_files: Mutex<SecretFiles>,
+    remote_signer: bool,
+    enclave: Enclave,
+    kes_monitor: KesMonitor,
+    notifier: watch::Sender<Vec<String>>,
+}
+
+impl SecretsReloader {
+    pub fn new(
+        secret_files: SecretFiles,
+        remote_signer: bool,
+        enclave: Enclave,
+        kes_monitor: KesMonitor,
+    ) -> (Self, LeadersReloaded) {
+        let (notifier, receiver) = watch::channel(leaders_identity(&enclave.leaders()));
+        let reloader = SecretsReloader {
+            inner: Arc::new(Inner {
+                secret_files: Mutex::new(secret_files),
+                remote_signer,
+                enclave,
+                kes_monitor,
+                notifier,
+            }),
+        };
+        (reloader, receiver)
+    }
+
+    /// re-reads the secret files and replaces the leader keys of the
+    /// enclave, returns the public identities of the new leaders
+    pub async fn reload(&self) -> Result<Vec<String>, ReloadError> {
+        if self.inner.remote_signer {
+            return Err(ReloadError::RemoteSigner);
+        }
+
+        // reading an encrypted file runs the key derivation function, which
+        // is purposely slow
+        let inner = Arc::clone(&self.inner);
+        let leaders = tokio::task::spawn_blocking(move || {
+            // the passphrase was read when the node started, an encrypted
+            // file fails to load right away if there was none
+            let secret_files = inner.secret_files.lock().unwrap();
+            if secret_files.is_empty() {
+                return Err(ReloadError::NoSecretFile);
+            }
+            let secrets = secret_files.load()?;
+            let leaders: Vec<Leader> = secrets
+                .iter()
+                .filter_map(|secret| secret.leader())
+                .collect();
+            let identities = leaders_identity(&leaders);
+            inner.enclave.set_leaders(leaders);
+            inner.kes_monitor.set_keys(
+                secrets
+                    .iter()
+                    .filter_map(|secret| secret.kes_key())
+                    .collect(),
+            );
+            Ok(identities)
+        })
+        .await??;
+
+        tracing::info!(leaders = ?leaders, "leader keys reloaded");
+        // the leadership module may not be running, nothing to notify then
+        let _ = self.inner.notifier.send(leaders.clone());
+        Ok(leaders)
+    }
+}
+
+fn leaders_identity(leaders: &[Leader]) -> Vec<String> {
+    leaders.iter().flat_map(leader_ids).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secure::PassphraseSource;
+    use chain_crypto::Ed25519;
+    use jormungandr_lib::{
+        crypto::key::SigningKey,
+        interfaces::{Bft, EncryptedSecretFile, NodeSecret},
+    };
+    use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
+    use std::{
+        fs,
+        path::PathBuf,
+        time::{Duration, SystemTime},
+    };
+    use zeroize::Zeroizing;
+
+    const PASSPHRASE: &str = "passphrase";
+
+    /// A secret file with a BFT leader and the identity of the leader
+    fn bft_secret(seed: u64) -> (Vec<u8>, String) {
+        let signing_key = SigningKey::<Ed25519>::generate(ChaChaRng::seed_from_u64(seed));
+        let identity = hex::encode(signing_key.clone().into_secret_key().to_public());
+        let secret = NodeSecret {
+            bft: Some(Bft { signing_key }),
+            genesis: None,
+        };
+        (serde_yaml::to_vec(&secret).unwrap(), identity)
+    }
+
+    fn encrypted(secret: &[u8]) -> Vec<u8> {
+        let encrypted = EncryptedSecretFile::encrypt(
+            secret,
+            PASSPHRASE.as_bytes(),
+            1_000,
+            &mut rand::thread_rng(),
+        )
+        .unwrap();
+        serde_yaml::to_vec(&encrypted).unwrap()
+    }
+
+    fn secret_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "jormungandr-reload-{}-{}",
+            name,
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("secret.yaml")
+    }
+
+    fn new_reloader(secret_files: SecretFiles, remote_signer: bool) -> (SecretsReloader, Enclave) {
+        let leaders = if secret_files.is_empty() {
+            Vec::new()
+        } else {
+            secret_files
+                .load()
+                .unwrap()
+                .iter()
+                .filter_map(|secret| secret.leader())
+                .collect()
+        };
+        let enclave = Enclave::new(leaders);
+        let kes_monitor = KesMonitor::new(
+            Vec::new(),
+            SystemTime::UNIX_EPOCH,
+            Duration::from_secs(3600),
+        );
+        let (reloader, _) =
+            SecretsReloader::new(secret_files, remote_signer, enclave.clone(), kes_monitor);
+        (reloader, enclave)
+    }
+
+    #[tokio::test]
+    async fn reload_replaces_the_leaders() {
+        let path = secret_path("replace");
+        let (secret, first) = bft_secret(1);
+        fs::write(&path, secret).unwrap();
+        let secret_files =
+            SecretFiles::new(vec![path.clone()], PassphraseSource::EnvOrPrompt, None).unwrap();
+        let enclave = Enclave::new(
+            secret_files
+                .load()
+                .unwrap()
+                .iter()
+                .filter_map(|secret| secret.leader())
+                .collect(),
+        );
+        let kes_monitor = KesMonitor::new(
+            Vec::new(),
+            SystemTime::UNIX_EPOCH,
+            Duration::from_secs(3600),
+        );
+        let (reloader, mut reloaded) =
+            SecretsReloader::new(secret_files, false, enclave.clone(), kes_monitor);
+        assert_eq!(*reloaded.borrow(), [first]);
+
+        let (secret, second) = bft_secret(2);
+        fs::write(&path, secret).unwrap();
+        assert_eq!(reloader.reload().await.unwrap(), [second.clone()]);
+        reloaded.changed().await.unwrap();
+        assert_eq!(*reloaded.borrow(), [second.clone()]);
+        assert_eq!(leaders_identity(&enclave.leaders()), [second]);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn encrypted_file_is_reloaded_with_the_startup_passphrase() {
+        let path = secret_path("encrypted");
+        let (secret, _) = bft_secret(1);
+        fs::write(&path, encrypted(&secret)).unwrap();
+        let passphrase = Some(Zeroizing::new(PASSPHRASE.to_owned()));
+        let secret_files = SecretFiles::new(
+            vec![path.clone()],
+            PassphraseSource::EnvOrPrompt,
+            passphrase,
+        )
+        .unwrap();
+        let (reloader, enclave) = new_reloader(secret_files, false);
+
+        let (secret, second) = bft_secret(2);
+        fs::write(&path, encrypted(&secret)).unwrap();
+        assert_eq!(reloader.reload().await.unwrap(), [second.clone()]);
+        assert_eq!(leaders_identity(&enclave.leaders()), [second]);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn encrypted_file_without_passphrase_fails_fast() {
+        let path = secret_path("no-passphrase");
+        let (secret, first) = bft_secret(1);
+        fs::write(&path, &secret).unwrap();
+        // no file is encrypted when the node starts, no passphrase is asked for
+        let secret_files =
+            SecretFiles::new(vec![path.clone()], PassphraseSource::EnvOrPrompt, None).unwrap();
+        let (reloader, enclave) = new_reloader(secret_files, false);
+
+        let (secret, _) = bft_secret(2);
+        fs::write(&path, encrypted(&secret)).unwrap();
+        let result = reloader.reload().await;
+        assert!(
+            matches!(
+                &result,
+                Err(ReloadError::Load(NodeSecretFromFileError::File { source, .. }))
+                    if matches!(**source, NodeSecretFromFileError::NoPassphrase)
+            ),
+            "unexpected result {:?}",
+            result
+        );
+        // the keys in use are kept
+        assert_eq!(leaders_identity(&enclave.leaders()), [first]);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reload_without_local_keys_is_refused() {
+        let no_files = SecretFiles::new(Vec::new(), PassphraseSource::EnvOrPrompt, None).unwrap();
+        let (reloader, _) = new_reloader(no_files, false);
+        assert!(matches!(
+            reloader.reload().await,
+            Err(ReloadError::NoSecretFile)
+        ));
+
+        let no_files = SecretFiles::new(Vec::new(), PassphraseSource::EnvOrPrompt, None).unwrap();
+        let (reloader, _) = new_reloader(no_files, true);
+        assert!(matches!(
+            reloader.reload().await,
+            Err(ReloadError::RemoteSigner)
+        ));
+    }
+}
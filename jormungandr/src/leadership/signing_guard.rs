@@ -0,0 +1,227 @@
+//! protection against signing two blocks for the same slot
+//!
+//! the date of the last block signed by each leader is recorded in the
+//! storage directory before the block is signed, and no block is signed
+//! at or below it, so that a node restarted in the middle of a slot does
+//! not sign a second block for it.
+//!
+//! A standby node hosting the same leaders is fenced: it does not sign
+//! any block until the active node hands off its watermarks and is fenced
+//! in turn.
+
+use jormungandr_lib::interfaces::{BlockDate, SigningWatermarks};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+
+const SIGNING_GUARD_FILE: &str = "signing_watermarks.json";
+
+#[derive(Debug, Error)]
+pub enum SigningGuardError {
+    #[error("the signing is handed off to another node")]
+    Fenced,
+    #[error(
+        "leader {leader_id} already signed a block at {watermark}, refusing to sign at {date}"
+    )]
+    AlreadySigned {
+        leader_id: String,
+        watermark: BlockDate,
+        date: BlockDate,
+    },
+    #[error("cannot access the signing watermarks file {}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("invalid signing watermarks file {}", path.display())]
+    Format {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+struct State {
+    fenced: bool,
+    watermarks: SigningWatermarks,
+}
+
+/// shared between the leadership module, which asks for the permission
+/// to sign, and the REST API, which hands off or takes over the signing
+#[derive(Clone)]
+pub struct SigningGuard {
+    state: Arc<Mutex<State>>,
+    path: Option<PathBuf>,
+}
+
+impl SigningGuard {
+    /// loads the state recorded in the storage directory, a node without
+    /// recorded state starts fenced if it is a standby node.
+    ///
+    /// Without storage directory the watermarks are kept in memory only.
+    pub fn load(storage: Option<&Path>, standby: bool) -> Result<Self, SigningGuardError> {
+        let path = match storage {
+            Some(dir) => dir.join(SIGNING_GUARD_FILE),
+            None => {
+                return Ok(SigningGuard {
+                    state: Arc::new(Mutex::new(State {
+                        fenced: standby,
+                        watermarks: SigningWatermarks::default(),
+                    })),
+                    path: None,
+                });
+            }
+        };
+
+        let state = match fs::read(&path) {
+            Ok(bytes) => {
+                serde_json::from_slice(&bytes).map_err(|source| SigningGuardError::Format {
+                    path: path.clone(),
+                    source,
+                })?
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => State {
+                fenced: standby,
+                watermarks: SigningWatermarks::default(),
+            },
+            Err(source) => return Err(SigningGuardError::Io { path, source }),
+        };
+        if state.fenced {
+            tracing::info!("the signing is fenced until the leaders are taken over");
+        }
+
+        Ok(SigningGuard {
+            state: Arc::new(Mutex::new(state)),
+            path: Some(path),
+        })
+    }
+
+    /// records the date as the watermark of the leader, failing if the node
+    /// is fenced or if the leader already signed at or after this date.
+    ///
+    /// The block may only be signed once this succeeded.
+    pub fn authorize(&self, leader_id: &str, date: BlockDate) -> Result<(), SigningGuardError> {
+        let mut state = self.state.lock().unwrap();
+        if state.fenced {
+            return Err(SigningGuardError::Fenced);
+        }
+        if let Some(watermark) = state.watermarks.get(leader_id) {
+            if date <= watermark {
+                return Err(SigningGuardError::AlreadySigned {
+                    leader_id: leader_id.to_owned(),
+                    watermark,
+                    date,
+                });
+            }
+        }
+
+        let previous = state.watermarks.clone();
+        state.watermarks.set(leader_id.to_owned(), date);
+        if let Err(e) = self.persist(&state) {
+            // the block is not signed, the slot can be signed again
+            state.watermarks = previous;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// stops the signing, returns the watermarks for the node taking over
+    pub fn handoff(&self) -> Result<SigningWatermarks, SigningGuardError> {
+        let mut state = self.state.lock().unwrap();
+        state.fenced = true;
+        self.persist(&state)?;
+        tracing::info!("signing handed off");
+        Ok(state.watermarks.clone())
+    }
+
+    /// resumes the signing from the watermarks of the node which handed
+    /// off, returns the resulting watermarks
+    pub fn take_over(
+        &self,
+        watermarks: &SigningWatermarks,
+    ) -> Result<SigningWatermarks, SigningGuardError> {
+        let mut state = self.state.lock().unwrap();
+        let previous = state.watermarks.clone();
+        state.watermarks.merge(watermarks);
+        state.fenced = false;
+        if let Err(e) = self.persist(&state) {
+            state.watermarks = previous;
+            state.fenced = true;
+            return Err(e);
+        }
+        tracing::info!("signing taken over");
+        Ok(state.watermarks.clone())
+    }
+
+    // the file is replaced atomically so that a crash leaves either the
+    // previous or the new state
+    fn persist(&self, state: &State) -> Result<(), SigningGuardError> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let io_error = |source| SigningGuardError::Io {
+            path: path.clone(),
+            source,
+        };
+        let bytes = serde_json::to_vec(state).map_err(|source| SigningGuardError::Format {
+            path: path.clone(),
+            source,
+        })?;
+        let tmp_path = path.with_extension("json.tmp");
+        {
+            let mut file = fs::File::create(&tmp_path).map_err(io_error)?;
+            io::Write::write_all(&mut file, &bytes).map_err(io_error)?;
+            file.sync_all().map_err(io_error)?;
+        }
+        fs::rename(&tmp_path, path).map_err(io_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_to_sign_at_or_below_the_watermark() {
+        let guard = SigningGuard::load(None, false).unwrap();
+        guard.authorize("a", BlockDate::new(1, 10)).unwrap();
+        assert!(matches!(
+            guard.authorize("a", BlockDate::new(1, 10)),
+            Err(SigningGuardError::AlreadySigned { .. })
+        ));
+        assert!(matches!(
+            guard.authorize("a", BlockDate::new(0, 20)),
+            Err(SigningGuardError::AlreadySigned { .. })
+        ));
+        guard.authorize("b", BlockDate::new(1, 10)).unwrap();
+        guard.authorize("a", BlockDate::new(1, 11)).unwrap();
+    }
+
+    #[test]
+    fn handoff_and_take_over() {
+        let active = SigningGuard::load(None, false).unwrap();
+        let standby = SigningGuard::load(None, true).unwrap();
+        assert!(matches!(
+            standby.authorize("a", BlockDate::new(1, 1)),
+            Err(SigningGuardError::Fenced)
+        ));
+
+        active.authorize("a", BlockDate::new(1, 10)).unwrap();
+        let watermarks = active.handoff().unwrap();
+        assert!(matches!(
+            active.authorize("a", BlockDate::new(1, 11)),
+            Err(SigningGuardError::Fenced)
+        ));
+
+        standby.take_over(&watermarks).unwrap();
+        assert!(standby.authorize("a", BlockDate::new(1, 10)).is_err());
+        standby.authorize("a", BlockDate::new(1, 11)).unwrap();
+    }
+}
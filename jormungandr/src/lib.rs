@@ -24,10 +24,12 @@ use std::{sync::Arc, time::Duration};
 use tokio::signal;
 use tokio_util::sync::CancellationToken;
 use tracing::{span, Level, Span};
+use zeroize::Zeroizing;
 
 pub mod blockcfg;
 pub mod blockchain;
 pub mod client;
+pub mod clock_skew;
 pub mod context;
 pub mod diagnostic;
 pub mod fragment;
@@ -50,8 +52,8 @@ pub mod watch_client;
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_futures::Instrument;
 
-fn start() -> Result<(), start_up::Error> {
-    let initialized_node = initialize_node()?;
+fn start(env_passphrase: Option<Zeroizing<String>>) -> Result<(), start_up::Error> {
+    let initialized_node = initialize_node(env_passphrase)?;
 
     let bootstrapped_node = bootstrap(initialized_node)?;
 
@@ -60,6 +62,7 @@ fn start() -> Result<(), start_up::Error> {
 
 pub struct BootstrappedNode {
     settings: Settings,
+    secret_files: secure::SecretFiles,
     blockchain: Blockchain,
     blockchain_tip: blockchain::Tip,
     block0_hash: HeaderHash,
@@ -124,6 +127,23 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
         stats_counter.set_tip_block(&block, &block_ref);
     }
 
+    let clock_skew = {
+        let settings = &bootstrapped_node.settings.clock_skew;
+        let monitor = clock_skew::ClockSkewMonitor::new(
+            settings.max_skew_slot_ratio,
+            settings.time_sources.clone(),
+            settings.refuse_to_lead,
+        );
+        let blockchain_tip = blockchain_tip.clone();
+        let stats_counter = stats_counter.clone();
+        let check_interval = settings.check_interval.into();
+        let service_monitor = monitor.clone();
+        services.spawn_future("clock_skew", move |_| {
+            service_monitor.run(blockchain_tip, stats_counter, check_interval)
+        });
+        monitor
+    };
+
     let (watch_msgbox, watch_client) = {
         let (msgbox, queue) = async_msg::channel(WATCH_CLIENT_TASK_QUEUE_LEN);
 
@@ -148,6 +168,7 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
         // TODO: we should get this value from the configuration
         let block_cache_ttl: Duration = Duration::from_secs(120);
         let stats_counter = stats_counter.clone();
+        let clock_skew = clock_skew.clone();
         services.spawn_future("block", move |info| {
             let task_data = blockchain::TaskData {
                 blockchain,
@@ -157,6 +178,7 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
                 fragment_msgbox,
                 watch_msgbox,
                 garbage_collection_interval: block_cache_ttl,
+                clock_skew,
             };
             blockchain::start(task_data, info, block_queue)
         });
@@ -232,19 +254,24 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
             None
         }
     });
-    let node_secret = bootstrapped_node
-        .settings
-        .secret
-        .map::<Result<_, start_up::Error>, _>(|secret_path| {
-            let secret = secure::NodeSecret::load_from_file(secret_path.as_path())?;
-            Ok(secret)
-        })
-        .transpose()?;
+    let secret_files = bootstrapped_node.secret_files;
+    let node_secrets = secret_files.load()?;
+
+    let remote_signer = bootstrapped_node.settings.leadership.remote_signer.clone();
+    let leaders: Vec<Leader> = node_secrets
+        .iter()
+        .filter_map(|secret| secret.leader())
+        .collect();
+    if remote_signer.is_some() && !leaders.is_empty() {
+        tracing::warn!(
+            "the leader keys of the secret files are ignored, the remote signer is used instead"
+        );
+    }
 
-    let leader_secret = node_secret.as_ref().map(|secret| {
-        if let (Some(leaders), Some(leader)) = (&bft_leaders, secret.bft()) {
+    for leader in &leaders {
+        if let (Some(consensus_leaders), Some(leader)) = (&bft_leaders, &leader.bft_leader) {
             let public_key = &leader.sig_key.to_public();
-            if !leaders.contains(public_key) {
+            if !consensus_leaders.contains(public_key) {
                 tracing::warn!(
                     "node was started with a BFT secret key but the corresponding \
                         public key {} is not listed among consensus leaders",
@@ -252,39 +279,116 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
                 );
             }
         };
-        Leader {
-            bft_leader: secret.bft(),
-            genesis_leader: secret.genesis(),
-        }
+    }
+    let enclave = Enclave::new(if remote_signer.is_none() {
+        leaders
+    } else {
+        Vec::new()
     });
-    let enclave = Enclave::new(leader_secret);
+    let kes_monitor = {
+        use crate::blockcfg::Block0DataSource as _;
+
+        let block0_hash = *blockchain.block0();
+        let block0 = blockchain
+            .storage()
+            .get(block0_hash)?
+            .ok_or(start_up::Error::Block0NotFound { hash: block0_hash })?;
+        let kes_keys = match &remote_signer {
+            #[cfg(unix)]
+            Some(path) => remote_signer_kes_keys(path.clone()),
+            #[cfg(not(unix))]
+            Some(_) => unreachable!("remote signer settings are rejected on this platform"),
+            None => node_secrets
+                .iter()
+                .filter_map(|secret| secret.kes_key())
+                .collect(),
+        };
+        // the ledger does not apply updates of the KES update speed, the
+        // value of block0 holds for the lifetime of the blockchain
+        leadership::KesMonitor::new(kes_keys, block0.start_time()?, block0.kes_update_speed()?)
+    };
+    let (secrets_reloader, leaders_reloaded) = leadership::SecretsReloader::new(
+        secret_files,
+        remote_signer.is_some(),
+        enclave.clone(),
+        kes_monitor.clone(),
+    );
+
+    {
+        let stats_counter = stats_counter.clone();
+        let kes_expiry_warning = bootstrapped_node.settings.leadership.kes_expiry_warning;
+        services.spawn_future("kes_monitor", move |_| {
+            kes_monitor.run(stats_counter, kes_expiry_warning.into())
+        });
+    }
+    if bootstrapped_node.settings.storage.is_none()
+        && (remote_signer.is_some() || !enclave.leaders().is_empty())
+    {
+        tracing::warn!("no storage directory, the signing watermarks will not survive a restart");
+    }
+    let signing_guard = leadership::SigningGuard::load(
+        bootstrapped_node.settings.storage.as_deref(),
+        bootstrapped_node.settings.leadership.standby,
+    )?;
 
     #[cfg(feature = "evm")]
     let evm_keys = Arc::new(
-        node_secret
-            .map(|secret| secret.evm_keys())
-            .unwrap_or_default(),
+        node_secrets
+            .iter()
+            .flat_map(|secret| secret.evm_keys())
+            .collect::<Vec<_>>(),
     );
 
+    let leadership_enclave = match remote_signer {
+        #[cfg(unix)]
+        Some(path) => remote_signer_enclave(path),
+        #[cfg(not(unix))]
+        Some(_) => unreachable!("remote signer settings are rejected on this platform"),
+        None => leadership::Enclave::new(enclave.clone()),
+    };
+
     {
         let logs = leadership_logs.clone();
         let block_message = block_msgbox;
         let tip = blockchain_tip.clone();
-        let enclave = leadership::Enclave::new(enclave.clone());
+        let storage = blockchain.storage().clone();
+        let enclave = leadership_enclave.clone();
         let pool = fragment_msgbox.clone();
         let rewards_report_all = bootstrapped_node.settings.rewards_report_all;
         let block_hard_deadline = bootstrapped_node.settings.block_hard_deadline;
+        let dry_run =
+            bootstrapped_node.settings.leadership.dry_run.then(|| {
+                leadership::DryRunBlocks::new(bootstrapped_node.settings.storage.as_deref())
+            });
+        let clock_skew = clock_skew.clone();
+        let stats_counter = stats_counter.clone();
+        match dry_run.as_ref().map(|dry_run_blocks| dry_run_blocks.dir()) {
+            Some(Some(dir)) => tracing::warn!(
+                "dry-run mode, the produced blocks are stored in {} and never propagated",
+                dir.display()
+            ),
+            Some(None) => tracing::warn!(
+                "dry-run mode, the produced blocks are kept in memory and never propagated"
+            ),
+            None => {}
+        }
 
         services.spawn_try_future("leadership", move |service_info| {
             leadership::Module::new(leadership::ModuleConfig {
                 service_info,
                 logs,
                 tip,
+                storage,
                 pool,
                 enclave,
+                leaders_reloaded,
+                signing_guard: signing_guard.clone(),
                 block_message,
                 rewards_report_all,
                 block_hard_deadline,
+                dry_run,
+                clock_skew,
+                stats_counter,
             })
             .and_then(|module| module.run())
         });
@@ -324,6 +428,9 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
             #[cfg(feature = "evm")]
             evm_keys,
             enclave,
+            schedule_look_ahead: leadership::ScheduleLookAhead::new(leadership_enclave),
+            secrets_reloader: secrets_reloader.clone(),
+            signing_guard,
             network_state,
             #[cfg(feature = "prometheus-metrics")]
             prometheus: prometheus_metric,
@@ -335,6 +442,9 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
         })
     };
 
+    #[cfg(unix)]
+    init_sighup_watcher(&mut services, secrets_reloader);
+
     {
         let blockchain_tip = blockchain_tip;
         let no_blockchain_updates_warning_interval = bootstrapped_node
@@ -377,6 +487,7 @@ fn start_services(bootstrapped_node: BootstrappedNode) -> Result<(), start_up::E
 fn bootstrap(initialized_node: InitializedNode) -> Result<BootstrappedNode, start_up::Error> {
     let InitializedNode {
         settings,
+        secret_files,
         block0,
         storage,
         context,
@@ -405,6 +516,7 @@ fn bootstrap(initialized_node: InitializedNode) -> Result<BootstrappedNode, star
 
     Ok(BootstrappedNode {
         settings,
+        secret_files,
         blockchain,
         blockchain_tip,
         block0_hash,
@@ -447,9 +559,19 @@ async fn bootstrap_internal(
 
     let cache_capacity = 102_400;
 
-    let (blockchain, blockchain_tip) =
-        start_up::load_blockchain(block0, storage, cache_capacity, settings.rewards_report_all)
-            .await?;
+    let ledger_snapshots = settings
+        .storage
+        .as_deref()
+        .map(blockchain::LedgerSnapshots::new);
+
+    let (blockchain, blockchain_tip) = start_up::load_blockchain(
+        block0,
+        storage,
+        cache_capacity,
+        settings.rewards_report_all,
+        ledger_snapshots,
+    )
+    .await?;
 
     if let Some(context) = &context {
         let mut context = context.write().await;
@@ -458,6 +580,11 @@ async fn bootstrap_internal(
         context.set_bootstrap_stopper(cancellation_token.clone());
     };
 
+    if let Some(path) = &settings.import_block_archive {
+        blockchain::archive::import_archive(blockchain.clone(), blockchain_tip.clone(), path)
+            .await?;
+    }
+
     let mut bootstrap_attempt: usize = 0;
 
     let network_res = loop {
@@ -516,6 +643,7 @@ async fn bootstrap_internal(
 
 pub struct InitializedNode {
     pub settings: Settings,
+    pub secret_files: secure::SecretFiles,
     pub block0: blockcfg::Block,
     pub storage: blockchain::Storage,
     pub context: Option<context::ContextLock>,
@@ -557,6 +685,33 @@ fn init_os_signal_watchers(services: &mut Services, token: CancellationToken) {
     });
 }
 
+/// reloads the leader keys from the node's secret file on SIGHUP
+#[cfg(unix)]
+fn init_sighup_watcher(services: &mut Services, secrets_reloader: leadership::SecretsReloader) {
+    use signal::unix::SignalKind;
+
+    async fn recv_signal_and_reload(
+        mut signal: signal::unix::Signal,
+        secrets_reloader: leadership::SecretsReloader,
+    ) {
+        while let Some(()) = signal.recv().await {
+            if let Err(e) = secrets_reloader.reload().await {
+                tracing::error!(reason = %e, "failed to reload the leader keys");
+            }
+        }
+    }
+
+    services.spawn_future("sighup_watcher", move |_info| {
+        match signal::unix::signal(SignalKind::hangup()) {
+            Ok(signal) => recv_signal_and_reload(signal, secrets_reloader).left_future(),
+            Err(e) => {
+                tracing::warn!(reason = %e, "failed to install handler for SIGHUP");
+                future::pending().right_future()
+            }
+        }
+    });
+}
+
 #[cfg(not(unix))]
 fn init_os_signal_watchers(services: &mut Services, token: CancellationToken) {
     use signal::ctrl_c;
@@ -575,9 +730,57 @@ fn init_os_signal_watchers(services: &mut Services, token: CancellationToken) {
     });
 }
 
-fn initialize_node() -> Result<InitializedNode, start_up::Error> {
+#[cfg(unix)]
+fn remote_signer_enclave(path: std::path::PathBuf) -> leadership::Enclave {
+    let signer = secure::remote::RemoteSigner::new(path);
+    // the signer may be started after the node, the connection is retried
+    // on every request
+    match signer.identity() {
+        Ok((bft_leader, genesis_node_id)) => tracing::info!(
+            bft_leader = ?bft_leader,
+            genesis_node_id = ?genesis_node_id,
+            "using the remote signer at {}",
+            signer.path().display()
+        ),
+        Err(e) => tracing::warn!(
+            reason = %e,
+            "remote signer at {} is not reachable yet",
+            signer.path().display()
+        ),
+    }
+    leadership::Enclave::remote(signer)
+}
+
+// the KES key held by the remote signer, known only if the signer is
+// reachable when the node starts
+#[cfg(unix)]
+fn remote_signer_kes_keys(path: std::path::PathBuf) -> Vec<leadership::KesKey> {
+    let signer = secure::remote::RemoteSigner::new(path);
+    match signer.kes_key() {
+        Ok(key) => key.into_iter().collect(),
+        Err(e) => {
+            tracing::warn!(
+                reason = %e,
+                "cannot get the KES key of the remote signer at {}, its expiry is not monitored",
+                signer.path().display()
+            );
+            Vec::new()
+        }
+    }
+}
+
+fn initialize_node(
+    env_passphrase: Option<Zeroizing<String>>,
+) -> Result<InitializedNode, start_up::Error> {
     let command_line = CommandLine::load();
     let exit_after_storage_setup = command_line.storage_check;
+    let export_block_archive = command_line.export_block_archive.clone();
+    #[cfg(unix)]
+    let remote_signer_listen = command_line.remote_signer_listen.clone();
+    #[cfg(unix)]
+    let remote_signer_secret = command_line.start_arguments.secret.clone();
+    #[cfg(unix)]
+    let remote_signer_passphrase = command_line.start_arguments.secret_passphrase();
 
     if command_line.full_version {
         println!("{}", env!("FULL_VERSION"));
@@ -605,10 +808,36 @@ fn initialize_node() -> Result<InitializedNode, start_up::Error> {
         }
     }
 
+    #[cfg(unix)]
+    if let Some(path) = remote_signer_listen {
+        if remote_signer_secret.is_empty() {
+            return Err(secure::remote::Error::MissingSecret.into());
+        } else if remote_signer_secret.len() > 1 {
+            return Err(secure::remote::Error::TooManySecrets.into());
+        }
+        let secret = secure::SecretFiles::new(
+            remote_signer_secret,
+            remote_signer_passphrase,
+            env_passphrase,
+        )?
+        .load()?
+        .remove(0);
+        let mut services = Services::new();
+        services.block_on_task("remote_signer", |_info| async move {
+            secure::remote::serve(&path, secret).await
+        })?;
+        std::process::exit(0);
+    }
+
     let diagnostic = Diagnostic::new()?;
     tracing::debug!("system settings are: {}", diagnostic);
 
     let settings = raw_settings.try_into_settings()?;
+    let secret_files = secure::SecretFiles::new(
+        settings.secret.clone(),
+        settings.secret_passphrase,
+        env_passphrase,
+    )?;
 
     let storage = start_up::prepare_storage(&settings)?;
     if exit_after_storage_setup {
@@ -619,6 +848,20 @@ fn initialize_node() -> Result<InitializedNode, start_up::Error> {
         std::process::exit(0);
     }
 
+    if let Some(path) = export_block_archive {
+        let info = block_on(blockchain::archive::export_archive(&storage, &path))?;
+        tracing::info!(
+            "exported {} blocks up to {} to {}, exiting",
+            info.blocks,
+            info.tip,
+            path.display()
+        );
+        std::mem::drop(_enter);
+        std::mem::drop(init_span);
+        std::mem::drop(storage);
+        std::process::exit(0);
+    }
+
     if settings.network.trusted_peers.is_empty() && !settings.network.skip_bootstrap {
         return Err(network::bootstrap::Error::EmptyTrustedPeers.into());
     }
@@ -720,6 +963,7 @@ fn initialize_node() -> Result<InitializedNode, start_up::Error> {
 
     Ok(InitializedNode {
         settings,
+        secret_files,
         block0,
         storage,
         context,
@@ -733,7 +977,12 @@ fn initialize_node() -> Result<InitializedNode, start_up::Error> {
 pub fn main() {
     use std::error::Error;
 
-    if let Err(error) = start() {
+    // the environment can only be modified safely before other threads are
+    // started, by the logger or the runtime
+    let result = secure::take_env_passphrase()
+        .map_err(start_up::Error::from)
+        .and_then(start);
+    if let Err(error) = result {
         eprintln!("{}", error);
         let mut source = error.source();
         while let Some(err) = source {
@@ -6,11 +6,17 @@ use chain_impl_mockchain::{
     transaction::Transaction,
     value::{Value, ValueError},
 };
+use jormungandr_lib::interfaces::BlockProductionStep;
 use prometheus::{
     core::{AtomicU64, GenericGauge},
-    Encoder, Gauge, IntCounter, Registry, TextEncoder,
+    exponential_buckets, Encoder, Gauge, HistogramOpts, HistogramVec, IntCounter, IntGauge,
+    Registry, TextEncoder,
+};
+use std::{
+    convert::TryInto,
+    sync::Arc,
+    time::{Duration, SystemTime},
 };
-use std::{convert::TryInto, sync::Arc, time::SystemTime};
 
 type UIntGauge = GenericGauge<AtomicU64>;
 
@@ -28,6 +34,11 @@ pub struct Prometheus {
     peer_available_cnt: UIntGauge,
     peer_total_cnt: UIntGauge,
     slot_start_time: UIntGauge,
+    kes_remaining_periods: UIntGauge,
+    clock_skew_millis: IntGauge,
+    soft_deadline_hit_cnt: IntCounter,
+    hard_deadline_hit_cnt: IntCounter,
+    block_production_step_seconds: HistogramVec,
     block_tx_count: UIntGauge,
     block_input_sum: UIntGauge,
     block_fee_sum: UIntGauge,
@@ -117,6 +128,39 @@ impl Default for Prometheus {
         registry
             .register(Box::new(slot_start_time.clone()))
             .unwrap();
+        let kes_remaining_periods =
+            UIntGauge::new("kesRemainingPeriods", "kesRemainingPeriods").unwrap();
+        registry
+            .register(Box::new(kes_remaining_periods.clone()))
+            .unwrap();
+        let clock_skew_millis = IntGauge::new("clockSkewMillis", "clockSkewMillis").unwrap();
+        registry
+            .register(Box::new(clock_skew_millis.clone()))
+            .unwrap();
+        let soft_deadline_hit_cnt =
+            IntCounter::new("softDeadlineHitCnt", "softDeadlineHitCnt").unwrap();
+        registry
+            .register(Box::new(soft_deadline_hit_cnt.clone()))
+            .unwrap();
+        let hard_deadline_hit_cnt =
+            IntCounter::new("hardDeadlineHitCnt", "hardDeadlineHitCnt").unwrap();
+        registry
+            .register(Box::new(hard_deadline_hit_cnt.clone()))
+            .unwrap();
+        // from a millisecond to about half a minute
+        let block_production_step_seconds = HistogramVec::new(
+            HistogramOpts::new("blockProductionStepSeconds", "blockProductionStepSeconds")
+                .buckets(exponential_buckets(0.001, 2.0, 16).unwrap()),
+            &["step"],
+        )
+        .unwrap();
+        // the steps are exported before the first block is produced
+        for step in BlockProductionStep::ALL {
+            block_production_step_seconds.with_label_values(&[step.as_str()]);
+        }
+        registry
+            .register(Box::new(block_production_step_seconds.clone()))
+            .unwrap();
         let block_tx_count = UIntGauge::new("lastBlockTx", "lastBlockTx").unwrap();
         registry.register(Box::new(block_tx_count.clone())).unwrap();
         let block_input_sum = UIntGauge::new("lastBlockInputTime", "lastBlockInputTime").unwrap();
@@ -165,6 +209,11 @@ impl Default for Prometheus {
             peer_available_cnt,
             peer_total_cnt,
             slot_start_time,
+            kes_remaining_periods,
+            clock_skew_millis,
+            soft_deadline_hit_cnt,
+            hard_deadline_hit_cnt,
+            block_production_step_seconds,
             block_tx_count,
             block_input_sum,
             block_fee_sum,
@@ -233,6 +282,30 @@ impl MetricsBackend for Prometheus {
         self.slot_start_time.set(time.to_secs());
     }
 
+    fn set_kes_remaining_periods(&self, periods: u32) {
+        self.kes_remaining_periods.set(periods.into());
+    }
+
+    fn set_clock_skew_millis(&self, skew: i64) {
+        self.clock_skew_millis.set(skew);
+    }
+
+    fn add_soft_deadline_hit_cnt(&self, count: usize) {
+        let count = count.try_into().unwrap();
+        self.soft_deadline_hit_cnt.inc_by(count);
+    }
+
+    fn add_hard_deadline_hit_cnt(&self, count: usize) {
+        let count = count.try_into().unwrap();
+        self.hard_deadline_hit_cnt.inc_by(count);
+    }
+
+    fn observe_block_production_step(&self, step: BlockProductionStep, duration: Duration) {
+        self.block_production_step_seconds
+            .with_label_values(&[step.as_str()])
+            .observe(duration.as_secs_f64());
+    }
+
     fn set_tip_block(
         &self,
         block: &chain_impl_mockchain::block::Block,
@@ -304,3 +377,32 @@ impl MetricsBackend for Prometheus {
         self.block_hash_value.store(Some(Arc::new(block_hash)));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_production_steps_are_labelled() {
+        let prometheus = Prometheus::new();
+        prometheus
+            .observe_block_production_step(BlockProductionStep::Schedule, Duration::from_millis(3));
+
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&prometheus.registry.gather(), &mut buffer)
+            .unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+
+        // every step is exported, observed or not
+        for step in BlockProductionStep::ALL {
+            assert!(text.contains(&format!(
+                "blockProductionStepSeconds_count{{step=\"{}\"}}",
+                step.as_str()
+            )));
+        }
+        assert!(text.contains("blockProductionStepSeconds_count{step=\"schedule\"} 1"));
+        assert!(text.contains("blockProductionStepSeconds_sum{step=\"schedule\"} 0.003"));
+        assert!(text.contains("blockProductionStepSeconds_count{step=\"ledger\"} 0"));
+    }
+}
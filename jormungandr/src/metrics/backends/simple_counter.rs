@@ -7,7 +7,7 @@ use chain_impl_mockchain::{
     value::{Value, ValueError},
 };
 use jormungandr_lib::{
-    interfaces::NodeStats,
+    interfaces::{BlockProductionStep, NodeStats},
     time::{SecondsSinceUnixEpoch, SystemTime},
 };
 use std::{
@@ -16,7 +16,7 @@ use std::{
         atomic::{AtomicU64, AtomicUsize, Ordering},
         Arc, RwLock,
     },
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 const EXP_MOVING_AVERAGE_COEFF: f64 = 0.5;
@@ -35,6 +35,8 @@ pub struct SimpleCounter {
     peers_connected_cnt: AtomicUsize,
     peers_quarantined_cnt: AtomicUsize,
     peers_available_cnt: AtomicUsize,
+    kes_remaining_periods: RwLock<Option<u32>>,
+    clock_skew_millis: RwLock<Option<i64>>,
     tip_block: ArcSwapOption<BlockCounters>,
     start_time: Instant,
 }
@@ -100,6 +102,8 @@ impl SimpleCounter {
                 .unwrap(),
             votes_cast: self.votes_cast.load(Ordering::Relaxed),
             uptime: Some(self.start_time.elapsed().as_secs()),
+            kes_remaining_periods: *self.kes_remaining_periods.read().unwrap(),
+            clock_skew_millis: *self.clock_skew_millis.read().unwrap(),
         }
     }
 }
@@ -117,6 +121,8 @@ impl Default for SimpleCounter {
             peers_connected_cnt: Default::default(),
             peers_quarantined_cnt: Default::default(),
             peers_available_cnt: Default::default(),
+            kes_remaining_periods: Default::default(),
+            clock_skew_millis: Default::default(),
             tip_block: Default::default(),
             start_time: Instant::now(),
         }
@@ -175,6 +181,22 @@ impl MetricsBackend for SimpleCounter {
             .store(time.to_secs(), Ordering::Relaxed);
     }
 
+    fn set_kes_remaining_periods(&self, periods: u32) {
+        *self.kes_remaining_periods.write().unwrap() = Some(periods);
+    }
+
+    fn set_clock_skew_millis(&self, skew: i64) {
+        *self.clock_skew_millis.write().unwrap() = Some(skew);
+    }
+
+    // the block production timings are only exported as histograms, see
+    // the leadership logs for the timings of each slot
+    fn add_soft_deadline_hit_cnt(&self, _count: usize) {}
+
+    fn add_hard_deadline_hit_cnt(&self, _count: usize) {}
+
+    fn observe_block_production_step(&self, _step: BlockProductionStep, _duration: Duration) {}
+
     fn set_tip_block(&self, block: &Block, block_ref: &Ref) {
         let mut block_tx_count = 0;
         let mut block_input_sum = Value::zero();
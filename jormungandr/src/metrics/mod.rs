@@ -1,7 +1,7 @@
 use crate::blockchain::Ref;
 use chain_impl_mockchain::block::Block;
-use jormungandr_lib::time::SecondsSinceUnixEpoch;
-use std::sync::Arc;
+use jormungandr_lib::{interfaces::BlockProductionStep, time::SecondsSinceUnixEpoch};
+use std::{sync::Arc, time::Duration};
 
 pub mod backends;
 
@@ -17,6 +17,11 @@ pub trait MetricsBackend {
     fn sub_peer_quarantined_cnt(&self, count: usize);
     fn set_peer_available_cnt(&self, count: usize);
     fn set_slot_start_time(&self, time: SecondsSinceUnixEpoch);
+    fn set_kes_remaining_periods(&self, periods: u32);
+    fn set_clock_skew_millis(&self, skew: i64);
+    fn add_soft_deadline_hit_cnt(&self, count: usize);
+    fn add_hard_deadline_hit_cnt(&self, count: usize);
+    fn observe_block_production_step(&self, step: BlockProductionStep, duration: Duration);
     fn set_tip_block(&self, block: &Block, block_ref: &Ref);
 }
 
@@ -77,6 +82,16 @@ impl MetricsBackend for Metrics {
     metrics_count_method!(sub_peer_quarantined_cnt);
     metrics_count_method!(set_peer_available_cnt);
     metrics_method!(set_slot_start_time, SecondsSinceUnixEpoch);
+    metrics_method!(set_kes_remaining_periods, u32);
+    metrics_method!(set_clock_skew_millis, i64);
+    metrics_count_method!(add_soft_deadline_hit_cnt);
+    metrics_count_method!(add_hard_deadline_hit_cnt);
+
+    fn observe_block_production_step(&self, step: BlockProductionStep, duration: Duration) {
+        for backend in &self.backends {
+            backend.observe_block_production_step(step, duration);
+        }
+    }
 
     fn set_tip_block(&self, block: &Block, block_ref: &Ref) {
         for backend in &self.backends {
@@ -1,4 +1,4 @@
-use super::grpc;
+use super::{grpc, tls::TlsContext};
 use crate::{
     blockchain::{self, Blockchain, BootstrapError, Error as BlockchainError, Tip},
     network::convert::Decode,
@@ -8,7 +8,7 @@ use crate::{
 use chain_core::property::ReadError;
 use chain_network::{data as net_data, error::Error as NetworkError};
 use futures::prelude::*;
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 use tokio_util::sync::CancellationToken;
 
 #[derive(thiserror::Error, Debug)]
@@ -41,10 +41,15 @@ pub enum Error {
 
 const MAX_BOOTSTRAP_PEERS: u32 = 32;
 
-pub async fn peers_from_trusted_peer(peer: &Peer) -> Result<Vec<topology::Peer>, Error> {
+pub async fn peers_from_trusted_peer(
+    peer: &Peer,
+    tls: Option<&Arc<TlsContext>>,
+) -> Result<Vec<topology::Peer>, Error> {
     tracing::info!("getting peers from bootstrap peer {}", peer.connection);
 
-    let mut client = grpc::connect(peer).await.map_err(Error::Connect)?;
+    let mut client = grpc::connect(peer, tls, None)
+        .await
+        .map_err(Error::Connect)?;
     let gossip = client
         .peers(MAX_BOOTSTRAP_PEERS)
         .await
@@ -63,6 +68,7 @@ pub async fn peers_from_trusted_peer(peer: &Peer) -> Result<Vec<topology::Peer>,
 
 pub async fn bootstrap_from_peer(
     peer: &Peer,
+    tls: Option<&Arc<TlsContext>>,
     blockchain: Blockchain,
     tip: Tip,
     cancellation_token: CancellationToken,
@@ -83,9 +89,10 @@ pub async fn bootstrap_from_peer(
 
     tracing::debug!("connecting to bootstrap peer {}", peer.connection);
 
-    let mut client = with_cancellation_token(grpc::connect(peer).boxed(), &cancellation_token)
-        .await?
-        .map_err(Error::Connect)?;
+    let mut client =
+        with_cancellation_token(grpc::connect(peer, tls, None).boxed(), &cancellation_token)
+            .await?
+            .map_err(Error::Connect)?;
 
     loop {
         let remote_tip = with_cancellation_token(client.tip().boxed(), &cancellation_token)
@@ -35,13 +35,14 @@ pub fn connect(
     let (sender, receiver) = oneshot::channel();
     let peer = state.peer();
     let keypair = state.global.keypair.clone();
+    let tls = state.global.config.tls.clone();
     let span = state.span().clone();
     let async_span = span.clone();
     let _enter = span.enter();
     let cf = async move {
         let mut grpc_client = {
             tracing::debug!("connecting");
-            grpc::connect(&peer).await
+            grpc::connect(&peer, tls.as_ref(), Some(expected_server_id)).await
         }
         .map_err(ConnectError::Transport)?;
 
@@ -60,7 +61,8 @@ pub fn connect(
 
         // Validate the server's node ID
         let peer_id = validate_peer_auth(hr.auth, &nonce)?;
-        // TODO: this should be better done by adding a network level authenticated / encrypted connection.
+        // If the connection is secured with TLS, the server certificate
+        // has already been checked against the expected node ID.
         if peer_id != expected_server_id {
             tracing::warn!(
                 "server id ({}) is different from the expected one ({}), aborting handshake",
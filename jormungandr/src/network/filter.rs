@@ -0,0 +1,251 @@
+//! Address based restrictions on p2p connections.
+//!
+//! The filter applies the configured CIDR allow and deny lists to
+//! inbound connections and to the peers learned from gossip, and caps
+//! the number of inbound connections accepted from a single subnet so that
+//! one operator cannot occupy all the inbound slots of the node.
+
+use crate::settings::start::config::P2pConnectionFilter;
+use ipnet::{IpNet, Ipv4Net, Ipv6Net, PrefixLenError};
+use jormungandr_lib::interfaces::RejectedConnections;
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+const DEFAULT_IPV4_SUBNET_PREFIX: u8 = 24;
+const DEFAULT_IPV6_SUBNET_PREFIX: u8 = 48;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("invalid CIDR address range '{range}'")]
+    InvalidRange {
+        range: String,
+        #[source]
+        source: ipnet::AddrParseError,
+    },
+    #[error("invalid subnet prefix length")]
+    InvalidPrefix(#[from] PrefixLenError),
+}
+
+/// The reason for refusing an inbound connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum Rejection {
+    #[error("peer address is not allowed")]
+    Denied,
+    #[error("too many inbound connections from subnet {0}")]
+    SubnetLimit(IpNet),
+}
+
+pub struct ConnectionFilter {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+    max_inbound_per_subnet: Option<usize>,
+    ipv4_subnet_prefix: u8,
+    ipv6_subnet_prefix: u8,
+    inbound: Mutex<HashMap<IpNet, usize>>,
+    rejected_denied: AtomicU64,
+    rejected_subnet_limit: AtomicU64,
+}
+
+impl Default for ConnectionFilter {
+    fn default() -> Self {
+        ConnectionFilter {
+            allow: Vec::new(),
+            deny: Vec::new(),
+            max_inbound_per_subnet: None,
+            ipv4_subnet_prefix: DEFAULT_IPV4_SUBNET_PREFIX,
+            ipv6_subnet_prefix: DEFAULT_IPV6_SUBNET_PREFIX,
+            inbound: Mutex::new(HashMap::new()),
+            rejected_denied: AtomicU64::new(0),
+            rejected_subnet_limit: AtomicU64::new(0),
+        }
+    }
+}
+
+impl ConnectionFilter {
+    pub fn new(config: &P2pConnectionFilter) -> Result<Self, Error> {
+        let ipv4_subnet_prefix = config
+            .ipv4_subnet_prefix
+            .unwrap_or(DEFAULT_IPV4_SUBNET_PREFIX);
+        let ipv6_subnet_prefix = config
+            .ipv6_subnet_prefix
+            .unwrap_or(DEFAULT_IPV6_SUBNET_PREFIX);
+        // validate the prefix lengths once so that subnet() cannot fail
+        Ipv4Net::new([0; 4].into(), ipv4_subnet_prefix)?;
+        Ipv6Net::new([0; 16].into(), ipv6_subnet_prefix)?;
+        Ok(ConnectionFilter {
+            allow: parse_ranges(&config.allow)?,
+            deny: parse_ranges(&config.deny)?,
+            max_inbound_per_subnet: config.max_inbound_per_subnet,
+            ipv4_subnet_prefix,
+            ipv6_subnet_prefix,
+            ..Default::default()
+        })
+    }
+
+    /// Checks the address against the allow and deny lists.
+    pub fn is_allowed(&self, addr: IpAddr) -> bool {
+        let addr = canonical(addr);
+        if self.deny.iter().any(|net| net.contains(&addr)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|net| net.contains(&addr))
+    }
+
+    /// Admits an inbound connection from the given address. The returned
+    /// slot holds a place in the per-subnet count until it is dropped.
+    pub fn accept_inbound(self: &Arc<Self>, addr: SocketAddr) -> Result<InboundSlot, Rejection> {
+        if !self.is_allowed(addr.ip()) {
+            self.rejected_denied.fetch_add(1, Ordering::Relaxed);
+            return Err(Rejection::Denied);
+        }
+        let subnet = self.subnet(addr.ip());
+        let mut inbound = self.inbound.lock().unwrap();
+        let count = inbound.entry(subnet).or_default();
+        if let Some(max) = self.max_inbound_per_subnet {
+            if *count >= max {
+                drop(inbound);
+                self.rejected_subnet_limit.fetch_add(1, Ordering::Relaxed);
+                return Err(Rejection::SubnetLimit(subnet));
+            }
+        }
+        *count += 1;
+        Ok(InboundSlot {
+            filter: Arc::clone(self),
+            subnet,
+        })
+    }
+
+    pub fn rejected(&self) -> RejectedConnections {
+        RejectedConnections {
+            denied: self.rejected_denied.load(Ordering::Relaxed),
+            subnet_limit: self.rejected_subnet_limit.load(Ordering::Relaxed),
+        }
+    }
+
+    fn subnet(&self, addr: IpAddr) -> IpNet {
+        let net = match canonical(addr) {
+            IpAddr::V4(addr) => Ipv4Net::new(addr, self.ipv4_subnet_prefix).map(IpNet::V4),
+            IpAddr::V6(addr) => Ipv6Net::new(addr, self.ipv6_subnet_prefix).map(IpNet::V6),
+        };
+        net.expect("prefix lengths are validated on construction")
+            .trunc()
+    }
+
+    fn release(&self, subnet: &IpNet) {
+        let mut inbound = self.inbound.lock().unwrap();
+        if let Some(count) = inbound.get_mut(subnet) {
+            *count -= 1;
+            if *count == 0 {
+                inbound.remove(subnet);
+            }
+        }
+    }
+}
+
+/// A place taken by an inbound connection in the count of its subnet.
+pub struct InboundSlot {
+    filter: Arc<ConnectionFilter>,
+    subnet: IpNet,
+}
+
+impl Drop for InboundSlot {
+    fn drop(&mut self) {
+        self.filter.release(&self.subnet);
+    }
+}
+
+fn parse_ranges(ranges: &[String]) -> Result<Vec<IpNet>, Error> {
+    ranges
+        .iter()
+        .map(|range| {
+            range.parse().map_err(|source| Error::InvalidRange {
+                range: range.clone(),
+                source,
+            })
+        })
+        .collect()
+}
+
+// IPv4 peers connecting to a dual-stack socket appear with
+// IPv4-mapped IPv6 addresses, the ranges are matched against the IPv4 form.
+fn canonical(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => match v6.octets() {
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, a, b, c, d] => IpAddr::from([a, b, c, d]),
+            _ => addr,
+        },
+        addr => addr,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(config: P2pConnectionFilter) -> Arc<ConnectionFilter> {
+        Arc::new(ConnectionFilter::new(&config).unwrap())
+    }
+
+    #[test]
+    fn deny_list_takes_precedence() {
+        let filter = filter(P2pConnectionFilter {
+            allow: vec!["10.0.0.0/8".to_owned()],
+            deny: vec!["10.1.0.0/16".to_owned()],
+            ..Default::default()
+        });
+        assert!(filter.is_allowed("10.2.3.4".parse().unwrap()));
+        assert!(!filter.is_allowed("10.1.3.4".parse().unwrap()));
+        assert!(!filter.is_allowed("192.168.0.1".parse().unwrap()));
+        assert!(filter.is_allowed("::ffff:10.2.3.4".parse().unwrap()));
+        assert!(filter
+            .accept_inbound("10.1.0.1:3000".parse().unwrap())
+            .is_err());
+        assert_eq!(filter.rejected().denied, 1);
+    }
+
+    #[test]
+    fn subnet_limit_is_released_on_drop() {
+        let filter = filter(P2pConnectionFilter {
+            max_inbound_per_subnet: Some(2),
+            ..Default::default()
+        });
+        let first = filter
+            .accept_inbound("1.2.3.4:3000".parse().unwrap())
+            .unwrap();
+        let _second = filter
+            .accept_inbound("1.2.3.5:3000".parse().unwrap())
+            .unwrap();
+        assert!(matches!(
+            filter.accept_inbound("1.2.3.6:3000".parse().unwrap()),
+            Err(Rejection::SubnetLimit(_))
+        ));
+        assert!(filter
+            .accept_inbound("1.2.4.6:3000".parse().unwrap())
+            .is_ok());
+        drop(first);
+        assert!(filter
+            .accept_inbound("1.2.3.6:3000".parse().unwrap())
+            .is_ok());
+        assert_eq!(filter.rejected().subnet_limit, 1);
+    }
+
+    #[test]
+    fn invalid_config() {
+        assert!(ConnectionFilter::new(&P2pConnectionFilter {
+            deny: vec!["10.0.0.0/33".to_owned()],
+            ..Default::default()
+        })
+        .is_err());
+        assert!(ConnectionFilter::new(&P2pConnectionFilter {
+            ipv6_subnet_prefix: Some(129),
+            ..Default::default()
+        })
+        .is_err());
+    }
+}
@@ -1,16 +1,23 @@
 use crate::{
     blockcfg::{Block, HeaderHash},
-    network::{concurrency_limits, convert::Decode, keepalive_durations},
+    network::{
+        concurrency_limits,
+        convert::Decode,
+        keepalive_durations,
+        tls::{self, TlsContext},
+    },
     settings::start::network::{Peer, Protocol},
+    topology::NodeId,
 };
 pub use chain_network::grpc::client::{
     BlockSubscription, FragmentSubscription, GossipSubscription,
 };
 use chain_network::{data as net_data, error as net_error, grpc::client::Builder};
 use futures::prelude::*;
-use std::{convert::TryFrom, net::SocketAddr};
+use std::{convert::TryFrom, net::SocketAddr, sync::Arc};
 use thiserror::Error;
-use tonic::transport;
+use tokio::net::TcpStream;
+use tonic::transport::{self, Uri};
 
 #[derive(Error, Debug)]
 pub enum FetchBlockError {
@@ -33,18 +40,74 @@ pub type ConnectError = transport::Error;
 
 pub type Client = chain_network::grpc::Client<tonic::transport::Channel>;
 
-pub async fn connect(peer: &Peer) -> Result<Client, ConnectError> {
-    connect_internal(peer, Builder::new()).await
+/// Connects to the peer over TLS if the context is provided, otherwise
+/// over plaintext. If `server_id` is given, the TLS certificate of the peer
+/// must be bound to that node ID.
+///
+/// Unless TLS is required, a connection failing in the TLS handshake is
+/// retried over plaintext for compatibility with peers that do not support
+/// TLS. A certificate that is invalid or not bound to `server_id` fails the
+/// connection regardless.
+pub async fn connect(
+    peer: &Peer,
+    tls: Option<&Arc<TlsContext>>,
+    server_id: Option<NodeId>,
+) -> Result<Client, ConnectError> {
+    match tls {
+        None => connect_internal(peer, Builder::new()).await,
+        Some(tls) => match connect_tls(peer, Builder::new(), tls.clone(), server_id).await {
+            Err(e) if !tls.is_required() && allows_plaintext_fallback(&e) => {
+                tracing::debug!(reason = %e, "TLS connection failed, falling back to plaintext");
+                connect_internal(peer, Builder::new()).await
+            }
+            res => res,
+        },
+    }
+}
+
+fn allows_plaintext_fallback(e: &ConnectError) -> bool {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(e);
+    while let Some(err) = source {
+        if let Some(e) = err.downcast_ref::<tls::ConnectError>() {
+            return e.allows_plaintext_fallback();
+        }
+        source = err.source();
+    }
+    false
 }
 
 async fn connect_internal(peer: &Peer, builder: Builder) -> Result<Client, ConnectError> {
+    builder.connect(configure_endpoint(peer)).await
+}
+
+async fn connect_tls(
+    peer: &Peer,
+    builder: Builder,
+    tls: Arc<TlsContext>,
+    server_id: Option<NodeId>,
+) -> Result<Client, ConnectError> {
+    let addr = peer.connection;
+    let connector = tower::service_fn(move |_: Uri| {
+        let tls = tls.clone();
+        async move {
+            let stream = TcpStream::connect(addr).await?;
+            stream.set_nodelay(true)?;
+            tls.connect(stream, server_id).await
+        }
+    });
+    let channel = configure_endpoint(peer)
+        .connect_with_connector(connector)
+        .await?;
+    Ok(builder.build(channel))
+}
+
+fn configure_endpoint(peer: &Peer) -> transport::Endpoint {
     assert!(peer.protocol == Protocol::Grpc);
-    let endpoint = destination_endpoint(peer.connection)
+    destination_endpoint(peer.connection)
         .concurrency_limit(concurrency_limits::CLIENT_REQUESTS)
         .tcp_keepalive(Some(keepalive_durations::TCP))
         .http2_keep_alive_interval(keepalive_durations::HTTP2)
-        .timeout(peer.timeout);
-    builder.connect(endpoint).await
+        .timeout(peer.timeout)
 }
 
 fn destination_endpoint(addr: SocketAddr) -> transport::Endpoint {
@@ -54,9 +117,13 @@ fn destination_endpoint(addr: SocketAddr) -> transport::Endpoint {
 
 // Fetches a block from a network peer.
 // This function is used during node bootstrap to fetch the genesis block.
-pub async fn fetch_block(peer: &Peer, hash: HeaderHash) -> Result<Block, FetchBlockError> {
+pub async fn fetch_block(
+    peer: &Peer,
+    tls: Option<&Arc<TlsContext>>,
+    hash: HeaderHash,
+) -> Result<Block, FetchBlockError> {
     tracing::info!("fetching block {}", hash);
-    let mut client = connect(peer)
+    let mut client = connect(peer, tls, None)
         .await
         .map_err(|err| FetchBlockError::Connect { source: err })?;
     let block_id = net_data::BlockId::try_from(hash.as_bytes()).unwrap();
@@ -1,13 +1,36 @@
 use super::super::{
-    concurrency_limits, keepalive_durations, service::NodeService, Channels, GlobalStateR,
-    ListenError,
+    concurrency_limits,
+    filter::InboundSlot,
+    keepalive_durations,
+    service::NodeService,
+    tls::{P2pStream, TlsContext},
+    Channels, GlobalStateR, ListenError,
 };
 use crate::settings::start::network::Listen;
 use chain_network::grpc::{self, watch::server::Server as WatchServer};
-use tonic::transport::Server;
+use futures::Stream;
+use socket2::{SockRef, TcpKeepalive};
+use std::{
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::{server::Connected, server::TcpConnectInfo, Server};
 use tracing::{span, Level};
 use tracing_futures::Instrument;
 
+// The maximum number of inbound connections that have been accepted
+// but have not been picked up by the server yet.
+const ACCEPT_BACKLOG: usize = 64;
+
 pub async fn run_listen_socket(
     listen: &Listen,
     state: GlobalStateR,
@@ -18,17 +41,149 @@ pub async fn run_listen_socket(
     let span = span!(parent: &state.span, Level::TRACE, "listen_socket", local_addr = %sockaddr.to_string());
     async {
         tracing::info!("listening and accepting gRPC connections");
-        let service = grpc::server::Builder::new().build(NodeService::new(channels, state));
+        if let Some(tls) = &state.config.tls {
+            tracing::info!(required = tls.is_required(), "p2p TLS is enabled");
+        }
+        let service = grpc::server::Builder::new().build(NodeService::new(channels, state.clone()));
+
+        let listener = TcpListener::bind(sockaddr)
+            .await
+            .map_err(|cause| ListenError::new(cause, sockaddr))?;
 
         Server::builder()
             .concurrency_limit_per_connection(concurrency_limits::SERVER_REQUESTS)
-            .tcp_keepalive(Some(keepalive_durations::TCP))
             .add_service(service)
             .add_service(watch_service)
-            .serve(sockaddr)
+            .serve_with_incoming(accept(listener, state))
             .await
-            .map_err(|cause| ListenError { cause, sockaddr })
+            .map_err(|cause| ListenError::new(cause, sockaddr))
     }
     .instrument(span)
     .await
 }
+
+// Accepts connections on the listener, refusing the ones not admitted by
+// the connection filter. TLS handshakes are performed in separate tasks
+// so that a slow or malicious client cannot stall the others.
+fn accept(
+    listener: TcpListener,
+    state: GlobalStateR,
+) -> impl Stream<Item = Result<InboundStream, io::Error>> {
+    let (sender, receiver) = mpsc::channel(ACCEPT_BACKLOG);
+    tokio::spawn(async move {
+        while !sender.is_closed() {
+            let (stream, addr) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    tracing::warn!(reason = %e, "failed to accept a p2p connection");
+                    continue;
+                }
+            };
+            let slot = match state.config.connection_filter.accept_inbound(addr) {
+                Ok(slot) => slot,
+                Err(e) => {
+                    tracing::info!(peer = %addr, reason = %e, "inbound p2p connection refused");
+                    continue;
+                }
+            };
+            configure_socket(&stream, addr);
+            match &state.config.tls {
+                None => {
+                    let stream = InboundStream {
+                        stream: P2pStream::Plain(stream),
+                        _slot: slot,
+                    };
+                    let _ = sender.send(Ok(stream)).await;
+                }
+                Some(tls) => {
+                    let tls = Arc::clone(tls);
+                    let state = state.clone();
+                    let sender = sender.clone();
+                    tokio::spawn(async move {
+                        if let Some(stream) = accept_tls(stream, addr, &tls, state).await {
+                            let stream = InboundStream {
+                                stream,
+                                _slot: slot,
+                            };
+                            let _ = sender.send(Ok(stream)).await;
+                        }
+                    });
+                }
+            }
+        }
+    });
+    ReceiverStream::new(receiver)
+}
+
+async fn accept_tls(
+    stream: TcpStream,
+    addr: SocketAddr,
+    tls: &TlsContext,
+    state: GlobalStateR,
+) -> Option<P2pStream> {
+    let handshake = tls.accept(stream, state.tls_sessions.clone());
+    match tokio::time::timeout(keepalive_durations::TLS_HANDSHAKE, handshake).await {
+        Ok(Ok(stream)) => Some(stream),
+        Ok(Err(e)) => {
+            tracing::info!(peer = %addr, reason = %e, "inbound p2p connection rejected");
+            None
+        }
+        Err(_) => {
+            tracing::info!(peer = %addr, "TLS handshake with inbound p2p connection timed out");
+            None
+        }
+    }
+}
+
+fn configure_socket(stream: &TcpStream, addr: SocketAddr) {
+    if let Err(e) = stream.set_nodelay(true) {
+        tracing::debug!(peer = %addr, reason = %e, "failed to set TCP_NODELAY");
+    }
+    let keepalive = TcpKeepalive::new().with_time(keepalive_durations::TCP);
+    if let Err(e) = SockRef::from(stream).set_tcp_keepalive(&keepalive) {
+        tracing::debug!(peer = %addr, reason = %e, "failed to set TCP keepalive");
+    }
+}
+
+// An inbound connection, holding its place in the count of connections
+// from its subnet for as long as it is open.
+struct InboundStream {
+    stream: P2pStream,
+    _slot: InboundSlot,
+}
+
+impl Connected for InboundStream {
+    type ConnectInfo = TcpConnectInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.stream.connect_info()
+    }
+}
+
+impl AsyncRead for InboundStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for InboundStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
@@ -8,10 +8,12 @@
 pub mod bootstrap;
 mod client;
 mod convert;
+pub mod filter;
 mod grpc;
 pub mod p2p;
 mod service;
 mod subscription;
+pub mod tls;
 
 use self::convert::Encode;
 use futures::{future, prelude::*};
@@ -62,6 +64,9 @@ mod keepalive_durations {
 
     // HTTP/2 keepalive for client connections
     pub const HTTP2: Duration = Duration::from_secs(120);
+
+    // Time allowed for an inbound client to complete the TLS handshake
+    pub const TLS_HANDSHAKE: Duration = Duration::from_secs(10);
 }
 
 mod security_params {
@@ -69,7 +74,7 @@ mod security_params {
 }
 
 pub use self::bootstrap::Error as BootstrapError;
-use self::{client::ConnectError, p2p::comm::Peers};
+use self::{client::ConnectError, p2p::comm::Peers, tls::TlsSessions};
 use crate::{
     blockcfg::{Block, HeaderHash},
     blockchain::{Blockchain as NewBlockchain, Tip},
@@ -92,16 +97,27 @@ use std::{
     },
     time::Duration,
 };
-use tonic::transport;
 use tracing::{instrument, span, Level, Span};
 use tracing_futures::Instrument;
 
 #[derive(Debug)]
 pub struct ListenError {
-    cause: transport::Error,
+    cause: Box<dyn error::Error + Send + Sync>,
     sockaddr: SocketAddr,
 }
 
+impl ListenError {
+    fn new<E>(cause: E, sockaddr: SocketAddr) -> Self
+    where
+        E: Into<Box<dyn error::Error + Send + Sync>>,
+    {
+        ListenError {
+            cause: cause.into(),
+            sockaddr,
+        }
+    }
+}
+
 impl fmt::Display for ListenError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -114,7 +130,7 @@ impl fmt::Display for ListenError {
 
 impl error::Error for ListenError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
-        Some(&self.cause)
+        Some(&*self.cause)
     }
 }
 
@@ -153,6 +169,7 @@ pub struct GlobalState {
     config: Configuration,
     peers: Peers,
     keypair: NodeKeyPair,
+    tls_sessions: Arc<TlsSessions>,
     span: Span,
 
     connected_count: AtomicUsize,
@@ -179,6 +196,7 @@ impl GlobalState {
             config,
             peers,
             keypair,
+            tls_sessions: Arc::new(TlsSessions::default()),
             span,
             connected_count: AtomicUsize::new(0),
         }
@@ -333,6 +351,9 @@ async fn handle_network_input(
             NetworkMsg::PeerInfo(reply) => {
                 state.peers.infos().map(|infos| reply.reply_ok(infos)).await;
             }
+            NetworkMsg::RejectedConnections(reply) => {
+                reply.reply_ok(state.config.connection_filter.rejected());
+            }
         };
         tracing::trace!("item handling finished");
     }
@@ -467,6 +488,10 @@ fn connect_and_propagate(
             return;
         }
     }
+    if !state.config.connection_filter.is_allowed(addr.ip()) {
+        tracing::debug!(peer = %addr, "peer address is not allowed, not connecting");
+        return;
+    }
     drop(_enter);
     let peer = Peer::new(addr);
     let conn_span = span!(parent: &state.span, Level::DEBUG, "client", %addr, %id);
@@ -567,7 +592,7 @@ async fn netboot_peers(config: &Configuration, parent_span: &Span) -> BootstrapP
             peer_addr = %tpeer.to_string()
         );
         let received_peers = async move {
-            let res = bootstrap::peers_from_trusted_peer(&Peer::new(*tpeer))
+            let res = bootstrap::peers_from_trusted_peer(&Peer::new(*tpeer), config.tls.as_ref())
                 .await
                 .unwrap_or_else(|e| {
                     tracing::warn!(
@@ -651,6 +676,7 @@ pub async fn bootstrap(
             span!(parent: span, Level::DEBUG, "bootstrap", peer_addr = %peer.address().to_string());
         let res = bootstrap::bootstrap_from_peer(
             &Peer::new(peer.address()),
+            config.tls.as_ref(),
             blockchain.clone(),
             branch.clone(),
             cancellation_token.clone(),
@@ -731,7 +757,7 @@ pub async fn fetch_block(
         for address in trusted_peers_shuffled(config) {
             let peer_span = span!(Level::TRACE, "peer_address", address = %address.to_string());
             let peer = Peer::new(address);
-            match grpc::fetch_block(&peer, hash)
+            match grpc::fetch_block(&peer, config.tls.as_ref(), hash)
                 .instrument(peer_span.clone())
                 .await
             {
@@ -57,10 +57,19 @@ impl Node for NodeService {
     type GossipService = Self;
 
     async fn handshake(&self, peer: Peer, nonce: &[u8]) -> Result<HandshakeResponse, Error> {
+        let addr = peer.addr();
+        if let Some(tls) = &self.global_state.config.tls {
+            if tls.is_required() && self.global_state.tls_sessions.peer_id(addr).is_none() {
+                return Err(Error::new(
+                    ErrorCode::FailedPrecondition,
+                    "TLS is required to connect to this node",
+                ));
+            }
+        }
+
         let block0_id = BlockId::try_from(self.global_state.block0_hash.as_bytes()).unwrap();
         let keypair = &self.global_state.keypair;
         let auth = keypair.sign(nonce);
-        let addr = peer.addr();
         let nonce = self.global_state.peers.generate_auth_nonce(addr).await;
 
         Ok(HandshakeResponse {
@@ -74,6 +83,23 @@ impl Node for NodeService {
     async fn client_auth(&self, peer: Peer, auth: AuthenticatedNodeId) -> Result<(), Error> {
         let addr = peer.addr();
         let id = auth.id().clone().decode()?;
+        // The node ID must match the key of the client TLS certificate,
+        // so that the authenticated identity cannot be relayed over
+        // a TLS session established by another node.
+        if let Some(tls_id) = self.global_state.tls_sessions.peer_id(addr) {
+            if tls_id != id {
+                tracing::warn!(
+                    %addr,
+                    %id,
+                    %tls_id,
+                    "client node ID does not match its TLS certificate"
+                );
+                return Err(Error::new(
+                    ErrorCode::FailedPrecondition,
+                    "node ID does not match the TLS certificate",
+                ));
+            }
+        }
         self.global_state
             .peers
             .server_complete_handshake(addr, id, |nonce| auth.verify(&nonce[..]))
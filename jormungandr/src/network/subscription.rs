@@ -21,11 +21,12 @@ use std::{
 use tracing_futures::Instrument;
 
 fn filter_gossip_node(node: &Gossip, config: &Configuration) -> bool {
-    if config.allow_private_addresses {
+    let valid = if config.allow_private_addresses {
         node.has_valid_address()
     } else {
         node.is_global()
-    }
+    };
+    valid && config.connection_filter.is_allowed(node.address().ip())
 }
 
 fn handle_mbox_error(err: async_msg::SendError) -> Error {
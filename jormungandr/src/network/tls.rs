@@ -0,0 +1,601 @@
+//! TLS transport for the node-to-node gRPC connections.
+//!
+//! Every node presents a self-signed X.509 certificate generated from its
+//! ed25519 node key, so the key securing the transport can be matched against
+//! the node ID authenticated in the protocol handshake. No certificate
+//! authority is involved: a certificate is acceptable as long as it carries
+//! an ed25519 key and the peer proves possession of it during the TLS handshake.
+
+use super::p2p::Address;
+use crate::topology::NodeId;
+use chain_crypto::Ed25519;
+use jormungandr_lib::crypto::key::SigningKey;
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    io,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::SystemTime,
+};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::TcpStream,
+};
+use tokio_rustls::{
+    rustls::{
+        self,
+        client::{ServerCertVerified, ServerCertVerifier, ServerName},
+        server::{ClientCertVerified, ClientCertVerifier},
+        Certificate, ClientConfig, DistinguishedNames, PrivateKey, ServerConfig,
+    },
+    TlsAcceptor, TlsConnector,
+};
+use tonic::transport::server::{Connected, TcpConnectInfo};
+
+/// The first byte of a TLS handshake record, used to tell TLS connections
+/// from plaintext HTTP/2 ones on the same listening socket.
+const TLS_HANDSHAKE_RECORD: u8 = 0x16;
+
+// PKCS#8 v1 envelope for a raw ed25519 private key (RFC 8410)
+const ED25519_PKCS8_PREFIX: [u8; 16] = [
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+
+// DER encoding of the ed25519 AlgorithmIdentifier (RFC 8410)
+const ED25519_ALGORITHM: [u8; 7] = [0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70];
+// DER encoding of the commonName attribute type
+const COMMON_NAME_OID: [u8; 5] = [0x06, 0x03, 0x55, 0x04, 0x03];
+
+const DER_INTEGER: u8 = 0x02;
+const DER_BIT_STRING: u8 = 0x03;
+const DER_UTF8_STRING: u8 = 0x0c;
+const DER_GENERALIZED_TIME: u8 = 0x18;
+const DER_SEQUENCE: u8 = 0x30;
+const DER_SET: u8 = 0x31;
+const DER_VERSION: u8 = 0xa0;
+
+// the certificates are not checked for expiry, the validity covers the
+// lifetime of any node
+const NOT_BEFORE: &[u8] = b"19750101000000Z";
+const NOT_AFTER: &[u8] = b"40960101000000Z";
+
+const ALPN_H2: &[u8] = b"h2";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("invalid p2p TLS configuration")]
+    Config(#[from] rustls::Error),
+}
+
+/// Failure to secure an outbound connection.
+#[derive(Debug, Error)]
+pub enum ConnectError {
+    #[error("connection failed")]
+    Io(#[from] io::Error),
+    #[error("TLS handshake failed")]
+    Handshake(#[source] io::Error),
+    #[error("TLS identity of the peer rejected")]
+    Identity(#[source] io::Error),
+}
+
+impl ConnectError {
+    /// Whether the failure may be caused by a peer that does not support
+    /// TLS, so that the connection can be retried over plaintext.
+    /// A certificate that is invalid or bound to another node is never
+    /// a reason to downgrade.
+    pub fn allows_plaintext_fallback(&self) -> bool {
+        matches!(self, ConnectError::Handshake(_))
+    }
+
+    fn from_handshake(e: io::Error) -> Self {
+        let certificate_error = e
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<rustls::Error>())
+            .map(|inner| {
+                matches!(
+                    inner,
+                    rustls::Error::InvalidCertificateEncoding
+                        | rustls::Error::InvalidCertificateSignatureType
+                        | rustls::Error::InvalidCertificateSignature
+                        | rustls::Error::InvalidCertificateData(_)
+                        | rustls::Error::NoCertificatesPresented
+                        | rustls::Error::General(_)
+                )
+            })
+            .unwrap_or(false);
+
+        if certificate_error {
+            ConnectError::Identity(e)
+        } else {
+            ConnectError::Handshake(e)
+        }
+    }
+}
+
+/// TLS settings shared by the p2p listener and the outbound connections.
+pub struct TlsContext {
+    acceptor: TlsAcceptor,
+    connector: TlsConnector,
+    required: bool,
+}
+
+impl TlsContext {
+    pub fn new(node_key: &SigningKey<Ed25519>, required: bool) -> Result<Self, Error> {
+        let (cert, key) = self_signed_certificate(node_key);
+
+        let mut server = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(Arc::new(NodeKeyVerifier))
+            .with_single_cert(vec![cert.clone()], key.clone())?;
+        server.alpn_protocols = vec![ALPN_H2.to_vec()];
+
+        let mut client = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(Arc::new(NodeKeyVerifier))
+            .with_single_cert(vec![cert], key)?;
+        client.alpn_protocols = vec![ALPN_H2.to_vec()];
+
+        Ok(TlsContext {
+            acceptor: TlsAcceptor::from(Arc::new(server)),
+            connector: TlsConnector::from(Arc::new(client)),
+            required,
+        })
+    }
+
+    /// Whether connections with peers that do not negotiate TLS are refused.
+    pub fn is_required(&self) -> bool {
+        self.required
+    }
+
+    /// Secures an outbound connection. If `expected_id` is given, the
+    /// connection fails unless the server certificate is bound to that node ID.
+    pub async fn connect(
+        &self,
+        stream: TcpStream,
+        expected_id: Option<NodeId>,
+    ) -> Result<tokio_rustls::client::TlsStream<TcpStream>, ConnectError> {
+        let server_name = ServerName::IpAddress(stream.peer_addr()?.ip());
+        let stream = self
+            .connector
+            .connect(server_name, stream)
+            .await
+            .map_err(ConnectError::from_handshake)?;
+        let peer_id =
+            peer_node_id(stream.get_ref().1.peer_certificates()).map_err(ConnectError::Identity)?;
+        match expected_id {
+            Some(expected_id) if expected_id != peer_id => {
+                Err(ConnectError::Identity(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!(
+                        "TLS certificate of the server is bound to node {}, expected {}",
+                        peer_id, expected_id
+                    ),
+                )))
+            }
+            _ => Ok(stream),
+        }
+    }
+
+    /// Accepts an inbound connection, securing it if the client has started
+    /// a TLS handshake. Plaintext connections are refused if TLS is required.
+    pub async fn accept(
+        &self,
+        stream: TcpStream,
+        sessions: Arc<TlsSessions>,
+    ) -> io::Result<P2pStream> {
+        let mut first_byte = [0u8; 1];
+        stream.peek(&mut first_byte).await?;
+        if first_byte[0] != TLS_HANDSHAKE_RECORD {
+            if self.required {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "plaintext connection refused, TLS is required",
+                ));
+            }
+            return Ok(P2pStream::Plain(stream));
+        }
+
+        let addr = stream.peer_addr()?;
+        let stream = self.acceptor.accept(stream).await?;
+        let peer_id = peer_node_id(stream.get_ref().1.peer_certificates())?;
+        let session = sessions.register(addr, peer_id);
+        Ok(P2pStream::Tls(Box::new(stream), session))
+    }
+}
+
+/// Node IDs bound to the TLS certificates of the currently open
+/// inbound connections, indexed by the remote address.
+#[derive(Default)]
+pub struct TlsSessions {
+    inner: Mutex<HashMap<Address, NodeId>>,
+}
+
+impl TlsSessions {
+    fn register(self: Arc<Self>, addr: Address, id: NodeId) -> TlsSession {
+        self.inner.lock().unwrap().insert(addr, id);
+        TlsSession {
+            sessions: self,
+            addr,
+        }
+    }
+
+    /// Returns the node ID bound to the TLS certificate presented by
+    /// the client at the given address, or `None` for plaintext clients.
+    pub fn peer_id(&self, addr: Address) -> Option<NodeId> {
+        self.inner.lock().unwrap().get(&addr).copied()
+    }
+}
+
+/// Keeps the TLS binding registered for as long as the connection is open.
+pub struct TlsSession {
+    sessions: Arc<TlsSessions>,
+    addr: Address,
+}
+
+impl Drop for TlsSession {
+    fn drop(&mut self) {
+        self.sessions.inner.lock().unwrap().remove(&self.addr);
+    }
+}
+
+/// An inbound p2p connection, with or without TLS.
+pub enum P2pStream {
+    Plain(TcpStream),
+    Tls(Box<tokio_rustls::server::TlsStream<TcpStream>>, TlsSession),
+}
+
+impl Connected for P2pStream {
+    type ConnectInfo = TcpConnectInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        match self {
+            P2pStream::Plain(stream) => stream.connect_info(),
+            P2pStream::Tls(stream, _) => stream.get_ref().0.connect_info(),
+        }
+    }
+}
+
+impl AsyncRead for P2pStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            P2pStream::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            P2pStream::Tls(stream, _) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for P2pStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            P2pStream::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            P2pStream::Tls(stream, _) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            P2pStream::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            P2pStream::Tls(stream, _) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            P2pStream::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            P2pStream::Tls(stream, _) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+/// Encodes a DER element with the given tag and contents.
+fn der(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    let len = contents.len();
+    if len < 0x80 {
+        encoded.push(len as u8);
+    } else {
+        let len_bytes = len.to_be_bytes();
+        let len_bytes = &len_bytes[len.leading_zeros() as usize / 8..];
+        encoded.push(0x80 | len_bytes.len() as u8);
+        encoded.extend_from_slice(len_bytes);
+    }
+    encoded.extend_from_slice(contents);
+    encoded
+}
+
+/// Splits the first DER element off the input, returning its tag and contents.
+fn der_split(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, input) = input.split_first()?;
+    let (&first, input) = input.split_first()?;
+    let (len, input) = if first < 0x80 {
+        (first as usize, input)
+    } else {
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > std::mem::size_of::<usize>() || input.len() < count {
+            return None;
+        }
+        let (len_bytes, input) = input.split_at(count);
+        let len = len_bytes
+            .iter()
+            .fold(0usize, |len, byte| (len << 8) | *byte as usize);
+        (len, input)
+    };
+    if input.len() < len {
+        return None;
+    }
+    let (contents, rest) = input.split_at(len);
+    Some((tag, contents, rest))
+}
+
+/// Builds the self-signed X.509 v3 certificate of the node key, with the
+/// bech32 node ID as the common name of the subject and the issuer.
+fn self_signed_certificate(node_key: &SigningKey<Ed25519>) -> (Certificate, PrivateKey) {
+    let secret = node_key.clone().into_secret_key().leak_secret();
+    let mut pkcs8 = ED25519_PKCS8_PREFIX.to_vec();
+    pkcs8.extend_from_slice(secret.as_ref());
+
+    let public_key = node_key.identifier().into_public_key();
+    let name = der(
+        DER_SEQUENCE,
+        &der(
+            DER_SET,
+            &der(
+                DER_SEQUENCE,
+                &[
+                    COMMON_NAME_OID.to_vec(),
+                    der(
+                        DER_UTF8_STRING,
+                        node_key.identifier().to_bech32_str().as_bytes(),
+                    ),
+                ]
+                .concat(),
+            ),
+        ),
+    );
+    let validity = der(
+        DER_SEQUENCE,
+        &[
+            der(DER_GENERALIZED_TIME, NOT_BEFORE),
+            der(DER_GENERALIZED_TIME, NOT_AFTER),
+        ]
+        .concat(),
+    );
+    let spki = der(
+        DER_SEQUENCE,
+        &[
+            ED25519_ALGORITHM.to_vec(),
+            der(DER_BIT_STRING, &[&[0u8][..], public_key.as_ref()].concat()),
+        ]
+        .concat(),
+    );
+    let tbs = der(
+        DER_SEQUENCE,
+        &[
+            der(DER_VERSION, &der(DER_INTEGER, &[2])),
+            der(DER_INTEGER, &[1]),
+            ED25519_ALGORITHM.to_vec(),
+            name.clone(),
+            validity,
+            name,
+            spki,
+        ]
+        .concat(),
+    );
+    let signature = node_key.as_ref().sign(&tbs);
+    let cert = der(
+        DER_SEQUENCE,
+        &[
+            tbs,
+            ED25519_ALGORITHM.to_vec(),
+            der(DER_BIT_STRING, &[&[0u8][..], signature.as_ref()].concat()),
+        ]
+        .concat(),
+    );
+
+    (Certificate(cert), PrivateKey(pkcs8))
+}
+
+/// Finds the SubjectPublicKeyInfo of a DER encoded X.509 certificate.
+fn subject_public_key_info(cert: &[u8]) -> Option<&[u8]> {
+    let cert = match der_split(cert)? {
+        (DER_SEQUENCE, cert, _) => cert,
+        _ => return None,
+    };
+    let mut fields = match der_split(cert)? {
+        (DER_SEQUENCE, tbs, _) => tbs,
+        _ => return None,
+    };
+    if fields.first() == Some(&DER_VERSION) {
+        fields = der_split(fields)?.2;
+    }
+    // serial number, signature algorithm, issuer, validity and subject
+    for _ in 0..5 {
+        fields = der_split(fields)?.2;
+    }
+    match der_split(fields)? {
+        (DER_SEQUENCE, spki, _) => Some(spki),
+        _ => None,
+    }
+}
+
+/// Extracts the node ID from the ed25519 key of the peer's end-entity certificate.
+fn node_id_from_certificate(cert: &Certificate) -> Result<NodeId, rustls::Error> {
+    let spki = subject_public_key_info(&cert.0).ok_or(rustls::Error::InvalidCertificateEncoding)?;
+    let key = spki
+        .strip_prefix(&ED25519_ALGORITHM[..])
+        .and_then(der_split)
+        .and_then(|(tag, key, _)| match (tag, key) {
+            (DER_BIT_STRING, [0, key @ ..]) => Some(key),
+            _ => None,
+        })
+        .ok_or_else(|| {
+            rustls::Error::General("peer certificate does not carry an ed25519 node key".into())
+        })?;
+    NodeId::try_from(key).map_err(|_| {
+        rustls::Error::General("peer certificate carries an invalid ed25519 key".into())
+    })
+}
+
+fn peer_node_id(certs: Option<&[Certificate]>) -> io::Result<NodeId> {
+    certs
+        .and_then(|certs| certs.first())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "peer did not present a TLS certificate",
+            )
+        })
+        .and_then(|cert| {
+            node_id_from_certificate(cert)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        })
+}
+
+/// Accepts any self-signed certificate carrying an ed25519 key.
+/// Possession of the key is checked by rustls when verifying the handshake
+/// signature, and the key itself is checked against the node ID
+/// authenticated in the protocol handshake.
+struct NodeKeyVerifier;
+
+impl ServerCertVerifier for NodeKeyVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        node_id_from_certificate(end_entity)?;
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn request_scts(&self) -> bool {
+        false
+    }
+}
+
+impl ClientCertVerifier for NodeKeyVerifier {
+    fn client_auth_root_subjects(&self) -> Option<DistinguishedNames> {
+        Some(Vec::new())
+    }
+
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _now: SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        node_id_from_certificate(end_entity)?;
+        Ok(ClientCertVerified::assertion())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
+    use tokio::{io::AsyncWriteExt, net::TcpListener};
+
+    fn node_key(seed: u64) -> SigningKey<Ed25519> {
+        SigningKey::generate(ChaChaRng::seed_from_u64(seed))
+    }
+
+    fn node_id(key: &SigningKey<Ed25519>) -> NodeId {
+        NodeId::try_from(key.identifier().into_public_key().as_ref()).unwrap()
+    }
+
+    #[test]
+    fn certificate_round_trip() {
+        let key = node_key(0);
+        let (cert, _) = self_signed_certificate(&key);
+        assert!(node_id_from_certificate(&cert).unwrap() == node_id(&key));
+
+        let other_key = node_key(1);
+        let (other_cert, _) = self_signed_certificate(&other_key);
+        assert!(node_id_from_certificate(&other_cert).unwrap() != node_id(&key));
+    }
+
+    #[test]
+    fn truncated_certificate_is_rejected() {
+        let (cert, _) = self_signed_certificate(&node_key(0));
+        let truncated = Certificate(cert.0[..cert.0.len() - 80].to_vec());
+        assert!(node_id_from_certificate(&truncated).is_err());
+        assert!(node_id_from_certificate(&Certificate(Vec::new())).is_err());
+    }
+
+    async fn handshake(
+        expected_id: NodeId,
+    ) -> (
+        Result<tokio_rustls::client::TlsStream<TcpStream>, ConnectError>,
+        Arc<TlsSessions>,
+    ) {
+        let server_key = node_key(0);
+        let client_key = node_key(1);
+        let server = TlsContext::new(&server_key, true).unwrap();
+        let client = TlsContext::new(&client_key, true).unwrap();
+        let sessions = Arc::new(TlsSessions::default());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = listener.local_addr().unwrap();
+        let server_sessions = Arc::clone(&sessions);
+        let accepting = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            server.accept(stream, server_sessions).await
+        });
+
+        let stream = TcpStream::connect(server_addr).await.unwrap();
+        let client_addr = stream.local_addr().unwrap();
+        let connected = client.connect(stream, Some(expected_id)).await;
+
+        if connected.is_ok() {
+            let _inbound = accepting.await.unwrap().unwrap();
+            assert!(sessions.peer_id(client_addr) == Some(node_id(&client_key)));
+        }
+        (connected, sessions)
+    }
+
+    #[tokio::test]
+    async fn connection_bound_to_the_node_keys() {
+        let (connected, _) = handshake(node_id(&node_key(0))).await;
+        assert!(connected.is_ok());
+    }
+
+    #[tokio::test]
+    async fn wrong_node_id_is_rejected_without_fallback() {
+        let (connected, _) = handshake(node_id(&node_key(2))).await;
+        match connected {
+            Err(e @ ConnectError::Identity(_)) => assert!(!e.allows_plaintext_fallback()),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("connection to the wrong node succeeded"),
+        }
+    }
+
+    #[tokio::test]
+    async fn plaintext_peer_allows_fallback() {
+        let client = TlsContext::new(&node_key(1), false).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            // the preface of a plaintext HTTP/2 server
+            stream.write_all(b"\0\0\0\x04\0\0\0\0\0").await.unwrap();
+        });
+
+        let stream = TcpStream::connect(server_addr).await.unwrap();
+        let error = client.connect(stream, None).await.err().unwrap();
+        assert!(error.allows_plaintext_fallback());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::rest::{v0::logic, ContextLock};
+use jormungandr_lib::interfaces::SigningWatermarks;
 use warp::{reject::Reject, Rejection, Reply};
 
 impl Reject for logic::Error {}
@@ -120,6 +121,41 @@ pub async fn get_leaders_logs(context: ContextLock) -> Result<impl Reply, Reject
         .map_err(warp::reject::custom)
 }
 
+pub async fn get_leaders_schedule(context: ContextLock) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::get_leaders_schedule(&context)
+        .await
+        .map(|r| warp::reply::json(&r))
+        .map_err(warp::reject::custom)
+}
+
+pub async fn reload_leaders(context: ContextLock) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::reload_leaders(&context)
+        .await
+        .map(|r| warp::reply::json(&r))
+        .map_err(warp::reject::custom)
+}
+
+pub async fn handoff_leaders(context: ContextLock) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::handoff_leaders(&context)
+        .await
+        .map(|r| warp::reply::json(&r))
+        .map_err(warp::reject::custom)
+}
+
+pub async fn take_over_leaders(
+    watermarks: SigningWatermarks,
+    context: ContextLock,
+) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::take_over_leaders(&context, &watermarks)
+        .await
+        .map(|r| warp::reply::json(&r))
+        .map_err(warp::reject::custom)
+}
+
 pub async fn get_stake_pools(context: ContextLock) -> Result<impl Reply, Rejection> {
     let context = context.read().await;
     logic::get_stake_pools(&context)
@@ -208,6 +244,14 @@ pub async fn get_network_p2p_quarantined(context: ContextLock) -> Result<impl Re
         .map_err(warp::reject::custom)
 }
 
+pub async fn get_network_p2p_rejected(context: ContextLock) -> Result<impl Reply, Rejection> {
+    let context = context.read().await;
+    logic::get_network_p2p_rejected(&context)
+        .await
+        .map(|r| warp::reply::json(&r))
+        .map_err(warp::reject::custom)
+}
+
 pub async fn get_network_p2p_non_public(context: ContextLock) -> Result<impl Reply, Rejection> {
     let context = context.read().await;
     logic::get_network_p2p_non_public(&context)
@@ -9,6 +9,7 @@ use crate::{
     blockchain::StorageError,
     diagnostic::Diagnostic,
     intercom::{self, NetworkMsg, TopologyMsg, TransactionMsg},
+    leadership::{LookAheadError, ReloadError, SigningGuardError},
     rest::Context,
     topology::PeerInfo,
     utils::async_msg::MessageBox,
@@ -35,9 +36,10 @@ use futures::{
 use jormungandr_lib::{
     interfaces::{
         AccountState, EpochRewardsInfo, FragmentLog, FragmentOrigin, FragmentsProcessingSummary,
-        LeadershipLog, NodeStatsDto, PeerStats, Rewards as StakePoolRewards, SettingsDto,
-        StakeDistribution, StakeDistributionDto, StakePoolStats, TaxTypeSerde, TransactionOutput,
-        Value, VotePlanStatus,
+        LeadershipLog, LeadershipScheduleEntry, NodeStatsDto, PeerStats, RejectedConnections,
+        Rewards as StakePoolRewards, SettingsDto, SigningWatermarks, StakeDistribution,
+        StakeDistributionDto, StakePoolStats, TaxTypeSerde, TransactionOutput, Value,
+        VotePlanStatus,
     },
     time::SystemTime,
 };
@@ -78,6 +80,12 @@ pub enum Error {
     Hex(#[from] hex::FromHexError),
     #[error("Could not process fragment")]
     Fragment(FragmentsProcessingSummary),
+    #[error(transparent)]
+    LeadersReload(#[from] ReloadError),
+    #[error(transparent)]
+    SigningGuard(#[from] SigningGuardError),
+    #[error(transparent)]
+    LeadersSchedule(#[from] LookAheadError),
     #[cfg(feature = "evm")]
     #[error("Can not parse address: {0}")]
     AddressParseError(String),
@@ -327,6 +335,32 @@ pub async fn get_leaders_logs(context: &Context) -> Result<Vec<LeadershipLog>, E
     Ok(context.try_full()?.leadership_logs.logs().await)
 }
 
+pub async fn get_leaders_schedule(
+    context: &Context,
+) -> Result<Vec<LeadershipScheduleEntry>, Error> {
+    let tip = context.blockchain_tip()?.get_ref().await;
+    Ok(context
+        .try_full()?
+        .schedule_look_ahead
+        .schedule(tip)
+        .await?)
+}
+
+pub async fn reload_leaders(context: &Context) -> Result<Vec<String>, Error> {
+    Ok(context.try_full()?.secrets_reloader.reload().await?)
+}
+
+pub async fn handoff_leaders(context: &Context) -> Result<SigningWatermarks, Error> {
+    Ok(context.try_full()?.signing_guard.handoff()?)
+}
+
+pub async fn take_over_leaders(
+    context: &Context,
+    watermarks: &SigningWatermarks,
+) -> Result<SigningWatermarks, Error> {
+    Ok(context.try_full()?.signing_guard.take_over(watermarks)?)
+}
+
 pub async fn get_stake_pools(context: &Context) -> Result<Vec<String>, Error> {
     Ok(context
         .blockchain_tip()?
@@ -497,6 +531,18 @@ pub async fn get_network_p2p_quarantined(context: &Context) -> Result<Vec<PeerIn
     reply_future.await.map_err(Into::into)
 }
 
+pub async fn get_network_p2p_rejected(context: &Context) -> Result<RejectedConnections, Error> {
+    let (reply_handle, reply_future) = intercom::unary_reply();
+    let mut mbox = context.try_full()?.network_task.clone();
+    mbox.send(NetworkMsg::RejectedConnections(reply_handle))
+        .await
+        .map_err(|e| {
+            tracing::debug!(reason = %e, "error getting rejected connection counts");
+            Error::MsgSendError(e)
+        })?;
+    reply_future.await.map_err(Into::into)
+}
+
 pub async fn get_network_p2p_non_public(context: &Context) -> Result<Vec<PeerInfo>, Error> {
     let (reply_handle, reply_future) = intercom::unary_reply();
     let mut mbox = context.try_full()?.topology_task.clone();
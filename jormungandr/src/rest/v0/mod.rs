@@ -1,7 +1,10 @@
 mod handlers;
 pub mod logic;
 
-use crate::rest::{display_internal_server_error, ContextLock};
+use crate::{
+    leadership::ReloadError,
+    rest::{display_internal_server_error, ContextLock},
+};
 use warp::{http::StatusCode, Filter, Rejection, Reply};
 
 pub fn filter(
@@ -81,7 +84,33 @@ pub fn filter(
             .and_then(handlers::get_leaders_logs)
             .boxed();
 
-        root.and(logs).boxed()
+        let schedule = warp::path!("schedule")
+            .and(warp::get())
+            .and(with_context.clone())
+            .and_then(handlers::get_leaders_schedule)
+            .boxed();
+
+        let reload = warp::path!("reload")
+            .and(warp::post())
+            .and(with_context.clone())
+            .and_then(handlers::reload_leaders)
+            .boxed();
+
+        let handoff = warp::path!("handoff")
+            .and(warp::post())
+            .and(with_context.clone())
+            .and_then(handlers::handoff_leaders)
+            .boxed();
+
+        let take_over = warp::path!("takeover")
+            .and(warp::post())
+            .and(warp::body::json())
+            .and(with_context.clone())
+            .and_then(handlers::take_over_leaders)
+            .boxed();
+
+        root.and(logs.or(schedule).or(reload).or(handoff).or(take_over))
+            .boxed()
     };
 
     let p2p = {
@@ -93,6 +122,12 @@ pub fn filter(
             .and_then(handlers::get_network_p2p_quarantined)
             .boxed();
 
+        let rejected = warp::path!("rejected")
+            .and(warp::get())
+            .and(with_context.clone())
+            .and_then(handlers::get_network_p2p_rejected)
+            .boxed();
+
         let non_public = warp::path!("non_public")
             .and(warp::get())
             .and(with_context.clone())
@@ -123,8 +158,14 @@ pub fn filter(
             root.and(view.or(view_topic)).boxed()
         };
 
-        root.and(quarantined.or(non_public).or(available).or(view))
-            .boxed()
+        root.and(
+            quarantined
+                .or(rejected)
+                .or(non_public)
+                .or(available)
+                .or(view),
+        )
+        .boxed()
     };
 
     let network = {
@@ -281,6 +322,9 @@ async fn handle_rejection(err: Rejection) -> Result<impl Reply, Rejection> {
                 serde_json::to_string(&summary).unwrap(),
                 StatusCode::BAD_REQUEST,
             ),
+            logic::Error::LeadersReload(ReloadError::NoSecretFile | ReloadError::RemoteSigner) => {
+                (err.to_string(), StatusCode::CONFLICT)
+            }
             err => (
                 display_internal_server_error(err),
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -2,13 +2,20 @@ use crate::blockcfg::{
     BlockDate, HeaderBft, HeaderBftBuilder, HeaderGenesisPraos, HeaderGenesisPraosBuilder,
     HeaderSetConsensusSignature,
 };
-use chain_impl_mockchain::leadership::{Leader, LeaderOutput, Leadership};
+use arc_swap::ArcSwap;
+use chain_impl_mockchain::{
+    certificate::PoolId,
+    key::BftLeaderId,
+    leadership::{BftLeader, GenesisLeader, Leader, LeaderOutput, Leadership},
+};
 use chain_time::Epoch;
 use std::sync::Arc;
 
+/// holds the keys of the leaders hosted by the node, the clones share the
+/// same keys so that they can be replaced while the node is running
 #[derive(Clone)]
 pub struct Enclave {
-    leader_data: Arc<Option<Leader>>,
+    leaders: Arc<ArcSwap<Vec<Leader>>>,
 }
 
 pub struct LeaderEvent {
@@ -17,7 +24,7 @@ pub struct LeaderEvent {
 }
 
 pub struct Schedule {
-    enclave: Arc<Enclave>,
+    leaders: Arc<Vec<Leader>>,
     leadership: Arc<Leadership>,
     current_slot: u32,
     stop_at_slot: u32,
@@ -25,39 +32,90 @@ pub struct Schedule {
 }
 
 impl Enclave {
-    pub fn new(leader_data: Option<Leader>) -> Self {
+    pub fn new(leaders: Vec<Leader>) -> Self {
         Enclave {
-            leader_data: Arc::new(leader_data),
+            leaders: Arc::new(ArcSwap::from_pointee(leaders)),
         }
     }
 
+    /// replaces the leader keys, the signatures requested from now on
+    /// are made with the new keys
+    pub fn set_leaders(&self, leaders: Vec<Leader>) {
+        self.leaders.store(Arc::new(leaders));
+    }
+
+    pub fn leaders(&self) -> Arc<Vec<Leader>> {
+        self.leaders.load_full()
+    }
+
+    /// sign the header with the key of the given stake pool
     pub fn create_header_genesis_praos(
         &self,
         header_builder: HeaderGenesisPraosBuilder<HeaderSetConsensusSignature>,
+        node_id: &PoolId,
     ) -> Option<HeaderGenesisPraos> {
-        let leader = self
-            .leader_data
-            .as_ref()
-            .as_ref()?
-            .genesis_leader
-            .as_ref()?;
+        let leaders = self.leaders();
+        let leader = leaders
+            .iter()
+            .filter_map(|leader| leader.genesis_leader.as_ref())
+            .find(|leader| &leader.node_id == node_id)?;
         let data = header_builder.get_authenticated_data();
         let signature = leader.sig_key.sign_slice(data);
         Some(header_builder.set_signature(signature.into()))
     }
 
+    /// sign the header with the key of the given BFT leader
     pub fn create_header_bft(
         &self,
         header_builder: HeaderBftBuilder<HeaderSetConsensusSignature>,
+        leader_id: &BftLeaderId,
     ) -> Option<HeaderBft> {
-        let leader = self.leader_data.as_ref().as_ref()?.bft_leader.as_ref()?;
+        let leaders = self.leaders();
+        let leader = leaders
+            .iter()
+            .filter_map(|leader| leader.bft_leader.as_ref())
+            .find(|leader| &leader.sig_key.to_public() == leader_id.as_public_key())?;
         let data = header_builder.get_authenticated_data();
         let signature = leader.sig_key.sign_slice(data);
         Some(header_builder.set_signature(signature.into()))
     }
 }
 
+/// the public identities of the leader: the hex encoded public key of the
+/// BFT leader and the stake pool id of the Genesis Praos leader
+pub fn leader_ids(leader: &Leader) -> Vec<String> {
+    leader
+        .bft_leader
+        .iter()
+        .map(bft_leader_id)
+        .chain(leader.genesis_leader.iter().map(genesis_leader_id))
+        .collect()
+}
+
+fn bft_leader_id(leader: &BftLeader) -> String {
+    hex::encode(leader.sig_key.to_public())
+}
+
+fn genesis_leader_id(leader: &GenesisLeader) -> String {
+    leader.node_id.to_string()
+}
+
+impl LeaderEvent {
+    /// the public identity of the leader elected for the event, as given
+    /// by [`leader_ids`]
+    pub fn leader_id(&self) -> Option<String> {
+        match &self.output {
+            LeaderOutput::None => None,
+            LeaderOutput::Bft(leader_id) => Some(hex::encode(leader_id.as_public_key())),
+            LeaderOutput::GenesisPraos(node_id, _) => Some(node_id.to_string()),
+        }
+    }
+}
+
 impl Schedule {
+    /// the schedule is evaluated for the keys held by the enclave at the
+    /// time of its creation, each slot going to the first of the leaders
+    /// elected for it
     pub fn new(
         enclave: Arc<Enclave>,
         leadership: Arc<Leadership>,
@@ -67,7 +125,7 @@ impl Schedule {
         let stop_at_slot = slot_start + nb_slots;
 
         Self {
-            enclave,
+            leaders: enclave.leaders(),
             leadership,
             current_slot: slot_start,
             stop_at_slot,
@@ -75,25 +133,35 @@ impl Schedule {
         }
     }
 
-    fn fill(&mut self) {
-        let leader = if let Some(leader) = self.enclave.leader_data.as_ref() {
-            leader
-        } else {
-            return;
-        };
+    /// A schedule of events computed beforehand, given in slot order.
+    pub fn from_events(leadership: Arc<Leadership>, mut events: Vec<LeaderEvent>) -> Self {
+        // the events are popped from the end
+        events.reverse();
+        Self {
+            leaders: Arc::new(Vec::new()),
+            leadership,
+            current_slot: 0,
+            stop_at_slot: 0,
+            current_slot_data: events,
+        }
+    }
 
-        if !self.current_slot_data.is_empty() {
+    fn fill(&mut self) {
+        if self.leaders.is_empty() || !self.current_slot_data.is_empty() {
             return;
         }
 
         while self.current_slot < self.stop_at_slot && self.current_slot_data.is_empty() {
             let date = self.leadership.date_at_slot(self.current_slot);
-            match self.leadership.is_leader_for_date(leader, date) {
-                LeaderOutput::None => (),
-                leader_output => self.current_slot_data.push(LeaderEvent {
-                    date,
-                    output: leader_output,
-                }),
+            // the node can only create one block per slot, the leaders
+            // hosted by the node do not compete with each other
+            let output = self
+                .leaders
+                .iter()
+                .map(|leader| self.leadership.is_leader_for_date(leader, date))
+                .find(|output| !matches!(output, LeaderOutput::None));
+            if let Some(output) = output {
+                self.current_slot_data.push(LeaderEvent { date, output });
             }
 
             self.current_slot += 1;
@@ -114,3 +182,207 @@ impl Schedule {
         Epoch(self.leadership.epoch())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_addr::Discrimination;
+    use chain_crypto::{Ed25519, SecretKey};
+    use chain_impl_mockchain::{
+        chaintypes::ConsensusVersion,
+        fee::LinearFee,
+        ledger::Ledger,
+        milli::Milli,
+        testing::{
+            data::StakePool,
+            scenario::{prepare_scenario, wallet},
+            ConfigBuilder,
+        },
+    };
+    use jormungandr_lib::{
+        crypto::key::SigningKey,
+        interfaces::{Block0Configuration, BlockchainConfiguration, ConsensusLeaderId},
+    };
+    use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
+
+    fn bft_key(seed: u64) -> SecretKey<Ed25519> {
+        SigningKey::<Ed25519>::generate(ChaChaRng::seed_from_u64(seed)).into_secret_key()
+    }
+
+    fn bft_leader(key: &SecretKey<Ed25519>) -> Leader {
+        Leader {
+            bft_leader: Some(BftLeader {
+                sig_key: key.clone(),
+            }),
+            genesis_leader: None,
+        }
+    }
+
+    fn genesis_leader(stake_pool: &StakePool) -> Leader {
+        Leader {
+            bft_leader: None,
+            genesis_leader: Some(GenesisLeader {
+                node_id: stake_pool.id(),
+                sig_key: stake_pool.kes().private_key().clone(),
+                vrf_key: stake_pool.vrf().private_key().clone(),
+            }),
+        }
+    }
+
+    /// the leadership of the first epoch of a BFT chain with the given
+    /// leaders, the slots go to the leaders in turn
+    fn bft_leadership(keys: &[SecretKey<Ed25519>]) -> Arc<Leadership> {
+        let mut blockchain_configuration = BlockchainConfiguration::new(
+            Discrimination::Test,
+            ConsensusVersion::Bft,
+            LinearFee::new(0, 0, 0),
+        );
+        blockchain_configuration.consensus_leader_ids = keys
+            .iter()
+            .map(|key| ConsensusLeaderId::from(key.to_public()))
+            .collect();
+        let block0 = Block0Configuration {
+            blockchain_configuration,
+            initial: Vec::new(),
+        }
+        .to_block();
+        let ledger = Ledger::new(block0.header().id(), block0.contents().iter()).unwrap();
+        Arc::new(Leadership::new(0, &ledger))
+    }
+
+    /// the leadership of the first epoch of a Genesis Praos chain where
+    /// both stake pools are elected for every slot
+    fn genesis_praos_leadership() -> (Arc<Leadership>, StakePool, StakePool) {
+        let (ledger, controller) = prepare_scenario()
+            .with_config(
+                ConfigBuilder::new()
+                    .with_consensus_version(ConsensusVersion::GenesisPraos)
+                    .with_active_slots_coeff(Milli::ONE),
+            )
+            .with_initials(vec![
+                wallet("Alice")
+                    .with(1_000)
+                    .owns_and_delegates_to("alice_pool"),
+                wallet("Bob").with(1_000).owns_and_delegates_to("bob_pool"),
+            ])
+            .build()
+            .unwrap();
+        let leadership = Arc::new(Leadership::new(0, &ledger.ledger));
+        (
+            leadership,
+            controller.stake_pool("alice_pool").unwrap(),
+            controller.stake_pool("bob_pool").unwrap(),
+        )
+    }
+
+    fn schedule_leader_ids(schedule: &mut Schedule) -> Vec<(u32, String)> {
+        std::iter::from_fn(|| schedule.next_event())
+            .map(|event| (event.date.slot_id, event.leader_id().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn multi_leader_schedule_follows_the_leaders_in_turn() {
+        let keys = [bft_key(1), bft_key(2), bft_key(3)];
+        let leadership = bft_leadership(&keys);
+        // the third leader is hosted by another node
+        let enclave = Arc::new(Enclave::new(vec![
+            bft_leader(&keys[1]),
+            bft_leader(&keys[0]),
+        ]));
+        let ids: Vec<_> = keys
+            .iter()
+            .map(|key| hex::encode(key.to_public()))
+            .collect();
+
+        let mut schedule = Schedule::new(enclave, leadership, 0, 6);
+
+        assert_eq!(
+            schedule_leader_ids(&mut schedule),
+            vec![
+                (0, ids[0].clone()),
+                (1, ids[1].clone()),
+                (3, ids[0].clone()),
+                (4, ids[1].clone()),
+            ]
+        );
+        assert!(schedule.peek().is_none());
+    }
+
+    #[test]
+    fn schedule_uses_the_leaders_at_its_creation() {
+        let keys = [bft_key(1), bft_key(2)];
+        let leadership = bft_leadership(&keys);
+        let enclave = Arc::new(Enclave::new(vec![bft_leader(&keys[0])]));
+
+        let mut schedule = Schedule::new(Arc::clone(&enclave), leadership, 0, 4);
+        enclave.set_leaders(vec![bft_leader(&keys[1])]);
+
+        let slots: Vec<_> = schedule_leader_ids(&mut schedule)
+            .into_iter()
+            .map(|(slot, _)| slot)
+            .collect();
+        assert_eq!(slots, vec![0, 2]);
+    }
+
+    #[test]
+    fn first_elected_leader_takes_the_slot() {
+        let (leadership, alice_pool, bob_pool) = genesis_praos_leadership();
+
+        for (first, second) in [(&alice_pool, &bob_pool), (&bob_pool, &alice_pool)] {
+            let enclave = Arc::new(Enclave::new(vec![
+                genesis_leader(first),
+                genesis_leader(second),
+            ]));
+            let mut schedule = Schedule::new(enclave, Arc::clone(&leadership), 0, 5);
+
+            let events = schedule_leader_ids(&mut schedule);
+
+            // one event per slot, all for the first of the leaders
+            assert_eq!(
+                events,
+                (0..5)
+                    .map(|slot| (slot, first.id().to_string()))
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn events_are_attributed_to_the_stake_pool() {
+        let (leadership, alice_pool, _) = genesis_praos_leadership();
+        let leader = genesis_leader(&alice_pool);
+        assert_eq!(leader_ids(&leader), vec![alice_pool.id().to_string()]);
+        let enclave = Arc::new(Enclave::new(vec![leader]));
+
+        let mut schedule = Schedule::new(enclave, leadership, 0, 1);
+        let event = schedule.next_event().unwrap();
+
+        assert!(matches!(
+            &event.output,
+            LeaderOutput::GenesisPraos(node_id, _) if node_id == &alice_pool.id()
+        ));
+        assert_eq!(event.leader_id(), Some(alice_pool.id().to_string()));
+    }
+
+    #[test]
+    fn leader_ids_list_the_bft_key_then_the_pool() {
+        let (_, alice_pool, _) = genesis_praos_leadership();
+        let key = bft_key(1);
+        let leader = Leader {
+            bft_leader: bft_leader(&key).bft_leader,
+            genesis_leader: genesis_leader(&alice_pool).genesis_leader,
+        };
+
+        assert_eq!(
+            leader_ids(&leader),
+            vec![hex::encode(key.to_public()), alice_pool.id().to_string()]
+        );
+        assert!(LeaderEvent {
+            date: BlockDate::first(),
+            output: LeaderOutput::None,
+        }
+        .leader_id()
+        .is_none());
+    }
+}
@@ -1,14 +1,33 @@
+use crate::leadership::KesKey;
 use chain_crypto::{Blake2b256, Ed25519, PublicKey, RistrettoGroup2HashDh, SumEd25519_12};
-use chain_impl_mockchain::leadership::{BftLeader, GenesisLeader};
-use jormungandr_lib::crypto::{
-    hash::Hash,
-    key::{Identifier, SigningKey},
+use chain_impl_mockchain::{
+    certificate::PoolId,
+    leadership::{BftLeader, GenesisLeader, Leader},
+};
+use jormungandr_lib::{
+    crypto::{
+        hash::Hash,
+        key::{Identifier, SigningKey},
+    },
+    interfaces::{EncryptedSecretFile, SecretEncryptionError},
 };
 use serde::Deserialize;
-use std::path::Path;
+#[cfg(unix)]
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::{
+    env, io,
+    path::{Path, PathBuf},
+};
 use thiserror::Error;
+use zeroize::Zeroizing;
+
+/// The environment variable the passphrase of an encrypted secret file
+/// is read from. It is removed from the environment when the node starts.
+pub const PASSPHRASE_ENV_VAR: &str = "JORMUNGANDR_SECRET_PASSPHRASE";
 
 pub mod enclave;
+#[cfg(unix)]
+pub mod remote;
 
 /// hold the node's bft secret setting
 #[derive(Clone, Deserialize)]
@@ -23,6 +42,8 @@ pub struct GenesisPraos {
     node_id: Hash,
     sig_key: SigningKey<SumEd25519_12>,
     vrf_key: SigningKey<RistrettoGroup2HashDh>,
+    #[serde(default)]
+    kes_start_period: Option<u32>,
 }
 
 #[derive(Clone, Deserialize)]
@@ -43,26 +64,68 @@ pub struct NodePublic {
     pub block_publickey: PublicKey<Ed25519>,
 }
 
+/// Where the passphrase of an encrypted secret file is read from
+#[derive(Debug, Clone, Copy)]
+pub enum PassphraseSource {
+    /// the `JORMUNGANDR_SECRET_PASSPHRASE` environment variable if set,
+    /// an interactive prompt otherwise
+    EnvOrPrompt,
+    /// the first line read from the given file descriptor, which is closed
+    /// afterwards
+    #[cfg(unix)]
+    Fd(RawFd),
+}
+
+/// The node's secret files, kept to load the leader keys again while the
+/// node is running.
+///
+/// The encrypted files share the same passphrase. It is read once when the
+/// node starts and kept in memory, so that reloading the files never waits
+/// for a passphrase.
+pub struct SecretFiles {
+    paths: Vec<PathBuf>,
+    passphrase: Option<Zeroizing<String>>,
+}
+
 #[derive(Debug, Error)]
 pub enum NodeSecretFromFileError {
     #[error("Cannot read node's secrets: {0}")]
     Io(#[from] std::io::Error),
     #[error("Invalid Node secret file: {0}")]
     Format(#[from] serde_yaml::Error),
+    #[error("Cannot read the passphrase of the encrypted secret file: {0}")]
+    Passphrase(#[source] std::io::Error),
+    #[error("The secret file is encrypted but no passphrase was given when the node started")]
+    NoPassphrase,
+    #[error(transparent)]
+    Decryption(#[from] SecretEncryptionError),
+    #[error("Cannot load the secret file {}: {source}", path.display())]
+    File {
+        path: PathBuf,
+        #[source]
+        source: Box<NodeSecretFromFileError>,
+    },
 }
 
 impl NodeSecret {
-    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<NodeSecret, NodeSecretFromFileError> {
-        let file = std::fs::File::open(path)?;
-        Ok(serde_yaml::from_reader(file)?)
-    }
-
     pub fn bft(&self) -> Option<BftLeader> {
         self.bft.clone().map(|bft| BftLeader {
             sig_key: bft.signing_key.into_secret_key(),
         })
     }
 
+    /// The leader keys of the secret, if it holds any.
+    pub fn leader(&self) -> Option<Leader> {
+        let leader = Leader {
+            bft_leader: self.bft(),
+            genesis_leader: self.genesis(),
+        };
+        if leader.bft_leader.is_none() && leader.genesis_leader.is_none() {
+            return None;
+        }
+        Some(leader)
+    }
+
     pub fn genesis(&self) -> Option<GenesisLeader> {
         self.genesis.clone().map(|genesis| GenesisLeader {
             node_id: Blake2b256::from(genesis.node_id).into(),
@@ -71,6 +134,15 @@ impl NodeSecret {
         })
     }
 
+    /// The KES key of the Genesis Praos leader, in use from the block0
+    /// date unless the secret gives its start period.
+    pub fn kes_key(&self) -> Option<KesKey> {
+        self.genesis.as_ref().map(|genesis| KesKey {
+            pool_id: PoolId::from(Blake2b256::from(genesis.node_id)).to_string(),
+            start_period: genesis.kes_start_period.unwrap_or(0),
+        })
+    }
+
     #[cfg(feature = "evm")]
     pub fn evm_keys(&self) -> Vec<chain_evm::util::Secret> {
         self.evm_keys
@@ -83,3 +155,134 @@ impl NodeSecret {
             .unwrap_or_default()
     }
 }
+
+/// Takes the passphrase out of the `JORMUNGANDR_SECRET_PASSPHRASE`
+/// environment variable.
+///
+/// The environment can only be modified safely while the process runs a
+/// single thread, this is done first thing in `main`.
+pub fn take_env_passphrase() -> Result<Option<Zeroizing<String>>, NodeSecretFromFileError> {
+    let passphrase = match env::var_os(PASSPHRASE_ENV_VAR) {
+        Some(passphrase) => passphrase,
+        None => return Ok(None),
+    };
+    env::remove_var(PASSPHRASE_ENV_VAR);
+    passphrase
+        .into_string()
+        .map(|passphrase| Some(Zeroizing::new(passphrase)))
+        .map_err(|_| {
+            NodeSecretFromFileError::Passphrase(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "passphrase is not valid UTF-8",
+            ))
+        })
+}
+
+impl SecretFiles {
+    /// Reads the passphrase from its source right away: the file descriptor
+    /// if one is given, the environment variable taken by
+    /// [`take_env_passphrase`] otherwise, and finally an interactive prompt
+    /// if one of the files is encrypted.
+    pub fn new(
+        paths: Vec<PathBuf>,
+        passphrase_source: PassphraseSource,
+        env_passphrase: Option<Zeroizing<String>>,
+    ) -> Result<Self, NodeSecretFromFileError> {
+        let passphrase = match passphrase_source {
+            PassphraseSource::EnvOrPrompt => match env_passphrase {
+                Some(passphrase) => Some(passphrase),
+                None if paths.iter().any(|path| is_encrypted(path)) => Some(
+                    rpassword::prompt_password("Passphrase of the node secret file: ")
+                        .map(Zeroizing::new)
+                        .map_err(NodeSecretFromFileError::Passphrase)?,
+                ),
+                None => None,
+            },
+            #[cfg(unix)]
+            PassphraseSource::Fd(fd) => {
+                Some(read_passphrase_fd(fd).map_err(NodeSecretFromFileError::Passphrase)?)
+            }
+        };
+        Ok(SecretFiles { paths, passphrase })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Reads the secret files, decrypting the encrypted ones.
+    pub fn load(&self) -> Result<Vec<NodeSecret>, NodeSecretFromFileError> {
+        self.paths
+            .iter()
+            .map(|path| {
+                load_file(path, self.passphrase.as_deref()).map_err(|source| {
+                    NodeSecretFromFileError::File {
+                        path: path.clone(),
+                        source: Box::new(source),
+                    }
+                })
+            })
+            .collect()
+    }
+}
+
+/// Reads a secret file, decrypting it with the passphrase if it is encrypted.
+fn load_file(path: &Path, passphrase: Option<&str>) -> Result<NodeSecret, NodeSecretFromFileError> {
+    let bytes = Zeroizing::new(std::fs::read(path)?);
+    match serde_yaml::from_slice::<EncryptedSecretFile>(&bytes) {
+        Ok(encrypted) => {
+            let passphrase = passphrase.ok_or(NodeSecretFromFileError::NoPassphrase)?;
+            let bytes = encrypted.decrypt(passphrase.as_bytes())?;
+            Ok(serde_yaml::from_slice(&bytes)?)
+        }
+        Err(_) => Ok(serde_yaml::from_slice(&bytes)?),
+    }
+}
+
+/// Whether the file is an encrypted secret file, the files that cannot be
+/// read are reported when they are loaded.
+fn is_encrypted(path: &Path) -> bool {
+    std::fs::read(path)
+        .map(Zeroizing::new)
+        .map(|bytes| serde_yaml::from_slice::<EncryptedSecretFile>(&bytes).is_ok())
+        .unwrap_or(false)
+}
+
+#[cfg(unix)]
+fn read_passphrase_fd(fd: RawFd) -> io::Result<Zeroizing<String>> {
+    // SAFETY: the descriptor is given to the node for this sole purpose and
+    // read once when the node starts, it is owned by the file from now on
+    // and closed once read
+    let file = unsafe { std::fs::File::from_raw_fd(fd) };
+    read_passphrase_line(io::BufReader::new(file))
+}
+
+/// Reads the first line, without its line ending. The other trailing
+/// whitespace is part of the passphrase.
+#[cfg(unix)]
+fn read_passphrase_line(mut reader: impl io::BufRead) -> io::Result<Zeroizing<String>> {
+    let mut line = Zeroizing::new(String::new());
+    reader.read_line(&mut line)?;
+    let passphrase = line
+        .strip_suffix('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .unwrap_or(line.as_str());
+    Ok(Zeroizing::new(passphrase.to_owned()))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passphrase_line_ending_is_stripped() {
+        let read = |input: &str| read_passphrase_line(input.as_bytes()).unwrap();
+
+        assert_eq!(read("secret\n").as_str(), "secret");
+        assert_eq!(read("secret\r\n").as_str(), "secret");
+        assert_eq!(read("secret").as_str(), "secret");
+        assert_eq!(read("secret \t\n").as_str(), "secret \t");
+        assert_eq!(read("secret\n\n").as_str(), "secret");
+        assert_eq!(read("").as_str(), "");
+    }
+}
@@ -0,0 +1,615 @@
+//! Remote signer for the leadership keys.
+//!
+//! The signer runs in a separate process holding the node's secret file
+//! and serves requests on a Unix socket, so that the node process never
+//! has the private keys in memory. The node asks the signer which slots
+//! it is elected for and to sign the headers of the blocks it creates.
+//!
+//! The messages are JSON documents, each preceded by its length as a
+//! big-endian `u32`. Every request is answered by exactly one response.
+//!
+//! The signer does not have the ledger: for the Genesis Praos leadership,
+//! the node sends the epoch nonce, the active slots coefficient and the
+//! stake of the signer's pool, and the signer evaluates the VRF with its
+//! key for each slot.
+
+use super::NodeSecret;
+use crate::{
+    blockcfg::{BftLeader, GenesisLeader},
+    leadership::KesKey,
+};
+use chain_crypto::{Ed25519, Signature, SumEd25519_12};
+use chain_impl_mockchain::{
+    leadership::genesis::{Nonce, VrfEvaluator, Witness},
+    milli::Milli,
+    setting::ActiveSlotsCoeff,
+    stake::{PercentStake, Stake},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{self, Read, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use thiserror::Error;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::UnixListener,
+};
+
+const MAX_MESSAGE_SIZE: u32 = 1024 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("the remote signer needs the node's secret file")]
+    MissingSecret,
+    #[error("the remote signer holds the keys of a single secret file")]
+    TooManySecrets,
+    #[error("the secret file holds neither a BFT nor a Genesis Praos leader key")]
+    NoLeaderKeys,
+    #[error("cannot listen on the remote signer socket {}", path.display())]
+    Listen {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("I/O error with the remote signer")]
+    Io(#[from] io::Error),
+    #[error("invalid message: {0}")]
+    Format(#[from] serde_json::Error),
+    #[error("message of {0} bytes exceeds the maximum size")]
+    TooLarge(u32),
+    #[error("the remote signer refused the request: {0}")]
+    Refused(String),
+    #[error("unexpected response from the remote signer")]
+    UnexpectedResponse,
+    #[error("invalid signature returned by the remote signer")]
+    InvalidSignature,
+    #[error("invalid VRF proof returned by the remote signer")]
+    InvalidVrfProof,
+}
+
+/// The kind of key a signing request is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyKind {
+    Bft,
+    GenesisPraos,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Request {
+    /// The public identities of the leaders held by the signer.
+    Identity,
+    /// The slots, among the given ones, for which the signer's BFT leader
+    /// is elected by the round robin over the hex encoded `leaders`.
+    BftSchedule {
+        leaders: Vec<String>,
+        slots: Vec<u32>,
+    },
+    /// The slots, among the given ones, for which the signer's Genesis
+    /// Praos leader is elected with the hex encoded epoch `nonce`, the
+    /// active slots coefficient in thousandths and the stake of the pool.
+    VrfEvaluate {
+        nonce: String,
+        active_slots_coeff: u64,
+        stake: u64,
+        total_stake: u64,
+        slots: Vec<u32>,
+    },
+    /// Signs the hex encoded authenticated data of a header.
+    Sign { key: KeyKind, data: String },
+}
+
+/// A slot the Genesis Praos leader is elected for, with the hex encoded
+/// VRF proof of the election.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ElectedSlot {
+    pub slot: u32,
+    pub proof: String,
+}
+
+/// The parameters of the Genesis Praos leader election of an epoch.
+pub struct VrfParameters {
+    pub nonce: Nonce,
+    pub active_slots_coeff: ActiveSlotsCoeff,
+    pub stake: Stake,
+    pub total_stake: Stake,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    Identity {
+        bft_leader: Option<String>,
+        genesis_node_id: Option<String>,
+        /// The KES period from which the Genesis Praos key is in use.
+        #[serde(default)]
+        kes_start_period: Option<u32>,
+    },
+    BftSchedule {
+        slots: Vec<u32>,
+    },
+    VrfEvaluation {
+        elected: Vec<ElectedSlot>,
+    },
+    Signature {
+        signature: String,
+    },
+    Error {
+        reason: String,
+    },
+}
+
+/// The client side of the remote signer, used by the leadership enclave.
+///
+/// The requests are blocking; the connection is established on first
+/// use and re-established after a failure.
+pub struct RemoteSigner {
+    path: PathBuf,
+    connection: Mutex<Option<UnixStream>>,
+}
+
+impl RemoteSigner {
+    pub fn new(path: PathBuf) -> Self {
+        RemoteSigner {
+            path,
+            connection: Mutex::new(None),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn identity(&self) -> Result<(Option<String>, Option<String>), Error> {
+        match self.request(&Request::Identity)? {
+            Response::Identity {
+                bft_leader,
+                genesis_node_id,
+                ..
+            } => Ok((bft_leader, genesis_node_id)),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    /// The KES key of the Genesis Praos leader of the signer, if it holds
+    /// one.
+    pub fn kes_key(&self) -> Result<Option<KesKey>, Error> {
+        match self.request(&Request::Identity)? {
+            Response::Identity {
+                genesis_node_id,
+                kes_start_period,
+                ..
+            } => Ok(genesis_node_id.map(|pool_id| KesKey {
+                pool_id,
+                start_period: kes_start_period.unwrap_or(0),
+            })),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    pub fn bft_schedule(&self, leaders: Vec<String>, slots: Vec<u32>) -> Result<Vec<u32>, Error> {
+        match self.request(&Request::BftSchedule { leaders, slots })? {
+            Response::BftSchedule { slots } => Ok(slots),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    /// The slots the Genesis Praos leader of the signer is elected for,
+    /// with the proofs of the elections.
+    pub fn vrf_evaluate(
+        &self,
+        parameters: &VrfParameters,
+        slots: Vec<u32>,
+    ) -> Result<Vec<(u32, Witness)>, Error> {
+        let request = Request::VrfEvaluate {
+            nonce: hex::encode(parameters.nonce.as_ref()),
+            active_slots_coeff: Milli::from(parameters.active_slots_coeff).to_millis(),
+            stake: parameters.stake.0,
+            total_stake: parameters.total_stake.0,
+            slots,
+        };
+        match self.request(&request)? {
+            Response::VrfEvaluation { elected } => elected
+                .into_iter()
+                .map(|ElectedSlot { slot, proof }| {
+                    hex::decode(proof)
+                        .ok()
+                        .and_then(|proof| Witness::from_bytes_unverified(&proof))
+                        .map(|witness| (slot, witness))
+                        .ok_or(Error::InvalidVrfProof)
+                })
+                .collect(),
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    pub fn sign_bft(&self, data: &[u8]) -> Result<Signature<[u8], Ed25519>, Error> {
+        let signature = self.sign(KeyKind::Bft, data)?;
+        Signature::from_binary(&signature).map_err(|_| Error::InvalidSignature)
+    }
+
+    pub fn sign_genesis_praos(&self, data: &[u8]) -> Result<Signature<[u8], SumEd25519_12>, Error> {
+        let signature = self.sign(KeyKind::GenesisPraos, data)?;
+        Signature::from_binary(&signature).map_err(|_| Error::InvalidSignature)
+    }
+
+    fn sign(&self, key: KeyKind, data: &[u8]) -> Result<Vec<u8>, Error> {
+        let request = Request::Sign {
+            key,
+            data: hex::encode(data),
+        };
+        match self.request(&request)? {
+            Response::Signature { signature } => {
+                hex::decode(signature).map_err(|_| Error::InvalidSignature)
+            }
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    fn request(&self, request: &Request) -> Result<Response, Error> {
+        let mut connection = self.connection.lock().unwrap();
+        let stream = match connection.as_mut() {
+            Some(stream) => stream,
+            None => connection.insert(self.connect()?),
+        };
+        let result = write_message(stream, request).and_then(|()| read_message(stream));
+        if result.is_err() {
+            // the stream may hold a partial message, start afresh next time
+            *connection = None;
+        }
+        match result? {
+            Response::Error { reason } => Err(Error::Refused(reason)),
+            response => Ok(response),
+        }
+    }
+
+    fn connect(&self) -> Result<UnixStream, Error> {
+        let stream = UnixStream::connect(&self.path)?;
+        stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+        stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+        Ok(stream)
+    }
+}
+
+fn write_message<T: Serialize>(stream: &mut UnixStream, message: &T) -> Result<(), Error> {
+    let bytes = serde_json::to_vec(message)?;
+    stream.write_all(&(bytes.len() as u32).to_be_bytes())?;
+    stream.write_all(&bytes)?;
+    Ok(())
+}
+
+fn read_message<T: for<'de> Deserialize<'de>>(stream: &mut UnixStream) -> Result<T, Error> {
+    let mut len = [0; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len);
+    if len > MAX_MESSAGE_SIZE {
+        return Err(Error::TooLarge(len));
+    }
+    let mut bytes = vec![0; len as usize];
+    stream.read_exact(&mut bytes)?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+/// The leaders held by the signer process.
+struct Signer {
+    bft: Option<BftLeader>,
+    genesis: Option<GenesisLeader>,
+    kes_start_period: Option<u32>,
+}
+
+impl Signer {
+    fn handle(&self, request: Request) -> Response {
+        match request {
+            Request::Identity => Response::Identity {
+                bft_leader: self
+                    .bft
+                    .as_ref()
+                    .map(|leader| hex::encode(leader.sig_key.to_public())),
+                genesis_node_id: self
+                    .genesis
+                    .as_ref()
+                    .map(|leader| leader.node_id.to_string()),
+                kes_start_period: self.kes_start_period,
+            },
+            Request::BftSchedule { leaders, slots } => {
+                let public_key = match &self.bft {
+                    Some(leader) => hex::encode(leader.sig_key.to_public()),
+                    None => return refuse("no BFT leader key"),
+                };
+                if leaders.is_empty() {
+                    return Response::BftSchedule { slots: Vec::new() };
+                }
+                // same round robin as the BFT leader selection of the ledger
+                let slots = slots
+                    .into_iter()
+                    .filter(|slot| leaders[*slot as usize % leaders.len()] == public_key)
+                    .collect();
+                Response::BftSchedule { slots }
+            }
+            Request::VrfEvaluate {
+                nonce,
+                active_slots_coeff,
+                stake,
+                total_stake,
+                slots,
+            } => {
+                let leader = match &self.genesis {
+                    Some(leader) => leader,
+                    None => return refuse("no Genesis Praos leader key"),
+                };
+                let nonce = match hex::decode(nonce)
+                    .ok()
+                    .and_then(|nonce| <[u8; 32]>::try_from(nonce.as_slice()).ok())
+                {
+                    Some(nonce) => Nonce::from(nonce),
+                    None => return refuse("invalid nonce"),
+                };
+                let active_slots_coeff =
+                    match ActiveSlotsCoeff::try_from(Milli::from_millis(active_slots_coeff)) {
+                        Ok(coeff) => coeff,
+                        Err(_) => return refuse("invalid active slots coefficient"),
+                    };
+                let elected = slots
+                    .into_iter()
+                    .filter_map(|slot| {
+                        let evaluator = VrfEvaluator {
+                            stake: PercentStake {
+                                stake: Stake(stake),
+                                total: Stake(total_stake),
+                            },
+                            nonce: &nonce,
+                            slot_id: slot,
+                            active_slots_coeff,
+                        };
+                        evaluator
+                            .evaluate(&leader.vrf_key)
+                            .map(|witness| ElectedSlot {
+                                slot,
+                                proof: hex::encode(witness.to_bytes()),
+                            })
+                    })
+                    .collect();
+                Response::VrfEvaluation { elected }
+            }
+            Request::Sign { key, data } => {
+                let data = match hex::decode(data) {
+                    Ok(data) => data,
+                    Err(e) => return refuse(format!("invalid data: {}", e)),
+                };
+                let signature = match key {
+                    KeyKind::Bft => self
+                        .bft
+                        .as_ref()
+                        .map(|leader| hex::encode(leader.sig_key.sign_slice(&data))),
+                    KeyKind::GenesisPraos => self
+                        .genesis
+                        .as_ref()
+                        .map(|leader| hex::encode(leader.sig_key.sign_slice(&data))),
+                };
+                match signature {
+                    Some(signature) => Response::Signature { signature },
+                    None => refuse(format!("no {:?} leader key", key)),
+                }
+            }
+        }
+    }
+}
+
+fn refuse(reason: impl Into<String>) -> Response {
+    Response::Error {
+        reason: reason.into(),
+    }
+}
+
+/// Serves the leader keys of the secret file on the Unix socket at the
+/// given path, until an error occurs on the listening socket.
+pub async fn serve(path: &Path, secret: NodeSecret) -> Result<(), Error> {
+    let signer = Signer {
+        bft: secret.bft(),
+        genesis: secret.genesis(),
+        kes_start_period: secret.kes_key().map(|key| key.start_period),
+    };
+    if signer.bft.is_none() && signer.genesis.is_none() {
+        return Err(Error::NoLeaderKeys);
+    }
+    let signer = Arc::new(signer);
+
+    // a socket left over by a previous run would prevent binding
+    if path.exists() {
+        std::fs::remove_file(path).map_err(|source| Error::Listen {
+            path: path.to_owned(),
+            source,
+        })?;
+    }
+    let listener = UnixListener::bind(path).map_err(|source| Error::Listen {
+        path: path.to_owned(),
+        source,
+    })?;
+    tracing::info!("remote signer listening on {}", path.display());
+    serve_listener(listener, signer).await
+}
+
+async fn serve_listener(listener: UnixListener, signer: Arc<Signer>) -> Result<(), Error> {
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let signer = signer.clone();
+        tokio::spawn(async move {
+            tracing::debug!("remote signer client connected");
+            loop {
+                let request = match read_request(&mut stream).await {
+                    Ok(Some(request)) => request,
+                    Ok(None) => break,
+                    Err(e) => {
+                        tracing::warn!(reason = %e, "invalid request to the remote signer");
+                        break;
+                    }
+                };
+                let response = signer.handle(request);
+                if let Err(e) = write_response(&mut stream, &response).await {
+                    tracing::warn!(reason = %e, "failed to answer a remote signer client");
+                    break;
+                }
+            }
+            tracing::debug!("remote signer client disconnected");
+        });
+    }
+}
+
+async fn read_request(stream: &mut tokio::net::UnixStream) -> Result<Option<Request>, Error> {
+    let len = match stream.read_u32().await {
+        Ok(len) => len,
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if len > MAX_MESSAGE_SIZE {
+        return Err(Error::TooLarge(len));
+    }
+    let mut bytes = vec![0; len as usize];
+    stream.read_exact(&mut bytes).await?;
+    Ok(Some(serde_json::from_slice(&bytes)?))
+}
+
+async fn write_response(
+    stream: &mut tokio::net::UnixStream,
+    response: &Response,
+) -> Result<(), Error> {
+    let bytes = serde_json::to_vec(response)?;
+    stream.write_u32(bytes.len() as u32).await?;
+    stream.write_all(&bytes).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chain_crypto::{Blake2b256, RistrettoGroup2HashDh};
+    use jormungandr_lib::crypto::key::SigningKey;
+    use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
+
+    fn genesis_signer() -> Signer {
+        Signer {
+            bft: None,
+            genesis: Some(GenesisLeader {
+                node_id: Blake2b256::new(b"remote signer pool").into(),
+                sig_key: SigningKey::<SumEd25519_12>::generate(ChaChaRng::seed_from_u64(1))
+                    .into_secret_key(),
+                vrf_key: SigningKey::<RistrettoGroup2HashDh>::generate(ChaChaRng::seed_from_u64(2))
+                    .into_secret_key(),
+            }),
+            kes_start_period: Some(42),
+        }
+    }
+
+    fn vrf_parameters(active_slots_coeff: u64, stake: u64, total_stake: u64) -> VrfParameters {
+        VrfParameters {
+            nonce: Nonce::from([1; 32]),
+            active_slots_coeff: ActiveSlotsCoeff::try_from(Milli::from_millis(active_slots_coeff))
+                .unwrap(),
+            stake: Stake(stake),
+            total_stake: Stake(total_stake),
+        }
+    }
+
+    /// Serves the signer on a socket of its own and runs the client
+    /// requests on a blocking thread, as the leadership task does.
+    async fn with_fake_signer<T, F>(name: &str, signer: Signer, client: F) -> T
+    where
+        T: Send + 'static,
+        F: FnOnce(RemoteSigner) -> T + Send + 'static,
+    {
+        let path = std::env::temp_dir().join(format!(
+            "jormungandr-signer-{}-{}.sock",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = tokio::spawn(serve_listener(listener, Arc::new(signer)));
+        let remote = RemoteSigner::new(path.clone());
+        let result = tokio::task::spawn_blocking(move || client(remote))
+            .await
+            .unwrap();
+        server.abort();
+        std::fs::remove_file(path).unwrap();
+        result
+    }
+
+    #[tokio::test]
+    async fn genesis_praos_schedule_through_the_signer() {
+        let signer = genesis_signer();
+        let node_id = signer.genesis.as_ref().unwrap().node_id.to_string();
+
+        let (identity, kes_key, elected, not_elected) =
+            with_fake_signer("genesis", signer, |remote| {
+                let identity = remote.identity().unwrap();
+                let kes_key = remote.kes_key().unwrap();
+                // with all the stake and a coefficient of 1, every slot is won
+                let elected = remote
+                    .vrf_evaluate(&vrf_parameters(1000, 100, 100), (10..20).collect())
+                    .unwrap();
+                // without stake, no slot is won
+                let not_elected = remote
+                    .vrf_evaluate(&vrf_parameters(500, 0, 100), (10..20).collect())
+                    .unwrap();
+                (identity, kes_key, elected, not_elected)
+            })
+            .await;
+
+        assert_eq!(identity, (None, Some(node_id.clone())));
+        let kes_key = kes_key.unwrap();
+        assert_eq!((kes_key.pool_id, kes_key.start_period), (node_id, 42));
+        assert_eq!(
+            elected.iter().map(|(slot, _)| *slot).collect::<Vec<_>>(),
+            (10..20).collect::<Vec<_>>()
+        );
+        // each election has its own proof
+        assert_ne!(elected[0].1.to_bytes(), elected[1].1.to_bytes());
+        assert!(not_elected.is_empty());
+    }
+
+    #[tokio::test]
+    async fn vrf_evaluation_without_key_is_refused() {
+        let signer = Signer {
+            bft: None,
+            genesis: None,
+            kes_start_period: None,
+        };
+        let result = with_fake_signer("no-genesis", signer, |remote| {
+            remote.vrf_evaluate(&vrf_parameters(1000, 1, 1), vec![0])
+        })
+        .await;
+        assert!(matches!(result, Err(Error::Refused(_))));
+    }
+
+    #[test]
+    fn bft_schedule_without_key_is_refused() {
+        let signer = Signer {
+            bft: None,
+            genesis: None,
+            kes_start_period: None,
+        };
+        let response = signer.handle(Request::BftSchedule {
+            leaders: vec!["00".to_owned()],
+            slots: vec![0, 1],
+        });
+        assert!(matches!(response, Response::Error { .. }));
+    }
+
+    #[test]
+    fn request_encoding() {
+        let request = Request::Sign {
+            key: KeyKind::GenesisPraos,
+            data: "0102".to_owned(),
+        };
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"sign":{"key":"genesis_praos","data":"0102"}}"#
+        );
+    }
+}
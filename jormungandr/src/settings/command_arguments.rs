@@ -1,5 +1,6 @@
 use crate::{
     blockcfg::HeaderHash,
+    secure::PassphraseSource,
     settings::{
         logging::{LogFormat, LogOutput},
         start::config::TrustedPeer,
@@ -25,9 +26,17 @@ pub struct StartArguments {
     #[structopt(long = "config", parse(from_os_str))]
     pub node_config: Option<PathBuf>,
 
-    /// Set the secret node config (in YAML format).
+    /// Set the secret node config (in YAML format). Can be given several
+    /// times for a node hosting several stake pools.
     #[structopt(long = "secret", parse(from_os_str))]
-    pub secret: Option<PathBuf>,
+    pub secret: Vec<PathBuf>,
+
+    /// Read the passphrase of an encrypted secret file from the given file
+    /// descriptor instead of the `JORMUNGANDR_SECRET_PASSPHRASE` environment
+    /// variable or an interactive prompt.
+    #[cfg(unix)]
+    #[structopt(long = "secret-passphrase-fd")]
+    pub secret_passphrase_fd: Option<i32>,
 
     /// Path to the genesis block (the block0) of the blockchain
     #[structopt(long = "genesis-block", parse(try_from_str))]
@@ -61,6 +70,23 @@ pub struct StartArguments {
     /// to `public_address`.
     #[structopt(long = "listen-address")]
     pub listen_address: Option<SocketAddr>,
+
+    /// Path to a block archive to import before joining the network.
+    ///
+    /// The blocks are verified and applied on top of the blocks already
+    /// in the storage, which avoids downloading them from the network.
+    #[structopt(long = "import-block-archive", parse(from_os_str))]
+    pub import_block_archive: Option<PathBuf>,
+}
+
+impl StartArguments {
+    pub fn secret_passphrase(&self) -> PassphraseSource {
+        #[cfg(unix)]
+        if let Some(fd) = self.secret_passphrase_fd {
+            return PassphraseSource::Fd(fd);
+        }
+        PassphraseSource::EnvOrPrompt
+    }
 }
 
 #[derive(StructOpt, Debug)]
@@ -133,6 +159,18 @@ pub struct CommandLine {
     /// Initialize the storage and exit, useful to check that the storage has been set up correctly.
     #[structopt(long = "storage-check")]
     pub storage_check: bool,
+
+    /// Export the blocks of the main branch in the storage to a block archive
+    /// at the given path and exit.
+    #[structopt(long = "export-block-archive", parse(from_os_str))]
+    pub export_block_archive: Option<PathBuf>,
+
+    /// Run as a remote signer for the leader keys of the single secret file
+    /// given with `--secret`, serving the node on the Unix socket at the given path,
+    /// instead of starting a node.
+    #[cfg(unix)]
+    #[structopt(long = "remote-signer-listen", parse(from_os_str))]
+    pub remote_signer_listen: Option<PathBuf>,
 }
 
 impl CommandLine {
@@ -6,7 +6,9 @@ use crate::{
     },
     topology::QuarantineConfig,
 };
-pub use jormungandr_lib::interfaces::{Cors, JRpc, LayersConfig, Rest, Tls, TrustedPeer};
+pub use jormungandr_lib::interfaces::{
+    Cors, JRpc, LayersConfig, P2pConnectionFilter, P2pTls, Rest, Tls, TrustedPeer,
+};
 use jormungandr_lib::{interfaces::Mempool, time::Duration};
 use multiaddr::Multiaddr;
 use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
@@ -16,7 +18,7 @@ use tracing::level_filters::LevelFilter;
 #[derive(Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
-    pub secret_file: Option<PathBuf>,
+    pub secret_file: Option<SecretFilePaths>,
     pub storage: Option<PathBuf>,
     pub log: Option<ConfigLogSettings>,
 
@@ -27,6 +29,10 @@ pub struct Config {
     #[serde(default)]
     pub leadership: Leadership,
 
+    /// detection of a skew of the local clock
+    #[serde(default)]
+    pub clock_skew: ClockSkew,
+
     pub rest: Option<Rest>,
 
     pub jrpc: Option<JRpc>,
@@ -37,6 +43,19 @@ pub struct Config {
     #[serde(default)]
     pub http_fetch_block0_service: Vec<String>,
 
+    /// the time allowed for each HTTP request fetching the genesis block
+    #[serde(default)]
+    pub http_fetch_block0_timeout: Option<Duration>,
+
+    /// the number of times a failed HTTP request for the genesis block is retried
+    #[serde(default)]
+    pub http_fetch_block0_retries: Option<u32>,
+
+    /// directories containing genesis blocks named `<hash>.block0`,
+    /// looked up before fetching the genesis block from the network
+    #[serde(default)]
+    pub block0_local_mirrors: Vec<PathBuf>,
+
     #[cfg(feature = "prometheus-metrics")]
     pub prometheus: Option<Prometheus>,
 
@@ -132,6 +151,34 @@ pub struct P2pConfig {
     /// gossip with the trusted peers if any are defined.
     #[serde(default)]
     pub max_bootstrap_attempts: Option<usize>,
+
+    /// Enables TLS for the p2p connections, with a certificate bound to the
+    /// node key. Plaintext connections are used if not provided.
+    #[serde(default)]
+    pub tls: Option<P2pTls>,
+
+    /// CIDR allow and deny lists for peer addresses and the limit on
+    /// inbound connections accepted from a single subnet.
+    #[serde(default)]
+    pub connection_filter: Option<P2pConnectionFilter>,
+}
+
+/// the secret file of the node, or the secret files of the stake pools
+/// hosted by the node
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum SecretFilePaths {
+    One(PathBuf),
+    Many(Vec<PathBuf>),
+}
+
+impl SecretFilePaths {
+    pub fn into_paths(self) -> Vec<PathBuf> {
+        match self {
+            SecretFilePaths::One(path) => vec![path],
+            SecretFilePaths::Many(paths) => paths,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
@@ -141,6 +188,71 @@ pub struct Leadership {
     /// the least recently used log will be erased from the logs for a new one
     /// to be inserted.
     pub logs_capacity: usize,
+
+    /// Unix socket of a remote signer holding the leader keys, used instead
+    /// of the keys from the secret file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_signer: Option<PathBuf>,
+
+    /// the node is a standby for another node hosting the same leaders, it
+    /// does not sign blocks until it takes the leaders over.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub standby: bool,
+
+    /// the node builds and signs its blocks but stores them under a separate
+    /// tag instead of the main branch, and never propagates them.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+
+    /// the node warns about the KES keys of its Genesis Praos leaders
+    /// expiring within this duration.
+    #[serde(default = "default_kes_expiry_warning")]
+    pub kes_expiry_warning: Duration,
+}
+
+fn default_kes_expiry_warning() -> Duration {
+    Duration::new(7 * 24 * 3600, 0)
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClockSkew {
+    /// the largest skew of the local clock tolerated, as a fraction of the
+    /// slot duration.
+    #[serde(default = "default_max_skew_slot_ratio")]
+    pub max_skew_slot_ratio: f64,
+
+    /// NTP servers, as `host:port`, the local clock is checked against. The
+    /// skew is estimated from the blocks announced by the peers otherwise.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub time_sources: Vec<String>,
+
+    /// how often the skew of the local clock is checked.
+    #[serde(default = "default_clock_check_interval")]
+    pub check_interval: Duration,
+
+    /// the node does not produce blocks while its clock is skewed.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub refuse_to_lead: bool,
+}
+
+fn default_max_skew_slot_ratio() -> f64 {
+    0.5
+}
+
+fn default_clock_check_interval() -> Duration {
+    Duration::new(60, 0)
+}
+
+impl Default for ClockSkew {
+    fn default() -> Self {
+        ClockSkew {
+            max_skew_slot_ratio: default_max_skew_slot_ratio(),
+            time_sources: Vec::new(),
+            check_interval: default_clock_check_interval(),
+            refuse_to_lead: false,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -153,6 +265,10 @@ impl Default for Leadership {
     fn default() -> Self {
         Leadership {
             logs_capacity: 1_024,
+            remote_signer: None,
+            standby: false,
+            dry_run: false,
+            kes_expiry_warning: default_kes_expiry_warning(),
         }
     }
 }
@@ -2,10 +2,15 @@ pub mod config;
 pub mod network;
 
 use self::{
-    config::{Config, Leadership},
+    config::{ClockSkew, Config, Leadership, SecretFilePaths},
     network::{Protocol, TrustedPeer},
 };
 use crate::{
+    network::{
+        filter::{self, ConnectionFilter},
+        tls::{self, TlsContext},
+    },
+    secure::PassphraseSource,
     settings::{
         command_arguments::*,
         logging::{LogFormat, LogInfoMsg, LogOutput, LogSettings, LogSettingsEntry},
@@ -16,7 +21,7 @@ use crate::{
 use chain_crypto::Ed25519;
 pub use jormungandr_lib::interfaces::{Cors, JRpc, Mempool, Rest, Tls};
 use jormungandr_lib::{crypto::key::SigningKey, multiaddr};
-use std::{convert::TryFrom, fs::File, path::PathBuf};
+use std::{convert::TryFrom, fs::File, path::PathBuf, sync::Arc};
 use thiserror::Error;
 use tracing::level_filters::LevelFilter;
 
@@ -25,6 +30,8 @@ const DEFAULT_LOG_FORMAT: LogFormat = LogFormat::Default;
 const DEFAULT_LOG_OUTPUT: LogOutput = LogOutput::Stderr;
 const DEFAULT_NO_BLOCKCHAIN_UPDATES_WARNING_INTERVAL: u64 = 1800; // 30 min
 const DEFAULT_BLOCK_HARD_DEADLINE: u32 = 50;
+const DEFAULT_HTTP_FETCH_BLOCK0_TIMEOUT: u64 = 30;
+const DEFAULT_HTTP_FETCH_BLOCK0_RETRIES: u32 = 2;
 const DEFAULT_LOG_SETTINGS_ENTRY: LogSettingsEntry = LogSettingsEntry {
     level: DEFAULT_FILTER_LEVEL,
     format: DEFAULT_LOG_FORMAT,
@@ -45,6 +52,12 @@ pub enum Error {
     InvalidKey(#[from] chain_crypto::bech32::Error),
     #[error(transparent)]
     InvalidLayersConfig(#[from] layers::ParseError),
+    #[error("cannot set up TLS for p2p connections")]
+    P2pTls(#[from] tls::Error),
+    #[error("invalid p2p connection filter")]
+    P2pConnectionFilter(#[from] filter::Error),
+    #[error("the remote signer is only supported on Unix platforms")]
+    RemoteSignerUnsupported,
 }
 
 /// Overall Settings for node
@@ -52,16 +65,30 @@ pub struct Settings {
     pub network: network::Configuration,
     pub storage: Option<PathBuf>,
     pub block_0: Block0Info,
-    pub secret: Option<PathBuf>,
+    pub secret: Vec<PathBuf>,
+    pub secret_passphrase: PassphraseSource,
     pub rest: Option<Rest>,
     pub jrpc: Option<JRpc>,
     pub mempool: Mempool,
     pub rewards_report_all: bool,
     pub leadership: Leadership,
+    pub clock_skew: ClockSkew,
     #[cfg(feature = "prometheus-metrics")]
     pub prometheus: bool,
     pub no_blockchain_updates_warning_interval: std::time::Duration,
     pub block_hard_deadline: u32,
+    pub import_block_archive: Option<PathBuf>,
+    pub block0_fetch: Block0Fetch,
+}
+
+/// Where to look for the genesis block when only its hash is known
+pub struct Block0Fetch {
+    /// Directories containing genesis blocks as `<hash>.block0` files
+    pub local_mirrors: Vec<PathBuf>,
+    /// Base URLs of the HTTP services serving `<hash>.block0` files
+    pub http_services: Vec<String>,
+    pub http_timeout: std::time::Duration,
+    pub http_retries: u32,
 }
 
 pub struct RawSettings {
@@ -194,11 +221,16 @@ impl RawSettings {
             (None, None) => None,
         };
 
-        let secret = command_arguments
-            .secret
-            .clone()
-            .or_else(|| config.as_ref().and_then(|cfg| cfg.secret_file.clone()));
-        if secret.is_none() {
+        let secret = if command_arguments.secret.is_empty() {
+            config
+                .as_ref()
+                .and_then(|cfg| cfg.secret_file.clone())
+                .map(SecretFilePaths::into_paths)
+                .unwrap_or_default()
+        } else {
+            command_arguments.secret.clone()
+        };
+        if secret.is_empty() {
             tracing::warn!(
                 "Node started without path to the stored secret keys (not a stake pool or a BFT leader)"
             );
@@ -214,6 +246,13 @@ impl RawSettings {
             (None, Some(hash)) => Block0Info::Hash(*hash),
         };
 
+        let leadership = config
+            .as_ref()
+            .map_or(Leadership::default(), |cfg| cfg.leadership.clone());
+        if cfg!(not(unix)) && leadership.remote_signer.is_some() {
+            return Err(Error::RemoteSignerUnsupported);
+        }
+
         #[cfg(feature = "prometheus-metrics")]
         let prometheus = command_arguments.prometheus_enabled
             || config.as_ref().map_or(false, |cfg| {
@@ -227,15 +266,17 @@ impl RawSettings {
             block_0,
             network,
             secret,
+            secret_passphrase: command_arguments.secret_passphrase(),
             rewards_report_all: command_line.rewards_report_all,
             rest,
             jrpc,
             mempool: config
                 .as_ref()
                 .map_or(Mempool::default(), |cfg| cfg.mempool.clone()),
-            leadership: config
+            leadership,
+            clock_skew: config
                 .as_ref()
-                .map_or(Leadership::default(), |cfg| cfg.leadership.clone()),
+                .map_or(ClockSkew::default(), |cfg| cfg.clock_skew.clone()),
             #[cfg(feature = "prometheus-metrics")]
             prometheus,
             no_blockchain_updates_warning_interval: config
@@ -249,6 +290,26 @@ impl RawSettings {
                 .as_ref()
                 .and_then(|config| config.block_hard_deadline)
                 .unwrap_or(DEFAULT_BLOCK_HARD_DEADLINE),
+            import_block_archive: command_arguments.import_block_archive.clone(),
+            block0_fetch: Block0Fetch {
+                local_mirrors: config
+                    .as_ref()
+                    .map_or_else(Vec::new, |cfg| cfg.block0_local_mirrors.clone()),
+                http_services: config
+                    .as_ref()
+                    .map_or_else(Vec::new, |cfg| cfg.http_fetch_block0_service.clone()),
+                http_timeout: config
+                    .as_ref()
+                    .and_then(|cfg| cfg.http_fetch_block0_timeout)
+                    .map(Into::into)
+                    .unwrap_or_else(|| {
+                        std::time::Duration::from_secs(DEFAULT_HTTP_FETCH_BLOCK0_TIMEOUT)
+                    }),
+                http_retries: config
+                    .as_ref()
+                    .and_then(|cfg| cfg.http_fetch_block0_retries)
+                    .unwrap_or(DEFAULT_HTTP_FETCH_BLOCK0_RETRIES),
+            },
         })
     }
 }
@@ -282,17 +343,15 @@ fn generate_network(
     command_arguments: &StartArguments,
     config: &Option<Config>,
 ) -> Result<network::Configuration, Error> {
-    let (mut p2p, http_fetch_block0_service, skip_bootstrap, bootstrap_from_trusted_peers) =
-        if let Some(cfg) = config {
-            (
-                cfg.p2p.clone(),
-                cfg.http_fetch_block0_service.clone(),
-                cfg.skip_bootstrap,
-                cfg.bootstrap_from_trusted_peers,
-            )
-        } else {
-            (config::P2pConfig::default(), Vec::new(), false, false)
-        };
+    let (mut p2p, skip_bootstrap, bootstrap_from_trusted_peers) = if let Some(cfg) = config {
+        (
+            cfg.p2p.clone(),
+            cfg.skip_bootstrap,
+            cfg.bootstrap_from_trusted_peers,
+        )
+    } else {
+        (config::P2pConfig::default(), false, false)
+    };
 
     if p2p.trusted_peers.is_some() {
         if let Some(peers) = p2p.trusted_peers.as_mut() {
@@ -336,6 +395,18 @@ fn generate_network(
         None => SigningKey::generate(rand::thread_rng()),
     };
 
+    let tls = p2p
+        .tls
+        .map(|tls| TlsContext::new(&node_key, tls.required).map(Arc::new))
+        .transpose()?;
+
+    let connection_filter = p2p
+        .connection_filter
+        .as_ref()
+        .map(ConnectionFilter::new)
+        .transpose()?
+        .unwrap_or_default();
+
     let p2p_listen_address = p2p.listen.as_ref();
     let listen_address = command_arguments
         .listen_address
@@ -371,9 +442,10 @@ fn generate_network(
             .map(Into::into)
             .unwrap_or(crate::topology::DEFAULT_NETWORK_STUCK_INTERVAL),
         max_bootstrap_attempts: p2p.max_bootstrap_attempts,
-        http_fetch_block0_service,
         bootstrap_from_trusted_peers,
         skip_bootstrap,
+        tls,
+        connection_filter: Arc::new(connection_filter),
     };
 
     if network.max_client_connections > network.max_connections {
@@ -1,11 +1,11 @@
 use super::config;
 use crate::{
-    network::p2p::Address,
+    network::{filter::ConnectionFilter, p2p::Address, tls::TlsContext},
     topology::{layers::LayersConfig, NodeId, QuarantineConfig},
 };
 use chain_crypto::Ed25519;
 use jormungandr_lib::{crypto::key::SigningKey, multiaddr};
-use std::{net::SocketAddr, str, time::Duration};
+use std::{net::SocketAddr, str, sync::Arc, time::Duration};
 
 /// Protocol to use for a connection.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -97,7 +97,11 @@ pub struct Configuration {
     /// Whether to skip bootstrap, not recommended in normal settings. useful to true for self-node
     pub skip_bootstrap: bool,
 
-    pub http_fetch_block0_service: Vec<String>,
+    /// TLS settings for p2p connections, plaintext is used if not set
+    pub tls: Option<Arc<TlsContext>>,
+
+    /// Address restrictions for peers and limits on inbound connections per subnet
+    pub connection_filter: Arc<ConnectionFilter>,
 }
 
 /// Trusted peer with DNS address resolved.
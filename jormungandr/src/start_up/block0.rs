@@ -0,0 +1,455 @@
+//! Retrieval of the genesis block when only its hash is given.
+//!
+//! The sources are tried in order until one provides a block with the
+//! expected hash. The block retrieved from the network is cached in the
+//! storage directory, so that the following restarts find it locally.
+
+use crate::{
+    blockcfg::{Block, HeaderId},
+    blockchain::Storage,
+    network,
+    settings::start::{network::Configuration as NetworkConfiguration, Block0Fetch, Settings},
+};
+use async_trait::async_trait;
+use chain_core::{
+    packer::Codec,
+    property::{Deserialize, Serialize},
+};
+use futures::future::{self, FutureExt};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const CACHE_DIR: &str = "block0";
+/// Delay before the first retry of an HTTP request, doubled on each retry.
+const HTTP_RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+const HTTP_RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// A place the genesis block may be retrieved from.
+#[async_trait]
+pub trait Block0Source: Send + Sync {
+    /// Describes the source in the logs.
+    fn name(&self) -> String;
+
+    /// Whether the block retrieved from this source should be cached locally.
+    fn is_remote(&self) -> bool;
+
+    /// Looks up the block with the given hash, returns `Ok(None)` if
+    /// the source does not have it. The hash of the returned block is
+    /// verified by the resolver.
+    async fn fetch(&self, block0_id: &HeaderId) -> Result<Option<Block>, String>;
+}
+
+/// Tries the configured sources in order to retrieve the genesis block.
+pub struct Block0Resolver {
+    sources: Vec<Box<dyn Block0Source>>,
+    cache_dir: Option<PathBuf>,
+}
+
+impl Block0Resolver {
+    pub fn new(cache_dir: Option<PathBuf>) -> Self {
+        Block0Resolver {
+            sources: Vec::new(),
+            cache_dir,
+        }
+    }
+
+    /// The sources used by the node: the block storage, the local cache
+    /// and mirrors, the HTTP services and finally the trusted peers.
+    pub fn from_settings(settings: &Settings, storage: &Storage) -> Self {
+        let Block0Fetch {
+            local_mirrors,
+            http_services,
+            http_timeout,
+            http_retries,
+        } = &settings.block0_fetch;
+        let cache_dir = settings.storage.as_ref().map(|dir| dir.join(CACHE_DIR));
+
+        let mut resolver = Block0Resolver::new(cache_dir.clone());
+        resolver.push(StorageSource(storage.clone()));
+        for dir in cache_dir.iter().chain(local_mirrors) {
+            resolver.push(LocalMirror(dir.clone()));
+        }
+        if !http_services.is_empty() {
+            resolver.push(HttpMirrors {
+                base_urls: http_services.clone(),
+                timeout: *http_timeout,
+                retries: *http_retries,
+            });
+        }
+        resolver.push(TrustedPeers(settings.network.clone()));
+        resolver
+    }
+
+    pub fn push<S: Block0Source + 'static>(&mut self, source: S) {
+        self.sources.push(Box::new(source));
+    }
+
+    pub async fn resolve(&self, block0_id: &HeaderId) -> Option<Block> {
+        for source in &self.sources {
+            let block = match source.fetch(block0_id).await {
+                Ok(Some(block)) => block,
+                Ok(None) => {
+                    tracing::debug!("block0 {} not found in {}", block0_id, source.name());
+                    continue;
+                }
+                Err(e) => {
+                    tracing::info!(
+                        "failed to get block0 {} from {}: {}",
+                        block0_id,
+                        source.name(),
+                        e
+                    );
+                    continue;
+                }
+            };
+            let got = block.header().id();
+            if &got != block0_id {
+                tracing::warn!(
+                    "{} returned block {} instead of the expected block0 {}",
+                    source.name(),
+                    got,
+                    block0_id
+                );
+                continue;
+            }
+            tracing::info!("block0 {} retrieved from {}", block0_id, source.name());
+            if source.is_remote() {
+                if let Some(dir) = &self.cache_dir {
+                    if let Err(e) = write_cache(dir, block0_id, &block) {
+                        tracing::warn!(reason = %e, "failed to cache block0 in {}", dir.display());
+                    }
+                }
+            }
+            return Some(block);
+        }
+        None
+    }
+}
+
+/// The block storage of the node, which contains the genesis block
+/// once the node has started with it.
+pub struct StorageSource(pub Storage);
+
+#[async_trait]
+impl Block0Source for StorageSource {
+    fn name(&self) -> String {
+        "the block storage".to_owned()
+    }
+
+    fn is_remote(&self) -> bool {
+        false
+    }
+
+    async fn fetch(&self, block0_id: &HeaderId) -> Result<Option<Block>, String> {
+        self.0.get(*block0_id).map_err(|e| e.to_string())
+    }
+}
+
+/// A local directory containing genesis blocks as `<hash>.block0` files.
+pub struct LocalMirror(pub PathBuf);
+
+#[async_trait]
+impl Block0Source for LocalMirror {
+    fn name(&self) -> String {
+        format!("directory {}", self.0.display())
+    }
+
+    fn is_remote(&self) -> bool {
+        false
+    }
+
+    async fn fetch(&self, block0_id: &HeaderId) -> Result<Option<Block>, String> {
+        let bytes = match fs::read(block0_path(&self.0, block0_id)) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("cannot read file: {}", e)),
+        };
+        Block::deserialize(&mut Codec::new(bytes.as_slice()))
+            .map(Some)
+            .map_err(|e| format!("parse error on data {}", e))
+    }
+}
+
+/// HTTP services serving genesis blocks at `<base url>/<hash>.block0`.
+/// All the services are queried at once and the first valid response is used.
+pub struct HttpMirrors {
+    pub base_urls: Vec<String>,
+    pub timeout: Duration,
+    pub retries: u32,
+}
+
+impl HttpMirrors {
+    async fn fetch_one(&self, block0_id: &HeaderId, base_url: &str) -> Result<Block, String> {
+        let url = format!("{}/{}.block0", base_url, block0_id);
+        let mut attempt = 0;
+        loop {
+            let error = match tokio::time::timeout(self.timeout, fetch_url(&url)).await {
+                Ok(Ok(block)) => {
+                    let got = block.header().id();
+                    // a mirror serving another block is not retried
+                    return if &got == block0_id {
+                        Ok(block)
+                    } else {
+                        Err(format!(
+                            "{}: invalid block expecting {} got {}",
+                            url, block0_id, got
+                        ))
+                    };
+                }
+                Ok(Err(e)) => e,
+                Err(_) => "request timed out".to_owned(),
+            };
+            if attempt >= self.retries {
+                return Err(format!("{}: {}", url, error));
+            }
+            let delay = retry_delay(attempt);
+            attempt += 1;
+            tracing::debug!(
+                "HTTP fetch: failed to get {}: {}, retrying in {:?}",
+                url,
+                error,
+                delay
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+/// Exponential backoff between the attempts to fetch from a mirror.
+fn retry_delay(attempt: u32) -> Duration {
+    HTTP_RETRY_BASE_DELAY
+        .checked_mul(1 << attempt.min(16))
+        .map_or(HTTP_RETRY_MAX_DELAY, |delay| {
+            delay.min(HTTP_RETRY_MAX_DELAY)
+        })
+}
+
+#[async_trait]
+impl Block0Source for HttpMirrors {
+    fn name(&self) -> String {
+        format!("{} HTTP services", self.base_urls.len())
+    }
+
+    fn is_remote(&self) -> bool {
+        true
+    }
+
+    async fn fetch(&self, block0_id: &HeaderId) -> Result<Option<Block>, String> {
+        let requests = self
+            .base_urls
+            .iter()
+            .map(|base_url| self.fetch_one(block0_id, base_url).boxed());
+        future::select_ok(requests)
+            .await
+            .map(|(block, _)| Some(block))
+    }
+}
+
+async fn fetch_url(url: &str) -> Result<Block, String> {
+    let response = reqwest::get(url)
+        .await
+        .map_err(|e| format!("cannot get {}", e))?;
+    if response.status() != reqwest::StatusCode::OK {
+        return Err(format!("fetch failed status code: {}", response.status()));
+    }
+
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| format!("cannot get data {}", e))?;
+    Block::deserialize(&mut Codec::new(bytes.as_ref()))
+        .map_err(|err| format!("parse error on data {}", err))
+}
+
+/// The trusted peers of the node, queried over the p2p protocol.
+pub struct TrustedPeers(pub NetworkConfiguration);
+
+#[async_trait]
+impl Block0Source for TrustedPeers {
+    fn name(&self) -> String {
+        "the trusted peers".to_owned()
+    }
+
+    fn is_remote(&self) -> bool {
+        true
+    }
+
+    async fn fetch(&self, block0_id: &HeaderId) -> Result<Option<Block>, String> {
+        match network::fetch_block(&self.0, *block0_id).await {
+            Ok(block) => Ok(Some(block)),
+            Err(network::FetchBlockError::NoTrustedPeers) => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+fn block0_path(dir: &Path, block0_id: &HeaderId) -> PathBuf {
+    dir.join(format!("{}.block0", block0_id))
+}
+
+fn write_cache(dir: &Path, block0_id: &HeaderId, block: &Block) -> io::Result<()> {
+    let bytes = block
+        .serialize_as_vec()
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    fs::create_dir_all(dir)?;
+    let path = block0_path(dir, block0_id);
+    let tmp_path = path.with_extension("block0.tmp");
+    let mut file = fs::File::create(&tmp_path)?;
+    file.write_all(&bytes)?;
+    file.sync_all()?;
+    fs::rename(tmp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blockcfg::{block_builder, BlockDate, BlockVersion, ChainLength, Contents};
+    use std::{
+        convert::Infallible,
+        sync::{Arc, Mutex},
+    };
+
+    fn block(slot_id: u32) -> Block {
+        block_builder(BlockVersion::Genesis, Contents::empty(), |hdr_builder| {
+            Ok::<_, Infallible>(
+                hdr_builder
+                    .set_parent(&HeaderId::zero_hash(), ChainLength::from(0))
+                    .set_date(BlockDate { epoch: 0, slot_id })
+                    .into_unsigned_header()
+                    .unwrap()
+                    .generalize(),
+            )
+        })
+        .unwrap()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "jormungandr-block0-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// A source returning a fixed result and recording that it was queried.
+    struct FakeSource {
+        name: &'static str,
+        remote: bool,
+        result: Result<Option<Block>, String>,
+        queried: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    #[async_trait]
+    impl Block0Source for FakeSource {
+        fn name(&self) -> String {
+            self.name.to_owned()
+        }
+
+        fn is_remote(&self) -> bool {
+            self.remote
+        }
+
+        async fn fetch(&self, _: &HeaderId) -> Result<Option<Block>, String> {
+            self.queried.lock().unwrap().push(self.name);
+            self.result.clone()
+        }
+    }
+
+    fn resolver_with(
+        cache_dir: Option<PathBuf>,
+        sources: Vec<(&'static str, bool, Result<Option<Block>, String>)>,
+    ) -> (Block0Resolver, Arc<Mutex<Vec<&'static str>>>) {
+        let queried = Arc::new(Mutex::new(Vec::new()));
+        let mut resolver = Block0Resolver::new(cache_dir);
+        for (name, remote, result) in sources {
+            resolver.push(FakeSource {
+                name,
+                remote,
+                result,
+                queried: queried.clone(),
+            });
+        }
+        (resolver, queried)
+    }
+
+    #[tokio::test]
+    async fn sources_are_tried_in_order() {
+        let block0 = block(0);
+        let block0_id = block0.header().id();
+        let (resolver, queried) = resolver_with(
+            None,
+            vec![
+                ("missing", false, Ok(None)),
+                ("failing", false, Err("unavailable".to_owned())),
+                ("first", false, Ok(Some(block0.clone()))),
+                ("second", false, Ok(Some(block0))),
+            ],
+        );
+
+        let found = resolver.resolve(&block0_id).await.unwrap();
+        assert_eq!(found.header().id(), block0_id);
+        assert_eq!(*queried.lock().unwrap(), ["missing", "failing", "first"]);
+    }
+
+    #[tokio::test]
+    async fn block_with_another_hash_is_skipped() {
+        let block0 = block(0);
+        let block0_id = block0.header().id();
+        let (resolver, queried) = resolver_with(
+            None,
+            vec![
+                ("wrong", false, Ok(Some(block(1)))),
+                ("right", false, Ok(Some(block0))),
+            ],
+        );
+        let found = resolver.resolve(&block0_id).await.unwrap();
+        assert_eq!(found.header().id(), block0_id);
+        assert_eq!(*queried.lock().unwrap(), ["wrong", "right"]);
+
+        let (resolver, _) = resolver_with(None, vec![("wrong", true, Ok(Some(block(1))))]);
+        assert!(resolver.resolve(&block0_id).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn remote_block_is_cached() {
+        let block0 = block(0);
+        let block0_id = block0.header().id();
+        let dir = temp_dir("cache");
+
+        // local sources are not written back to the cache
+        let (resolver, _) = resolver_with(
+            Some(dir.clone()),
+            vec![("local", false, Ok(Some(block0.clone())))],
+        );
+        resolver.resolve(&block0_id).await.unwrap();
+        assert!(!block0_path(&dir, &block0_id).exists());
+
+        let (resolver, _) =
+            resolver_with(Some(dir.clone()), vec![("remote", true, Ok(Some(block0)))]);
+        resolver.resolve(&block0_id).await.unwrap();
+
+        let cached = LocalMirror(dir.clone()).fetch(&block0_id).await;
+        assert_eq!(cached.unwrap().unwrap().header().id(), block0_id);
+        assert!(LocalMirror(dir.clone())
+            .fetch(&block(1).header().id())
+            .await
+            .unwrap()
+            .is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn retry_delay_backs_off_exponentially() {
+        assert_eq!(retry_delay(0), HTTP_RETRY_BASE_DELAY);
+        assert_eq!(retry_delay(1), HTTP_RETRY_BASE_DELAY * 2);
+        assert_eq!(retry_delay(2), HTTP_RETRY_BASE_DELAY * 4);
+        assert_eq!(retry_delay(10), HTTP_RETRY_MAX_DELAY);
+        assert_eq!(retry_delay(u32::MAX), HTTP_RETRY_MAX_DELAY);
+    }
+}
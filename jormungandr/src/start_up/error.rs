@@ -2,7 +2,7 @@ use crate::{
     blockcfg, blockchain,
     blockchain::StorageError,
     diagnostic::DiagnosticError,
-    network, secure,
+    leadership, network, secure,
     settings::{self, logging},
 };
 use chain_core::property::ReadError;
@@ -46,8 +46,8 @@ pub enum Error {
     Blockchain(#[from] Box<blockchain::Error>),
     #[error("Error in the genesis-block")]
     Block0(#[from] blockcfg::Block0Error),
-    #[error("Error fetching the genesis block from the network")]
-    FetchBlock0(#[from] network::FetchBlockError),
+    #[error("Could not retrieve the genesis block {hash} from any source")]
+    Block0NotFound { hash: blockcfg::HeaderId },
     #[error("Error while loading the blockchain from the network")]
     NetworkBootstrapError(#[source] network::BootstrapError),
     #[error("Error while loading the node's secrets.")]
@@ -58,6 +58,13 @@ pub enum Error {
     ServiceTerminatedWithError(#[from] crate::utils::task::ServiceError),
     #[error("Unable to get system limits: {0}")]
     DiagnosticError(#[from] DiagnosticError),
+    #[error("Error with the block archive")]
+    BlockArchive(#[from] blockchain::archive::Error),
+    #[cfg(unix)]
+    #[error("Error in the remote signer")]
+    RemoteSigner(#[from] secure::remote::Error),
+    #[error("Error while loading the signing watermarks")]
+    SigningGuard(#[from] leadership::SigningGuardError),
     #[error("Interrupted by the user")]
     Interrupted,
 }
@@ -86,10 +93,14 @@ impl Error {
             Error::Block0Mismatch { .. } => 7,
             Error::Block0InFuture => 7,
             Error::NodeSecrets { .. } => 8,
-            Error::FetchBlock0 { .. } => 9,
+            Error::Block0NotFound { .. } => 9,
             Error::NetworkBootstrapError { .. } => 10,
             Error::ServiceTerminatedWithError { .. } => 12,
             Error::DiagnosticError { .. } => 13,
+            Error::BlockArchive { .. } => 14,
+            #[cfg(unix)]
+            Error::RemoteSigner { .. } => 15,
+            Error::SigningGuard { .. } => 16,
         }
     }
 }
@@ -1,10 +1,11 @@
+pub mod block0;
 mod error;
 
+use self::block0::Block0Resolver;
 pub use self::error::{Error, ErrorKind};
 use crate::{
-    blockcfg::{Block, HeaderId},
-    blockchain::{Blockchain, Error as BlockchainError, Storage, Tip},
-    network,
+    blockcfg::Block,
+    blockchain::{Blockchain, Error as BlockchainError, LedgerSnapshots, Storage, Tip},
     settings::start::Settings,
 };
 use chain_core::packer::Codec;
@@ -29,66 +30,15 @@ pub fn prepare_storage(setting: &Settings) -> Result<Storage, Error> {
     }
 }
 
-/// Try to fetch the block0_id from the HTTP base URL (services) in the array
-///
-/// The HTTP url is expecting to be of the form: URL/<hash-id>.block0
-async fn fetch_block0_http(base_services: &[String], block0_id: &HeaderId) -> Option<Block> {
-    use chain_core::property::Deserialize as _;
-
-    if base_services.is_empty() {
-        return None;
-    }
-
-    async fn fetch_one(block0_id: &HeaderId, url: &str) -> Result<Block, String> {
-        let response = reqwest::get(url)
-            .await
-            .map_err(|e| format!("cannot get {}", e))?;
-        if response.status() != reqwest::StatusCode::OK {
-            return Err(format!("fetch failed status code: {}", response.status()));
-        }
-
-        let bytes = response
-            .bytes()
-            .await
-            .map_err(|e| format!("cannot get data {}", e))?;
-        let block = Block::deserialize(&mut Codec::new(bytes.as_ref()))
-            .map_err(|err| format!("parse error on data {}", err))?;
-        let got = block.header().id();
-        if &got != block0_id {
-            return Err(format!("invalid block expecting {} got {}", block0_id, got));
-        }
-        Ok(block)
-    }
-
-    for base_url in base_services {
-        // trying to fetch from service base url
-        let url = format!("{}/{}.block0", base_url, block0_id);
-        match fetch_one(block0_id, &url).await {
-            Err(e) => {
-                tracing::debug!("HTTP fetch : fail to get from {} : error {}", base_url, e);
-            }
-            Ok(block) => {
-                tracing::info!("block0 {} fetched by HTTP from {}", block0_id, url);
-                return Some(block);
-            }
-        }
-    }
-
-    tracing::info!(
-        "block0 {} fetch by HTTP unsuccessful after trying {} services",
-        block0_id,
-        base_services.len()
-    );
-    None
-}
-
 /// loading the block 0 is not as trivial as it seems,
 /// there are different cases that we may encounter:
 ///
 /// 1. we have the block_0 given as parameter of the settings: easy, we read it;
-/// 2. we have the block_0 hash only:
-///     1. check the storage if we don't have it already there;
-///     2. check the network nodes we know about
+/// 2. we have the block_0 hash only, it is looked up in order (see `Block0Resolver`):
+///     1. in the storage, if we don't have it already there;
+///     2. in the local cache and the configured local mirrors;
+///     3. from the configured HTTP services;
+///     4. from the network nodes we know about
 pub async fn prepare_block_0(settings: &Settings, storage: &Storage) -> Result<Block, Error> {
     use crate::settings::Block0Info;
     use chain_core::property::Deserialize as _;
@@ -122,28 +72,10 @@ pub async fn prepare_block_0(settings: &Settings, storage: &Storage) -> Result<B
 
             Ok(block)
         }
-        Block0Info::Hash(block0_id) => {
-            let storage_or_http_block0 = {
-                if let Some(block0) = storage.get(*block0_id).unwrap() {
-                    tracing::debug!("retrieved block0 from storage with hash {}", block0_id);
-                    // TODO verify block0 retrieved is the expected value
-                    Some(block0)
-                } else {
-                    tracing::debug!("retrieving block0 from network with hash {}", block0_id);
-
-                    fetch_block0_http(&settings.network.http_fetch_block0_service, block0_id).await
-                }
-            };
-            // fetch from network:: is moved here since it start a runtime, and
-            // runtime cannot be started by a runtime.
-            match storage_or_http_block0 {
-                Some(block0) => Ok(block0),
-                None => {
-                    let block0 = network::fetch_block(&settings.network, *block0_id).await?;
-                    Ok(block0)
-                }
-            }
-        }
+        Block0Info::Hash(block0_id) => Block0Resolver::from_settings(settings, storage)
+            .resolve(block0_id)
+            .await
+            .ok_or(Error::Block0NotFound { hash: *block0_id }),
     }
 }
 
@@ -152,12 +84,14 @@ pub async fn load_blockchain(
     storage: Storage,
     cache_capacity: usize,
     rewards_report_all: bool,
+    ledger_snapshots: Option<LedgerSnapshots>,
 ) -> Result<(Blockchain, Tip), Error> {
     let blockchain = Blockchain::new(
         block0.header().hash(),
         storage,
         cache_capacity,
         rewards_report_all,
+        ledger_snapshots,
     );
 
     let tip = match blockchain.load_from_block0(block0.clone()).await {
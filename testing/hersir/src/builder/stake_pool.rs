@@ -36,6 +36,7 @@ pub fn generate(
                             let bytes: [u8; 32] = node_id.clone().into();
                             bytes.into()
                         },
+                        kes_start_period: None,
                     });
 
                     initials.push(Initial::Cert(
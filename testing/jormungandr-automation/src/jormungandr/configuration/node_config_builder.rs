@@ -2,8 +2,8 @@
 
 use jormungandr_lib::{
     interfaces::{
-        Cors, JRpc, LayersConfig, Log, Mempool, NodeConfig, P2p, Policy, Rest, Tls,
-        TopicsOfInterest, TrustedPeer,
+        Cors, JRpc, LayersConfig, Log, Mempool, NodeConfig, P2p, P2pConnectionFilter, P2pTls,
+        Policy, Rest, Tls, TopicsOfInterest, TrustedPeer,
     },
     time::Duration,
 };
@@ -72,6 +72,8 @@ impl NodeConfigBuilder {
                 gossip_interval: None,
                 max_bootstrap_attempts: None,
                 network_stuck_check: None,
+                tls: None,
+                connection_filter: None,
             },
             mempool: Some(Mempool::default()),
         }
@@ -107,6 +109,16 @@ impl NodeConfigBuilder {
         self
     }
 
+    pub fn with_p2p_tls_config(&mut self, tls: P2pTls) -> &mut Self {
+        self.p2p.tls = Some(tls);
+        self
+    }
+
+    pub fn with_p2p_connection_filter(&mut self, filter: P2pConnectionFilter) -> &mut Self {
+        self.p2p.connection_filter = Some(filter);
+        self
+    }
+
     pub fn with_rest_cors_config(&mut self, cors: Cors) -> &mut Self {
         self.rest.cors = Some(cors);
         self
@@ -3,7 +3,6 @@ use jormungandr_automation::{
     jcli::JCli,
     jormungandr::{ConfigurationBuilder, JormungandrProcess, StartupVerificationMode},
 };
-use jormungandr_lib::interfaces::LeadershipLogStatus;
 use std::time::Duration;
 
 #[test]
@@ -40,7 +39,7 @@ fn verify_leadership_logs_parent_hash(jormungandr: JormungandrProcess) {
 
     // leadership logs are fetched in reverse order (newest first)
     for leadership in leadership_logs.iter().take(10).rev() {
-        if let LeadershipLogStatus::Block { block, parent, .. } = leadership.status() {
+        if let Some((block, parent, _)) = leadership.status().block() {
             let actual_block =
                 jcli.rest()
                     .v0()